@@ -1,4 +1,5 @@
 use {
+    crate::console::{self, ConsolePorts},
     anyhow::Result,
     axum::{
         extract::Query,
@@ -10,6 +11,7 @@ use {
     std::net::Ipv4Addr,
     std::sync::Arc,
     streamhub::{
+        cluster::{StreamLocation, StreamLocationStore},
         define::{self, RelayType, StreamHubEventSender},
         stream::StreamIdentifier,
         utils::Uuid,
@@ -48,13 +50,33 @@ struct RelayStreamParams {
     //guaranteed by the user to be unique
     id: String,
     identifier: Option<StreamIdentifier>,
+    //the rtmp/rtmps url with the stream key for the Push relay type,
+    //user:password@host:port for pulling a rtsp stream from a protected camera,
+    //the WHEP/WHIP endpoint url for pulling/pushing a webrtc stream
     server_address: Option<String>,
     relay_type: RelayType,
+    //the bearer token of the WHEP/WHIP endpoint
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SendTextDataParams {
+    identifier: StreamIdentifier,
+    //relayed to the WHEP subscribers by the data channels and to the
+    //RTMP/HTTP-FLV subscribers as onTextData
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ResolveStreamParams {
+    identifier: StreamIdentifier,
 }
 
 #[derive(Clone)]
 struct ApiService {
     channel_event_producer: StreamHubEventSender,
+    //the stream registry of the cluster, only served on the registry node
+    stream_locations: Option<Arc<StreamLocationStore>>,
 }
 
 impl ApiService {
@@ -63,7 +85,10 @@ impl ApiService {
             "Usage of xiu http api:
                 ./api/query_whole_streams(get) query whole streams' information or top streams' information.
                 ./api/query_stream(post) query stream information by identifier and uuid.
-                ./api/kick_off_client(post) kick off client by publish/subscribe id.\n",
+                ./api/kick_off_client(post) kick off client by publish/subscribe id.
+                ./api/start_relay_stream(post) start pulling a remote stream(relay_type: Pull) or restreaming a local rtmp stream to a rtmp/rtmps url(relay_type: Push), a webrtc stream is pulled from a WHEP url or pushed to a WHIP url with an optional bearer token.
+                ./api/stop_relay_stream(post) stop the relay stream by id.
+                ./console(get) the web console to list, play and publish the live streams.\n",
         )
     }
 
@@ -173,6 +198,7 @@ impl ApiService {
             identifier: relay_info.identifier.unwrap(),
             server_address: relay_info.server_address.unwrap(),
             relay_type: relay_info.relay_type,
+            token: relay_info.token,
             result_sender,
         };
 
@@ -210,6 +236,80 @@ impl ApiService {
         }
     }
 
+    async fn send_text_data(&self, params: SendTextDataParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        let hub_event = define::StreamHubEvent::ApiSendTextData {
+            identifier: params.identifier,
+            data: params.text,
+            result_sender,
+        };
+
+        if let Err(err) = self.channel_event_producer.send(hub_event) {
+            log::error!("send api send_text_data event error: {}", err);
+        }
+
+        let result = match result_receiver.await {
+            Ok(val) => val.map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        match result {
+            Ok(()) => Json(ApiResponse {
+                error_code: 0,
+                desp: String::from("succ"),
+                data: Value::Null,
+            }),
+            Err(err) => Json(ApiResponse {
+                error_code: -1,
+                desp: String::from("failed"),
+                data: serde_json::json!(err),
+            }),
+        }
+    }
+
+    fn register_stream(&self, location: StreamLocation) -> Json<ApiResponse<Value>> {
+        if let Some(stream_locations) = &self.stream_locations {
+            stream_locations.register(location);
+        }
+        Json(ApiResponse {
+            error_code: 0,
+            desp: String::from("succ"),
+            data: Value::Null,
+        })
+    }
+
+    fn unregister_stream(&self, location: StreamLocation) -> Json<ApiResponse<Value>> {
+        if let Some(stream_locations) = &self.stream_locations {
+            stream_locations.unregister(&location.identifier, &location.address);
+        }
+        Json(ApiResponse {
+            error_code: 0,
+            desp: String::from("succ"),
+            data: Value::Null,
+        })
+    }
+
+    fn resolve_stream(&self, params: ResolveStreamParams) -> Json<ApiResponse<Value>> {
+        let address = self
+            .stream_locations
+            .as_ref()
+            .and_then(|stream_locations| stream_locations.resolve(&params.identifier));
+
+        match address {
+            Some(address) => Json(ApiResponse {
+                error_code: 0,
+                desp: String::from("succ"),
+                data: Value::String(address),
+            }),
+            None => Json(ApiResponse {
+                error_code: -1,
+                desp: String::from("stream not found"),
+                data: Value::Null,
+            }),
+        }
+    }
+
     async fn stop_relay_stream(&self, relay_info: RelayStreamParams) -> Json<ApiResponse<Value>> {
         let (result_sender, result_receiver) = oneshot::channel();
 
@@ -254,9 +354,16 @@ impl ApiService {
     }
 }
 
-pub async fn run(producer: StreamHubEventSender, port: usize) {
+pub async fn run(
+    producer: StreamHubEventSender,
+    port: usize,
+    stream_locations: Option<Arc<StreamLocationStore>>,
+    console_ports: ConsolePorts,
+) {
+    let registry_enabled = stream_locations.is_some();
     let api = Arc::new(ApiService {
         channel_event_producer: producer,
+        stream_locations,
     });
 
     let api_root = api.clone();
@@ -290,13 +397,42 @@ pub async fn run(producer: StreamHubEventSender, port: usize) {
         api_stop_relay_stream.stop_relay_stream(params).await
     };
 
-    let app = Router::new()
+    let api_send_text_data = api.clone();
+    let send_text_data = move |Json(params): Json<SendTextDataParams>| async move {
+        api_send_text_data.send_text_data(params).await
+    };
+
+    let api_register_stream = api.clone();
+    let register_stream = move |Json(location): Json<StreamLocation>| async move {
+        api_register_stream.register_stream(location)
+    };
+
+    let api_unregister_stream = api.clone();
+    let unregister_stream = move |Json(location): Json<StreamLocation>| async move {
+        api_unregister_stream.unregister_stream(location)
+    };
+
+    let api_resolve_stream = api.clone();
+    let resolve_stream = move |Json(params): Json<ResolveStreamParams>| async move {
+        api_resolve_stream.resolve_stream(params)
+    };
+
+    let mut app = Router::new()
         .route("/", get(root))
         .route("/api/query_whole_streams", get(query_streams))
         .route("/api/query_stream", post(query_stream))
         .route("/api/kick_off_client", post(kick_off))
         .route("/api/start_relay_stream", post(start_relay_stream))
-        .route("/api/stop_relay_stream", post(stop_relay_stream));
+        .route("/api/stop_relay_stream", post(stop_relay_stream))
+        .route("/api/send_text_data", post(send_text_data))
+        .merge(console::routes(console_ports));
+
+    if registry_enabled {
+        app = app
+            .route("/api/cluster/register", post(register_stream))
+            .route("/api/cluster/unregister", post(unregister_stream))
+            .route("/api/cluster/resolve", post(resolve_stream));
+    }
 
     log::info!("Http api server listening on http://0.0.0.0:{}", port);
     let listener = tokio::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, port as u16)).await.unwrap();
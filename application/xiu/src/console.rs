@@ -0,0 +1,79 @@
+use {
+    axum::{
+        extract::Path,
+        http::{header, StatusCode},
+        response::{IntoResponse, Response},
+        routing::get,
+        Json, Router,
+    },
+    serde::Serialize,
+};
+
+/* The web console compiled into the binary, it lists the live streams by the
+statistics api and plays them by WHEP, HTTP-FLV(flv.js) and HLS(hls.js), or
+publishes the camera by WHIP. */
+const INDEX_HTML: &str = include_str!("console/index.html");
+const CONSOLE_JS: &str = include_str!("console/console.js");
+/* The players are not loaded from a CDN, they are read from the console directory
+beside the executable, e.g. console/flv.min.js and console/hls.min.js, which are
+downloaded by console/download_players.sh when packaging. */
+const PLAYER_SCRIPTS: [&str; 2] = ["flv.min.js", "hls.min.js"];
+
+//the ports of the playback services, 0 if the service is not enabled
+#[derive(Serialize, Clone, Default)]
+pub struct ConsolePorts {
+    pub webrtc: usize,
+    pub httpflv: usize,
+    pub hls: usize,
+}
+
+pub fn routes(ports: ConsolePorts) -> Router {
+    let index = || async {
+        (
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            INDEX_HTML,
+        )
+    };
+    let script = || async {
+        (
+            [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+            CONSOLE_JS,
+        )
+    };
+    let query_ports = move || async move { Json(ports) };
+
+    Router::new()
+        .route("/console", get(index))
+        .route("/console/console.js", get(script))
+        .route("/console/ports", get(query_ports))
+        .route("/console/lib/{name}", get(player_script))
+}
+
+async fn player_script(Path(name): Path<String>) -> Response {
+    if !PLAYER_SCRIPTS.contains(&name.as_str()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let Ok(mut path) = std::env::current_exe() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    path.pop();
+    path.push("console");
+    path.push(&name);
+
+    match tokio::fs::read(&path).await {
+        Ok(data) => (
+            [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+            data,
+        )
+            .into_response(),
+        Err(err) => {
+            log::warn!(
+                "the console player {} is not found: {}",
+                path.display(),
+                err
+            );
+            StatusCode::NOT_FOUND.into_response()
+        }
+    }
+}
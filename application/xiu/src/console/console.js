@@ -0,0 +1,258 @@
+// The ports of the http services, 0 if the service is not enabled
+let ports = { webrtc: 0, httpflv: 0, hls: 0 };
+
+let player = {
+    pc: null,
+    resource: null,
+    flv: null,
+    hls: null,
+};
+
+let publisher = {
+    pc: null,
+    resource: null,
+    stream: null,
+};
+
+function serviceUrl(port, path) {
+    return location.protocol + "//" + location.hostname + ":" + port + path;
+}
+
+function withToken(url) {
+    const token = document.getElementById("token").value;
+    if (!token)
+        return url;
+    return url + (url.includes("?") ? "&" : "?") + "token=" + encodeURIComponent(token);
+}
+
+// The identifier is serialized as {"rtmp": {...}}, {"rtsp": {...}} or {"webrtc": {...}}
+function parseIdentifier(identifier) {
+    const protocol = Object.keys(identifier)[0];
+    const value = identifier[protocol];
+    if (protocol == "rtsp") {
+        const names = value.stream_path.replace(/^\//, "").split("/");
+        return { protocol, app: names[0], stream: names.slice(1).join("/") };
+    }
+    return { protocol, app: value.app_name, stream: value.stream_name };
+}
+
+function addPlayButton(cell, name, port, play) {
+    if (!port)
+        return;
+    const button = document.createElement("button");
+    button.textContent = name;
+    button.onclick = play;
+    cell.appendChild(button);
+}
+
+async function refreshStreams() {
+    const tbody = document.getElementById("streams");
+    tbody.innerHTML = "";
+
+    let streams = [];
+    try {
+        const response = await fetch("/api/query_whole_streams");
+        const result = await response.json();
+        //an empty object is returned if there is no stream
+        if (Array.isArray(result.data))
+            streams = result.data;
+    } catch (err) {
+        console.error("query streams error", err);
+    }
+
+    if (streams.length == 0) {
+        const row = tbody.insertRow();
+        const cell = row.insertCell();
+        cell.colSpan = 6;
+        cell.textContent = "No live stream";
+        return;
+    }
+
+    for (const stream of streams) {
+        const publisher = stream.publisher;
+        const { protocol, app, stream: name } = parseIdentifier(publisher.identifier);
+        const video = publisher.video || {};
+        const audio = publisher.audio || {};
+
+        const row = tbody.insertRow();
+        row.insertCell().textContent = protocol + ": " + app + "/" + name;
+        row.insertCell().textContent = video.width ? video.codec + " " + video.width + "x" + video.height : "-";
+        row.insertCell().textContent = audio.samplerate ? audio.sound_format + " " + audio.samplerate + "Hz" : "-";
+        row.insertCell().textContent = publisher["recv_bitrate(kbits/s)"];
+        row.insertCell().textContent = stream.subscriber_count;
+
+        const cell = row.insertCell();
+        addPlayButton(cell, "WHEP", ports.webrtc, () => playWhep(app, name));
+        addPlayButton(cell, "HTTP-FLV", ports.httpflv, () => playFlv(app, name));
+        addPlayButton(cell, "HLS", ports.hls, () => playHls(app, name));
+    }
+}
+
+// Post the offer to a WHIP/WHEP endpoint after the ICE gathering is complete
+async function negotiate(pc, url) {
+    const offer = await pc.createOffer();
+    await pc.setLocalDescription(offer);
+    await new Promise((resolve) => {
+        if (pc.iceGatheringState == "complete")
+            return resolve();
+        pc.onicegatheringstatechange = () => {
+            if (pc.iceGatheringState == "complete")
+                resolve();
+        };
+    });
+
+    const response = await fetch(url, {
+        method: "POST",
+        body: pc.localDescription.sdp,
+        headers: { "Content-Type": "application/sdp" },
+    });
+    if (!response.ok)
+        throw new Error("request " + url + " failed: " + response.status);
+
+    const location = response.headers.get("location");
+    const answer = await response.text();
+    await pc.setRemoteDescription({ type: "answer", sdp: answer });
+
+    return location ? new URL(location, url).href : null;
+}
+
+function deleteResource(resource) {
+    if (resource)
+        fetch(resource, { method: "DELETE" }).catch((err) => console.error(err));
+}
+
+function stopPlay() {
+    const video = document.getElementById("player");
+    if (player.pc) {
+        player.pc.close();
+        deleteResource(player.resource);
+    }
+    if (player.flv)
+        player.flv.destroy();
+    if (player.hls)
+        player.hls.destroy();
+    player = { pc: null, resource: null, flv: null, hls: null };
+
+    video.pause();
+    video.srcObject = null;
+    video.removeAttribute("src");
+    video.load();
+    document.getElementById("player-url").textContent = "";
+}
+
+function showPlayerUrl(url) {
+    document.getElementById("player-url").textContent = url;
+}
+
+async function playWhep(app, stream) {
+    stopPlay();
+    const url = withToken(serviceUrl(ports.webrtc, "/whep?app=" + app + "&stream=" + stream));
+    showPlayerUrl(url);
+
+    const pc = player.pc = new RTCPeerConnection();
+    pc.addTransceiver("audio", { direction: "recvonly" });
+    pc.addTransceiver("video", { direction: "recvonly" });
+    pc.ontrack = (event) => {
+        document.getElementById("player").srcObject = event.streams[0];
+    };
+
+    try {
+        player.resource = await negotiate(pc, url);
+    } catch (err) {
+        showPlayerUrl(err.message);
+    }
+}
+
+function playFlv(app, stream) {
+    stopPlay();
+    const url = withToken(serviceUrl(ports.httpflv, "/" + app + "/" + stream + ".flv"));
+    showPlayerUrl(url);
+
+    if (!window.flvjs || !flvjs.isSupported()) {
+        showPlayerUrl("flv.js(console/flv.min.js beside xiu) is not found or not supported by the browser: " + url);
+        return;
+    }
+    player.flv = flvjs.createPlayer({ type: "flv", isLive: true, url });
+    player.flv.attachMediaElement(document.getElementById("player"));
+    player.flv.load();
+    player.flv.play();
+}
+
+function playHls(app, stream) {
+    stopPlay();
+    const url = withToken(serviceUrl(ports.hls, "/" + app + "/" + stream + "/" + stream + ".m3u8"));
+    showPlayerUrl(url);
+
+    const video = document.getElementById("player");
+    if (window.Hls && Hls.isSupported()) {
+        player.hls = new Hls();
+        player.hls.loadSource(url);
+        player.hls.attachMedia(video);
+    } else if (video.canPlayType("application/vnd.apple.mpegurl")) {
+        video.src = url;
+    } else {
+        showPlayerUrl("hls.js(console/hls.min.js beside xiu) is not found or not supported by the browser: " + url);
+        return;
+    }
+    video.play();
+}
+
+function showPublishState(state) {
+    document.getElementById("publish-state").textContent = state;
+}
+
+async function publish() {
+    stopPublish();
+    if (!ports.webrtc) {
+        showPublishState("the webrtc service is not enabled");
+        return;
+    }
+    const app = document.getElementById("publish-app").value;
+    const stream = document.getElementById("publish-stream").value;
+    const url = withToken(serviceUrl(ports.webrtc, "/whip?app=" + app + "&stream=" + stream));
+
+    try {
+        publisher.stream = await navigator.mediaDevices.getUserMedia({ audio: true, video: true });
+        document.getElementById("preview").srcObject = publisher.stream;
+
+        const pc = publisher.pc = new RTCPeerConnection();
+        pc.onconnectionstatechange = () => showPublishState(url + " " + pc.connectionState);
+        for (const track of publisher.stream.getTracks())
+            pc.addTransceiver(track, { direction: "sendonly" });
+
+        //the whip server only depacketizes h264
+        const transceiver = pc.getTransceivers().find((t) => t.sender.track.kind == "video");
+        if (transceiver && transceiver.setCodecPreferences) {
+            const codecs = RTCRtpSender.getCapabilities("video").codecs;
+            transceiver.setCodecPreferences(codecs.filter((c) => c.mimeType == "video/H264"));
+        }
+
+        publisher.resource = await negotiate(pc, url);
+    } catch (err) {
+        showPublishState(err.message);
+    }
+}
+
+function stopPublish() {
+    if (publisher.pc) {
+        publisher.pc.close();
+        deleteResource(publisher.resource);
+    }
+    if (publisher.stream)
+        publisher.stream.getTracks().forEach((track) => track.stop());
+    publisher = { pc: null, resource: null, stream: null };
+
+    document.getElementById("preview").srcObject = null;
+    showPublishState("");
+}
+
+document.getElementById("refresh-btn").onclick = refreshStreams;
+document.getElementById("stop-play-btn").onclick = stopPlay;
+document.getElementById("publish-btn").onclick = publish;
+document.getElementById("stop-publish-btn").onclick = stopPublish;
+
+fetch("/console/ports")
+    .then((response) => response.json())
+    .then((result) => { ports = result; })
+    .catch((err) => console.error("query ports error", err))
+    .finally(refreshStreams);
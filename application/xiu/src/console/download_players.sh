@@ -0,0 +1,16 @@
+#!/bin/sh
+
+#download the players of the web console, they are served from the console directory beside xiu
+#e.g. sh download_players.sh ./target/release/console
+if [ $# -ne 1 ]; then
+    echo "USAGE: $0 <console directory beside xiu>"
+    exit 1
+fi
+CONSOLE_DIR=$1
+
+FLV_JS_VERSION=1.6.2
+HLS_JS_VERSION=1.5.17
+
+mkdir -p "$CONSOLE_DIR" || exit 1
+curl -fsSL -o "$CONSOLE_DIR/flv.min.js" "https://cdn.jsdelivr.net/npm/flv.js@$FLV_JS_VERSION/dist/flv.min.js" || exit 1
+curl -fsSL -o "$CONSOLE_DIR/hls.min.js" "https://cdn.jsdelivr.net/npm/hls.js@$HLS_JS_VERSION/dist/hls.min.js" || exit 1
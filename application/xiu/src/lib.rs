@@ -1,3 +1,4 @@
 extern crate config;
 pub mod api;
+pub mod console;
 pub mod service;
@@ -1,12 +1,14 @@
-use crate::config::{AuthConfig, AuthSecretConfig};
+use crate::config::{AuthConfig, AuthSecretConfig, WebRTCIceConfig};
 use commonlib::auth::AuthType;
 use rtmp::remuxer::RtmpRemuxer;
 use std::sync::Arc;
+use std::time::Duration;
 use xrtsp::relay::pull_client_manager::RtspPullClientManager;
 
 use {
     super::api,
     super::config::Config,
+    super::console::ConsolePorts,
     //https://rustcc.cn/article?id=6dcbf032-0483-4980-8bfe-c64a7dfb33c7
     anyhow::Result,
     commonlib::auth::Auth,
@@ -14,13 +16,31 @@ use {
     hls::server as hls_server,
     httpflv::server as httpflv_server,
     rtmp::{
-        relay::{pull_client::PullClient, push_client::PushClient},
+        relay::{
+            pull_client::PullClient,
+            push_client::PushClient,
+            restream::{RestreamClient, RestreamRule, RestreamTarget},
+        },
         rtmp::RtmpServer,
     },
-    streamhub::{notify::http::HttpNotifier, notify::Notifier, StreamsHub},
+    streamhub::{
+        cluster::{http::HttpStreamRegistry, StreamLocationStore},
+        notify::http::HttpNotifier,
+        notify::Notifier,
+        utils::Backoff,
+        StreamsHub,
+    },
     tokio,
     tokio::sync::mpsc,
     xrtsp::rtsp::RtspServer,
+    xrtsp::rtsp_auth::{AuthScheme, RtspAuthenticator},
+    xrtsp::session::define::DEFAULT_SESSION_TIMEOUT,
+    xrtsp::rtsp_multicast::{
+        RtspMulticastManager, DEFAULT_MULTICAST_ADDRESS, DEFAULT_MULTICAST_PORT,
+        DEFAULT_MULTICAST_TTL,
+    },
+    xwebrtc::ice_config::{IceConfig, IceServer},
+    xwebrtc::relay::client_manager::WebRTCRelayClientManager,
     xwebrtc::webrtc::WebRTCServer,
 };
 
@@ -63,6 +83,46 @@ impl Service {
         }
     }
 
+    fn gen_ice_config(ice_config: &Option<WebRTCIceConfig>) -> IceConfig {
+        let mut result = IceConfig::default();
+        let Some(cfg) = ice_config else {
+            return result;
+        };
+
+        if let Some(servers) = &cfg.servers {
+            result.ice_servers = servers
+                .iter()
+                .map(|server| IceServer {
+                    urls: server.urls.clone(),
+                    username: server.username.clone().unwrap_or_default(),
+                    credential: server.credential.clone().unwrap_or_default(),
+                })
+                .collect();
+        }
+        result.nat_1to1_ips = cfg.nat_1to1_ips.clone().unwrap_or_default();
+        result.nat_1to1_srflx = cfg.nat_1to1_candidate_type.as_deref() == Some("srflx");
+        result.interfaces = cfg.interfaces.clone().unwrap_or_default();
+        result.ips = cfg
+            .ips
+            .iter()
+            .flatten()
+            .filter_map(|ip| match ip.parse() {
+                Ok(ip) => Some(ip),
+                Err(err) => {
+                    log::error!("webrtc ice ip: {} error: {}", ip, err);
+                    None
+                }
+            })
+            .collect();
+        if let (Some(port_min), Some(port_max)) = (cfg.udp_port_min, cfg.udp_port_max) {
+            result.udp_port_range = Some((port_min, port_max));
+        }
+        result.udp_mux_port = cfg.udp_mux_port;
+        result.lite = cfg.lite.unwrap_or(false);
+
+        result
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let (event_producer, event_consumer) = mpsc::unbounded_channel();
         let notifier: Option<Arc<dyn Notifier>> = if let Some(httpnotifier) = &self.cfg.httpnotify {
@@ -79,6 +139,16 @@ impl Service {
         };
 
         let mut stream_hub = StreamsHub::new(notifier, event_producer, event_consumer);
+        if let Some(failover_cfg) = &self.cfg.failover {
+            stream_hub.set_failover_config(failover_cfg.clone());
+        }
+        if let Some(watchdog_cfg) = &self.cfg.watchdog {
+            if watchdog_cfg.enabled {
+                stream_hub.set_publish_timeout(Duration::from_secs(
+                    watchdog_cfg.timeout.unwrap_or(10),
+                ));
+            }
+        }
 
         self.start_httpflv(&mut stream_hub).await?;
         self.start_hls(&mut stream_hub).await?;
@@ -86,6 +156,7 @@ impl Service {
         self.start_rtsp(&mut stream_hub).await?;
         self.start_webrtc(&mut stream_hub).await?;
         self.start_http_api_server(&mut stream_hub).await?;
+        self.start_cluster(&mut stream_hub).await?;
         self.start_rtmp_remuxer(&mut stream_hub).await?;
 
         tokio::spawn(async move {
@@ -104,8 +175,33 @@ impl Service {
             8000
         };
 
+        let stream_locations = match &self.cfg.cluster {
+            Some(cluster_cfg)
+                if cluster_cfg.enabled && cluster_cfg.registry_enabled == Some(true) =>
+            {
+                Some(Arc::new(StreamLocationStore::new()))
+            }
+            _ => None,
+        };
+
+        //the ports of the enabled services which play the streams of the web console
+        let console_ports = ConsolePorts {
+            webrtc: match &self.cfg.webrtc {
+                Some(cfg) if cfg.enabled => cfg.port,
+                _ => 0,
+            },
+            httpflv: match &self.cfg.httpflv {
+                Some(cfg) if cfg.enabled => cfg.port,
+                _ => 0,
+            },
+            hls: match &self.cfg.hls {
+                Some(cfg) if cfg.enabled => cfg.port,
+                _ => 0,
+            },
+        };
+
         tokio::spawn(async move {
-            api::run(producer, http_api_port).await;
+            api::run(producer, http_api_port, stream_locations, console_ports).await;
         });
         Ok(())
     }
@@ -143,6 +239,7 @@ impl Service {
                         address,
                         stream_hub.get_client_event_consumer(),
                         producer.clone(),
+                        Backoff::from_config(&push_value.reconnect),
                     );
                     tokio::spawn(async move {
                         if let Err(err) = push_client.run().await {
@@ -163,9 +260,10 @@ impl Service {
                     );
                     log::info!("start rtmp pull client from address: {}", address);
                     let mut pull_client = PullClient::new(
-                        address,
+                        Some(address),
                         stream_hub.get_client_event_consumer(),
                         producer.clone(),
+                        Backoff::from_config(&pull_cfg_value.reconnect),
                     );
 
                     tokio::spawn(async move {
@@ -178,11 +276,51 @@ impl Service {
                 }
             }
 
+            /*restream to other platforms*/
+            if let Some(restream_cfg_value) = &rtmp_cfg_value.restream {
+                if restream_cfg_value.enabled {
+                    log::info!("start rtmp restream client..");
+                    let rules = restream_cfg_value
+                        .rules
+                        .iter()
+                        .flatten()
+                        .map(|rule| RestreamRule {
+                            app_name: rule.app_name.clone(),
+                            stream_name: rule.stream_name.clone(),
+                            targets: rule
+                                .targets
+                                .iter()
+                                .map(|target| RestreamTarget {
+                                    id: target.id.clone(),
+                                    url: target.url.clone(),
+                                })
+                                .collect(),
+                        })
+                        .collect();
+
+                    let mut restream_client = RestreamClient::new(
+                        stream_hub.get_client_event_consumer(),
+                        producer.clone(),
+                        Backoff::from_config(&restream_cfg_value.reconnect),
+                        rules,
+                    );
+                    tokio::spawn(async move {
+                        if let Err(err) = restream_client.run().await {
+                            log::error!("restream client error {}", err);
+                        }
+                    });
+
+                    stream_hub.set_rtmp_restream_enabled(true);
+                }
+            }
+
             let listen_port = rtmp_cfg_value.port;
             let address = format!("0.0.0.0:{listen_port}");
 
             let auth = Self::gen_auth(&rtmp_cfg_value.auth, &self.cfg.authsecret);
-            let mut rtmp_server = RtmpServer::new(address, producer, gop_num, auth);
+            let read_timeout = Duration::from_secs(rtmp_cfg_value.read_timeout.unwrap_or(2));
+            let mut rtmp_server =
+                RtmpServer::new(address, producer, gop_num, auth, read_timeout);
             tokio::spawn(async move {
                 if let Err(err) = rtmp_server.run().await {
                     log::error!("rtmp server error: {}", err);
@@ -193,6 +331,41 @@ impl Service {
         Ok(())
     }
 
+    async fn start_cluster(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
+        let Some(cluster_cfg) = &self.cfg.cluster else {
+            return Ok(());
+        };
+        if !cluster_cfg.enabled {
+            return Ok(());
+        }
+
+        log::info!("start cluster with registry: {}", cluster_cfg.registry);
+        stream_hub.set_stream_registry(Arc::new(HttpStreamRegistry::new(cluster_cfg.clone())));
+
+        //the streams resolved from the registry are pulled by the rtmp pull client,
+        //start one if the static pull is not enabled.
+        let pull_enabled = self.cfg.rtmp.as_ref().is_some_and(|rtmp_cfg| {
+            rtmp_cfg.enabled && rtmp_cfg.pull.as_ref().is_some_and(|pull| pull.enabled)
+        });
+        if !pull_enabled {
+            let mut pull_client = PullClient::new(
+                None,
+                stream_hub.get_client_event_consumer(),
+                stream_hub.get_hub_event_sender(),
+                //keep pulling the relayed streams during the outages of the origin
+                Backoff::unlimited(),
+            );
+
+            tokio::spawn(async move {
+                if let Err(err) = pull_client.run().await {
+                    log::error!("cluster pull client error {}", err);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
     async fn start_rtmp_remuxer(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
         //The remuxer now is used for rtsp2rtmp/whip2rtmp, so both rtsp(or whip)/rtmp cfg need to be enabled.
         let mut rtsp_enabled = false;
@@ -250,7 +423,69 @@ impl Service {
             let address = format!("0.0.0.0:{listen_port}");
 
             let auth = Self::gen_auth(&rtsp_cfg_value.auth, &self.cfg.authsecret);
-            let mut rtsp_server = RtspServer::new(address, producer, auth);
+            let read_timeout = rtsp_cfg_value.read_timeout.map(Duration::from_secs);
+            let vod_path = rtsp_cfg_value
+                .vod
+                .as_ref()
+                .filter(|cfg| cfg.enabled)
+                .map(|cfg| {
+                    cfg.path
+                        .clone()
+                        .or_else(|| self.cfg.hls.as_ref().and_then(|hls| hls.path.clone()))
+                        .unwrap_or(String::from("./"))
+                });
+            let session_timeout = Duration::from_secs(
+                rtsp_cfg_value
+                    .session_timeout
+                    .unwrap_or(DEFAULT_SESSION_TIMEOUT),
+            );
+            let authenticator = rtsp_cfg_value.credential_auth.as_ref().map(|cfg| {
+                let scheme = match cfg.scheme.as_deref() {
+                    Some("basic") => AuthScheme::Basic,
+                    _ => AuthScheme::Digest,
+                };
+                let users = cfg
+                    .users
+                    .iter()
+                    .map(|user| (user.username.clone(), user.password.clone()))
+                    .collect();
+                RtspAuthenticator::new(
+                    scheme,
+                    cfg.realm.clone().unwrap_or(String::from("xiu")),
+                    users,
+                    cfg.pull_enabled,
+                    cfg.push_enabled,
+                )
+            });
+            let multicast = rtsp_cfg_value
+                .multicast
+                .as_ref()
+                .filter(|cfg| cfg.enabled)
+                .map(|cfg| {
+                    let address = match cfg.address.as_ref().map(|address| address.parse()) {
+                        Some(Ok(address)) => address,
+                        Some(Err(err)) => {
+                            log::error!("rtsp multicast address error: {}", err);
+                            DEFAULT_MULTICAST_ADDRESS
+                        }
+                        None => DEFAULT_MULTICAST_ADDRESS,
+                    };
+                    RtspMulticastManager::new(
+                        address,
+                        cfg.port.unwrap_or(DEFAULT_MULTICAST_PORT),
+                        cfg.ttl.unwrap_or(DEFAULT_MULTICAST_TTL),
+                    )
+                });
+            let mut rtsp_server = RtspServer::new(
+                address,
+                producer,
+                auth,
+                read_timeout,
+                authenticator,
+                multicast,
+                session_timeout,
+                vod_path,
+            );
             tokio::spawn(async move {
                 if let Err(err) = rtsp_server.run().await {
                     log::error!("rtsp server error: {}", err);
@@ -261,6 +496,7 @@ impl Service {
                 let mut rtsp_relay_manager = RtspPullClientManager::new(
                     stream_hub.get_client_event_consumer(),
                     stream_hub.get_hub_event_sender(),
+                    Backoff::from_config(&rtsp_cfg_value.relay_reconnect),
                 );
 
                 tokio::spawn(async move {
@@ -288,7 +524,25 @@ impl Service {
             let address = format!("0.0.0.0:{listen_port}");
 
             let auth = Self::gen_auth(&webrtc_cfg_value.auth, &self.cfg.authsecret);
-            let mut webrtc_server = WebRTCServer::new(address, producer, auth);
+            let ice_config = Self::gen_ice_config(&webrtc_cfg_value.ice);
+
+            if webrtc_cfg_value.relay_enabled.unwrap_or(false) {
+                stream_hub.set_webrtc_relay_enabled(true);
+                let mut webrtc_relay_manager = WebRTCRelayClientManager::new(
+                    stream_hub.get_client_event_consumer(),
+                    stream_hub.get_hub_event_sender(),
+                    Backoff::from_config(&webrtc_cfg_value.relay_reconnect),
+                    ice_config.clone(),
+                );
+
+                tokio::spawn(async move {
+                    if let Err(err) = webrtc_relay_manager.run().await {
+                        log::error!("webrtc relay manager error: {}", err);
+                    }
+                });
+            }
+
+            let mut webrtc_server = WebRTCServer::new(address, producer, auth, ice_config);
             tokio::spawn(async move {
                 if let Err(err) = webrtc_server.run().await {
                     log::error!("webrtc server error: {}", err);
@@ -9,6 +9,8 @@ use futures::SinkExt;
 use futures::StreamExt;
 use tokio::net::TcpStream;
 use tokio::net::UdpSocket;
+use tokio_native_tls::native_tls;
+use tokio_native_tls::TlsStream;
 use tokio_util::codec::BytesCodec;
 use tokio_util::codec::Framed;
 
@@ -76,6 +78,13 @@ impl UdpIO {
 
         None
     }
+
+    //limit how many routers the multicast packets can pass through
+    pub fn set_multicast_ttl(&self, ttl: u32) {
+        if let Err(err) = self.socket.set_multicast_ttl_v4(ttl) {
+            log::error!("set multicast ttl error: {}", err);
+        }
+    }
 }
 
 pub async fn new_udpio_pair() -> Option<(UdpIO, UdpIO)> {
@@ -214,6 +223,56 @@ impl TNetIO for TcpIO {
     }
 }
 
+pub struct TlsIO {
+    stream: Framed<TlsStream<TcpStream>, BytesCodec>,
+}
+
+impl TlsIO {
+    /* do the TLS handshake on a connected TCP stream, the domain is used to verify the certificate */
+    pub async fn connect(domain: &str, stream: TcpStream) -> Result<Self, BytesIOError> {
+        let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+        let tls_stream = connector.connect(domain, stream).await?;
+
+        Ok(Self {
+            stream: Framed::new(tls_stream, BytesCodec::new()),
+        })
+    }
+}
+
+#[async_trait]
+impl TNetIO for TlsIO {
+    fn get_net_type(&self) -> NetType {
+        NetType::TCP
+    }
+
+    async fn write(&mut self, bytes: Bytes) -> Result<(), BytesIOError> {
+        self.stream.send(bytes).await?;
+
+        Ok(())
+    }
+
+    async fn read_timeout(&mut self, duration: Duration) -> Result<BytesMut, BytesIOError> {
+        match tokio::time::timeout(duration, self.read()).await {
+            Ok(data) => data,
+            Err(err) => Err(BytesIOError {
+                value: BytesIOErrorValue::TimeoutError(err),
+            }),
+        }
+    }
+
+    async fn read(&mut self) -> Result<BytesMut, BytesIOError> {
+        match self.stream.next().await {
+            Some(Ok(bytes)) => Ok(bytes),
+            Some(Err(err)) => Err(BytesIOError {
+                value: BytesIOErrorValue::IOError(err),
+            }),
+            None => Err(BytesIOError {
+                value: BytesIOErrorValue::NoneReturn,
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -16,6 +16,8 @@ pub enum BytesIOErrorValue {
     TimeoutError(tokio::time::error::Elapsed),
     #[fail(display = "none return")]
     NoneReturn,
+    #[fail(display = "tls error")]
+    TlsError(tokio_native_tls::native_tls::Error),
 }
 #[derive(Debug)]
 pub struct BytesIOError {
@@ -36,6 +38,14 @@ impl From<io::Error> for BytesIOError {
     }
 }
 
+impl From<tokio_native_tls::native_tls::Error> for BytesIOError {
+    fn from(error: tokio_native_tls::native_tls::Error) -> Self {
+        BytesIOError {
+            value: BytesIOErrorValue::TlsError(error),
+        }
+    }
+}
+
 // impl From<Elapsed> for NetIOError {
 //     fn from(error: Elapsed) -> Self {
 //         NetIOError {
@@ -15,6 +15,9 @@ pub struct Config {
     pub hls: Option<HlsConfig>,
     pub httpapi: Option<HttpApiConfig>,
     pub httpnotify: Option<HttpNotifierConfig>,
+    pub cluster: Option<ClusterConfig>,
+    pub failover: Option<FailoverConfig>,
+    pub watchdog: Option<WatchdogConfig>,
     pub authsecret: AuthSecretConfig,
     pub log: Option<LogConfig>,
 }
@@ -36,7 +39,9 @@ impl Config {
                 port: rtmp_port,
                 pull: None,
                 push: None,
+                restream: None,
                 auth: None,
+                read_timeout: None,
             });
         }
 
@@ -45,8 +50,14 @@ impl Config {
             rtsp_config = Some(RtspConfig {
                 enabled: true,
                 relay_enabled: false,
+                relay_reconnect: None,
                 port: rtsp_port,
                 auth: None,
+                read_timeout: None,
+                credential_auth: None,
+                multicast: None,
+                session_timeout: None,
+                vod: None,
             });
         }
 
@@ -56,6 +67,9 @@ impl Config {
                 enabled: true,
                 port: webrtc_port,
                 auth: None,
+                ice: None,
+                relay_enabled: None,
+                relay_reconnect: None,
             });
         }
 
@@ -95,6 +109,9 @@ impl Config {
             hls: hls_config,
             httpapi: None,
             httpnotify: None,
+            cluster: None,
+            failover: None,
+            watchdog: None,
             authsecret: AuthSecretConfig::default(),
             log: log_config,
         }
@@ -108,19 +125,56 @@ pub struct RtmpConfig {
     pub gop_num: Option<usize>,
     pub pull: Option<RtmpPullConfig>,
     pub push: Option<Vec<RtmpPushConfig>>,
+    pub restream: Option<RtmpRestreamConfig>,
     pub auth: Option<AuthConfig>,
+    //seconds, a session is closed if it receives nothing in time(default 2)
+    pub read_timeout: Option<u64>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPullConfig {
     pub enabled: bool,
     pub address: String,
     pub port: u16,
+    pub reconnect: Option<ReconnectConfig>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPushConfig {
     pub enabled: bool,
     pub address: String,
     pub port: usize,
+    pub reconnect: Option<ReconnectConfig>,
+}
+
+//push the published streams to several platforms(rtmp:// or rtmps:// urls with stream keys)
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtmpRestreamConfig {
+    pub enabled: bool,
+    pub reconnect: Option<ReconnectConfig>,
+    pub rules: Option<Vec<RestreamRuleConfig>>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RestreamRuleConfig {
+    pub app_name: String,
+    //match all the streams of the app if it is not set
+    pub stream_name: Option<String>,
+    pub targets: Vec<RestreamTargetConfig>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RestreamTargetConfig {
+    //used to identify the target in the statistics and api
+    pub id: String,
+    pub url: String,
+}
+
+//used by the relay clients to reconnect to the remote server
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ReconnectConfig {
+    //the delay(ms) before the first retry, it is doubled after every failed retry
+    pub initial_delay: Option<u64>,
+    //the max delay(ms) between two retries
+    pub max_delay: Option<u64>,
+    //give up after retrying so many times, it is 10 if it is not set and 0 retries forever
+    pub max_retries: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -129,6 +183,51 @@ pub struct RtspConfig {
     pub port: usize,
     pub auth: Option<AuthConfig>,
     pub relay_enabled: bool,
+    pub relay_reconnect: Option<ReconnectConfig>,
+    //seconds, a session is closed if it receives nothing in time(no timeout by default)
+    pub read_timeout: Option<u64>,
+    pub credential_auth: Option<RtspCredentialAuthConfig>,
+    pub multicast: Option<RtspMulticastConfig>,
+    //seconds, told to the clients in the Session header, a session without any
+    //RTSP, RTP or RTCP activity in time is torn down(60 by default)
+    pub session_timeout: Option<u64>,
+    pub vod: Option<RtspVodConfig>,
+}
+
+//the Basic/Digest authentication with the Authorization header, which is
+//expected by ip cameras, vlc and ffmpeg instead of the token in the url query.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspCredentialAuthConfig {
+    pub pull_enabled: bool,
+    pub push_enabled: bool,
+    //basic or digest(default)
+    pub scheme: Option<String>,
+    pub realm: Option<String>,
+    pub users: Vec<RtspUserConfig>,
+}
+//deliver the played streams over multicast if the players ask for it in the
+//Transport header, all the viewers of a stream share one group.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspMulticastConfig {
+    pub enabled: bool,
+    //the group of the first stream, the next streams use the following addresses(239.0.0.1 by default)
+    pub address: Option<String>,
+    //the video uses port and port + 1, the audio uses port + 2 and port + 3(5000 by default)
+    pub port: Option<u16>,
+    //16 by default
+    pub ttl: Option<u32>,
+}
+//play the recordings of hls by rtsp://host:port/vod/{app}/{stream} with seeking
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspVodConfig {
+    pub enabled: bool,
+    //the folder of the recordings, the path of hls by default
+    pub path: Option<String>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspUserConfig {
+    pub username: String,
+    pub password: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -136,6 +235,36 @@ pub struct WebRTCConfig {
     pub enabled: bool,
     pub port: usize,
     pub auth: Option<AuthConfig>,
+    pub ice: Option<WebRTCIceConfig>,
+    //pull/push the streams from/to the remote WHEP/WHIP endpoints by the relay api
+    pub relay_enabled: Option<bool>,
+    pub relay_reconnect: Option<ReconnectConfig>,
+}
+//the ICE settings of the peer connections for the NATed or air-gapped networks
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebRTCIceConfig {
+    //stun:stun.l.google.com:19302 by default, an empty list disables it
+    pub servers: Option<Vec<WebRTCIceServerConfig>>,
+    //the public ips of a 1:1 NAT
+    pub nat_1to1_ips: Option<Vec<String>>,
+    //host(replace the private ips, by default) or srflx(add them as the server reflexive candidates)
+    pub nat_1to1_candidate_type: Option<String>,
+    //only gather the host candidates of these network interfaces or ips
+    pub interfaces: Option<Vec<String>>,
+    pub ips: Option<Vec<String>>,
+    //the range of the udp ports
+    pub udp_port_min: Option<u16>,
+    pub udp_port_max: Option<u16>,
+    //all the peer connections share this udp port, the port range is not used if it is set
+    pub udp_mux_port: Option<u16>,
+    pub lite: Option<bool>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebRTCIceServerConfig {
+    pub urls: Vec<String>,
+    //the credential of the TURN servers
+    pub username: Option<String>,
+    pub credential: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -184,6 +313,42 @@ pub struct HttpApiConfig {
     pub port: usize,
 }
 
+//origins register the published streams in the registry, edges resolve the
+//origin of a stream from the registry and pull it when it is not found locally.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    //the http api address of the registry node, e.g. http://192.168.0.1:8000
+    pub registry: String,
+    //serve the registry on the http api of this node
+    pub registry_enabled: Option<bool>,
+    //the rtmp address(ip:port) the other nodes pull streams from, the local
+    //streams are not registered if it is not set(an edge only node)
+    pub advertise_address: Option<String>,
+    //seconds, the stream location expires if it is not refreshed in time
+    pub ttl: Option<u64>,
+}
+
+//a second publisher of a published stream becomes the hot standby, the
+//subscribers are switched to it when the active publisher stops.
+#[derive(Debug, Deserialize, Clone)]
+pub struct FailoverConfig {
+    pub enabled: bool,
+    //milliseconds, the active publisher is stalled if no data arrives in time
+    pub timeout: Option<u64>,
+    //switch back to the primary publisher when it is published again
+    pub switch_back: Option<bool>,
+}
+
+//unpublish the streams which receive no data from their publishers in time,
+//so the reconnecting publishers are not rejected by the zombie sessions.
+#[derive(Debug, Deserialize, Clone)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    //seconds
+    pub timeout: Option<u64>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct HttpNotifierConfig {
     pub enabled: bool,
@@ -192,6 +357,7 @@ pub struct HttpNotifierConfig {
     pub on_play: Option<String>,
     pub on_stop: Option<String>,
     pub on_hls: Option<String>,
+    pub on_publish_timeout: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -1,39 +1,94 @@
 use {
-    super::{amf0_markers, errors::Amf0ReadErrorValue, Amf0ReadError, Amf0ValueType},
+    super::{
+        super::amf3::{
+            amf3_markers,
+            amf3_reader::{Amf3Reader, MAX_DECODED_SIZE, MAX_NESTING_DEPTH},
+        },
+        amf0_markers,
+        errors::Amf0ReadErrorValue,
+        Amf0ReadError, Amf0ValueType,
+    },
     byteorder::BigEndian,
-    // bytes::BytesMut,
+    bytes::BytesMut,
     bytesio::bytes_reader::BytesReader,
     indexmap::IndexMap,
 };
 
+/* The objects, typed objects and arrays can be referenced by their indexes in
+the order they are read. In the recoverable mode the bytes after a value which
+cannot be parsed are skipped, and the values read before are returned.
+The expanded references are limited like the amf3 ones. */
 pub struct Amf0Reader {
     reader: BytesReader,
+    //the slot is none until the complex value is read completely
+    object_refs: Vec<Option<Amf0ValueType>>,
+    //the decoded sizes of the complex values, which are consumed again by the references
+    object_sizes: Vec<usize>,
+    recoverable: bool,
+    depth: usize,
+    //the amf3 values share the size limit of the decoded values
+    decoded_size: usize,
 }
 
 impl Amf0Reader {
     pub fn new(reader: BytesReader) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            object_refs: Vec::new(),
+            object_sizes: Vec::new(),
+            recoverable: false,
+            depth: 0,
+            decoded_size: 0,
+        }
+    }
+    pub fn new_recoverable(reader: BytesReader) -> Self {
+        Self {
+            recoverable: true,
+            ..Self::new(reader)
+        }
     }
     pub fn read_all(&mut self) -> Result<Vec<Amf0ValueType>, Amf0ReadError> {
         let mut results = vec![];
 
         loop {
-            let result = self.read_any()?;
-            match result {
-                Amf0ValueType::END => {
+            let result = self.read_any();
+            match self.recover(result)? {
+                Some(Amf0ValueType::END) | None => {
                     break;
                 }
-                _ => {
+                Some(result) => {
                     results.push(result);
                 }
             }
         }
         Ok(results)
     }
+    fn consume(&mut self, size: usize) -> Result<(), Amf0ReadError> {
+        self.decoded_size = self.decoded_size.saturating_add(size);
+        if self.decoded_size > MAX_DECODED_SIZE {
+            return Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::SizeLimitExceeded,
+            });
+        }
+        Ok(())
+    }
     pub fn read_any(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
         if self.reader.is_empty() {
             return Ok(Amf0ValueType::END);
         }
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(Amf0ReadError {
+                value: Amf0ReadErrorValue::NestingTooDeep,
+            });
+        }
+        self.consume(std::mem::size_of::<Amf0ValueType>())?;
+
+        self.depth += 1;
+        let result = self.read_value();
+        self.depth -= 1;
+        result
+    }
+    fn read_value(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
         let markers = self.reader.read_u8()?;
 
         if markers == amf0_markers::OBJECT_END {
@@ -46,16 +101,45 @@ impl Amf0Reader {
             amf0_markers::STRING => self.read_string(),
             amf0_markers::OBJECT => self.read_object(),
             amf0_markers::NULL => self.read_null(),
+            amf0_markers::UNDEFINED => Ok(Amf0ValueType::Undefined),
+            amf0_markers::REFERENCE => self.read_reference(),
             amf0_markers::ECMA_ARRAY => self.read_ecma_array(),
-            amf0_markers::LONG_STRING => self.read_long_string(),
             amf0_markers::STRICT_ARRAY => self.read_strict_array(),
+            amf0_markers::DATE => self.read_date(),
+            amf0_markers::LONG_STRING => self.read_long_string(),
+            amf0_markers::UNSUPPORTED => Ok(Amf0ValueType::Unsupported),
+            amf0_markers::XML_DOCUMENT => self.read_xml_document(),
+            amf0_markers::TYPED_OBJECT => self.read_typed_object(),
+            amf0_markers::AVMPLUS_OBJECT => self.read_avmplus_object(),
             _ => Err(Amf0ReadError {
                 value: Amf0ReadErrorValue::UnknownMarker { marker: markers },
             }),
         }
     }
+
+    //return none if the error is recovered by skipping the remaining bytes
+    fn recover<T>(&mut self, result: Result<T, Amf0ReadError>) -> Result<Option<T>, Amf0ReadError> {
+        match result {
+            Ok(val) => Ok(Some(val)),
+            Err(err) if self.recoverable => {
+                log::warn!(
+                    "amf0 reader skips the remaining {} bytes: {}",
+                    self.reader.len(),
+                    err
+                );
+                self.reader.extract_remaining_bytes();
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
     pub fn read_with_type(&mut self, specified_marker: u8) -> Result<Amf0ValueType, Amf0ReadError> {
-        let marker = self.reader.advance_u8()?;
+        let mut marker = self.reader.advance_u8()?;
+
+        //check the type of the amf3 value after the switch marker
+        if marker == amf0_markers::AVMPLUS_OBJECT {
+            marker = amf3_markers::to_amf0_marker(self.reader.get(1)?).unwrap_or(marker);
+        }
 
         if marker != specified_marker {
             return Err(Amf0ReadError {
@@ -86,6 +170,7 @@ impl Amf0Reader {
 
         let bytes = self.reader.read_bytes(l as usize)?;
         let val = String::from_utf8(bytes.to_vec())?;
+        self.consume(val.len())?;
 
         Ok(val)
     }
@@ -108,43 +193,117 @@ impl Amf0Reader {
         Ok(false)
     }
 
-    pub fn read_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+    fn read_property(&mut self) -> Result<Option<(String, Amf0ValueType)>, Amf0ReadError> {
+        if self.is_read_object_eof()? {
+            return Ok(None);
+        }
+
+        let key = self.read_raw_string()?;
+        let val = self.read_any()?;
+        Ok(Some((key, val)))
+    }
+
+    fn read_properties(&mut self) -> Result<IndexMap<String, Amf0ValueType>, Amf0ReadError> {
         let mut properties = IndexMap::new();
 
         loop {
-            let is_eof = self.is_read_object_eof()?;
-
-            if is_eof {
-                break;
+            let property = self.read_property();
+            match self.recover(property)? {
+                Some(Some((key, val))) => {
+                    properties.insert(key, val);
+                }
+                _ => break,
             }
+        }
 
-            let key = self.read_raw_string()?;
-            let val = self.read_any()?;
+        Ok(properties)
+    }
 
-            properties.insert(key, val);
-        }
+    //reserve the reference index before reading the members of a complex value
+    fn reserve_reference(&mut self) -> usize {
+        self.object_refs.push(None);
+        self.object_sizes.push(self.decoded_size);
+        self.object_refs.len() - 1
+    }
+
+    fn save_reference(&mut self, index: usize, value: &Amf0ValueType) {
+        self.object_refs[index] = Some(value.clone());
+        self.object_sizes[index] = self.decoded_size - self.object_sizes[index];
+    }
+
+    pub fn read_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
+        let properties = self.read_properties()?;
 
-        Ok(Amf0ValueType::Object(properties))
+        let value = Amf0ValueType::Object(properties);
+        self.save_reference(index, &value);
+        Ok(value)
     }
 
     pub fn read_ecma_array(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
         let len = self.reader.read_u32::<BigEndian>()?;
 
-        let mut properties = IndexMap::new();
-
         //here we do not use length to traverse the map, because in some
         //other media server, the length is 0 which is not correct.
-        while !self.is_read_object_eof()? {
-            let key = self.read_raw_string()?;
-            let val = self.read_any()?;
-            properties.insert(key, val);
-        }
+        let properties = self.read_properties()?;
 
         if len != properties.len() as u32 {
             log::warn!("the ecma array length is not correct!");
         }
 
-        Ok(Amf0ValueType::Object(properties))
+        let value = Amf0ValueType::Object(properties);
+        self.save_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_typed_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
+        let class_name = self.read_raw_string()?;
+        let properties = self.read_properties()?;
+
+        let value = Amf0ValueType::TypedObject {
+            class_name,
+            properties,
+        };
+        self.save_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_reference(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reader.read_u16::<BigEndian>()?;
+
+        let value = match self.object_refs.get(index as usize) {
+            Some(Some(value)) => value.clone(),
+            //the cyclic references cannot be represented by the values
+            _ => {
+                return Err(Amf0ReadError {
+                    value: Amf0ReadErrorValue::InvalidReference { index },
+                })
+            }
+        };
+        self.consume(self.object_sizes[index as usize])?;
+        Ok(value)
+    }
+
+    pub fn read_date(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let unix_time = self.reader.read_f64::<BigEndian>()?;
+        let time_zone = self.reader.read_u16::<BigEndian>()? as i16;
+
+        Ok(Amf0ValueType::Date {
+            unix_time,
+            time_zone,
+        })
+    }
+
+    pub fn read_xml_document(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let l = self.reader.read_u32::<BigEndian>()?;
+
+        let buff = self.reader.read_bytes(l as usize)?;
+
+        let val = String::from_utf8(buff.to_vec())?;
+        self.consume(val.len())?;
+        Ok(Amf0ValueType::XmlDocument(val))
     }
 
     pub fn read_long_string(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
@@ -153,20 +312,40 @@ impl Amf0Reader {
         let buff = self.reader.read_bytes(l as usize)?;
 
         let val = String::from_utf8(buff.to_vec())?;
+        self.consume(val.len())?;
         Ok(Amf0ValueType::LongUTF8String(val))
     }
 
     pub fn read_strict_array(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let index = self.reserve_reference();
         let l = self.reader.read_u32::<BigEndian>()?;
 
-        let mut properties = Vec::with_capacity(l as usize);
+        let mut properties = Vec::new();
 
         for _ in 0..l {
-            let val = self.read_any()?;
-            properties.push(val);
+            let val = self.read_any();
+            match self.recover(val)? {
+                Some(val) => properties.push(val),
+                None => break,
+            }
         }
-         
-        Ok(Amf0ValueType::StrictArray(properties))
+
+        let value = Amf0ValueType::StrictArray(properties);
+        self.save_reference(index, &value);
+        Ok(value)
+    }
+
+    /* The next value is encoded in amf3 and converted to amf0, the amf3 reference
+    tables are not shared between the switches. */
+    pub fn read_avmplus_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let reader = std::mem::replace(&mut self.reader, BytesReader::new(BytesMut::new()));
+        let mut amf3_reader = Amf3Reader::new(reader);
+        amf3_reader.set_decoded_size(self.decoded_size);
+        let result = amf3_reader.read_any();
+        self.decoded_size = amf3_reader.decoded_size();
+        self.reader = amf3_reader.into_reader();
+
+        Ok(result?.into())
     }
 
     // pub fn get_remaining_bytes(&mut self) -> BytesMut {
@@ -189,6 +368,7 @@ mod tests {
     }
 
     use super::amf0_markers;
+    use super::Amf0ReadErrorValue;
     use super::Amf0Reader;
     use super::Amf0ValueType;
 
@@ -410,4 +590,146 @@ mod tests {
 
         assert_eq!(strict_array, Amf0ValueType::StrictArray(expected));
     }
+
+    #[test]
+    fn test_avmplus_object() {
+        // the amf3 object {level: "status", code: 3} after the AVMPLUS_OBJECT marker
+        let data: [u8; 26] = [
+            0x11, 0x0a, 0x0b, 0x01, // AVMPLUS_OBJECT marker + amf3 dynamic anonymous object
+            0x0b, 0x6c, 0x65, 0x76, 0x65, 0x6c, // "level"
+            0x06, 0x0d, 0x73, 0x74, 0x61, 0x74, 0x75, 0x73, // STRING marker + "status"
+            0x09, 0x63, 0x6f, 0x64, 0x65, // "code"
+            0x04, 0x03, 0x01, // INTEGER marker + 3 + end of dynamic members
+        ];
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+
+        let object = amf_reader.read_with_type(amf0_markers::OBJECT).unwrap();
+
+        let mut properties = IndexMap::new();
+        properties.insert(
+            String::from("level"),
+            Amf0ValueType::UTF8String(String::from("status")),
+        );
+        properties.insert(String::from("code"), Amf0ValueType::Number(3.0));
+        assert_eq!(object, Amf0ValueType::Object(properties));
+        assert_eq!(amf_reader.read_any().unwrap(), Amf0ValueType::END);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        use super::super::amf0_writer::Amf0Writer;
+
+        let mut properties = IndexMap::new();
+        properties.insert(String::from("width"), Amf0ValueType::Number(1280.0));
+        properties.insert(String::from("encoder"), Amf0ValueType::Undefined);
+        let values = vec![
+            Amf0ValueType::TypedObject {
+                class_name: String::from("VideoInfo"),
+                properties,
+            },
+            Amf0ValueType::Date {
+                unix_time: 1_700_000_000_000.0,
+                time_zone: 0,
+            },
+            Amf0ValueType::XmlDocument(String::from("<a/>")),
+            Amf0ValueType::StrictArray(vec![Amf0ValueType::Unsupported]),
+            Amf0ValueType::LongUTF8String(String::from("long")),
+        ];
+
+        let mut amf_writer = Amf0Writer::new();
+        amf_writer.write_anys(&values).unwrap();
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&amf_writer.extract_current_bytes());
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+
+        assert_eq!(amf_reader.read_all().unwrap(), values);
+    }
+
+    #[test]
+    fn test_reference() {
+        let data: [u8; 16] = [
+            0x0a, 0x00, 0x00, 0x00, 0x02, // STRICT_ARRAY marker + length = 2
+            0x03, 0x00, 0x01, 0x61, 0x05, 0x00, 0x00, 0x09, // OBJECT {a: null}
+            0x07, 0x00, 0x00, // REFERENCE marker + index 0
+        ];
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+
+        let mut properties = IndexMap::new();
+        properties.insert(String::from("a"), Amf0ValueType::Null);
+        let object = Amf0ValueType::Object(properties);
+
+        // the index 0 is the strict array which is not read completely
+        assert!(amf_reader.read_any().is_err());
+
+        let mut data = data;
+        data[15] = 0x01;
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+        assert_eq!(
+            amf_reader.read_any().unwrap(),
+            Amf0ValueType::StrictArray(vec![object.clone(), object])
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        // each strict array references the previous one twice, the decoded size doubles
+        let mut data = vec![0x0a, 0x00, 0x00, 0x00, 0x00];
+        for index in 0..40u8 {
+            data.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x02]);
+            data.extend_from_slice(&[0x07, 0x00, index, 0x07, 0x00, index]);
+        }
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let err = Amf0Reader::new(bytes_reader).read_all().unwrap_err();
+        assert!(matches!(err.value, Amf0ReadErrorValue::SizeLimitExceeded));
+
+        // the strict arrays nested in the only value of each other
+        let mut data = Vec::new();
+        for _ in 0..100 {
+            data.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x01]);
+        }
+        data.push(0x05);
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let err = Amf0Reader::new(bytes_reader).read_any().unwrap_err();
+        assert!(matches!(err.value, Amf0ReadErrorValue::NestingTooDeep));
+    }
+
+    #[test]
+    fn test_recoverable() {
+        // "onMetaData" + ECMA_ARRAY {duration: 0, x: value of the unknown marker 0xff}
+        let data: [u8; 42] = [
+            0x02, 0x00, 0x0a, // STRING marker + length = 10
+            0x6f, 0x6e, 0x4d, 0x65, 0x74, 0x61, 0x44, 0x61, 0x74, 0x61, // "onMetaData"
+            0x08, 0x00, 0x00, 0x00, 0x02, // ECMA_ARRAY marker + length = 2
+            0x00, 0x08, 0x64, 0x75, 0x72, 0x61, 0x74, 0x69, 0x6f, 0x6e, // "duration"
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // NUMBER 0.0
+            0x00, 0x01, 0x78, 0xff, 0x00, // "x" + unknown marker
+        ];
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+        assert!(amf_reader.read_all().is_err());
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new_recoverable(bytes_reader);
+        let mut properties = IndexMap::new();
+        properties.insert(String::from("duration"), Amf0ValueType::Number(0.0));
+        assert_eq!(
+            amf_reader.read_all().unwrap(),
+            vec![
+                Amf0ValueType::UTF8String(String::from("onMetaData")),
+                Amf0ValueType::Object(properties),
+            ]
+        );
+    }
 }
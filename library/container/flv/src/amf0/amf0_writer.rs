@@ -1,5 +1,10 @@
 use {
-    super::{amf0_markers, errors::Amf0WriteErrorValue, Amf0ValueType, Amf0WriteError},
+    super::{
+        super::amf3::{amf3_writer::Amf3Writer, Amf3ValueType},
+        amf0_markers,
+        errors::Amf0WriteErrorValue,
+        Amf0ValueType, Amf0WriteError,
+    },
     byteorder::BigEndian,
     bytes::BytesMut,
     bytesio::bytes_writer::BytesWriter,
@@ -32,7 +37,20 @@ impl Amf0Writer {
             Amf0ValueType::UTF8String(ref val) => self.write_string(val),
             Amf0ValueType::Object(ref val) => self.write_object(val),
             Amf0ValueType::EcmaArray(ref val) => self.write_eacm_array(val),
-            _ => Ok(()),
+            Amf0ValueType::StrictArray(ref val) => self.write_strict_array(val),
+            Amf0ValueType::LongUTF8String(ref val) => self.write_long_string(val),
+            Amf0ValueType::Undefined => self.write_undefined(),
+            Amf0ValueType::Date {
+                ref unix_time,
+                ref time_zone,
+            } => self.write_date(unix_time, time_zone),
+            Amf0ValueType::XmlDocument(ref val) => self.write_xml_document(val),
+            Amf0ValueType::TypedObject {
+                ref class_name,
+                ref properties,
+            } => self.write_typed_object(class_name, properties),
+            Amf0ValueType::Unsupported => self.write_unsupported(),
+            Amf0ValueType::END => Ok(()),
         }
     }
 
@@ -62,11 +80,44 @@ impl Amf0Writer {
         Ok(())
     }
 
+    pub fn write_long_string(&mut self, value: &String) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::LONG_STRING)?;
+        self.writer.write_u32::<BigEndian>(value.len() as u32)?;
+        self.writer.write(value.as_bytes())?;
+
+        Ok(())
+    }
+
     pub fn write_null(&mut self) -> Result<(), Amf0WriteError> {
         self.writer.write_u8(amf0_markers::NULL)?;
         Ok(())
     }
 
+    pub fn write_undefined(&mut self) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::UNDEFINED)?;
+        Ok(())
+    }
+
+    pub fn write_unsupported(&mut self) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::UNSUPPORTED)?;
+        Ok(())
+    }
+
+    pub fn write_date(&mut self, unix_time: &f64, time_zone: &i16) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::DATE)?;
+        self.writer.write_f64::<BigEndian>(*unix_time)?;
+        self.writer.write_u16::<BigEndian>(*time_zone as u16)?;
+        Ok(())
+    }
+
+    pub fn write_xml_document(&mut self, value: &String) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::XML_DOCUMENT)?;
+        self.writer.write_u32::<BigEndian>(value.len() as u32)?;
+        self.writer.write(value.as_bytes())?;
+
+        Ok(())
+    }
+
     pub fn write_object_eof(&mut self) -> Result<(), Amf0WriteError> {
         self.writer
             .write_u24::<BigEndian>(amf0_markers::OBJECT_END as u32)?;
@@ -89,6 +140,32 @@ impl Amf0Writer {
         Ok(())
     }
 
+    pub fn write_typed_object(
+        &mut self,
+        class_name: &String,
+        properties: &IndexMap<String, Amf0ValueType>,
+    ) -> Result<(), Amf0WriteError> {
+        if class_name.len() > (u16::MAX as usize) {
+            return Err(Amf0WriteError {
+                value: Amf0WriteErrorValue::NormalStringTooLong,
+            });
+        }
+
+        self.writer.write_u8(amf0_markers::TYPED_OBJECT)?;
+        self.writer
+            .write_u16::<BigEndian>(class_name.len() as u16)?;
+        self.writer.write(class_name.as_bytes())?;
+
+        for (key, value) in properties {
+            self.writer.write_u16::<BigEndian>(key.len() as u16)?;
+            self.writer.write(key.as_bytes())?;
+            self.write_any(value)?;
+        }
+
+        self.write_object_eof()?;
+        Ok(())
+    }
+
     pub fn write_eacm_array(
         &mut self,
         properties: &IndexMap<String, Amf0ValueType>,
@@ -107,6 +184,29 @@ impl Amf0Writer {
         Ok(())
     }
 
+    pub fn write_strict_array(
+        &mut self,
+        values: &Vec<Amf0ValueType>,
+    ) -> Result<(), Amf0WriteError> {
+        self.writer.write_u8(amf0_markers::STRICT_ARRAY)?;
+        self.writer.write_u32::<BigEndian>(values.len() as u32)?;
+
+        for value in values {
+            self.write_any(value)?;
+        }
+        Ok(())
+    }
+
+    //switch to amf3 for the next value
+    pub fn write_avmplus_object(&mut self, value: &Amf3ValueType) -> Result<(), Amf0WriteError> {
+        let mut amf3_writer = Amf3Writer::new();
+        amf3_writer.write_any(value)?;
+
+        self.writer.write_u8(amf0_markers::AVMPLUS_OBJECT)?;
+        self.writer.write(&amf3_writer.extract_current_bytes())?;
+        Ok(())
+    }
+
     // pub async fn flush(&mut self) -> Result<(), Amf0WriteError> {
     //     self.writer.flush()?;
     // }
@@ -10,6 +10,18 @@ pub enum Amf0ValueType {
     Null,
     EcmaArray(IndexMap<String, Amf0ValueType>),
     LongUTF8String(String),
+    Undefined,
+    //milliseconds since the unix epoch, the time zone is reserved and should be 0
+    Date {
+        unix_time: f64,
+        time_zone: i16,
+    },
+    XmlDocument(String),
+    TypedObject {
+        class_name: String,
+        properties: IndexMap<String, Amf0ValueType>,
+    },
+    Unsupported,
     END,
 }
 
@@ -1,5 +1,6 @@
 #![allow(non_local_definitions)]
 use {
+    super::super::amf3::{Amf3ReadError, Amf3WriteError},
     bytesio::bytes_errors::{BytesReadError, BytesWriteError},
     failure::{Backtrace, Fail},
     std::{
@@ -17,6 +18,14 @@ pub enum Amf0ReadErrorValue {
     BytesReadError(BytesReadError),
     #[fail(display = "wrong type")]
     WrongType,
+    #[fail(display = "invalid reference: {}", index)]
+    InvalidReference { index: u16 },
+    #[fail(display = "amf3 read error: {}", _0)]
+    Amf3ReadError(Amf3ReadError),
+    #[fail(display = "the values are nested too deep")]
+    NestingTooDeep,
+    #[fail(display = "the decoded values exceed the size limit")]
+    SizeLimitExceeded,
 }
 
 #[derive(Debug)]
@@ -40,6 +49,14 @@ impl From<BytesReadError> for Amf0ReadError {
     }
 }
 
+impl From<Amf3ReadError> for Amf0ReadError {
+    fn from(error: Amf3ReadError) -> Self {
+        Amf0ReadError {
+            value: Amf0ReadErrorValue::Amf3ReadError(error),
+        }
+    }
+}
+
 #[derive(Debug, Fail)]
 pub enum Amf0WriteErrorValue {
     #[fail(display = "normal string too long")]
@@ -48,6 +65,8 @@ pub enum Amf0WriteErrorValue {
     BufferWriteError(io::Error),
     #[fail(display = "bytes write error")]
     BytesWriteError(BytesWriteError),
+    #[fail(display = "amf3 write error: {}", _0)]
+    Amf3WriteError(Amf3WriteError),
 }
 
 #[derive(Debug)]
@@ -71,6 +90,14 @@ impl From<BytesWriteError> for Amf0WriteError {
     }
 }
 
+impl From<Amf3WriteError> for Amf0WriteError {
+    fn from(error: Amf3WriteError) -> Self {
+        Amf0WriteError {
+            value: Amf0WriteErrorValue::Amf3WriteError(error),
+        }
+    }
+}
+
 impl fmt::Display for Amf0ReadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
@@ -0,0 +1,35 @@
+use super::super::amf0::amf0_markers;
+
+pub const UNDEFINED: u8 = 0x00;
+pub const NULL: u8 = 0x01;
+pub const FALSE: u8 = 0x02;
+pub const TRUE: u8 = 0x03;
+pub const INTEGER: u8 = 0x04;
+pub const DOUBLE: u8 = 0x05;
+pub const STRING: u8 = 0x06;
+pub const XML_DOC: u8 = 0x07;
+pub const DATE: u8 = 0x08;
+pub const ARRAY: u8 = 0x09;
+pub const OBJECT: u8 = 0x0a;
+pub const XML: u8 = 0x0b;
+pub const BYTE_ARRAY: u8 = 0x0c;
+pub const VECTOR_INT: u8 = 0x0d;
+pub const VECTOR_UINT: u8 = 0x0e;
+pub const VECTOR_DOUBLE: u8 = 0x0f;
+pub const VECTOR_OBJECT: u8 = 0x10;
+pub const DICTIONARY: u8 = 0x11;
+
+//the amf0 marker of the value converted from an amf3 value
+pub fn to_amf0_marker(marker: u8) -> Option<u8> {
+    match marker {
+        UNDEFINED => Some(amf0_markers::UNDEFINED),
+        NULL => Some(amf0_markers::NULL),
+        FALSE | TRUE => Some(amf0_markers::BOOLEAN),
+        INTEGER | DOUBLE => Some(amf0_markers::NUMBER),
+        STRING => Some(amf0_markers::STRING),
+        XML_DOC | XML => Some(amf0_markers::XML_DOCUMENT),
+        DATE => Some(amf0_markers::DATE),
+        OBJECT => Some(amf0_markers::OBJECT),
+        _ => None,
+    }
+}
@@ -0,0 +1,575 @@
+use {
+    super::{
+        amf3_markers,
+        errors::{Amf3ReadError, Amf3ReadErrorValue},
+        Amf3Object, Amf3Traits, Amf3ValueType,
+    },
+    byteorder::BigEndian,
+    bytesio::bytes_reader::BytesReader,
+    indexmap::IndexMap,
+};
+
+//a few bytes of nested references can expand to a huge value, so the decoded values are limited
+pub const MAX_NESTING_DEPTH: usize = 64;
+pub const MAX_DECODED_SIZE: usize = 16 * 1024 * 1024;
+
+/* The strings, the complex values and the object traits are sent once and then
+referenced by their indexes, so the reader keeps the tables of the values it has
+read. */
+pub struct Amf3Reader {
+    reader: BytesReader,
+    string_refs: Vec<String>,
+    //the slot is none until the complex value is read completely
+    object_refs: Vec<Option<Amf3ValueType>>,
+    //the decoded sizes of the complex values, which are consumed again by the references
+    object_sizes: Vec<usize>,
+    traits_refs: Vec<Amf3Traits>,
+    depth: usize,
+    decoded_size: usize,
+}
+
+impl Amf3Reader {
+    pub fn new(reader: BytesReader) -> Self {
+        Self {
+            reader,
+            string_refs: Vec::new(),
+            object_refs: Vec::new(),
+            object_sizes: Vec::new(),
+            traits_refs: Vec::new(),
+            depth: 0,
+            decoded_size: 0,
+        }
+    }
+
+    pub fn into_reader(self) -> BytesReader {
+        self.reader
+    }
+
+    pub fn read_all(&mut self) -> Result<Vec<Amf3ValueType>, Amf3ReadError> {
+        let mut results = vec![];
+
+        while !self.reader.is_empty() {
+            results.push(self.read_any()?);
+        }
+        Ok(results)
+    }
+
+    //the estimated memory size of all the values read, including the expanded references
+    pub fn decoded_size(&self) -> usize {
+        self.decoded_size
+    }
+
+    //continue with the size consumed by the previous readers of the same message
+    pub fn set_decoded_size(&mut self, decoded_size: usize) {
+        self.decoded_size = decoded_size;
+    }
+
+    fn consume(&mut self, size: usize) -> Result<(), Amf3ReadError> {
+        self.decoded_size = self.decoded_size.saturating_add(size);
+        if self.decoded_size > MAX_DECODED_SIZE {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::SizeLimitExceeded,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn read_any(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::NestingTooDeep,
+            });
+        }
+        self.consume(std::mem::size_of::<Amf3ValueType>())?;
+
+        self.depth += 1;
+        let result = self.read_value();
+        self.depth -= 1;
+        result
+    }
+
+    fn read_value(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let marker = self.reader.read_u8()?;
+
+        match marker {
+            amf3_markers::UNDEFINED => Ok(Amf3ValueType::Undefined),
+            amf3_markers::NULL => Ok(Amf3ValueType::Null),
+            amf3_markers::FALSE => Ok(Amf3ValueType::Boolean(false)),
+            amf3_markers::TRUE => Ok(Amf3ValueType::Boolean(true)),
+            amf3_markers::INTEGER => self.read_integer(),
+            amf3_markers::DOUBLE => self.read_double(),
+            amf3_markers::STRING => self.read_string(),
+            amf3_markers::XML_DOC => self.read_xml(true),
+            amf3_markers::DATE => self.read_date(),
+            amf3_markers::ARRAY => self.read_array(),
+            amf3_markers::OBJECT => self.read_object(),
+            amf3_markers::XML => self.read_xml(false),
+            amf3_markers::BYTE_ARRAY => self.read_byte_array(),
+            amf3_markers::VECTOR_INT
+            | amf3_markers::VECTOR_UINT
+            | amf3_markers::VECTOR_DOUBLE
+            | amf3_markers::VECTOR_OBJECT => self.read_vector(marker),
+            amf3_markers::DICTIONARY => self.read_dictionary(),
+            _ => Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::UnknownMarker { marker },
+            }),
+        }
+    }
+
+    //the variable length unsigned 29-bit integer
+    pub fn read_u29(&mut self) -> Result<u32, Amf3ReadError> {
+        let mut value: u32 = 0;
+
+        for _ in 0..3 {
+            let byte = self.reader.read_u8()? as u32;
+            value = (value << 7) | (byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        //all the 8 bits of the fourth byte are used
+        let byte = self.reader.read_u8()? as u32;
+        Ok((value << 8) | byte)
+    }
+
+    pub fn read_integer(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let value = self.read_u29()?;
+        //sign extend the 29-bit integer
+        Ok(Amf3ValueType::Integer(((value << 3) as i32) >> 3))
+    }
+
+    pub fn read_double(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        Ok(Amf3ValueType::Double(self.reader.read_f64::<BigEndian>()?))
+    }
+
+    //the string without the marker, which is also used by the class names and keys
+    pub fn read_raw_string(&mut self) -> Result<String, Amf3ReadError> {
+        let header = self.read_u29()?;
+        if header & 0x01 == 0 {
+            let index = (header >> 1) as usize;
+            let val = self.string_refs.get(index).cloned().ok_or(Amf3ReadError {
+                value: Amf3ReadErrorValue::InvalidReference { index },
+            })?;
+            self.consume(val.len())?;
+            return Ok(val);
+        }
+
+        let bytes = self.reader.read_bytes((header >> 1) as usize)?;
+        let val = String::from_utf8(bytes.to_vec())?;
+        self.consume(val.len())?;
+
+        //the empty string is never sent by reference
+        if !val.is_empty() {
+            self.string_refs.push(val.clone());
+        }
+        Ok(val)
+    }
+
+    pub fn read_string(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        Ok(Amf3ValueType::UTF8String(self.read_raw_string()?))
+    }
+
+    /* Read the header of a complex value, return the referenced value, or the
+    length and the reserved slot of the value which is sent inline. */
+    fn read_object_header(&mut self) -> Result<Result<(u32, usize), Amf3ValueType>, Amf3ReadError> {
+        let header = self.read_u29()?;
+
+        if header & 0x01 == 0 {
+            let index = (header >> 1) as usize;
+            let value = match self.object_refs.get(index) {
+                Some(Some(value)) => value.clone(),
+                //the cyclic references cannot be represented by the values
+                _ => {
+                    return Err(Amf3ReadError {
+                        value: Amf3ReadErrorValue::InvalidReference { index },
+                    })
+                }
+            };
+            self.consume(self.object_sizes[index])?;
+            return Ok(Err(value));
+        }
+
+        self.object_refs.push(None);
+        self.object_sizes.push(self.decoded_size);
+        Ok(Ok((header >> 1, self.object_refs.len() - 1)))
+    }
+
+    fn save_object_reference(&mut self, index: usize, value: &Amf3ValueType) {
+        self.object_refs[index] = Some(value.clone());
+        self.object_sizes[index] = self.decoded_size - self.object_sizes[index];
+    }
+
+    pub fn read_xml(&mut self, is_document: bool) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (len, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let bytes = self.reader.read_bytes(len as usize)?;
+        let val = String::from_utf8(bytes.to_vec())?;
+        self.consume(val.len())?;
+
+        let value = if is_document {
+            Amf3ValueType::XmlDocument(val)
+        } else {
+            Amf3ValueType::Xml(val)
+        };
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_date(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (_, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let value = Amf3ValueType::Date(self.reader.read_f64::<BigEndian>()?);
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (len, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        //the associative part ends with an empty key
+        let mut assoc = IndexMap::new();
+        loop {
+            let key = self.read_raw_string()?;
+            if key.is_empty() {
+                break;
+            }
+            let val = self.read_any()?;
+            assoc.insert(key, val);
+        }
+
+        let mut dense = Vec::new();
+        for _ in 0..len {
+            dense.push(self.read_any()?);
+        }
+
+        let value = Amf3ValueType::Array { assoc, dense };
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    fn read_traits(&mut self, header: u32) -> Result<Amf3Traits, Amf3ReadError> {
+        if header & 0x01 == 0 {
+            let index = (header >> 1) as usize;
+            return self.traits_refs.get(index).cloned().ok_or(Amf3ReadError {
+                value: Amf3ReadErrorValue::InvalidReference { index },
+            });
+        }
+
+        let class_name = self.read_raw_string()?;
+        let mut sealed_names = Vec::new();
+        for _ in 0..(header >> 3) {
+            sealed_names.push(self.read_raw_string()?);
+        }
+
+        let traits = Amf3Traits {
+            class_name,
+            is_externalizable: header & 0x02 != 0,
+            is_dynamic: header & 0x04 != 0,
+            sealed_names,
+        };
+        self.traits_refs.push(traits.clone());
+        Ok(traits)
+    }
+
+    pub fn read_object(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (header, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let traits = self.read_traits(header)?;
+        //the externalizable objects are serialized by the classes themselves
+        if traits.is_externalizable {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::ExternalizableObject {
+                    class_name: traits.class_name,
+                },
+            });
+        }
+
+        let mut sealed_properties = IndexMap::new();
+        for name in traits.sealed_names {
+            let val = self.read_any()?;
+            sealed_properties.insert(name, val);
+        }
+
+        let mut dynamic_properties = IndexMap::new();
+        if traits.is_dynamic {
+            loop {
+                let key = self.read_raw_string()?;
+                if key.is_empty() {
+                    break;
+                }
+                let val = self.read_any()?;
+                dynamic_properties.insert(key, val);
+            }
+        }
+
+        let value = Amf3ValueType::Object(Amf3Object {
+            class_name: traits.class_name,
+            is_dynamic: traits.is_dynamic,
+            sealed_properties,
+            dynamic_properties,
+        });
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_byte_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (len, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let bytes = self.reader.read_bytes(len as usize)?;
+        self.consume(bytes.len())?;
+
+        let value = Amf3ValueType::ByteArray(bytes.to_vec());
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_vector(&mut self, marker: u8) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (len, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let fixed = self.reader.read_u8()? != 0;
+        if marker != amf3_markers::VECTOR_OBJECT {
+            self.consume((len as usize).saturating_mul(std::mem::size_of::<f64>()))?;
+        }
+        let value = match marker {
+            amf3_markers::VECTOR_INT => {
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.reader.read_u32::<BigEndian>()? as i32);
+                }
+                Amf3ValueType::VectorInt { fixed, values }
+            }
+            amf3_markers::VECTOR_UINT => {
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.reader.read_u32::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorUInt { fixed, values }
+            }
+            amf3_markers::VECTOR_DOUBLE => {
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.reader.read_f64::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorDouble { fixed, values }
+            }
+            _ => {
+                let type_name = self.read_raw_string()?;
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.read_any()?);
+                }
+                Amf3ValueType::VectorObject {
+                    fixed,
+                    type_name,
+                    values,
+                }
+            }
+        };
+
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+
+    pub fn read_dictionary(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let (len, index) = match self.read_object_header()? {
+            Ok(val) => val,
+            Err(value) => return Ok(value),
+        };
+
+        let weak_keys = self.reader.read_u8()? != 0;
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            let key = self.read_any()?;
+            let val = self.read_any()?;
+            entries.push((key, val));
+        }
+
+        let value = Amf3ValueType::Dictionary { weak_keys, entries };
+        self.save_object_reference(index, &value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::amf3_writer::Amf3Writer;
+    use super::{Amf3Object, Amf3ReadErrorValue, Amf3Reader, Amf3ValueType};
+
+    use bytes::BytesMut;
+    use bytesio::bytes_reader::BytesReader;
+    use indexmap::IndexMap;
+
+    fn new_reader(data: &[u8]) -> Amf3Reader {
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(data);
+        Amf3Reader::new(bytes_reader)
+    }
+
+    #[test]
+    fn test_u29() {
+        // the boundaries of the 1 to 4 bytes encodings
+        let values = [
+            0,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            0x1f_ffff,
+            0x20_0000,
+            0x1fff_ffff,
+        ];
+        for value in values {
+            let mut amf_writer = Amf3Writer::new();
+            amf_writer.write_u29(value).unwrap();
+            let data = amf_writer.extract_current_bytes();
+
+            let mut amf_reader = new_reader(&data);
+            assert_eq!(amf_reader.read_u29().unwrap(), value);
+        }
+
+        let mut amf_reader = new_reader(&[0x04, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(amf_reader.read_any().unwrap(), Amf3ValueType::Integer(-1));
+    }
+
+    #[test]
+    fn test_references() {
+        // an array of an anonymous dynamic object {a: "b"} and the reference of the object
+        let data: [u8; 14] = [
+            0x09, 0x05, 0x01, // ARRAY marker + 2 dense values + empty associative part
+            0x0a, 0x0b, 0x01, // OBJECT marker + dynamic traits without sealed members + ""
+            0x03, 0x61, 0x06, 0x03, 0x62, 0x01, // a: "b" + end of dynamic members
+            0x0a, 0x02, // OBJECT marker + object reference 1
+        ];
+
+        let mut amf_reader = new_reader(&data);
+        let value = amf_reader.read_any().unwrap();
+
+        let mut dynamic_properties = IndexMap::new();
+        dynamic_properties.insert(
+            String::from("a"),
+            Amf3ValueType::UTF8String(String::from("b")),
+        );
+        let object = Amf3ValueType::Object(Amf3Object {
+            class_name: String::new(),
+            is_dynamic: true,
+            sealed_properties: IndexMap::new(),
+            dynamic_properties,
+        });
+        assert_eq!(
+            value,
+            Amf3ValueType::Array {
+                assoc: IndexMap::new(),
+                dense: vec![object.clone(), object],
+            }
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        // each array references the previous one twice, the decoded size doubles
+        let mut data = vec![0x09, 0x01, 0x01];
+        for index in 0..40u8 {
+            data.extend_from_slice(&[0x09, 0x05, 0x01, 0x09, index << 1, 0x09, index << 1]);
+        }
+        let err = new_reader(&data).read_all().unwrap_err();
+        assert!(matches!(err.value, Amf3ReadErrorValue::SizeLimitExceeded));
+
+        // the arrays nested in the only dense value of each other
+        let mut data = Vec::new();
+        for _ in 0..100 {
+            data.extend_from_slice(&[0x09, 0x03, 0x01]);
+        }
+        data.push(0x01);
+        let err = new_reader(&data).read_any().unwrap_err();
+        assert!(matches!(err.value, Amf3ReadErrorValue::NestingTooDeep));
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut sealed_properties = IndexMap::new();
+        sealed_properties.insert(String::from("width"), Amf3ValueType::Integer(1280));
+        sealed_properties.insert(String::from("height"), Amf3ValueType::Integer(720));
+        let object = Amf3ValueType::Object(Amf3Object {
+            class_name: String::from("VideoInfo"),
+            is_dynamic: false,
+            sealed_properties,
+            dynamic_properties: IndexMap::new(),
+        });
+
+        let mut assoc = IndexMap::new();
+        assoc.insert(
+            String::from("codec"),
+            Amf3ValueType::UTF8String(String::from("avc1")),
+        );
+        let values = vec![
+            Amf3ValueType::Null,
+            Amf3ValueType::Boolean(true),
+            Amf3ValueType::Integer(-(1 << 28)),
+            Amf3ValueType::Double(29.97),
+            Amf3ValueType::UTF8String(String::from("codec")),
+            Amf3ValueType::Date(1_700_000_000_000.0),
+            Amf3ValueType::Array {
+                assoc,
+                dense: vec![object.clone(), object],
+            },
+            Amf3ValueType::Xml(String::from("<a/>")),
+            Amf3ValueType::ByteArray(vec![0, 1, 2]),
+            Amf3ValueType::VectorInt {
+                fixed: false,
+                values: vec![-1, 2],
+            },
+            Amf3ValueType::VectorUInt {
+                fixed: true,
+                values: vec![u32::MAX],
+            },
+            Amf3ValueType::VectorDouble {
+                fixed: false,
+                values: vec![0.5],
+            },
+            Amf3ValueType::VectorObject {
+                fixed: false,
+                type_name: String::from("String"),
+                values: vec![Amf3ValueType::UTF8String(String::from("codec"))],
+            },
+            Amf3ValueType::Dictionary {
+                weak_keys: false,
+                entries: vec![(Amf3ValueType::Integer(1), Amf3ValueType::Undefined)],
+            },
+        ];
+
+        let mut amf_writer = Amf3Writer::new();
+        amf_writer.write_anys(&values).unwrap();
+        let data = amf_writer.extract_current_bytes();
+
+        let mut amf_reader = new_reader(&data);
+        assert_eq!(amf_reader.read_all().unwrap(), values);
+
+        // the integer out of the 29-bit range is written as a double
+        let mut amf_writer = Amf3Writer::new();
+        amf_writer.write_integer(i32::MAX).unwrap();
+        let mut amf_reader = new_reader(&amf_writer.extract_current_bytes());
+        assert_eq!(
+            amf_reader.read_any().unwrap(),
+            Amf3ValueType::Double(i32::MAX as f64)
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use {
+    super::{
+        amf3_markers,
+        errors::{Amf3WriteError, Amf3WriteErrorValue},
+        Amf3Object, Amf3Traits, Amf3ValueType,
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+    indexmap::IndexMap,
+    std::collections::HashMap,
+};
+
+const U29_MAX: u32 = 0x1fff_ffff;
+const INTEGER_MIN: i32 = -(1 << 28);
+const INTEGER_MAX: i32 = (1 << 28) - 1;
+
+/* The repeated strings and object traits are written by reference, the complex
+values are always written inline. */
+#[derive(Default)]
+pub struct Amf3Writer {
+    writer: BytesWriter,
+    string_refs: HashMap<String, usize>,
+    traits_refs: Vec<Amf3Traits>,
+}
+
+impl Amf3Writer {
+    pub fn new() -> Self {
+        Self {
+            writer: BytesWriter::new(),
+            string_refs: HashMap::new(),
+            traits_refs: Vec::new(),
+        }
+    }
+
+    pub fn write_anys(&mut self, values: &Vec<Amf3ValueType>) -> Result<(), Amf3WriteError> {
+        for val in values {
+            self.write_any(val)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_any(&mut self, value: &Amf3ValueType) -> Result<(), Amf3WriteError> {
+        match value {
+            Amf3ValueType::Undefined => self.write_marker(amf3_markers::UNDEFINED),
+            Amf3ValueType::Null => self.write_marker(amf3_markers::NULL),
+            Amf3ValueType::Boolean(val) => self.write_bool(*val),
+            Amf3ValueType::Integer(val) => self.write_integer(*val),
+            Amf3ValueType::Double(val) => self.write_double(*val),
+            Amf3ValueType::UTF8String(val) => self.write_string(val),
+            Amf3ValueType::XmlDocument(val) => self.write_xml(amf3_markers::XML_DOC, val),
+            Amf3ValueType::Date(val) => self.write_date(*val),
+            Amf3ValueType::Array { assoc, dense } => self.write_array(assoc, dense),
+            Amf3ValueType::Object(val) => self.write_object(val),
+            Amf3ValueType::Xml(val) => self.write_xml(amf3_markers::XML, val),
+            Amf3ValueType::ByteArray(val) => self.write_byte_array(val),
+            Amf3ValueType::VectorInt { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_INT, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_u32::<BigEndian>(*val as u32)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorUInt { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_UINT, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_u32::<BigEndian>(*val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorDouble { fixed, values } => {
+                self.write_vector_header(amf3_markers::VECTOR_DOUBLE, values.len(), *fixed)?;
+                for val in values {
+                    self.writer.write_f64::<BigEndian>(*val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorObject {
+                fixed,
+                type_name,
+                values,
+            } => {
+                self.write_vector_header(amf3_markers::VECTOR_OBJECT, values.len(), *fixed)?;
+                self.write_raw_string(type_name)?;
+                for val in values {
+                    self.write_any(val)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::Dictionary { weak_keys, entries } => {
+                self.write_marker(amf3_markers::DICTIONARY)?;
+                self.write_inline_header(entries.len())?;
+                self.writer.write_u8(*weak_keys as u8)?;
+                for (key, val) in entries {
+                    self.write_any(key)?;
+                    self.write_any(val)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn write_marker(&mut self, marker: u8) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(marker)?;
+        Ok(())
+    }
+
+    //the variable length unsigned 29-bit integer
+    pub fn write_u29(&mut self, value: u32) -> Result<(), Amf3WriteError> {
+        match value {
+            0..=0x7f => {
+                self.writer.write_u8(value as u8)?;
+            }
+            0x80..=0x3fff => {
+                self.writer.write_u8((value >> 7) as u8 | 0x80)?;
+                self.writer.write_u8((value & 0x7f) as u8)?;
+            }
+            0x4000..=0x1f_ffff => {
+                self.writer.write_u8((value >> 14) as u8 | 0x80)?;
+                self.writer.write_u8(((value >> 7) & 0x7f) as u8 | 0x80)?;
+                self.writer.write_u8((value & 0x7f) as u8)?;
+            }
+            0x20_0000..=U29_MAX => {
+                self.writer.write_u8((value >> 22) as u8 | 0x80)?;
+                self.writer.write_u8(((value >> 15) & 0x7f) as u8 | 0x80)?;
+                self.writer.write_u8(((value >> 8) & 0x7f) as u8 | 0x80)?;
+                self.writer.write_u8((value & 0xff) as u8)?;
+            }
+            _ => {
+                return Err(Amf3WriteError {
+                    value: Amf3WriteErrorValue::U29OutOfRange(value),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    //the length of a value which is sent inline instead of by reference
+    fn write_inline_header(&mut self, len: usize) -> Result<(), Amf3WriteError> {
+        if len > (U29_MAX >> 1) as usize {
+            return Err(Amf3WriteError {
+                value: Amf3WriteErrorValue::U29OutOfRange(len as u32),
+            });
+        }
+        self.write_u29(((len as u32) << 1) | 0x01)
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<(), Amf3WriteError> {
+        self.write_marker(if value {
+            amf3_markers::TRUE
+        } else {
+            amf3_markers::FALSE
+        })
+    }
+
+    //the integers out of the 29-bit range are written as doubles
+    pub fn write_integer(&mut self, value: i32) -> Result<(), Amf3WriteError> {
+        if !(INTEGER_MIN..=INTEGER_MAX).contains(&value) {
+            return self.write_double(value as f64);
+        }
+        self.write_marker(amf3_markers::INTEGER)?;
+        self.write_u29(value as u32 & U29_MAX)
+    }
+
+    pub fn write_double(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::DOUBLE)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    //the string without the marker, which is also used by the class names and keys
+    pub fn write_raw_string(&mut self, value: &str) -> Result<(), Amf3WriteError> {
+        if let Some(index) = self.string_refs.get(value) {
+            return self.write_u29((*index as u32) << 1);
+        }
+
+        self.write_inline_header(value.len())?;
+        self.writer.write(value.as_bytes())?;
+
+        //the empty string is never sent by reference
+        if !value.is_empty() {
+            self.string_refs
+                .insert(value.to_string(), self.string_refs.len());
+        }
+        Ok(())
+    }
+
+    pub fn write_string(&mut self, value: &str) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::STRING)?;
+        self.write_raw_string(value)
+    }
+
+    pub fn write_xml(&mut self, marker: u8, value: &str) -> Result<(), Amf3WriteError> {
+        self.write_marker(marker)?;
+        self.write_inline_header(value.len())?;
+        self.writer.write(value.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_date(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::DATE)?;
+        self.write_inline_header(0)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    pub fn write_array(
+        &mut self,
+        assoc: &IndexMap<String, Amf3ValueType>,
+        dense: &Vec<Amf3ValueType>,
+    ) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::ARRAY)?;
+        self.write_inline_header(dense.len())?;
+
+        for (key, val) in assoc {
+            self.write_raw_string(key)?;
+            self.write_any(val)?;
+        }
+        self.write_raw_string("")?;
+
+        for val in dense {
+            self.write_any(val)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_object(&mut self, object: &Amf3Object) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::OBJECT)?;
+
+        let traits = object.traits();
+        match self.traits_refs.iter().position(|val| *val == traits) {
+            Some(index) => {
+                self.write_u29(((index as u32) << 2) | 0x01)?;
+            }
+            None => {
+                let count = traits.sealed_names.len() as u32;
+                if count > (U29_MAX >> 4) {
+                    return Err(Amf3WriteError {
+                        value: Amf3WriteErrorValue::U29OutOfRange(count),
+                    });
+                }
+                self.write_u29((count << 4) | ((traits.is_dynamic as u32) << 3) | 0x03)?;
+                self.write_raw_string(&traits.class_name)?;
+                for name in &traits.sealed_names {
+                    self.write_raw_string(name)?;
+                }
+                self.traits_refs.push(traits);
+            }
+        }
+
+        for val in object.sealed_properties.values() {
+            self.write_any(val)?;
+        }
+
+        if object.is_dynamic {
+            for (key, val) in &object.dynamic_properties {
+                self.write_raw_string(key)?;
+                self.write_any(val)?;
+            }
+            self.write_raw_string("")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_byte_array(&mut self, value: &[u8]) -> Result<(), Amf3WriteError> {
+        self.write_marker(amf3_markers::BYTE_ARRAY)?;
+        self.write_inline_header(value.len())?;
+        self.writer.write(value)?;
+        Ok(())
+    }
+
+    fn write_vector_header(
+        &mut self,
+        marker: u8,
+        len: usize,
+        fixed: bool,
+    ) -> Result<(), Amf3WriteError> {
+        self.write_marker(marker)?;
+        self.write_inline_header(len)?;
+        self.writer.write_u8(fixed as u8)?;
+        Ok(())
+    }
+
+    pub fn extract_current_bytes(&mut self) -> BytesMut {
+        self.writer.extract_current_bytes()
+    }
+
+    pub fn get_current_bytes(&mut self) -> BytesMut {
+        self.writer.get_current_bytes()
+    }
+
+    pub fn len(&self) -> usize {
+        self.writer.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
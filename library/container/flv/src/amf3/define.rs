@@ -0,0 +1,170 @@
+use {super::super::amf0::Amf0ValueType, indexmap::IndexMap};
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Amf3ValueType {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Integer(i32),
+    Double(f64),
+    UTF8String(String),
+    XmlDocument(String),
+    //milliseconds since the unix epoch
+    Date(f64),
+    Array {
+        assoc: IndexMap<String, Amf3ValueType>,
+        dense: Vec<Amf3ValueType>,
+    },
+    Object(Amf3Object),
+    Xml(String),
+    ByteArray(Vec<u8>),
+    VectorInt {
+        fixed: bool,
+        values: Vec<i32>,
+    },
+    VectorUInt {
+        fixed: bool,
+        values: Vec<u32>,
+    },
+    VectorDouble {
+        fixed: bool,
+        values: Vec<f64>,
+    },
+    VectorObject {
+        fixed: bool,
+        type_name: String,
+        values: Vec<Amf3ValueType>,
+    },
+    Dictionary {
+        weak_keys: bool,
+        entries: Vec<(Amf3ValueType, Amf3ValueType)>,
+    },
+}
+
+//an anonymous object has an empty class name
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Amf3Object {
+    pub class_name: String,
+    pub is_dynamic: bool,
+    pub sealed_properties: IndexMap<String, Amf3ValueType>,
+    pub dynamic_properties: IndexMap<String, Amf3ValueType>,
+}
+
+impl Amf3Object {
+    pub fn traits(&self) -> Amf3Traits {
+        Amf3Traits {
+            class_name: self.class_name.clone(),
+            is_dynamic: self.is_dynamic,
+            is_externalizable: false,
+            sealed_names: self.sealed_properties.keys().cloned().collect(),
+        }
+    }
+}
+
+//the class description of an object which can be referenced by the later objects
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Amf3Traits {
+    pub class_name: String,
+    pub is_dynamic: bool,
+    pub is_externalizable: bool,
+    pub sealed_names: Vec<String>,
+}
+
+/* The amf3 values are converted to amf0 to be handled like the values of the
+amf0 commands, the types which amf0 does not have are converted to the closest
+ones. */
+impl From<Amf3ValueType> for Amf0ValueType {
+    fn from(value: Amf3ValueType) -> Self {
+        match value {
+            Amf3ValueType::Undefined => Amf0ValueType::Undefined,
+            Amf3ValueType::Null => Amf0ValueType::Null,
+            Amf3ValueType::Boolean(val) => Amf0ValueType::Boolean(val),
+            Amf3ValueType::Integer(val) => Amf0ValueType::Number(val as f64),
+            Amf3ValueType::Double(val) => Amf0ValueType::Number(val),
+            Amf3ValueType::Date(val) => Amf0ValueType::Date {
+                unix_time: val,
+                time_zone: 0,
+            },
+            Amf3ValueType::UTF8String(val) => {
+                if val.len() > u16::MAX as usize {
+                    Amf0ValueType::LongUTF8String(val)
+                } else {
+                    Amf0ValueType::UTF8String(val)
+                }
+            }
+            Amf3ValueType::XmlDocument(val) | Amf3ValueType::Xml(val) => {
+                Amf0ValueType::XmlDocument(val)
+            }
+            Amf3ValueType::Array { assoc, dense } => {
+                if assoc.is_empty() {
+                    return Amf0ValueType::StrictArray(dense.into_iter().map(Into::into).collect());
+                }
+                let mut properties: IndexMap<String, Amf0ValueType> = dense
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, val)| (idx.to_string(), val.into()))
+                    .collect();
+                for (key, val) in assoc {
+                    properties.insert(key, val.into());
+                }
+                Amf0ValueType::EcmaArray(properties)
+            }
+            Amf3ValueType::Object(object) => {
+                let mut properties = IndexMap::new();
+                for (key, val) in object
+                    .sealed_properties
+                    .into_iter()
+                    .chain(object.dynamic_properties)
+                {
+                    properties.insert(key, val.into());
+                }
+                if object.class_name.is_empty() {
+                    Amf0ValueType::Object(properties)
+                } else {
+                    Amf0ValueType::TypedObject {
+                        class_name: object.class_name,
+                        properties,
+                    }
+                }
+            }
+            Amf3ValueType::ByteArray(val) => Amf0ValueType::StrictArray(
+                val.into_iter()
+                    .map(|byte| Amf0ValueType::Number(byte as f64))
+                    .collect(),
+            ),
+            Amf3ValueType::VectorInt { values, .. } => Amf0ValueType::StrictArray(
+                values
+                    .into_iter()
+                    .map(|val| Amf0ValueType::Number(val as f64))
+                    .collect(),
+            ),
+            Amf3ValueType::VectorUInt { values, .. } => Amf0ValueType::StrictArray(
+                values
+                    .into_iter()
+                    .map(|val| Amf0ValueType::Number(val as f64))
+                    .collect(),
+            ),
+            Amf3ValueType::VectorDouble { values, .. } => {
+                Amf0ValueType::StrictArray(values.into_iter().map(Amf0ValueType::Number).collect())
+            }
+            Amf3ValueType::VectorObject { values, .. } => {
+                Amf0ValueType::StrictArray(values.into_iter().map(Into::into).collect())
+            }
+            //only the entries with the primitive keys can be the amf0 properties
+            Amf3ValueType::Dictionary { entries, .. } => {
+                let mut properties = IndexMap::new();
+                for (key, val) in entries {
+                    let key = match key {
+                        Amf3ValueType::UTF8String(key) => key,
+                        Amf3ValueType::Integer(key) => key.to_string(),
+                        Amf3ValueType::Double(key) => key.to_string(),
+                        Amf3ValueType::Boolean(key) => key.to_string(),
+                        _ => continue,
+                    };
+                    properties.insert(key, val.into());
+                }
+                Amf0ValueType::EcmaArray(properties)
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod amf0;
+pub mod amf3;
 pub mod define;
 pub mod demuxer;
 pub mod errors;
@@ -7,6 +8,7 @@ pub mod mpeg4_aac;
 pub mod mpeg4_avc;
 pub mod mpeg4_hevc;
 pub mod muxer;
+pub mod script_data;
 
 pub trait Unmarshal<T1, T2> {
     fn unmarshal(reader: T1) -> T2
@@ -0,0 +1,73 @@
+use {
+    crate::amf0::{amf0_reader::Amf0Reader, amf0_writer::Amf0Writer, Amf0ValueType},
+    crate::amf0::{Amf0ReadError, Amf0WriteError},
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    indexmap::IndexMap,
+};
+
+pub const ON_TEXT_DATA: &str = "onTextData";
+
+/* The onTextData script data of a text message, it is the same as the one
+written by ffmpeg for the subtitle streams: onTextData {type: "Text", text: ...} */
+pub fn marshal_text_data(text: &str) -> Result<BytesMut, Amf0WriteError> {
+    let mut amf_writer = Amf0Writer::new();
+    amf_writer.write_string(&String::from(ON_TEXT_DATA))?;
+
+    let mut properties = IndexMap::new();
+    properties.insert(
+        String::from("type"),
+        Amf0ValueType::UTF8String(String::from("Text")),
+    );
+    properties.insert(
+        String::from("text"),
+        Amf0ValueType::UTF8String(text.to_string()),
+    );
+    amf_writer.write_eacm_array(&properties)?;
+
+    Ok(amf_writer.extract_current_bytes())
+}
+
+/* Get the text of an onTextData script data, None is returned for the other
+script data(e.g. onMetaData). */
+pub fn unmarshal_text_data(data: &BytesMut) -> Result<Option<String>, Amf0ReadError> {
+    let mut amf_reader = Amf0Reader::new(BytesReader::new(data.clone()));
+
+    match amf_reader.read_any()? {
+        Amf0ValueType::UTF8String(name) if name == ON_TEXT_DATA => {}
+        _ => return Ok(None),
+    }
+
+    if let Amf0ValueType::Object(properties) = amf_reader.read_any()? {
+        if let Some(Amf0ValueType::UTF8String(text)) = properties.get("text") {
+            return Ok(Some(text.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{marshal_text_data, unmarshal_text_data};
+    use crate::amf0::amf0_writer::Amf0Writer;
+
+    #[test]
+    fn test_text_data() {
+        let data = marshal_text_data("hello xiu").unwrap();
+        assert_eq!(
+            unmarshal_text_data(&data).unwrap(),
+            Some(String::from("hello xiu"))
+        );
+
+        let mut amf_writer = Amf0Writer::new();
+        amf_writer
+            .write_string(&String::from("@setDataFrame"))
+            .unwrap();
+        amf_writer
+            .write_string(&String::from("onMetaData"))
+            .unwrap();
+        let metadata = amf_writer.extract_current_bytes();
+        assert_eq!(unmarshal_text_data(&metadata).unwrap(), None);
+    }
+}
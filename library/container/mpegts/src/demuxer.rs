@@ -0,0 +1,239 @@
+use {
+    super::define::{self, epat_pid},
+    bytes::BytesMut,
+    std::collections::HashMap,
+};
+
+// A PES packet of an elementary stream, the pts/dts are in 90kHz.
+#[derive(Debug, Clone)]
+pub struct TsFrame {
+    pub pid: u16,
+    pub stream_type: u8,
+    pub pts: i64,
+    pub dts: i64,
+    pub is_random_access: bool,
+    pub data: BytesMut,
+}
+
+struct TsDemuxStream {
+    stream_type: u8,
+    frame: Option<TsFrame>,
+}
+
+// Demux the TS packets into PES payloads, the payload of a PES packet is
+// returned when the next PES packet of the same stream starts.
+#[derive(Default)]
+pub struct TsDemuxer {
+    pmt_pids: Vec<u16>,
+    streams: HashMap<u16, TsDemuxStream>,
+}
+
+impl TsDemuxer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn demux(&mut self, data: &[u8]) -> Vec<TsFrame> {
+        let mut frames = Vec::new();
+        for packet in data.chunks_exact(define::TS_PACKET_SIZE) {
+            if packet[0] != 0x47 {
+                log::warn!("ts demux: sync byte not found");
+                continue;
+            }
+            if let Some(frame) = self.demux_packet(packet) {
+                frames.push(frame);
+            }
+        }
+        frames
+    }
+
+    //return the remaining PES payloads at the end of the file
+    pub fn flush(&mut self) -> Vec<TsFrame> {
+        let mut frames: Vec<TsFrame> = self
+            .streams
+            .values_mut()
+            .filter_map(|stream| stream.frame.take())
+            .collect();
+        frames.sort_by_key(|frame| frame.dts);
+        frames
+    }
+
+    fn demux_packet(&mut self, packet: &[u8]) -> Option<TsFrame> {
+        let pid = (((packet[1] & 0x1F) as u16) << 8) | packet[2] as u16;
+        let is_start = (packet[1] & define::TS_PAYLOAD_UNIT_START_INDICATOR) > 0;
+        let adaptation_field_control = (packet[3] >> 4) & 0x03;
+
+        let mut offset = define::TS_HEADER_LEN as usize;
+        let mut is_random_access = false;
+        if adaptation_field_control & 0x02 > 0 {
+            let adaptation_field_length = packet[4] as usize;
+            if adaptation_field_length > 0 {
+                is_random_access = packet[5] & define::AF_FLAG_RANDOM_ACCESS_INDICATOR > 0;
+            }
+            offset += 1 + adaptation_field_length;
+        }
+        if adaptation_field_control & 0x01 == 0 || offset >= packet.len() {
+            return None;
+        }
+        let payload = &packet[offset..];
+
+        if pid == epat_pid::PAT_TID_PAS {
+            self.parse_pat(Self::section(payload, is_start)?);
+            None
+        } else if self.pmt_pids.contains(&pid) {
+            self.parse_pmt(Self::section(payload, is_start)?);
+            None
+        } else {
+            self.on_pes_payload(pid, payload, is_start, is_random_access)
+        }
+    }
+
+    //skip the pointer field and return the section without the crc32
+    fn section(payload: &[u8], is_start: bool) -> Option<&[u8]> {
+        if !is_start {
+            return None;
+        }
+        let section = payload.get(1 + payload[0] as usize..)?;
+        let section_length = (((section.get(1)? & 0x0F) as usize) << 8) | *section.get(2)? as usize;
+        section.get(..(3 + section_length).checked_sub(4)?)
+    }
+
+    fn parse_pat(&mut self, section: &[u8]) {
+        self.pmt_pids.clear();
+        for program in section.get(8..).unwrap_or_default().chunks_exact(4) {
+            let program_number = ((program[0] as u16) << 8) | program[1] as u16;
+            if program_number != 0 {
+                self.pmt_pids
+                    .push((((program[2] & 0x1F) as u16) << 8) | program[3] as u16);
+            }
+        }
+    }
+
+    fn parse_pmt(&mut self, section: &[u8]) {
+        let Some(program_info) = section.get(10..12) else {
+            return;
+        };
+        let program_info_length =
+            (((program_info[0] & 0x0F) as usize) << 8) | program_info[1] as usize;
+
+        let mut streams = section.get(12 + program_info_length..).unwrap_or_default();
+        while streams.len() >= 5 {
+            let stream_type = streams[0];
+            let pid = (((streams[1] & 0x1F) as u16) << 8) | streams[2] as u16;
+            let es_info_length = (((streams[3] & 0x0F) as usize) << 8) | streams[4] as usize;
+
+            self.streams.entry(pid).or_insert(TsDemuxStream {
+                stream_type,
+                frame: None,
+            });
+            streams = streams.get(5 + es_info_length..).unwrap_or_default();
+        }
+    }
+
+    fn on_pes_payload(
+        &mut self,
+        pid: u16,
+        payload: &[u8],
+        is_start: bool,
+        is_random_access: bool,
+    ) -> Option<TsFrame> {
+        let stream = self.streams.get_mut(&pid)?;
+
+        if !is_start {
+            if let Some(frame) = &mut stream.frame {
+                frame.data.extend_from_slice(payload);
+            }
+            return None;
+        }
+
+        //00 00 01 | stream id | packet length(2) | flags(2) | header data length
+        if payload.len() < 9 || payload[..3] != [0x00, 0x00, 0x01] {
+            return None;
+        }
+        let flags = payload[7];
+        let header_end = 9 + payload[8] as usize;
+
+        let pts = if flags & 0x80 > 0 {
+            payload.get(9..14).map(Self::read_timestamp)?
+        } else {
+            define::PTS_NO_VALUE
+        };
+        let dts = if flags & 0x40 > 0 {
+            payload.get(14..19).map(Self::read_timestamp)?
+        } else {
+            pts
+        };
+
+        let frame = TsFrame {
+            pid,
+            stream_type: stream.stream_type,
+            pts,
+            dts,
+            is_random_access,
+            data: BytesMut::from(payload.get(header_end..)?),
+        };
+
+        stream.frame.replace(frame)
+    }
+
+    fn read_timestamp(data: &[u8]) -> i64 {
+        (((data[0] >> 1) & 0x07) as i64) << 30
+            | (data[1] as i64) << 22
+            | ((data[2] >> 1) as i64) << 15
+            | (data[3] as i64) << 7
+            | (data[4] >> 1) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TsDemuxer;
+    use crate::define::{epsi_stream_type, MPEG_FLAG_IDR_FRAME};
+    use crate::ts::TsMuxer;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_demux_muxed_frames() {
+        let mut ts_muxer = TsMuxer::new();
+        let audio_pid = ts_muxer
+            .add_stream(epsi_stream_type::PSI_STREAM_AAC, BytesMut::new())
+            .unwrap();
+        let video_pid = ts_muxer
+            .add_stream(epsi_stream_type::PSI_STREAM_H264, BytesMut::new())
+            .unwrap();
+
+        let video = BytesMut::from(&[0x00, 0x00, 0x00, 0x01, 0x65, 0x88][..]);
+        let mut large_video = BytesMut::from(&[0x00, 0x00, 0x00, 0x01, 0x41][..]);
+        large_video.extend_from_slice(&[0x9A; 1000]);
+        let audio = BytesMut::from(&[0xFF, 0xF1, 0x50, 0x80, 0x01, 0x7F, 0xFC, 0x21][..]);
+
+        ts_muxer
+            .write(video_pid, 9000, 3000, MPEG_FLAG_IDR_FRAME, video.clone())
+            .unwrap();
+        ts_muxer
+            .write(audio_pid, 3000, 3000, 0, audio.clone())
+            .unwrap();
+        ts_muxer
+            .write(video_pid, 12000, 6000, 0, large_video.clone())
+            .unwrap();
+
+        let mut demuxer = TsDemuxer::new();
+        let mut frames = demuxer.demux(&ts_muxer.get_data()[..]);
+        frames.extend(demuxer.flush());
+        assert_eq!(frames.len(), 3);
+
+        //the muxer inserts an access unit delimiter before the h264 data
+        let key_frame = frames.iter().find(|frame| frame.pts == 9000).unwrap();
+        assert_eq!(key_frame.stream_type, epsi_stream_type::PSI_STREAM_H264);
+        assert_eq!(key_frame.dts, 3000);
+        assert!(key_frame.is_random_access);
+        assert!(key_frame.data.ends_with(&video[..]));
+
+        let audio_frame = frames.iter().find(|frame| frame.pid == audio_pid).unwrap();
+        assert_eq!(audio_frame.data, audio);
+
+        let inter_frame = frames.iter().find(|frame| frame.pts == 12000).unwrap();
+        assert!(!inter_frame.is_random_access);
+        assert!(inter_frame.data.ends_with(&large_video[..]));
+    }
+}
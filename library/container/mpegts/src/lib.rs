@@ -1,5 +1,6 @@
 pub mod crc32;
 pub mod define;
+pub mod demuxer;
 pub mod errors;
 pub mod ts;
 pub mod pat;
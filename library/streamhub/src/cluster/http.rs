@@ -0,0 +1,141 @@
+use {
+    super::{StreamLocation, StreamRegistry},
+    crate::stream::StreamIdentifier,
+    async_trait::async_trait,
+    config::ClusterConfig,
+    reqwest::Client,
+    serde_json::{json, Value},
+    std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+const DEFAULT_TTL: u64 = 30;
+//a request to an unreachable registry must not hang
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/* The registry client talking to the registry node by its http api:
+/api/cluster/register, /api/cluster/unregister and /api/cluster/resolve */
+pub struct HttpStreamRegistry {
+    request_client: Client,
+    registry_url: String,
+    advertise_address: Option<String>,
+    ttl: u64,
+    /* the local streams which are refreshed periodically before they expire */
+    registered_streams: Arc<Mutex<HashSet<StreamIdentifier>>>,
+}
+
+impl HttpStreamRegistry {
+    pub fn new(config: ClusterConfig) -> Self {
+        let registry = Self {
+            request_client: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            registry_url: config.registry.trim_end_matches('/').to_string(),
+            advertise_address: config.advertise_address,
+            ttl: config.ttl.unwrap_or(DEFAULT_TTL).max(1),
+            registered_streams: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        if let Some(address) = &registry.advertise_address {
+            let client = registry.request_client.clone();
+            let url = format!("{}/api/cluster/register", registry.registry_url);
+            let address = address.clone();
+            let ttl = registry.ttl;
+            let registered_streams = registry.registered_streams.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(ttl.div_ceil(3)));
+                loop {
+                    interval.tick().await;
+                    let identifiers: Vec<StreamIdentifier> =
+                        registered_streams.lock().unwrap().iter().cloned().collect();
+                    for identifier in identifiers {
+                        let location = StreamLocation {
+                            identifier,
+                            address: address.clone(),
+                            ttl,
+                        };
+                        post(&client, &url, json!(location)).await;
+                    }
+                }
+            });
+        }
+
+        registry
+    }
+
+    fn location(&self, identifier: &StreamIdentifier) -> Option<StreamLocation> {
+        self.advertise_address
+            .as_ref()
+            .map(|address| StreamLocation {
+                identifier: identifier.clone(),
+                address: address.clone(),
+                ttl: self.ttl,
+            })
+    }
+}
+
+async fn post(client: &Client, url: &str, body: Value) -> Option<Value> {
+    match client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+    {
+        Err(err) => {
+            log::error!("cluster registry request {} error: {}", url, err);
+            None
+        }
+        Ok(response) => match response.text().await {
+            Ok(text) => serde_json::from_str(&text).ok(),
+            Err(err) => {
+                log::error!("cluster registry response {} error: {}", url, err);
+                None
+            }
+        },
+    }
+}
+
+#[async_trait]
+impl StreamRegistry for HttpStreamRegistry {
+    async fn register(&self, identifier: &StreamIdentifier) {
+        if let Some(location) = self.location(identifier) {
+            self.registered_streams
+                .lock()
+                .unwrap()
+                .insert(identifier.clone());
+            let url = format!("{}/api/cluster/register", self.registry_url);
+            post(&self.request_client, &url, json!(location)).await;
+        }
+    }
+
+    async fn unregister(&self, identifier: &StreamIdentifier) {
+        if let Some(location) = self.location(identifier) {
+            self.registered_streams.lock().unwrap().remove(identifier);
+            let url = format!("{}/api/cluster/unregister", self.registry_url);
+            post(&self.request_client, &url, json!(location)).await;
+        }
+    }
+
+    async fn resolve(&self, identifier: &StreamIdentifier) -> Option<String> {
+        let url = format!("{}/api/cluster/resolve", self.registry_url);
+        let response = post(
+            &self.request_client,
+            &url,
+            json!({ "identifier": identifier }),
+        )
+        .await?;
+
+        let address = response.get("data")?.as_str()?.to_string();
+        //do not pull the stream from this node itself
+        if Some(&address) == self.advertise_address.as_ref() {
+            return None;
+        }
+        Some(address)
+    }
+}
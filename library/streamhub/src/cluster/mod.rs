@@ -0,0 +1,142 @@
+pub mod http;
+
+use {
+    crate::stream::StreamIdentifier,
+    async_trait::async_trait,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tokio::sync::mpsc,
+};
+
+/* The registry of the stream locations in a cluster. Origins register their
+published streams, edges resolve the origin of a stream they do not hold. */
+#[async_trait]
+pub trait StreamRegistry: Sync + Send {
+    async fn register(&self, identifier: &StreamIdentifier);
+    async fn unregister(&self, identifier: &StreamIdentifier);
+    /* return the rtmp address of the origin which holds the stream */
+    async fn resolve(&self, identifier: &StreamIdentifier) -> Option<String>;
+}
+
+pub enum RegistryEvent {
+    Register(StreamIdentifier),
+    Unregister(StreamIdentifier),
+}
+
+/* The registrations are sent in order by a task, so the register and unregister
+of the same stream are not reordered. */
+pub fn spawn_registration_task(
+    stream_registry: Arc<dyn StreamRegistry>,
+) -> mpsc::UnboundedSender<RegistryEvent> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            match event {
+                RegistryEvent::Register(identifier) => {
+                    stream_registry.register(&identifier).await;
+                }
+                RegistryEvent::Unregister(identifier) => {
+                    stream_registry.unregister(&identifier).await;
+                }
+            }
+        }
+    });
+
+    sender
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamLocation {
+    pub identifier: StreamIdentifier,
+    //the rtmp address(ip:port) of the origin
+    pub address: String,
+    //seconds
+    pub ttl: u64,
+}
+
+/* The in-memory registry served by the registry node. A location expires if the
+origin does not refresh it within the ttl, so the streams of a crashed origin
+are not resolved for long. */
+#[derive(Default)]
+pub struct StreamLocationStore {
+    locations: Mutex<HashMap<StreamIdentifier, (String, Instant)>>,
+}
+
+impl StreamLocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, location: StreamLocation) {
+        let expire_time = Instant::now() + Duration::from_secs(location.ttl);
+        self.locations
+            .lock()
+            .unwrap()
+            .insert(location.identifier, (location.address, expire_time));
+    }
+
+    /* only the origin holding the stream can unregister it */
+    pub fn unregister(&self, identifier: &StreamIdentifier, address: &str) {
+        let mut locations = self.locations.lock().unwrap();
+        if let Some((location_address, _)) = locations.get(identifier) {
+            if location_address == address {
+                locations.remove(identifier);
+            }
+        }
+    }
+
+    pub fn resolve(&self, identifier: &StreamIdentifier) -> Option<String> {
+        let mut locations = self.locations.lock().unwrap();
+        let now = Instant::now();
+        locations.retain(|_, (_, expire_time)| *expire_time > now);
+        locations
+            .get(identifier)
+            .map(|(address, _)| address.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StreamLocation, StreamLocationStore};
+    use crate::stream::StreamIdentifier;
+
+    fn identifier(stream_name: &str) -> StreamIdentifier {
+        StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from(stream_name),
+        }
+    }
+
+    #[test]
+    fn test_stream_location_store() {
+        let store = StreamLocationStore::new();
+        store.register(StreamLocation {
+            identifier: identifier("a"),
+            address: String::from("192.168.0.2:1935"),
+            ttl: 30,
+        });
+        store.register(StreamLocation {
+            identifier: identifier("b"),
+            address: String::from("192.168.0.3:1935"),
+            ttl: 0,
+        });
+
+        assert_eq!(
+            store.resolve(&identifier("a")),
+            Some(String::from("192.168.0.2:1935"))
+        );
+        //expired
+        assert_eq!(store.resolve(&identifier("b")), None);
+
+        //another origin cannot unregister the stream
+        store.unregister(&identifier("a"), "192.168.0.3:1935");
+        assert!(store.resolve(&identifier("a")).is_some());
+        store.unregister(&identifier("a"), "192.168.0.2:1935");
+        assert_eq!(store.resolve(&identifier("a")), None);
+    }
+}
@@ -7,7 +7,7 @@ use crate::utils;
 
 use {
     super::errors::StreamHubError,
-    crate::statistics::StatisticsStream,
+    crate::statistics::{RestreamState, StatisticsStream},
     crate::stream::StreamIdentifier,
     async_trait::async_trait,
     bytes::BytesMut,
@@ -45,6 +45,8 @@ pub enum SubscribeType {
     WhipRelay,
     /* Pull rtp stream by subscribing from stream hub.*/
     RtpPull,
+    /* The play of a RTMP published stream by rtsp clients triggers remuxing from RTMP to RTSP protocol.*/
+    RtmpRemux2Rtsp,
 }
 
 /* Publish streams to stream hub */
@@ -64,6 +66,10 @@ pub enum PublishType {
     WhepRelay,
     /* It used for publishing raw rtp data of rtsp/whbrtc(whip) */
     RtpPush,
+    /* Publish the RTSP stream remuxed from a RTMP stream. */
+    RtmpRemux2Rtsp,
+    /* Publish a simulcast layer(the rtp packets of one encoding) of a whip stream. */
+    WhipSimulcastLayer,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -122,6 +128,19 @@ impl Serialize for PublisherInfo {
 pub enum VideoCodecType {
     H264,
     H265,
+    //the codecs which are not remuxed, e.g. MJPEG
+    Unsupported,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AudioCodecType {
+    AAC,
+    //G.711 A-law
+    G711A,
+    //G.711 µ-law
+    G711U,
+    //the codecs which are not remuxed, e.g. Opus
+    Unsupported,
 }
 
 #[derive(Clone)]
@@ -129,6 +148,7 @@ pub struct MediaInfo {
     pub audio_clock_rate: u32,
     pub video_clock_rate: u32,
     pub vcodec: VideoCodecType,
+    pub acodec: AudioCodecType,
 }
 
 #[derive(Clone)]
@@ -137,6 +157,12 @@ pub enum FrameData {
     Audio { timestamp: u32, data: BytesMut },
     MetaData { timestamp: u32, data: BytesMut },
     MediaInfo { media_info: MediaInfo },
+    /* The source of the stream is switched(e.g. a relay session reconnects),
+    the following codec configs and timestamps may not continue the previous ones. */
+    Discontinuity {},
+    /* A text message sent along with the stream(e.g. a chat message or an overlay
+    command), it is relayed by the WebRTC data channels and mirrored as onTextData. */
+    TextData { data: String },
 }
 
 //Used to pass rtp raw data.
@@ -144,12 +170,15 @@ pub enum FrameData {
 pub enum PacketData {
     Video { timestamp: u32, data: BytesMut },
     Audio { timestamp: u32, data: BytesMut },
+    TextData { data: String },
 }
 
 //used to save data which needs to be transferred between client/server sessions
 #[derive(Clone)]
 pub enum Information {
     Sdp { data: String },
+    /* The rids of the simulcast layers of a whip stream, from the highest to the lowest quality. */
+    SimulcastLayers { rids: Vec<String> },
 }
 
 //used to transfer a/v frame between different protocols(rtmp/rtsp/webrtc/http-flv/hls)
@@ -198,7 +227,9 @@ pub type PubEventExecuteResultSender = oneshot::Sender<
 >;
 // The trait bound `BroadcastEvent: Clone` should be satisfied, so here we cannot use oneshot.
 pub type BroadcastEventExecuteResultSender = mpsc::Sender<Result<(), StreamHubError>>;
+pub type BroadcastEventExecuteResultReceiver = mpsc::Receiver<Result<(), StreamHubError>>;
 pub type ApiRelayStreamResultSender = oneshot::Sender<Result<(), StreamHubError>>;
+pub type ApiSendTextDataResultSender = oneshot::Sender<Result<(), StreamHubError>>;
 pub type TransceiverEventExecuteResultSender = oneshot::Sender<StatisticDataSender>;
 
 #[async_trait]
@@ -210,6 +241,10 @@ pub trait TStreamHandler: Send + Sync {
     ) -> Result<(), StreamHubError>;
     async fn get_statistic_data(&self) -> Option<StatisticsStream>;
     async fn send_information(&self, sender: InformationSender);
+    //only the publishers which can ask their sources for a key frame implement it
+    async fn request_key_frame(&self) {}
+    //the count of the subscribers is changed, e.g. an on-demand publisher stops without subscribers
+    async fn on_subscriber_count(&self, _count: usize) {}
 }
 
 //A publisher can publish one or two kinds of av stream at a time.
@@ -260,11 +295,16 @@ pub enum StreamHubEventMessage {
         identifier: StreamIdentifier,
         segment: Segment,
     },
+    PublishTimeout {
+        identifier: StreamIdentifier,
+        info: PublisherInfo,
+        reason: String,
+    },
     NotSupport {},
 }
 
 //we can pub frame or packet or both.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RelayType {
     Pull,
     Push,
@@ -309,6 +349,8 @@ pub enum StreamHubEvent {
         identifier: StreamIdentifier,
         server_address: String,
         relay_type: RelayType,
+        //the bearer token of the WHEP/WHIP endpoint
+        token: Option<String>,
         result_sender: ApiRelayStreamResultSender,
     },
     #[serde(skip_serializing)]
@@ -317,15 +359,40 @@ pub enum StreamHubEvent {
         relay_type: RelayType,
         result_sender: ApiRelayStreamResultSender,
     },
+    /*inject a text message to the publishers' data of a stream*/
+    #[serde(skip_serializing)]
+    ApiSendTextData {
+        identifier: StreamIdentifier,
+        data: String,
+        result_sender: ApiSendTextDataResultSender,
+    },
     #[serde(skip_serializing)]
     Request {
         identifier: StreamIdentifier,
         sender: InformationSender,
     },
+    /*submit the statistic data of a stream from outside its publisher and subscribers*/
+    #[serde(skip_serializing)]
+    Statistic {
+        identifier: StreamIdentifier,
+        data: StatisticData,
+    },
     OnHls {
         identifier: StreamIdentifier,
         segment: Segment,
-    }
+    },
+    /*the stream receives no data in time, its publishers are unpublished*/
+    #[serde(skip_serializing)]
+    PublishTimeout {
+        identifier: StreamIdentifier,
+        reason: String,
+    },
+    /*request a key frame from the publisher of the stream, e.g. for a joined subscriber*/
+    #[serde(skip_serializing)]
+    RequestKeyFrame { identifier: StreamIdentifier },
+    /*the relay client stops by itself, e.g. it gives up reconnecting, its id is released*/
+    #[serde(skip_serializing)]
+    RelayStopped { id: String },
 }
 
 impl StreamHubEvent {
@@ -386,6 +453,13 @@ pub enum TransceiverEvent {
     Request {
         sender: InformationSender,
     },
+    Statistic {
+        data: StatisticData,
+    },
+    RequestKeyFrame {},
+    TextData {
+        data: String,
+    },
 }
 
 impl fmt::Display for TransceiverEvent {
@@ -408,6 +482,8 @@ pub enum BroadcastEvent {
         id: String,
         identifier: StreamIdentifier,
         server_address: Option<String>,
+        //the bearer token of the WHEP endpoint
+        token: Option<String>,
         result_sender: Option<BroadcastEventExecuteResultSender>,
     },
     UnSubscribe {
@@ -416,8 +492,22 @@ pub enum BroadcastEvent {
         //identifier: StreamIdentifier,
         //server_address: Option<String>,
     },
+    /*Need restream(push) a local stream to the url of another platform*/
+    StartRestream {
+        id: String,
+        identifier: StreamIdentifier,
+        url: String,
+        //the bearer token of the WHIP endpoint
+        token: Option<String>,
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+    },
+    StopRestream {
+        id: String,
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+    },
 }
 
+#[derive(Debug)]
 pub enum StatisticData {
     AudioCodec {
         sound_format: SoundFormat,
@@ -456,6 +546,13 @@ pub enum StatisticData {
         sub_type: SubscribeType,
         start_time: DateTime<Local>,
     },
+    Restream {
+        id: String,
+        url: String,
+        state: RestreamState,
+        reconnect_count: usize,
+        subscriber_id: Option<Uuid>,
+    },
 }
 
 
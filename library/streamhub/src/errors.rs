@@ -34,6 +34,20 @@ pub enum StreamHubErrorValue {
     SerdeError(Error),
     #[fail(display = "the client session error: {}", _0)]
     RtspClientSessionError(String),
+    #[fail(display = "restream is not enabled")]
+    RestreamNotEnabled,
+    #[fail(display = "the restream error: {}", _0)]
+    RestreamError(String),
+    #[fail(display = "the stream does not exist")]
+    StreamNotExist,
+    #[fail(display = "webrtc relay is not enabled")]
+    WebRTCRelayNotEnabled,
+    #[fail(display = "the webrtc relay error: {}", _0)]
+    WebRTCRelayError(String),
+    #[fail(display = "the rtmp relay error: {}", _0)]
+    RtmpRelayError(String),
+    #[fail(display = "the relay client does not exist: {}", _0)]
+    RelayNotExist(String),
 }
 #[derive(Debug)]
 pub struct StreamHubError {
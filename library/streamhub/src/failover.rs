@@ -0,0 +1,478 @@
+use {
+    crate::{
+        define::{
+            DataSender, FrameData, FrameDataReceiver, InformationSender, SubscribeType,
+            TStreamHandler,
+        },
+        errors::StreamHubError,
+        statistics::StatisticsStream,
+        utils::{TimestampRebaser, Uuid},
+    },
+    async_trait::async_trait,
+    bytesio::bytes_reader::BytesReader,
+    std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tokio::sync::mpsc,
+    xflv::{define::frame_type, flv_tag_header::VideoTagHeader, Unmarshal},
+};
+
+/* Add or remove the publishers(sources) of a stream with hot standbys. */
+pub enum FrameSourceEvent {
+    Add {
+        id: Uuid,
+        receiver: FrameDataReceiver,
+        handler: Arc<dyn TStreamHandler>,
+    },
+    Remove {
+        id: Uuid,
+    },
+}
+
+pub type FrameSourceEventSender = mpsc::UnboundedSender<FrameSourceEvent>;
+pub type FrameSourceEventReceiver = mpsc::UnboundedReceiver<FrameSourceEvent>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameAction {
+    /* the frame is from the active source */
+    Forward,
+    /* the frame is the switch point of the pending source, switch to it first */
+    SwitchAndForward,
+    Drop,
+}
+
+struct FrameSource {
+    id: Uuid,
+    handler: Arc<dyn TStreamHandler>,
+    /* the first publisher, or the publisher coming after the primary left */
+    is_primary: bool,
+    has_video: bool,
+    last_data_time: Instant,
+}
+
+/* Decide which source of a stream is forwarded to the subscribers. Only the
+active source is forwarded, the others are hot standbys. The stream switches to
+another source when the active one leaves or stops sending data for the timeout,
+and the switch happens on a key frame so that the players can decode at once. */
+pub struct FailoverSources {
+    timeout: Duration,
+    switch_back: bool,
+    sources: Vec<FrameSource>,
+    active_id: Option<Uuid>,
+    pending_id: Option<Uuid>,
+}
+
+impl FailoverSources {
+    pub fn new(
+        timeout: Duration,
+        switch_back: bool,
+        id: Uuid,
+        handler: Arc<dyn TStreamHandler>,
+    ) -> Self {
+        Self {
+            timeout,
+            switch_back,
+            sources: vec![FrameSource {
+                id,
+                handler,
+                is_primary: true,
+                has_video: false,
+                last_data_time: Instant::now(),
+            }],
+            active_id: Some(id),
+            pending_id: None,
+        }
+    }
+
+    pub fn add_source(&mut self, id: Uuid, handler: Arc<dyn TStreamHandler>) {
+        let is_primary = !self.sources.iter().any(|source| source.is_primary);
+        self.sources.push(FrameSource {
+            id,
+            handler,
+            is_primary,
+            has_video: false,
+            last_data_time: Instant::now(),
+        });
+
+        if self.active_id.is_none() || (is_primary && self.switch_back) {
+            self.pending_id = Some(id);
+        }
+    }
+
+    pub fn remove_source(&mut self, id: Uuid) {
+        self.sources.retain(|source| source.id != id);
+
+        if self.pending_id == Some(id) {
+            self.pending_id = None;
+        }
+        if self.active_id == Some(id) {
+            self.active_id = None;
+            self.pending_id = self.candidate(None);
+        }
+    }
+
+    pub fn on_frame(&mut self, id: Uuid, frame: &FrameData, now: Instant) -> FrameAction {
+        let Some(source) = self.sources.iter_mut().find(|source| source.id == id) else {
+            return FrameAction::Drop;
+        };
+        source.last_data_time = now;
+
+        let is_video = matches!(frame, FrameData::Video { .. });
+        if is_video {
+            source.has_video = true;
+        }
+
+        if self.active_id == Some(id) {
+            return FrameAction::Forward;
+        }
+
+        if self.pending_id == Some(id) {
+            let is_switch_point = match frame {
+                FrameData::Video { data, .. } => is_key_frame(data),
+                //switch at once if the source does not contain video
+                FrameData::Audio { .. } => !source.has_video,
+                _ => false,
+            };
+            if is_switch_point {
+                log::info!("failover: switch the stream source to: {}", id);
+                self.active_id = Some(id);
+                self.pending_id = None;
+                return FrameAction::SwitchAndForward;
+            }
+        }
+
+        FrameAction::Drop
+    }
+
+    /* Called periodically to check whether the active source is stalled. */
+    pub fn on_tick(&mut self, now: Instant) {
+        if self.pending_id.is_some() {
+            return;
+        }
+
+        let Some(active) = self
+            .sources
+            .iter()
+            .find(|source| Some(source.id) == self.active_id)
+        else {
+            return;
+        };
+
+        if !self.is_alive(active, now) {
+            self.pending_id = self.candidate(Some(now));
+            if let Some(id) = self.pending_id {
+                log::warn!("failover: the active source is stalled, switch to: {}", id);
+            }
+        } else if self.switch_back && !active.is_primary {
+            self.pending_id = self
+                .sources
+                .iter()
+                .find(|source| source.is_primary && self.is_alive(source, now))
+                .map(|source| source.id);
+        }
+    }
+
+    pub fn active_handler(&self) -> Option<Arc<dyn TStreamHandler>> {
+        self.sources
+            .iter()
+            .find(|source| Some(source.id) == self.active_id)
+            .map(|source| source.handler.clone())
+    }
+
+    fn is_alive(&self, source: &FrameSource, now: Instant) -> bool {
+        now.saturating_duration_since(source.last_data_time) < self.timeout
+    }
+
+    /* choose a source other than the active one, the primary is preferred */
+    fn candidate(&self, now: Option<Instant>) -> Option<Uuid> {
+        let mut candidates: Vec<&FrameSource> = self
+            .sources
+            .iter()
+            .filter(|source| Some(source.id) != self.active_id)
+            .filter(|source| now.is_none_or(|now| self.is_alive(source, now)))
+            .collect();
+        candidates.sort_by_key(|source| !source.is_primary);
+        candidates.first().map(|source| source.id)
+    }
+}
+
+fn is_key_frame(data: &bytes::BytesMut) -> bool {
+    let mut reader = BytesReader::new(data.clone());
+    match VideoTagHeader::unmarshal(&mut reader) {
+        Ok(tag_header) => tag_header.frame_type == frame_type::KEY_FRAME,
+        Err(_) => false,
+    }
+}
+
+/* The stream handler of a stream with hot standbys, it serves the cached data
+of the active source with the timestamps aligned to the forwarded frames. */
+pub struct FailoverStreamHandler {
+    pub sources: Mutex<FailoverSources>,
+    pub timestamp_rebaser: Mutex<TimestampRebaser>,
+}
+
+impl FailoverStreamHandler {
+    pub fn new(sources: FailoverSources) -> Self {
+        Self {
+            sources: Mutex::new(sources),
+            timestamp_rebaser: Mutex::new(TimestampRebaser::new()),
+        }
+    }
+
+    /* rebase the timestamp of a forwarded frame */
+    pub fn rebase(&self, frame: FrameData) -> FrameData {
+        let mut rebaser = self.timestamp_rebaser.lock().unwrap();
+        match frame {
+            FrameData::Video { timestamp, data } => FrameData::Video {
+                timestamp: rebaser.rebase(timestamp),
+                data,
+            },
+            FrameData::Audio { timestamp, data } => FrameData::Audio {
+                timestamp: rebaser.rebase(timestamp),
+                data,
+            },
+            other => other,
+        }
+    }
+
+    /* The sequence headers of the new active source after a switch, they are
+    stamped with the last forwarded timestamp. */
+    pub async fn switch_headers(&self) -> Vec<FrameData> {
+        let Some(handler) = self.sources.lock().unwrap().active_handler() else {
+            return Vec::new();
+        };
+        self.timestamp_rebaser.lock().unwrap().discontinue();
+
+        let headers = Self::prior_data(handler, SubscribeType::RtmpRelay).await;
+        let timestamp = self
+            .timestamp_rebaser
+            .lock()
+            .unwrap()
+            .last_timestamp()
+            .unwrap_or(0);
+
+        headers
+            .into_iter()
+            .map(|frame| with_timestamp(frame, |_| timestamp))
+            .collect()
+    }
+
+    async fn prior_data(
+        handler: Arc<dyn TStreamHandler>,
+        sub_type: SubscribeType,
+    ) -> Vec<FrameData> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        if let Err(err) = handler
+            .send_prior_data(DataSender::Frame { sender }, sub_type)
+            .await
+        {
+            log::error!("failover: get prior data error: {}", err);
+        }
+
+        let mut frames = Vec::new();
+        while let Ok(frame) = receiver.try_recv() {
+            frames.push(frame);
+        }
+        frames
+    }
+}
+
+fn with_timestamp(frame: FrameData, f: impl Fn(u32) -> u32) -> FrameData {
+    match frame {
+        FrameData::Video { timestamp, data } => FrameData::Video {
+            timestamp: f(timestamp),
+            data,
+        },
+        FrameData::Audio { timestamp, data } => FrameData::Audio {
+            timestamp: f(timestamp),
+            data,
+        },
+        FrameData::MetaData { timestamp, data } => FrameData::MetaData {
+            timestamp: f(timestamp),
+            data,
+        },
+        other => other,
+    }
+}
+
+#[async_trait]
+impl TStreamHandler for FailoverStreamHandler {
+    async fn send_prior_data(
+        &self,
+        sender: DataSender,
+        sub_type: SubscribeType,
+    ) -> Result<(), StreamHubError> {
+        let Some(handler) = self.sources.lock().unwrap().active_handler() else {
+            return Ok(());
+        };
+
+        let DataSender::Frame {
+            sender: frame_sender,
+        } = sender
+        else {
+            return handler.send_prior_data(sender, sub_type).await;
+        };
+
+        let rebaser = self.timestamp_rebaser.lock().unwrap().clone();
+        for frame in Self::prior_data(handler, sub_type).await {
+            if frame_sender
+                .send(with_timestamp(frame, |timestamp| rebaser.map(timestamp)))
+                .is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_statistic_data(&self) -> Option<StatisticsStream> {
+        let handler = self.sources.lock().unwrap().active_handler()?;
+        handler.get_statistic_data().await
+    }
+
+    async fn send_information(&self, sender: InformationSender) {
+        let handler = self.sources.lock().unwrap().active_handler();
+        if let Some(handler) = handler {
+            handler.send_information(sender).await;
+        }
+    }
+
+    async fn request_key_frame(&self) {
+        let handler = self.sources.lock().unwrap().active_handler();
+        if let Some(handler) = handler {
+            handler.request_key_frame().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FailoverSources, FrameAction};
+    use crate::{
+        define::{DataSender, FrameData, InformationSender, SubscribeType, TStreamHandler},
+        errors::StreamHubError,
+        statistics::StatisticsStream,
+        utils::{RandomDigitCount, Uuid},
+    };
+    use async_trait::async_trait;
+    use bytes::BytesMut;
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    struct EmptyHandler;
+
+    #[async_trait]
+    impl TStreamHandler for EmptyHandler {
+        async fn send_prior_data(
+            &self,
+            _: DataSender,
+            _: SubscribeType,
+        ) -> Result<(), StreamHubError> {
+            Ok(())
+        }
+        async fn get_statistic_data(&self) -> Option<StatisticsStream> {
+            None
+        }
+        async fn send_information(&self, _: InformationSender) {}
+    }
+
+    fn video(key_frame: bool) -> FrameData {
+        //frame type(4 bits) + codec id(4 bits), avc packet type, composition time
+        let first_byte = if key_frame { 0x17 } else { 0x27 };
+        FrameData::Video {
+            timestamp: 0,
+            data: BytesMut::from(&[first_byte, 0x01, 0x00, 0x00, 0x00][..]),
+        }
+    }
+
+    #[test]
+    fn test_failover_on_remove() {
+        let primary = Uuid::new(RandomDigitCount::Four);
+        let backup = Uuid::new(RandomDigitCount::Four);
+        let now = Instant::now();
+
+        let mut sources = FailoverSources::new(
+            Duration::from_secs(3),
+            false,
+            primary,
+            Arc::new(EmptyHandler),
+        );
+        sources.add_source(backup, Arc::new(EmptyHandler));
+
+        assert_eq!(
+            sources.on_frame(primary, &video(false), now),
+            FrameAction::Forward
+        );
+        assert_eq!(
+            sources.on_frame(backup, &video(true), now),
+            FrameAction::Drop
+        );
+
+        sources.remove_source(primary);
+        //wait for the key frame
+        assert_eq!(
+            sources.on_frame(backup, &video(false), now),
+            FrameAction::Drop
+        );
+        assert_eq!(
+            sources.on_frame(backup, &video(true), now),
+            FrameAction::SwitchAndForward
+        );
+        assert_eq!(
+            sources.on_frame(backup, &video(false), now),
+            FrameAction::Forward
+        );
+
+        //the primary returns and it does not take over without switch back
+        let returned = Uuid::new(RandomDigitCount::Four);
+        sources.add_source(returned, Arc::new(EmptyHandler));
+        assert_eq!(
+            sources.on_frame(returned, &video(true), now),
+            FrameAction::Drop
+        );
+    }
+
+    #[test]
+    fn test_failover_on_timeout_and_switch_back() {
+        let primary = Uuid::new(RandomDigitCount::Four);
+        let backup = Uuid::new(RandomDigitCount::Four);
+        let now = Instant::now();
+
+        let mut sources = FailoverSources::new(
+            Duration::from_secs(3),
+            true,
+            primary,
+            Arc::new(EmptyHandler),
+        );
+        sources.add_source(backup, Arc::new(EmptyHandler));
+        sources.on_frame(primary, &video(true), now);
+
+        //the primary is stalled while the backup keeps sending data
+        let later = now + Duration::from_secs(4);
+        sources.on_frame(backup, &video(false), later);
+        sources.on_tick(later);
+        assert_eq!(
+            sources.on_frame(backup, &video(true), later),
+            FrameAction::SwitchAndForward
+        );
+
+        //the primary recovers and the stream switches back on its key frame
+        sources.on_frame(primary, &video(false), later);
+        sources.on_tick(later);
+        assert_eq!(
+            sources.on_frame(primary, &video(false), later),
+            FrameAction::Drop
+        );
+        assert_eq!(
+            sources.on_frame(primary, &video(true), later),
+            FrameAction::SwitchAndForward
+        );
+        assert_eq!(
+            sources.on_frame(backup, &video(true), later),
+            FrameAction::Drop
+        );
+    }
+}
@@ -1,32 +1,43 @@
 use define::{
-    FrameDataReceiver, PacketDataReceiver, PacketDataSender, RelayType, StatisticData,
-    StatisticDataReceiver, StatisticDataSender,
+    BroadcastEventExecuteResultReceiver, FrameDataReceiver, PacketDataReceiver, PacketDataSender,
+    RelayType, StatisticData, StatisticDataReceiver, StatisticDataSender,
 };
 use serde_json::{json, Value};
-use statistics::{StatisticSubscriber, StatisticsStream};
+use statistics::{RestreamState, StatisticRestream, StatisticSubscriber, StatisticsStream};
 use tokio::sync::oneshot;
 use xflv::define::aac_packet_type;
 
 use crate::define::PacketData;
 
+pub mod cluster;
 pub mod define;
 pub mod errors;
+pub mod failover;
 pub mod notify;
 pub mod statistics;
 pub mod stream;
 pub mod utils;
 
 use {
-    crate::notify::Notifier,
+    crate::{
+        cluster::{RegistryEvent, StreamRegistry},
+        failover::{
+            FailoverSources, FailoverStreamHandler, FrameAction, FrameSourceEvent,
+            FrameSourceEventReceiver, FrameSourceEventSender,
+        },
+        notify::Notifier,
+    },
+    config::FailoverConfig,
     define::{
         BroadcastEvent, BroadcastEventReceiver, BroadcastEventSender, DataReceiver, DataSender,
-        FrameData, FrameDataSender, Information, StreamHubEvent, StreamHubEventReceiver,
-        StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler, TransceiverEvent,
-        TransceiverEventReceiver, TransceiverEventSender,
+        FrameData, FrameDataSender, Information, PublishType, PublisherInfo, StreamHubEvent,
+        StreamHubEventMessage, StreamHubEventReceiver, StreamHubEventSender, SubscribeType, SubscriberInfo,
+        TStreamHandler, TransceiverEvent, TransceiverEventReceiver, TransceiverEventSender,
     },
     errors::{StreamHubError, StreamHubErrorValue},
     std::collections::HashMap,
     std::sync::Arc,
+    std::time::{Duration, Instant},
     stream::StreamIdentifier,
     tokio::sync::{broadcast, mpsc, mpsc::UnboundedReceiver, Mutex},
     utils::Uuid,
@@ -51,6 +62,13 @@ pub struct StreamDataTransceiver {
     statistic_data: Arc<Mutex<StatisticsStream>>,
     //a hander implement by protocols, such as rtmp, webrtc, http-flv, hls
     stream_handler: Arc<dyn TStreamHandler>,
+    //the frame data sources of a stream with hot standbys(the first publisher's id)
+    failover: Option<(Uuid, Arc<FailoverStreamHandler>, FrameSourceEventReceiver)>,
+    identifier: StreamIdentifier,
+    //the last time the publishers sent data, checked by the watchdog
+    last_data_time: Arc<std::sync::Mutex<Instant>>,
+    //ask the hub to unpublish the stream if no data is received in time
+    watchdog: Option<(Duration, StreamHubEventSender)>,
 }
 
 impl StreamDataTransceiver {
@@ -69,10 +87,36 @@ impl StreamDataTransceiver {
             id_to_frame_sender: Arc::new(Mutex::new(HashMap::new())),
             id_to_packet_sender: Arc::new(Mutex::new(HashMap::new())),
             stream_handler: h,
-            statistic_data: Arc::new(Mutex::new(StatisticsStream::new(identifier))),
+            statistic_data: Arc::new(Mutex::new(StatisticsStream::new(identifier.clone()))),
+            failover: None,
+            identifier,
+            last_data_time: Arc::new(std::sync::Mutex::new(Instant::now())),
+            watchdog: None,
         }
     }
 
+    fn new_with_failover(
+        data_receiver: DataReceiver,
+        event_receiver: UnboundedReceiver<TransceiverEvent>,
+        identifier: StreamIdentifier,
+        id: Uuid,
+        h: Arc<FailoverStreamHandler>,
+        source_event_receiver: FrameSourceEventReceiver,
+    ) -> Self {
+        let mut transceiver =
+            Self::new(data_receiver, event_receiver, identifier, h.clone());
+        transceiver.failover = Some((id, h, source_event_receiver));
+        transceiver
+    }
+
+    fn set_watchdog(&mut self, timeout: Duration, hub_event_sender: StreamHubEventSender) {
+        self.watchdog = Some((timeout, hub_event_sender));
+    }
+
+    fn update_last_data_time(last_data_time: &std::sync::Mutex<Instant>) {
+        *last_data_time.lock().unwrap() = Instant::now();
+    }
+
     async fn receive_frame_data(
         data: Option<FrameData>,
         frame_senders: &Arc<Mutex<HashMap<Uuid, FrameDataSender>>>,
@@ -124,6 +168,20 @@ impl StreamDataTransceiver {
                         }
                     }
                 }
+                FrameData::Discontinuity {} => {
+                    for (_, v) in frame_senders.lock().await.iter() {
+                        if let Err(err) = v.send(FrameData::Discontinuity {}) {
+                            log::error!("Transmiter send discontinuity error: {}", err);
+                        }
+                    }
+                }
+                FrameData::TextData { data } => {
+                    for (_, v) in frame_senders.lock().await.iter() {
+                        if let Err(err) = v.send(FrameData::TextData { data: data.clone() }) {
+                            log::error!("Transmiter send text data error: {}", err);
+                        }
+                    }
+                }
             }
         }
     }
@@ -132,11 +190,15 @@ impl StreamDataTransceiver {
         mut exit: broadcast::Receiver<()>,
         mut receiver: FrameDataReceiver,
         frame_senders: Arc<Mutex<HashMap<Uuid, FrameDataSender>>>,
+        last_data_time: Arc<std::sync::Mutex<Instant>>,
     ) {
         tokio::spawn(async move {
             loop {
                 tokio::select! {
                     data = receiver.recv() => {
+                       if data.is_some() {
+                           Self::update_last_data_time(&last_data_time);
+                       }
                        Self::receive_frame_data(data, &frame_senders).await;
                     }
                     _ = exit.recv()=>{
@@ -147,6 +209,87 @@ impl StreamDataTransceiver {
         });
     }
 
+    /* Receive the frame data from all the publishers of a stream and only forward
+    the active one's. The timestamps are rebased and the sequence headers of the
+    new publisher are sent when switching, so the players can continue playing. */
+    async fn receive_failover_frame_data_loop(
+        mut exit: broadcast::Receiver<()>,
+        (id, receiver): (Uuid, FrameDataReceiver),
+        handler: Arc<FailoverStreamHandler>,
+        mut source_event_receiver: FrameSourceEventReceiver,
+        frame_senders: Arc<Mutex<HashMap<Uuid, FrameDataSender>>>,
+        last_data_time: Arc<std::sync::Mutex<Instant>>,
+    ) {
+        tokio::spawn(async move {
+            let (merged_sender, mut merged_receiver) = mpsc::unbounded_channel();
+            Self::forward_source_frame_data(id, receiver, merged_sender.clone());
+
+            let mut interval = tokio::time::interval(Duration::from_millis(100));
+            loop {
+                tokio::select! {
+                    Some((id, data)) = merged_receiver.recv() => {
+                        Self::update_last_data_time(&last_data_time);
+                        let action = handler
+                            .sources
+                            .lock()
+                            .unwrap()
+                            .on_frame(id, &data, Instant::now());
+                        match action {
+                            FrameAction::Forward => {
+                                Self::receive_frame_data(Some(handler.rebase(data)), &frame_senders)
+                                    .await;
+                            }
+                            FrameAction::SwitchAndForward => {
+                                Self::receive_frame_data(
+                                    Some(FrameData::Discontinuity {}),
+                                    &frame_senders,
+                                )
+                                .await;
+                                for header in handler.switch_headers().await {
+                                    Self::receive_frame_data(Some(header), &frame_senders).await;
+                                }
+                                Self::receive_frame_data(Some(handler.rebase(data)), &frame_senders)
+                                    .await;
+                            }
+                            FrameAction::Drop => {}
+                        }
+                    }
+                    Some(event) = source_event_receiver.recv() => {
+                        match event {
+                            FrameSourceEvent::Add { id, receiver, handler: source_handler } => {
+                                handler.sources.lock().unwrap().add_source(id, source_handler);
+                                Self::forward_source_frame_data(id, receiver, merged_sender.clone());
+                            }
+                            FrameSourceEvent::Remove { id } => {
+                                handler.sources.lock().unwrap().remove_source(id);
+                            }
+                        }
+                    }
+                    _ = interval.tick() => {
+                        handler.sources.lock().unwrap().on_tick(Instant::now());
+                    }
+                    _ = exit.recv() => {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    fn forward_source_frame_data(
+        id: Uuid,
+        mut receiver: FrameDataReceiver,
+        sender: mpsc::UnboundedSender<(Uuid, FrameData)>,
+    ) {
+        tokio::spawn(async move {
+            while let Some(data) = receiver.recv().await {
+                if sender.send((id, data)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
     async fn receive_packet_data(
         data: Option<PacketData>,
         packet_senders: &Arc<Mutex<HashMap<Uuid, PacketDataSender>>>,
@@ -180,6 +323,13 @@ impl StreamDataTransceiver {
                         }
                     }
                 }
+                PacketData::TextData { data } => {
+                    for (_, v) in packet_senders.lock().await.iter() {
+                        if let Err(err) = v.send(PacketData::TextData { data: data.clone() }) {
+                            log::error!("Transmiter send text data error: {}", err);
+                        }
+                    }
+                }
             }
         }
     }
@@ -188,11 +338,15 @@ impl StreamDataTransceiver {
         mut exit: broadcast::Receiver<()>,
         mut receiver: PacketDataReceiver,
         packet_senders: Arc<Mutex<HashMap<Uuid, PacketDataSender>>>,
+        last_data_time: Arc<std::sync::Mutex<Instant>>,
     ) {
         tokio::spawn(async move {
             loop {
                 tokio::select! {
                     data = receiver.recv() => {
+                       if data.is_some() {
+                           Self::update_last_data_time(&last_data_time);
+                       }
                        Self::receive_packet_data(data, &packet_senders).await;
                     }
                     _ = exit.recv()=>{
@@ -203,6 +357,42 @@ impl StreamDataTransceiver {
         });
     }
 
+    /* Reap the zombie publishers which keep the connections open but send no
+    data, so the stream can be published again. */
+    async fn watchdog_loop(
+        mut exit: broadcast::Receiver<()>,
+        identifier: StreamIdentifier,
+        (timeout, hub_event_sender): (Duration, StreamHubEventSender),
+        last_data_time: Arc<std::sync::Mutex<Instant>>,
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let elapsed = last_data_time.lock().unwrap().elapsed();
+                        if elapsed < timeout {
+                            continue;
+                        }
+
+                        let reason = format!("no data is received in {} seconds", elapsed.as_secs());
+                        log::warn!("watchdog: stream identifier: {}, {}", identifier, reason);
+                        if let Err(err) = hub_event_sender.send(StreamHubEvent::PublishTimeout {
+                            identifier: identifier.clone(),
+                            reason,
+                        }) {
+                            log::error!("watchdog send publish timeout event error: {}", err);
+                        }
+                        break;
+                    }
+                    _ = exit.recv() => {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
     async fn receive_statistics_data(
         data: Option<StatisticData>,
         statistics_data: &Arc<Mutex<StatisticsStream>>,
@@ -328,6 +518,32 @@ impl StreamDataTransceiver {
                     };
                     subscriber.insert(id, sub);
                 }
+                StatisticData::Restream {
+                    id,
+                    url,
+                    state,
+                    reconnect_count,
+                    subscriber_id,
+                } => {
+                    let restreams = &mut statistics_data.lock().await.restreams;
+                    if state == RestreamState::Stopped {
+                        restreams.remove(&id);
+                    } else {
+                        let restream = restreams.entry(id.clone()).or_insert(StatisticRestream {
+                            id,
+                            url: String::default(),
+                            state,
+                            reconnect_count: 0,
+                            start_time: chrono::Local::now(),
+                            subscriber_id: None,
+                            send_bitrate: 0,
+                        });
+                        restream.url = url;
+                        restream.state = state;
+                        restream.reconnect_count = reconnect_count;
+                        restream.subscriber_id = subscriber_id;
+                    }
+                }
             }
         }
     }
@@ -402,8 +618,12 @@ impl StreamDataTransceiver {
                                 )
                             }
 
-                            let mut statistics_data = statistics_data.lock().await;
-                            statistics_data.subscriber_count += 1;
+                            let subscriber_count = {
+                                let mut statistics_data = statistics_data.lock().await;
+                                statistics_data.subscriber_count += 1;
+                                statistics_data.subscriber_count
+                            };
+                            stream_handler.on_subscriber_count(subscriber_count).await;
                         }
                         TransceiverEvent::UnSubscribe { info } => {
                             match info.sub_type {
@@ -414,11 +634,15 @@ impl StreamDataTransceiver {
                                     frame_senders.lock().await.remove(&info.id);
                                 }
                             }
-                            let mut statistics_data = statistics_data.lock().await;
-                            let subscribers = &mut statistics_data.subscribers;
-                            subscribers.remove(&info.id);
+                            let subscriber_count = {
+                                let mut statistics_data = statistics_data.lock().await;
+                                let subscribers = &mut statistics_data.subscribers;
+                                subscribers.remove(&info.id);
 
-                            statistics_data.subscriber_count -= 1;
+                                statistics_data.subscriber_count -= 1;
+                                statistics_data.subscriber_count
+                            };
+                            stream_handler.on_subscriber_count(subscriber_count).await;
                         }
                         TransceiverEvent::UnPublish {} => {
                             if let Err(err) = exit.send(()) {
@@ -442,6 +666,29 @@ impl StreamDataTransceiver {
                         TransceiverEvent::Request { sender } => {
                             stream_handler.send_information(sender).await;
                         }
+                        TransceiverEvent::RequestKeyFrame {} => {
+                            stream_handler.request_key_frame().await;
+                        }
+                        TransceiverEvent::TextData { data } => {
+                            for (_, v) in frame_senders.lock().await.iter() {
+                                if let Err(err) = v.send(FrameData::TextData { data: data.clone() })
+                                {
+                                    log::error!("receive_event_loop send text data err: {}", err);
+                                }
+                            }
+                            for (_, v) in packet_senders.lock().await.iter() {
+                                if let Err(err) =
+                                    v.send(PacketData::TextData { data: data.clone() })
+                                {
+                                    log::error!("receive_event_loop send text data err: {}", err);
+                                }
+                            }
+                        }
+                        TransceiverEvent::Statistic { data } => {
+                            if let Err(err) = statistic_sender.send(data) {
+                                log::error!("receive_event_loop send statistic data err: {}", err);
+                            }
+                        }
                     }
                 }
             }
@@ -452,12 +699,25 @@ impl StreamDataTransceiver {
         let (tx, _) = broadcast::channel::<()>(1);
 
         if let Some(receiver) = self.data_receiver.frame_receiver {
-            Self::receive_frame_data_loop(
-                tx.subscribe(),
-                receiver,
-                self.id_to_frame_sender.clone(),
-            )
-            .await;
+            if let Some((id, handler, source_event_receiver)) = self.failover {
+                Self::receive_failover_frame_data_loop(
+                    tx.subscribe(),
+                    (id, receiver),
+                    handler,
+                    source_event_receiver,
+                    self.id_to_frame_sender.clone(),
+                    self.last_data_time.clone(),
+                )
+                .await;
+            } else {
+                Self::receive_frame_data_loop(
+                    tx.subscribe(),
+                    receiver,
+                    self.id_to_frame_sender.clone(),
+                    self.last_data_time.clone(),
+                )
+                .await;
+            }
         }
 
         if let Some(receiver) = self.data_receiver.packet_receiver {
@@ -465,6 +725,17 @@ impl StreamDataTransceiver {
                 tx.subscribe(),
                 receiver,
                 self.id_to_packet_sender.clone(),
+                self.last_data_time.clone(),
+            )
+            .await;
+        }
+
+        if let Some(watchdog) = self.watchdog {
+            Self::watchdog_loop(
+                tx.subscribe(),
+                self.identifier,
+                watchdog,
+                self.last_data_time.clone(),
             )
             .await;
         }
@@ -511,14 +782,34 @@ pub struct StreamsHub {
     //add a control switches separately.
     rtmp_push_enabled: bool,
     rtmp_remuxer_enabled: bool,
+    //enable restreaming the published streams to other platforms
+    rtmp_restream_enabled: bool,
+    //enable pulling/pushing the webrtc streams from/to the remote WHEP/WHIP endpoints
+    webrtc_relay_enabled: bool,
     //enable rtmp pull
     rtmp_pull_enabled: bool,
     //enable hls
     hls_enabled: bool,
     //http notifier on sub/pub event
     notifier: Option<Arc<dyn Notifier>>,
+    //register the local streams and resolve the remote streams in a cluster
+    stream_registry: Option<Arc<dyn StreamRegistry>>,
+    registry_event_sender: Option<mpsc::UnboundedSender<RegistryEvent>>,
+    //switch to the backup publishers of a stream when the active one stops
+    failover_config: Option<FailoverConfig>,
+    //the streams with hot standbys: the source event sender and the publisher ids
+    failover_streams: HashMap<StreamIdentifier, (FrameSourceEventSender, Vec<Uuid>)>,
+    //unpublish the streams which receive no data in time
+    publish_timeout: Option<Duration>,
+    //the started relay clients: relay id to relay type
+    relay_streams: Arc<std::sync::Mutex<HashMap<String, RelayType>>>,
+    //the streams being pulled for the subscribers and the time the pulls start
+    pending_pulls: HashMap<StreamIdentifier, Instant>,
 }
 
+//the time to wait for a pulled stream to be published
+const PENDING_PULL_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl StreamsHub {
     pub fn new(
         notifier: Option<Arc<dyn Notifier>>,
@@ -536,8 +827,17 @@ impl StreamsHub {
             rtmp_push_enabled: false,
             rtmp_pull_enabled: false,
             rtmp_remuxer_enabled: false,
+            rtmp_restream_enabled: false,
+            webrtc_relay_enabled: false,
             hls_enabled: false,
             notifier,
+            stream_registry: None,
+            registry_event_sender: None,
+            failover_config: None,
+            failover_streams: HashMap::new(),
+            publish_timeout: None,
+            relay_streams: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            pending_pulls: HashMap::new(),
         }
     }
     pub async fn run(&mut self) {
@@ -556,10 +856,43 @@ impl StreamsHub {
         self.rtmp_remuxer_enabled = enabled;
     }
 
+    pub fn set_rtmp_restream_enabled(&mut self, enabled: bool) {
+        self.rtmp_restream_enabled = enabled;
+    }
+
+    pub fn set_webrtc_relay_enabled(&mut self, enabled: bool) {
+        self.webrtc_relay_enabled = enabled;
+    }
+
     pub fn set_hls_enabled(&mut self, enabled: bool) {
         self.hls_enabled = enabled;
     }
 
+    pub fn set_stream_registry(&mut self, stream_registry: Arc<dyn StreamRegistry>) {
+        self.registry_event_sender =
+            Some(cluster::spawn_registration_task(stream_registry.clone()));
+        self.stream_registry = Some(stream_registry);
+    }
+
+    //the registry requests are sent by another task, a slow registry does not block the hub
+    fn send_registry_event(&self, event: RegistryEvent) {
+        if let Some(sender) = &self.registry_event_sender {
+            if sender.send(event).is_err() {
+                log::error!("send registry event error");
+            }
+        }
+    }
+
+    pub fn set_failover_config(&mut self, failover_config: FailoverConfig) {
+        if failover_config.enabled {
+            self.failover_config = Some(failover_config);
+        }
+    }
+
+    pub fn set_publish_timeout(&mut self, timeout: Duration) {
+        self.publish_timeout = Some(timeout);
+    }
+
     pub fn get_hub_event_sender(&mut self) -> StreamHubEventSender {
         self.hub_event_sender.clone()
     }
@@ -620,17 +953,22 @@ impl StreamsHub {
                     };
 
                     let result = match self
-                        .publish(identifier.clone(), receiver, stream_handler)
+                        .publish(identifier.clone(), &info, receiver, stream_handler)
                         .await
                     {
                         Ok(statistic_data_sender) => {
                             if let Some(notifier) = &self.notifier {
                                 notifier.on_publish_notify(&message).await;
                             }
+                            if Self::is_registrable(&identifier, &info) {
+                                self.send_registry_event(RegistryEvent::Register(
+                                    identifier.clone(),
+                                ));
+                            }
                             self.un_pub_sub_events
                                 .insert(info.id, StreamHubEvent::UnPublish { identifier, info });
 
-                            Ok((frame_sender, packet_sender, Some(statistic_data_sender)))
+                            Ok((frame_sender, packet_sender, statistic_data_sender))
                         }
                         Err(err) => {
                             log::error!("event_loop Publish err: {}", err);
@@ -643,11 +981,18 @@ impl StreamsHub {
                     }
                 }
 
-                StreamHubEvent::UnPublish {
-                    identifier,
-                    info: _,
-                } => {
-                    if let Err(err) = self.unpublish(&identifier) {
+                StreamHubEvent::UnPublish { identifier, info } => {
+                    //the publisher was rejected or has been unpublished by the watchdog
+                    if self.un_pub_sub_events.remove(&info.id).is_none() {
+                        log::warn!(
+                            "event_loop Unpublish: the publisher: {} is not publishing identifier: {}",
+                            info.id,
+                            identifier
+                        );
+                        continue;
+                    }
+
+                    if let Err(err) = self.unpublish(&identifier, &info) {
                         log::error!(
                             "event_loop Unpublish err: {} with identifier: {}",
                             err,
@@ -655,6 +1000,13 @@ impl StreamsHub {
                         );
                     }
 
+                    //the stream is still served by a backup publisher
+                    if Self::is_registrable(&identifier, &info)
+                        && !self.streams.contains_key(&identifier)
+                    {
+                        self.send_registry_event(RegistryEvent::Unregister(identifier.clone()));
+                    }
+
                     if let Some(notifier) = &self.notifier {
                         notifier.on_unpublish_notify(&message).await;
                     }
@@ -751,22 +1103,62 @@ impl StreamsHub {
                     identifier,
                     server_address,
                     relay_type,
+                    token,
                     result_sender,
                 } => {
-                    let result = self
-                        .api_start_relay_stream(id, &relay_type, identifier, server_address)
-                        .await;
-
-                    if let Err(err) = result_sender.send(result) {
-                        log::error!("event_loop api error: {:?}", err);
+                    match self.api_start_relay_stream(
+                        id.clone(),
+                        &relay_type,
+                        identifier,
+                        server_address,
+                        token,
+                    ) {
+                        Ok(result_receiver) => {
+                            let relay_streams = self.relay_streams.clone();
+                            //the relay clients may connect for a while, do not block the event loop
+                            tokio::spawn(async move {
+                                let result =
+                                    Self::wait_relay_result(id.clone(), result_receiver).await;
+                                if result.is_err() {
+                                    relay_streams.lock().unwrap().remove(&id);
+                                }
+                                if let Err(err) = result_sender.send(result) {
+                                    log::error!("event_loop api error: {:?}", err);
+                                }
+                            });
+                        }
+                        Err(err) => {
+                            if let Err(err) = result_sender.send(Err(err)) {
+                                log::error!("event_loop api error: {:?}", err);
+                            }
+                        }
                     }
                 }
                 StreamHubEvent::ApiStopRelayStream {
                     id,
                     relay_type,
                     result_sender,
+                } => match self.api_stop_relay_stream(id.clone(), &relay_type) {
+                    Ok(result_receiver) => {
+                        tokio::spawn(async move {
+                            let result = Self::wait_relay_result(id, result_receiver).await;
+                            if let Err(err) = result_sender.send(result) {
+                                log::error!("event_loop api error: {:?}", err);
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        if let Err(err) = result_sender.send(Err(err)) {
+                            log::error!("event_loop api error: {:?}", err);
+                        }
+                    }
+                },
+                StreamHubEvent::ApiSendTextData {
+                    identifier,
+                    data,
+                    result_sender,
                 } => {
-                    let result = self.api_stop_relay_stream(id, &relay_type).await;
+                    let result = self.api_send_text_data(&identifier, data);
 
                     if let Err(err) = result_sender.send(result) {
                         log::error!("event_loop api error: {:?}", err);
@@ -777,15 +1169,66 @@ impl StreamsHub {
                         log::error!("event_loop request error: {}", err);
                     }
                 }
+                StreamHubEvent::Statistic { identifier, data } => {
+                    if let Some(producer) = self.streams.get(&identifier) {
+                        if let Err(err) = producer.send(TransceiverEvent::Statistic { data }) {
+                            log::error!("event_loop statistic error: {}", err);
+                        }
+                    }
+                }
                 StreamHubEvent::OnHls { identifier: _ , segment: _ } => {
                     if let Some(notifier) = &self.notifier {
                         notifier.on_hls_notify(&message).await;
                     } 
                 }
+                StreamHubEvent::PublishTimeout { identifier, reason } => {
+                    self.on_publish_timeout(identifier, reason).await;
+                }
+                StreamHubEvent::RequestKeyFrame { identifier } => {
+                    if let Some(producer) = self.streams.get(&identifier) {
+                        if let Err(err) = producer.send(TransceiverEvent::RequestKeyFrame {}) {
+                            log::error!("event_loop request key frame error: {}", err);
+                        }
+                    }
+                }
+                StreamHubEvent::RelayStopped { id } => {
+                    if self.relay_streams.lock().unwrap().remove(&id).is_some() {
+                        log::info!("the relay stream: {} is stopped", id);
+                    }
+                }
             }
         }
     }
 
+    /* Only the rtmp streams published to this node are registered in the cluster,
+    the streams relayed from other nodes are not. */
+    fn is_registrable(identifier: &StreamIdentifier, info: &PublisherInfo) -> bool {
+        matches!(identifier, StreamIdentifier::Rtmp { .. })
+            && !matches!(
+                info.pub_type,
+                PublishType::RtmpRelay | PublishType::RtspRelay | PublishType::WhepRelay
+            )
+    }
+
+    fn api_send_text_data(
+        &mut self,
+        identifier: &StreamIdentifier,
+        data: String,
+    ) -> Result<(), StreamHubError> {
+        if let Some(producer) = self.streams.get(identifier) {
+            log::info!("api_send_text_data:  stream identifier: {}", identifier);
+            producer
+                .send(TransceiverEvent::TextData { data })
+                .map_err(|_| StreamHubError {
+                    value: StreamHubErrorValue::SendError,
+                })
+        } else {
+            Err(StreamHubError {
+                value: StreamHubErrorValue::StreamNotExist,
+            })
+        }
+    }
+
     fn request(
         &mut self,
         identifier: &StreamIdentifier,
@@ -862,6 +1305,39 @@ impl StreamsHub {
         Ok(serde_json::to_value(data)?)
     }
 
+    async fn on_publish_timeout(&mut self, identifier: StreamIdentifier, reason: String) {
+        let publishers: Vec<PublisherInfo> = self
+            .un_pub_sub_events
+            .values()
+            .filter_map(|event| match event {
+                StreamHubEvent::UnPublish {
+                    identifier: pub_identifier,
+                    info,
+                } if *pub_identifier == identifier => Some(info.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for info in publishers {
+            log::warn!(
+                "unpublish the timeout publisher: {}, stream identifier: {}",
+                info.id,
+                identifier
+            );
+            if let Some(notifier) = &self.notifier {
+                let message = StreamHubEventMessage::PublishTimeout {
+                    identifier: identifier.clone(),
+                    info: info.clone(),
+                    reason: reason.clone(),
+                };
+                notifier.on_publish_timeout_notify(&message).await;
+            }
+            if let Err(err) = self.api_kick_off_client(info.id) {
+                log::error!("unpublish the timeout publisher error: {}", err);
+            }
+        }
+    }
+
     fn api_kick_off_client(&mut self, uid: Uuid) -> Result<(), StreamHubError> {
         if let Some(event) = self.un_pub_sub_events.get(&uid) {
             match event {
@@ -902,21 +1378,36 @@ impl StreamsHub {
         Ok(())
     }
 
-    async fn api_start_relay_stream(
+    fn api_start_relay_stream(
         &mut self,
         id: String,
         relay_type: &RelayType,
         identifier: StreamIdentifier,
         server_address: String,
-    ) -> Result<(), StreamHubError> {
-        let (result_sender, mut result_receiver) = mpsc::channel(1);
+        token: Option<String>,
+    ) -> Result<BroadcastEventExecuteResultReceiver, StreamHubError> {
+        let is_webrtc = matches!(identifier, StreamIdentifier::WebRTC { .. });
+        if is_webrtc && !self.webrtc_relay_enabled {
+            return Err(StreamHubError {
+                value: StreamHubErrorValue::WebRTCRelayNotEnabled,
+            });
+        }
+
+        if self.relay_streams.lock().unwrap().contains_key(&id) {
+            return Err(StreamHubError {
+                value: StreamHubErrorValue::Exists,
+            });
+        }
+
+        let (result_sender, result_receiver) = mpsc::channel(1);
 
         match relay_type {
             RelayType::Pull => {
                 let client_event = BroadcastEvent::Subscribe {
-                    id,
+                    id: id.clone(),
                     identifier,
                     server_address: Some(server_address),
+                    token,
                     result_sender: Some(result_sender),
                 };
 
@@ -927,21 +1418,53 @@ impl StreamsHub {
                         value: StreamHubErrorValue::SendError,
                     })?;
             }
-            RelayType::Push => {}
-        }
+            RelayType::Push => {
+                //the webrtc streams are pushed by the WHIP relay clients
+                if !is_webrtc && !self.rtmp_restream_enabled {
+                    return Err(StreamHubError {
+                        value: StreamHubErrorValue::RestreamNotEnabled,
+                    });
+                }
+                let client_event = BroadcastEvent::StartRestream {
+                    id: id.clone(),
+                    identifier,
+                    url: server_address,
+                    token,
+                    result_sender: Some(result_sender),
+                };
 
-        if let Some(received_message) = result_receiver.recv().await {
-            return received_message;
+                //send restream info to restream clients
+                self.client_event_sender
+                    .send(client_event)
+                    .map_err(|_| StreamHubError {
+                        value: StreamHubErrorValue::SendError,
+                    })?;
+            }
         }
-        Ok(())
+
+        self.relay_streams
+            .lock()
+            .unwrap()
+            .insert(id, relay_type.clone());
+        Ok(result_receiver)
     }
 
-    async fn api_stop_relay_stream(
+    fn api_stop_relay_stream(
         &mut self,
         id: String,
         relay_type: &RelayType,
-    ) -> Result<(), StreamHubError> {
-        let (result_sender, mut result_receiver) = mpsc::channel(1);
+    ) -> Result<BroadcastEventExecuteResultReceiver, StreamHubError> {
+        {
+            let mut relay_streams = self.relay_streams.lock().unwrap();
+            if relay_streams.get(&id) != Some(relay_type) {
+                return Err(StreamHubError {
+                    value: StreamHubErrorValue::RelayNotExist(id),
+                });
+            }
+            relay_streams.remove(&id);
+        }
+
+        let (result_sender, result_receiver) = mpsc::channel(1);
         match relay_type {
             RelayType::Pull => {
                 let client_event = BroadcastEvent::UnSubscribe {
@@ -956,13 +1479,41 @@ impl StreamsHub {
                         value: StreamHubErrorValue::SendError,
                     })?;
             }
-            RelayType::Push => {}
+            RelayType::Push => {
+                if !self.rtmp_restream_enabled && !self.webrtc_relay_enabled {
+                    return Err(StreamHubError {
+                        value: StreamHubErrorValue::RestreamNotEnabled,
+                    });
+                }
+                let client_event = BroadcastEvent::StopRestream {
+                    id,
+                    result_sender: Some(result_sender),
+                };
+
+                //send restream info to restream clients
+                self.client_event_sender
+                    .send(client_event)
+                    .map_err(|_| StreamHubError {
+                        value: StreamHubErrorValue::SendError,
+                    })?;
+            }
         }
 
+        Ok(result_receiver)
+    }
+
+    //only the relay client which owns the id replies
+    async fn wait_relay_result(
+        id: String,
+        mut result_receiver: BroadcastEventExecuteResultReceiver,
+    ) -> Result<(), StreamHubError> {
         if let Some(received_message) = result_receiver.recv().await {
             return received_message;
         }
-        Ok(())
+        //no relay client has handled the event
+        Err(StreamHubError {
+            value: StreamHubErrorValue::RelayNotExist(id),
+        })
     }
 
     //player subscribe a stream
@@ -987,6 +1538,66 @@ impl StreamsHub {
             return Ok(result_receiver.await?);
         }
 
+        //only one pull is started for the subscribers of a stream, a failed pull is
+        //started again after it expires
+        if self.stream_registry.is_some() || self.rtmp_pull_enabled {
+            self.pending_pulls
+                .retain(|_, start_time| start_time.elapsed() < PENDING_PULL_TIMEOUT);
+            if self.pending_pulls.contains_key(identifer) {
+                log::info!("subscribe: the stream is being pulled, identifier: {}", identifer);
+                return Err(StreamHubError {
+                    value: StreamHubErrorValue::NoAppOrStreamName,
+                });
+            }
+            self.pending_pulls.insert(identifer.clone(), Instant::now());
+        }
+
+        //resolve the origin in another task, the stream is pulled from the origin if it is found
+        if let Some(stream_registry) = &self.stream_registry {
+            let stream_registry = stream_registry.clone();
+            let client_event_sender = self.client_event_sender.clone();
+            let identifier = identifer.clone();
+            let rtmp_pull_enabled = self.rtmp_pull_enabled;
+
+            tokio::spawn(async move {
+                let client_event = if let Some(address) = stream_registry.resolve(&identifier).await
+                {
+                    log::info!(
+                        "subscribe: try to pull stream from origin: {}, identifier: {}",
+                        address,
+                        identifier
+                    );
+                    BroadcastEvent::Subscribe {
+                        id: String::from("cluster_relay"),
+                        identifier,
+                        server_address: Some(address),
+                        token: None,
+                        result_sender: None,
+                    }
+                } else if rtmp_pull_enabled {
+                    log::info!("subscribe: try to pull stream, identifier: {}", identifier);
+                    BroadcastEvent::Subscribe {
+                        id: String::from("rtmp_relay"),
+                        identifier,
+                        server_address: None,
+                        token: None,
+                        result_sender: None,
+                    }
+                } else {
+                    return;
+                };
+
+                //send subscribe info to pull clients
+                if client_event_sender.send(client_event).is_err() {
+                    log::error!("subscribe: send the relay subscribe event error");
+                }
+            });
+
+            return Err(StreamHubError {
+                value: StreamHubErrorValue::NoAppOrStreamName,
+            });
+        }
+
         if self.rtmp_pull_enabled {
             log::info!("subscribe: try to pull stream, identifier: {}", identifer);
 
@@ -994,6 +1605,7 @@ impl StreamsHub {
                 id: String::from("rtmp_relay"),
                 identifier: identifer.clone(),
                 server_address: None,
+                token: None,
                 result_sender: None,
             };
 
@@ -1034,22 +1646,69 @@ impl StreamsHub {
         Ok(())
     }
 
-    //publish a stream
+    //publish a stream, a backup publisher gets no statistic data sender
     pub async fn publish(
         &mut self,
         identifier: StreamIdentifier,
-        receiver: DataReceiver,
+        info: &PublisherInfo,
+        mut receiver: DataReceiver,
         handler: Arc<dyn TStreamHandler>,
-    ) -> Result<StatisticDataSender, StreamHubError> {
-        if self.streams.get(&identifier).is_some() {
+    ) -> Result<Option<StatisticDataSender>, StreamHubError> {
+        let is_failover_source = self.failover_config.is_some()
+            && matches!(info.pub_data_type, define::PubDataType::Frame);
+
+        if self.streams.contains_key(&identifier) {
+            if let (Some((source_event_sender, publisher_ids)), Some(frame_receiver)) = (
+                self.failover_streams.get_mut(&identifier),
+                receiver.frame_receiver.take(),
+            ) {
+                let event = FrameSourceEvent::Add {
+                    id: info.id,
+                    receiver: frame_receiver,
+                    handler,
+                };
+                source_event_sender.send(event).map_err(|_| StreamHubError {
+                    value: StreamHubErrorValue::SendError,
+                })?;
+                publisher_ids.push(info.id);
+                log::info!("publish a backup source, stream identifier: {}", identifier);
+                return Ok(None);
+            }
+
             return Err(StreamHubError {
                 value: StreamHubErrorValue::Exists,
             });
         }
 
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
-        let transceiver =
-            StreamDataTransceiver::new(receiver, event_receiver, identifier.clone(), handler);
+        let mut transceiver = match &self.failover_config {
+            Some(failover_config) if is_failover_source => {
+                let sources = FailoverSources::new(
+                    Duration::from_millis(failover_config.timeout.unwrap_or(3000)),
+                    failover_config.switch_back.unwrap_or(false),
+                    info.id,
+                    handler,
+                );
+                let (source_event_sender, source_event_receiver) = mpsc::unbounded_channel();
+                self.failover_streams.insert(
+                    identifier.clone(),
+                    (source_event_sender, vec![info.id]),
+                );
+
+                StreamDataTransceiver::new_with_failover(
+                    receiver,
+                    event_receiver,
+                    identifier.clone(),
+                    info.id,
+                    Arc::new(FailoverStreamHandler::new(sources)),
+                    source_event_receiver,
+                )
+            }
+            _ => StreamDataTransceiver::new(receiver, event_receiver, identifier.clone(), handler),
+        };
+        if let Some(timeout) = self.publish_timeout {
+            transceiver.set_watchdog(timeout, self.hub_event_sender.clone());
+        }
 
         let statistic_data_sender = transceiver.get_statistics_data_sender();
         let identifier_clone = identifier.clone();
@@ -1065,8 +1724,21 @@ impl StreamsHub {
         }
 
         self.streams.insert(identifier.clone(), event_sender);
-
-        if self.rtmp_push_enabled || self.hls_enabled || self.rtmp_remuxer_enabled {
+        self.pending_pulls.remove(&identifier);
+
+        //the rtsp stream remuxed from rtmp must not be remuxed back to rtmp, and the
+        //simulcast layers are only played by the whep subscribers of their whip stream
+        let is_not_remuxable = matches!(
+            info.pub_type,
+            PublishType::RtmpRemux2Rtsp | PublishType::WhipSimulcastLayer
+        );
+
+        if !is_not_remuxable
+            && (self.rtmp_push_enabled
+                || self.hls_enabled
+                || self.rtmp_remuxer_enabled
+                || self.rtmp_restream_enabled)
+        {
             let client_event = BroadcastEvent::Publish { identifier };
 
             //send publish info to push clients
@@ -1077,10 +1749,29 @@ impl StreamsHub {
                 })?;
         }
 
-        Ok(statistic_data_sender)
+        Ok(Some(statistic_data_sender))
     }
 
-    fn unpublish(&mut self, identifier: &StreamIdentifier) -> Result<(), StreamHubError> {
+    fn unpublish(
+        &mut self,
+        identifier: &StreamIdentifier,
+        info: &PublisherInfo,
+    ) -> Result<(), StreamHubError> {
+        if let Some((source_event_sender, publisher_ids)) =
+            self.failover_streams.get_mut(identifier)
+        {
+            publisher_ids.retain(|id| *id != info.id);
+            if !publisher_ids.is_empty() {
+                log::info!("unpublish a source, stream identifier: {}", identifier);
+                return source_event_sender
+                    .send(FrameSourceEvent::Remove { id: info.id })
+                    .map_err(|_| StreamHubError {
+                        value: StreamHubErrorValue::SendError,
+                    });
+            }
+            self.failover_streams.remove(identifier);
+        }
+
         match self.streams.get_mut(identifier) {
             Some(producer) => {
                 let event = TransceiverEvent::UnPublish {};
@@ -1089,6 +1780,19 @@ impl StreamsHub {
                 })?;
                 self.streams.remove(identifier);
                 log::info!("unpublish remove stream, stream identifier: {}", identifier);
+
+                if self.rtmp_push_enabled || self.rtmp_restream_enabled {
+                    let client_event = BroadcastEvent::UnPublish {
+                        identifier: identifier.clone(),
+                    };
+
+                    //send unpublish info to push clients
+                    self.client_event_sender
+                        .send(client_event)
+                        .map_err(|_| StreamHubError {
+                            value: StreamHubErrorValue::SendError,
+                        })?;
+                }
             }
             None => {
                 return Err(StreamHubError {
@@ -1100,3 +1804,111 @@ impl StreamsHub {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::StreamsHub;
+    use crate::{
+        define::{
+            BroadcastEvent, DataSender, NotifyInfo, RelayType, StreamHubEvent,
+            StreamHubEventSender, SubDataType, SubscribeType, SubscriberInfo,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        stream::StreamIdentifier,
+        utils::{RandomDigitCount, Uuid},
+    };
+    use tokio::sync::{mpsc, oneshot};
+
+    async fn start_relay(event_sender: &StreamHubEventSender) -> Result<(), StreamHubError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        event_sender
+            .send(StreamHubEvent::ApiStartRelayStream {
+                id: String::from("relay"),
+                identifier: StreamIdentifier::Rtmp {
+                    app_name: String::from("live"),
+                    stream_name: String::from("test"),
+                },
+                server_address: String::from("127.0.0.1:1935"),
+                relay_type: RelayType::Pull,
+                token: None,
+                result_sender,
+            })
+            .unwrap();
+        result_receiver.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_restart_stopped_relay() {
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let mut stream_hub = StreamsHub::new(None, event_sender.clone(), event_receiver);
+        let mut client_event_consumer = stream_hub.get_client_event_consumer();
+        tokio::spawn(async move { stream_hub.run().await });
+
+        //a relay client which starts all the relays
+        tokio::spawn(async move {
+            while let Ok(event) = client_event_consumer.recv().await {
+                if let BroadcastEvent::Subscribe {
+                    result_sender: Some(result_sender),
+                    ..
+                } = event
+                {
+                    let _ = result_sender.send(Ok(())).await;
+                }
+            }
+        });
+
+        assert!(start_relay(&event_sender).await.is_ok());
+        assert!(matches!(
+            start_relay(&event_sender).await,
+            Err(StreamHubError {
+                value: StreamHubErrorValue::Exists
+            })
+        ));
+
+        //the relay client gives up reconnecting
+        event_sender
+            .send(StreamHubEvent::RelayStopped {
+                id: String::from("relay"),
+            })
+            .unwrap();
+        assert!(start_relay(&event_sender).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pull_once_for_subscribers() {
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let mut stream_hub = StreamsHub::new(None, event_sender, event_receiver);
+        stream_hub.set_rtmp_pull_enabled(true);
+        let mut client_event_consumer = stream_hub.get_client_event_consumer();
+
+        let identifier = StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from("test"),
+        };
+        for _ in 0..3 {
+            let (frame_sender, _) = mpsc::unbounded_channel();
+            let sub_info = SubscriberInfo {
+                id: Uuid::new(RandomDigitCount::Zero),
+                sub_type: SubscribeType::RtmpPull,
+                notify_info: NotifyInfo {
+                    request_url: String::from(""),
+                    remote_addr: String::from(""),
+                },
+                sub_data_type: SubDataType::Frame,
+            };
+            let sender = DataSender::Frame {
+                sender: frame_sender,
+            };
+            assert!(stream_hub
+                .subscribe(&identifier, sub_info, sender)
+                .await
+                .is_err());
+        }
+
+        assert!(matches!(
+            client_event_consumer.try_recv(),
+            Ok(BroadcastEvent::Subscribe { .. })
+        ));
+        assert!(client_event_consumer.try_recv().is_err());
+    }
+}
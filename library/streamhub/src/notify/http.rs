@@ -25,6 +25,7 @@ pub struct HttpNotifier {
     on_play_url: Option<String>,
     on_stop_url: Option<String>,
     on_hls_url: Option<String>,
+    on_publish_timeout_url: Option<String>,
     event_producer: StreamHubEventSender,
 }
 
@@ -40,6 +41,7 @@ impl HttpNotifier {
             on_play_url: config.on_play,
             on_stop_url: config.on_stop,
             on_hls_url: config.on_hls,
+            on_publish_timeout_url: config.on_publish_timeout,
             event_producer,
         }
     }
@@ -145,6 +147,25 @@ impl Notifier for HttpNotifier {
         }
     }
 
+    async fn on_publish_timeout_notify(&self, event: &StreamHubEventMessage) {
+        if let Some(on_publish_timeout_url) = &self.on_publish_timeout_url {
+            match self
+                .request_client
+                .post(on_publish_timeout_url)
+                .body(serialize_event!(event))
+                .send()
+                .await
+            {
+                Err(err) => {
+                    log::error!("on_publish_timeout error: {}", err);
+                }
+                Ok(response) => {
+                    log::info!("on_publish_timeout success: {:?}", response);
+                }
+            }
+        }
+    }
+
     async fn kick_off_client(&self, event: &StreamHubEventMessage) {
         if let StreamHubEventMessage::Publish { identifier, info } = event {
             let hub_event = StreamHubEvent::ApiKickClient { id: info.id.clone() };
@@ -10,5 +10,6 @@ pub trait Notifier: Sync + Send {
     async fn on_play_notify(&self, event: &StreamHubEventMessage);
     async fn on_stop_notify(&self, event: &StreamHubEventMessage);
     async fn on_hls_notify(&self, event: &StreamHubEventMessage);
+    async fn on_publish_timeout_notify(&self, event: &StreamHubEventMessage);
     async fn kick_off_client(&self, event: &StreamHubEventMessage);
 }
\ No newline at end of file
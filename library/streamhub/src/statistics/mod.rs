@@ -56,6 +56,8 @@ pub struct StatisticsStream {
     pub total_recv_bytes: usize,
     /*calculate downstream traffic, now equals audio and video traffic sent to all subscribers*/
     pub total_send_bytes: usize,
+    /*the restream targets of this stream, indexed by the target id*/
+    pub restreams: HashMap<String, StatisticRestream>,
 }
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct StatisticPublisher {
@@ -97,6 +99,33 @@ pub struct StatisticSubscriber {
     pub total_send_bytes: usize,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum RestreamState {
+    Connecting,
+    Pushing,
+    /*the push session is interrupted and waits for reconnecting*/
+    Reconnecting,
+    /*the retries are exhausted*/
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatisticRestream {
+    pub id: String,
+    /*the target url without the stream key*/
+    pub url: String,
+    pub state: RestreamState,
+    /*how many times the target has been reconnected*/
+    pub reconnect_count: usize,
+    pub start_time: DateTime<Local>,
+    /*the subscriber id of the current push session*/
+    #[serde(skip_serializing)]
+    pub subscriber_id: Option<Uuid>,
+    #[serde(rename = "send_bitrate(kbits/s)")]
+    pub send_bitrate: usize,
+}
+
 impl StatisticsStream {
     pub fn new(identifier: StreamIdentifier) -> Self {
         Self {
@@ -159,6 +188,14 @@ impl StatisticsCaculate {
             subscriber.send_bitrate = subscriber.send_bytes * 8 / 5000;
             subscriber.send_bytes = 0;
         }
+
+        let stream_statistics = &mut **stream_statistics_clone;
+        for restream in stream_statistics.restreams.values_mut() {
+            restream.send_bitrate = restream
+                .subscriber_id
+                .and_then(|id| stream_statistics.subscribers.get(&id))
+                .map_or(0, |subscriber| subscriber.send_bitrate);
+        }
     }
     pub async fn start(&mut self) {
         let mut interval = time::interval(Duration::from_secs(5));
@@ -1,7 +1,7 @@
 use rand::RngExt;
 use serde::{Serialize, Serializer};
 use std::fmt;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Copy)]
 pub struct Uuid {
@@ -117,11 +117,146 @@ impl fmt::Display for Uuid {
     }
 }
 
+//the relay clients give up reconnecting after so many retries by default
+pub const DEFAULT_MAX_RETRIES: usize = 10;
+
+/* Exponential backoff with jitter, used by the relay clients to reconnect
+to the remote server. The delay doubles on every failed attempt until it
+reaches the max delay, and half of it is randomized to avoid all the relay
+sessions reconnecting at the same time. */
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    /* the relay gives up and unpublishes its stream after so many retries,
+    it retries forever if it is 0 */
+    max_retries: usize,
+    retries: usize,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_millis(500),
+            Duration::from_secs(30),
+            DEFAULT_MAX_RETRIES,
+        )
+    }
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration, max_retries: usize) -> Self {
+        Self {
+            initial_delay,
+            max_delay: std::cmp::max(initial_delay, max_delay),
+            max_retries,
+            retries: 0,
+        }
+    }
+
+    pub fn from_config(cfg: &Option<config::ReconnectConfig>) -> Self {
+        let mut backoff = Self::default();
+        if let Some(cfg) = cfg {
+            if let Some(initial_delay) = cfg.initial_delay {
+                backoff.initial_delay = Duration::from_millis(initial_delay);
+            }
+            if let Some(max_delay) = cfg.max_delay {
+                backoff.max_delay = Duration::from_millis(max_delay);
+            }
+            backoff.max_delay = std::cmp::max(backoff.initial_delay, backoff.max_delay);
+            if let Some(max_retries) = cfg.max_retries {
+                backoff.max_retries = max_retries;
+            }
+        }
+        backoff
+    }
+
+    /* Retry forever, e.g. the streams pulled from the other nodes of the cluster. */
+    pub fn unlimited() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /* Returns the delay before the next attempt, or None if the retries are exhausted. */
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_retries > 0 && self.retries >= self.max_retries {
+            return None;
+        }
+
+        let exponent = std::cmp::min(self.retries, 16) as u32;
+        let delay = std::cmp::min(
+            self.initial_delay.saturating_mul(1 << exponent),
+            self.max_delay,
+        );
+        self.retries += 1;
+
+        let half_millis = delay.as_millis() as u64 / 2;
+        let jitter = rand::rng().random_range(0..=half_millis);
+        Some(delay - Duration::from_millis(half_millis) + Duration::from_millis(jitter))
+    }
+
+    /* Called after a session is established successfully. */
+    pub fn reset(&mut self) {
+        self.retries = 0;
+    }
+
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+}
+
+/* Keeps the timestamps of a stream continuous when its source is switched,
+e.g. a relay session reconnects to the remote server and the remote timestamps
+restart from another value. It works for both the millisecond and the RTP clock
+timestamps, so the calculation wraps around. */
+#[derive(Debug, Clone, Default)]
+pub struct TimestampRebaser {
+    offset: u32,
+    last_timestamp: Option<u32>,
+    need_rebase: bool,
+}
+
+impl TimestampRebaser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /* The next incoming timestamp will be mapped right after the last outgoing one. */
+    pub fn discontinue(&mut self) {
+        self.need_rebase = true;
+    }
+
+    pub fn rebase(&mut self, timestamp: u32) -> u32 {
+        if self.need_rebase {
+            if let Some(last_timestamp) = self.last_timestamp {
+                self.offset = last_timestamp.wrapping_add(1).wrapping_sub(timestamp);
+            }
+            self.need_rebase = false;
+        }
+
+        let rebased = timestamp.wrapping_add(self.offset);
+        self.last_timestamp = Some(rebased);
+        rebased
+    }
+
+    /* Map a timestamp with the current offset without changing the state. */
+    pub fn map(&self, timestamp: u32) -> u32 {
+        timestamp.wrapping_add(self.offset)
+    }
+
+    pub fn last_timestamp(&self) -> Option<u32> {
+        self.last_timestamp
+    }
+}
+
 #[cfg(test)]
 mod tests {
     
 
-    use super::Uuid;
+    use super::{Backoff, TimestampRebaser, Uuid};
+    use std::time::Duration;
 
     #[test]
     fn test_uuid() {
@@ -139,4 +274,49 @@ mod tests {
             println!("{:?}", u.to_string());
         }
     }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(1000), 6);
+
+        let mut delays = Vec::new();
+        while let Some(delay) = backoff.next_delay() {
+            delays.push(delay);
+        }
+        assert_eq!(delays.len(), 6);
+
+        let expected_max = [100, 200, 400, 800, 1000, 1000];
+        for (delay, max) in delays.iter().zip(expected_max.iter()) {
+            assert!(delay.as_millis() as u64 <= *max);
+            assert!(delay.as_millis() as u64 >= *max / 2);
+        }
+
+        backoff.reset();
+        assert_eq!(backoff.retries(), 0);
+        assert!(backoff.next_delay().unwrap() <= Duration::from_millis(100));
+
+        //retry forever
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(1000), 0);
+        for _ in 0..100 {
+            assert!(backoff.next_delay().unwrap() <= Duration::from_millis(1000));
+        }
+        assert_eq!(backoff.retries(), 100);
+    }
+
+    #[test]
+    fn test_timestamp_rebaser() {
+        let mut rebaser = TimestampRebaser::new();
+        assert_eq!(rebaser.rebase(1000), 1000);
+        assert_eq!(rebaser.rebase(1040), 1040);
+
+        //the remote timestamps restart after reconnecting
+        rebaser.discontinue();
+        assert_eq!(rebaser.rebase(0), 1041);
+        assert_eq!(rebaser.rebase(40), 1081);
+
+        //rtp timestamps may wrap around
+        rebaser.discontinue();
+        assert_eq!(rebaser.rebase(u32::MAX), 1082);
+        assert_eq!(rebaser.rebase(9), 1092);
+    }
 }
@@ -24,6 +24,10 @@ pub struct Flv2HlsRemuxer {
 
     duration: i64,
     need_new_segment: bool,
+    /* the source is switched, cut the segment at the next key frame and
+    tag the following segment with EXT-X-DISCONTINUITY */
+    need_discontinuity: bool,
+    is_discontinuous_segment: bool,
 
     video_pid: u16,
     audio_pid: u16,
@@ -75,6 +79,8 @@ impl Flv2HlsRemuxer {
 
             duration,
             need_new_segment: false,
+            need_discontinuity: false,
+            is_discontinuous_segment: false,
 
             video_pid,
             audio_pid,
@@ -108,9 +114,13 @@ impl Flv2HlsRemuxer {
         Ok(())
     }
 
+    pub fn on_discontinuity(&mut self) {
+        self.need_discontinuity = true;
+    }
+
     pub fn flush_remaining_data(&mut self) -> Result<(), MediaError> {
         let data = self.ts_muxer.get_data();
-        let mut discontinuity: bool = false;
+        let mut discontinuity: bool = self.is_discontinuous_segment;
         if self.last_dts > self.last_ts_dts + 15 * 1000 {
             discontinuity = true;
         }
@@ -146,7 +156,8 @@ impl Flv2HlsRemuxer {
 
                 if data.frame_type == frame_type::KEY_FRAME {
                     flags = MPEG_FLAG_IDR_FRAME;
-                    if dts - self.last_ts_dts >= self.duration * 1000 {
+                    if dts - self.last_ts_dts >= self.duration * 1000 || self.need_discontinuity
+                    {
                         self.need_new_segment = true;
                     }
                 }
@@ -169,10 +180,12 @@ impl Flv2HlsRemuxer {
         }
 
         if self.need_new_segment {
-            let mut discontinuity: bool = false;
+            let mut discontinuity: bool = self.is_discontinuous_segment;
             if dts > self.last_ts_dts + 15 * 1000 {
                 discontinuity = true;
             }
+            self.is_discontinuous_segment = self.need_discontinuity;
+            self.need_discontinuity = false;
             let data = self.ts_muxer.get_data();
 
             if let Some(segment) = self.m3u8_handler.segments.back() {
@@ -66,6 +66,10 @@ impl FlvDataReceiver {
                 let flv_data: FlvData = match data {
                     FrameData::Audio { timestamp, data } => FlvData::Audio { timestamp, data },
                     FrameData::Video { timestamp, data } => FlvData::Video { timestamp, data },
+                    FrameData::Discontinuity {} => {
+                        self.media_processor.on_discontinuity();
+                        continue;
+                    }
                     _ => continue,
                 };
                 retry_count = 0;
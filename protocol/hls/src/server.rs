@@ -110,7 +110,10 @@ async fn response_file(hls_path: &HlsPath) -> Response<Body> {
     let file_path = hls_path.to_file_path();
 
     if let Ok(file) = File::open(&file_path).await {
-        let builder = Response::builder().header("Content-Type", hls_path.file_type.content_type());
+        //the playlists are also played by the web pages of the other origins, e.g. the console
+        let builder = Response::builder()
+            .header("Content-Type", hls_path.file_type.content_type())
+            .header("Access-Control-Allow-Origin", "*");
 
         // Serve a file by asynchronously reading it by chunks using tokio-util crate.
         let stream = FramedRead::new(file, BytesCodec::new());
@@ -18,6 +18,7 @@ use {
     tokio::sync::mpsc,
     xflv::amf0::amf0_writer::Amf0Writer,
     xflv::muxer::{FlvMuxer, HEADER_LENGTH},
+    xflv::script_data::marshal_text_data,
 };
 
 pub struct HttpFlv {
@@ -29,6 +30,8 @@ pub struct HttpFlv {
     has_audio: bool,
     has_video: bool,
     has_send_header: bool,
+    /* the timestamp of the last written audio/video tag, the text data is stamped with it */
+    last_timestamp: u32,
 
     event_producer: StreamHubEventSender,
     data_receiver: FrameDataReceiver,
@@ -59,6 +62,7 @@ impl HttpFlv {
             has_audio: false,
             has_video: false,
             has_send_header: false,
+            last_timestamp: 0,
             data_receiver,
             statistic_data_sender: None,
             event_producer,
@@ -85,6 +89,11 @@ impl HttpFlv {
         //write flv body
         loop {
             if let Some(data) = self.data_receiver.recv().await {
+                //the timestamps are kept continuous by the publisher, nothing to do here
+                if let FrameData::Discontinuity {} = data {
+                    continue;
+                }
+
                 if !self.has_send_header {
                     max_av_frame_num_to_guess_av += 1;
 
@@ -166,6 +175,7 @@ impl HttpFlv {
                     }
                 }
 
+                self.last_timestamp = timestamp;
                 (data, timestamp, tag_type::AUDIO)
             }
             FrameData::Video { timestamp, data } => {
@@ -182,6 +192,7 @@ impl HttpFlv {
                     }
                 }
 
+                self.last_timestamp = timestamp;
                 (data, timestamp, tag_type::VIDEO)
             }
             FrameData::MetaData { timestamp, data } => {
@@ -192,6 +203,11 @@ impl HttpFlv {
 
                 (BytesMut::from(right), timestamp, tag_type::SCRIPT_DATA_AMF)
             }
+            FrameData::TextData { data } => (
+                marshal_text_data(&data)?,
+                self.last_timestamp,
+                tag_type::SCRIPT_DATA_AMF,
+            ),
             _ => {
                 log::error!("should not be here!!!");
                 (BytesMut::new(), 0, 0)
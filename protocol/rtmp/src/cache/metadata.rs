@@ -31,7 +31,8 @@ impl MetaData {
 
     pub fn is_metadata(&mut self, body: BytesMut) -> bool {
         let reader = BytesReader::new(body);
-        let result = Amf0Reader::new(reader).read_all();
+        //the malformed values after the metadata name are skipped
+        let result = Amf0Reader::new_recoverable(reader).read_all();
 
         let mut values: Vec<Amf0ValueType> = Vec::new();
 
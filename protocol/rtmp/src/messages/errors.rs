@@ -7,7 +7,7 @@ use {
     bytesio::bytes_errors::BytesReadError,
     failure::{Backtrace, Fail},
     std::fmt,
-    xflv::amf0::errors::Amf0ReadError,
+    xflv::amf0::errors::{Amf0ReadError, Amf0WriteError},
 };
 
 #[derive(Debug, Fail)]
@@ -18,6 +18,8 @@ pub enum MessageErrorValue {
     UnknowReadState,
     #[fail(display = "amf0 read error: {}", _0)]
     Amf0ReadError(Amf0ReadError),
+    #[fail(display = "amf0 write error: {}", _0)]
+    Amf0WriteError(Amf0WriteError),
     #[fail(display = "unknown message type")]
     UnknowMessageType,
     #[fail(display = "protocol control message read error: {}", _0)]
@@ -53,6 +55,14 @@ impl From<Amf0ReadError> for MessageError {
     }
 }
 
+impl From<Amf0WriteError> for MessageError {
+    fn from(error: Amf0WriteError) -> Self {
+        MessageError {
+            value: MessageErrorValue::Amf0WriteError(error),
+        }
+    }
+}
+
 impl From<ProtocolControlMessageReaderError> for MessageError {
     fn from(error: ProtocolControlMessageReaderError) -> Self {
         MessageError {
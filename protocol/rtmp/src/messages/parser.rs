@@ -10,7 +10,7 @@ use {
         // utils,
     },
     bytesio::bytes_reader::BytesReader,
-    xflv::amf0::{amf0_markers, amf0_reader::Amf0Reader},
+    xflv::amf0::{amf0_markers, amf0_reader::Amf0Reader, amf0_writer::Amf0Writer},
 };
 
 pub struct MessageParser {
@@ -26,6 +26,8 @@ impl MessageParser {
 
         match self.chunk_info.message_header.msg_type_id {
             msg_type_id::COMMAND_AMF0 | msg_type_id::COMMAND_AMF3 => {
+                //the amf3 command is a format byte followed by the amf0 values,
+                //which switch to amf3 by the avmplus object marker
                 if self.chunk_info.message_header.msg_type_id == msg_type_id::COMMAND_AMF3 {
                     reader.read_u8()?;
                 }
@@ -109,12 +111,24 @@ impl MessageParser {
                     ProtocolControlMessageReader::new(reader).read_set_peer_bandwidth()?;
                 return Ok(Some(RtmpMessageData::SetPeerBandwidth { properties }));
             }
-            msg_type_id::DATA_AMF0 | msg_type_id::DATA_AMF3 => {
+            msg_type_id::DATA_AMF0 => {
                 //let values = Amf0Reader::new(reader).read_all()?;
                 return Ok(Some(RtmpMessageData::AmfData {
                     raw_data: reader.extract_remaining_bytes(),
                 }));
             }
+            msg_type_id::DATA_AMF3 => {
+                //the data is converted to amf0 which the subscribers can read,
+                //the values which cannot be parsed are dropped
+                reader.read_u8()?;
+                let values = Amf0Reader::new_recoverable(reader).read_all()?;
+
+                let mut amf_writer = Amf0Writer::new();
+                amf_writer.write_anys(&values)?;
+                return Ok(Some(RtmpMessageData::AmfData {
+                    raw_data: amf_writer.extract_current_bytes(),
+                }));
+            }
 
             msg_type_id::SHARED_OBJ_AMF3 | msg_type_id::SHARED_OBJ_AMF0 => {}
 
@@ -182,4 +196,59 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_amf3_command_parse() {
+        use super::super::define::{msg_type_id, RtmpMessageData};
+        use crate::chunk::{define::chunk_type, define::csid_type, ChunkInfo};
+        use bytes::BytesMut;
+        use indexmap::IndexMap;
+        use xflv::amf0::Amf0ValueType;
+
+        let data: [u8; 52] = [
+            0, //format byte
+            2, 0, 7, 99, 111, 110, 110, 101, 99, 116, //"connect"
+            0, 63, 240, 0, 0, 0, 0, 0, 0, //1.0
+            0x11, 0x0a, 0x0b, 0x01, //avmplus object marker + amf3 dynamic anonymous object
+            0x07, 97, 112, 112, 0x06, 0x09, 108, 105, 118, 101,  //app: "live"
+            0x1d, //the length of "objectEncoding"
+            111, 98, 106, 101, 99, 116, 69, 110, 99, 111, 100, 105, 110, 103, //objectEncoding
+            0x04, 0x03, 0x01, //3 + end of dynamic members
+        ];
+
+        let chunk_info = ChunkInfo::new(
+            csid_type::COMMAND_AMF0_AMF3,
+            chunk_type::TYPE_0,
+            0,
+            data.len() as u32,
+            msg_type_id::COMMAND_AMF3,
+            0,
+            BytesMut::from(&data[..]),
+        );
+
+        let Ok(Some(RtmpMessageData::Amf0Command {
+            command_name,
+            transaction_id,
+            command_object,
+            others,
+        })) = MessageParser::new(chunk_info).parse()
+        else {
+            panic!("the amf3 command is not parsed");
+        };
+
+        let mut properties = IndexMap::new();
+        properties.insert(
+            String::from("app"),
+            Amf0ValueType::UTF8String(String::from("live")),
+        );
+        properties.insert(String::from("objectEncoding"), Amf0ValueType::Number(3.0));
+
+        assert_eq!(
+            command_name,
+            Amf0ValueType::UTF8String(String::from("connect"))
+        );
+        assert_eq!(transaction_id, Amf0ValueType::Number(1.0));
+        assert_eq!(command_object, Amf0ValueType::Object(properties));
+        assert!(others.is_empty());
+    }
 }
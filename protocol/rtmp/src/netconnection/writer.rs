@@ -257,6 +257,19 @@ impl NetConnection {
         self.write_chunk().await
     }
 
+    pub async fn write_result(
+        &mut self,
+        transaction_id: &f64,
+        value: &Amf0ValueType,
+    ) -> Result<(), NetConnectionError> {
+        self.amf0_writer.write_string(&String::from("_result"))?;
+        self.amf0_writer.write_number(transaction_id)?;
+        self.amf0_writer.write_null()?;
+        self.amf0_writer.write_any(value)?;
+
+        self.write_chunk().await
+    }
+
     pub async fn write_get_stream_length(
         &mut self,
         transaction_id: &f64,
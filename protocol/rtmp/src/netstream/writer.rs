@@ -223,4 +223,31 @@ impl NetStreamWriter {
 
         self.write_chunk(1).await
     }
+
+    /*onFCPublish/onFCUnpublish, answer the FCPublish/FCUnpublish of some encoders*/
+    pub async fn write_on_fc_status(
+        &mut self,
+        command_name: &str,
+        code: &str,
+        description: &str,
+    ) -> Result<(), NetStreamError> {
+        self.amf0_writer.write_string(&command_name.to_owned())?;
+        self.amf0_writer.write_number(&0.0)?;
+        self.amf0_writer.write_null()?;
+
+        let mut properties_map = IndexMap::new();
+
+        properties_map.insert(
+            String::from("code"),
+            Amf0ValueType::UTF8String(code.to_owned()),
+        );
+        properties_map.insert(
+            String::from("description"),
+            Amf0ValueType::UTF8String(description.to_owned()),
+        );
+
+        self.amf0_writer.write_object(&properties_map)?;
+
+        self.write_chunk(0).await
+    }
 }
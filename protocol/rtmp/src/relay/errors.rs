@@ -1,5 +1,6 @@
 #![allow(non_local_definitions)]
 use {
+    bytesio::bytesio_errors::BytesIOError,
     failure::Fail,
     std::{fmt, io::Error},
     tokio::sync::broadcast::error::RecvError,
@@ -25,6 +26,8 @@ pub enum PushClientErrorValue {
     SendError,
     #[fail(display = "io error")]
     IOError(Error),
+    #[fail(display = "bytes io error")]
+    BytesIOError(BytesIOError),
 }
 
 impl From<Error> for ClientError {
@@ -35,6 +38,14 @@ impl From<Error> for ClientError {
     }
 }
 
+impl From<BytesIOError> for ClientError {
+    fn from(error: BytesIOError) -> Self {
+        ClientError {
+            value: PushClientErrorValue::BytesIOError(error),
+        }
+    }
+}
+
 impl From<RecvError> for ClientError {
     fn from(error: RecvError) -> Self {
         ClientError {
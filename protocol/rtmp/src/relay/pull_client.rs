@@ -2,28 +2,46 @@ use streamhub::stream::StreamIdentifier;
 
 use {
     super::errors::ClientError,
-    crate::session::client_session::{ClientSession, ClientSessionType},
-    streamhub::define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
-    tokio::net::TcpStream,
+    crate::session::{
+        client_session::{ClientSession, ClientSessionType},
+        common::RelayPublication,
+    },
+    std::{collections::HashMap, sync::Arc},
+    streamhub::{
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            StreamHubEvent, StreamHubEventSender,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        utils::Backoff,
+    },
+    tokio::{net::TcpStream, sync::watch, time::sleep},
 };
 
 pub struct PullClient {
-    address: String,
+    /* the default remote address, the address of the subscribe event takes precedence */
+    address: Option<String>,
+    /* the running state of the relays started by the api, the pulls of the
+    cluster and the static pull share their ids and are not tracked */
+    clients: HashMap<String, Arc<watch::Sender<bool>>>,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    backoff: Backoff,
 }
 
 impl PullClient {
     pub fn new(
-        address: String,
+        address: Option<String>,
         consumer: BroadcastEventReceiver,
         producer: StreamHubEventSender,
+        backoff: Backoff,
     ) -> Self {
         Self {
             address,
-
+            clients: HashMap::new(),
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            backoff,
         }
     }
 
@@ -31,40 +49,249 @@ impl PullClient {
         loop {
             let event = self.client_event_consumer.recv().await?;
 
-            if let BroadcastEvent::Subscribe {
-                id: _,
-                identifier:
-                    StreamIdentifier::Rtmp {
+            match event {
+                BroadcastEvent::Subscribe {
+                    id,
+                    identifier:
+                        StreamIdentifier::Rtmp {
+                            app_name,
+                            stream_name,
+                        },
+                    server_address,
+                    token: _,
+                    result_sender,
+                } => {
+                    let Some(address) = server_address.or_else(|| self.address.clone()) else {
+                        log::error!(
+                            "no address to pull, app_name :{}, stream_name: {}",
+                            app_name,
+                            stream_name
+                        );
+                        Self::send_result(result_sender, Err(relay_error("no address to pull")))
+                            .await;
+                        continue;
+                    };
+                    log::info!(
+                        "receive pull event, app_name :{}, stream_name: {}, address: {}",
                         app_name,
                         stream_name,
-                    },
-                server_address: _,
-                result_sender: _,
-            } = event
-            {
+                        address
+                    );
+
+                    let is_api_relay = result_sender.is_some();
+                    let is_running = if is_api_relay {
+                        match self.new_client(&id) {
+                            Ok(is_running) => is_running,
+                            Err(err) => {
+                                Self::send_result(result_sender, Err(err)).await;
+                                continue;
+                            }
+                        }
+                    } else {
+                        Arc::new(watch::Sender::new(true))
+                    };
+                    let producer = self.channel_event_producer.clone();
+                    let backoff = self.backoff.clone();
+
+                    //connect in the task, a slow upstream does not hold up the other events
+                    tokio::spawn(async move {
+                        let stream = match TcpStream::connect(address.clone()).await {
+                            Ok(stream) => stream,
+                            Err(err) => {
+                                log::error!("pull client connect {} error: {}", address, err);
+                                is_running.send_replace(false);
+                                Self::send_result(
+                                    result_sender,
+                                    Err(relay_error(&err.to_string())),
+                                )
+                                .await;
+                                return;
+                            }
+                        };
+                        Self::send_result(result_sender, Ok(())).await;
+
+                        let client_session = ClientSession::new(
+                            stream,
+                            ClientSessionType::Pull,
+                            address.clone(),
+                            app_name.clone(),
+                            stream_name.clone(),
+                            producer.clone(),
+                            0,
+                        );
+
+                        let gives_up = Self::supervise(
+                            client_session,
+                            address,
+                            app_name,
+                            stream_name,
+                            producer.clone(),
+                            backoff,
+                            is_running,
+                        )
+                        .await;
+
+                        //release the id of the api relay in the stream hub
+                        if gives_up && is_api_relay {
+                            if let Err(err) = producer.send(StreamHubEvent::RelayStopped { id }) {
+                                log::error!("pull client send relay stopped error: {}", err);
+                            }
+                        }
+                    });
+                }
+                //the ids of the other relay clients are ignored
+                BroadcastEvent::UnSubscribe { id, result_sender } => {
+                    if let Some(is_running) = self.clients.remove(&id) {
+                        is_running.send_replace(false);
+                        Self::send_result(result_sender, Ok(())).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    //the id of a relay which gave up reconnecting can be reused
+    fn new_client(&mut self, id: &str) -> Result<Arc<watch::Sender<bool>>, StreamHubError> {
+        if let Some(is_running) = self.clients.get(id) {
+            if *is_running.borrow() {
+                return Err(relay_error("the relay id exists"));
+            }
+        }
+
+        let is_running = Arc::new(watch::Sender::new(true));
+        self.clients.insert(id.to_string(), is_running.clone());
+        Ok(is_running)
+    }
+
+    async fn send_result(
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+        result: Result<(), StreamHubError>,
+    ) {
+        if let Some(sender) = result_sender {
+            if let Err(err) = sender.send(result).await {
+                log::error!("pull client send result error: {}", err);
+            }
+        }
+    }
+
+    //wait until the relay is stopped by the api
+    async fn wait_stopped(state_receiver: &mut watch::Receiver<bool>) {
+        let _ = state_receiver.wait_for(|is_running| !*is_running).await;
+    }
+
+    /* Run the pull session and reconnect it when it fails. The local publication
+    is handed over between the sessions, so the subscribers stay connected during
+    a short upstream outage. It is unpublished after the retries are exhausted or
+    the relay is stopped. Return true if it gives up reconnecting. */
+    async fn supervise(
+        mut client_session: ClientSession,
+        address: String,
+        app_name: String,
+        stream_name: String,
+        producer: StreamHubEventSender,
+        mut backoff: Backoff,
+        is_running: Arc<watch::Sender<bool>>,
+    ) -> bool {
+        let mut publication: Option<RelayPublication>;
+        let mut state_receiver = is_running.subscribe();
+
+        loop {
+            let result = tokio::select! {
+                result = client_session.run() => result,
+                _ = Self::wait_stopped(&mut state_receiver) => {
+                    log::info!(
+                        "pull client stops pulling from {}, app_name: {}, stream_name: {}",
+                        address,
+                        app_name,
+                        stream_name
+                    );
+                    if let Err(err) = client_session.exit().await {
+                        log::error!("pull client exit error: {}", err);
+                    }
+                    return false;
+                }
+            };
+            if let Err(err) = result {
+                //the stream is pulled by another relay, reconnecting cannot publish it either
+                if err.is_stream_exists() {
+                    log::warn!(
+                        "pull client stops pulling from {}, the stream exists, app_name: {}, stream_name: {}",
+                        address,
+                        app_name,
+                        stream_name
+                    );
+                    return is_running.send_replace(false);
+                }
+                log::error!("client_session as pull client run error: {}", err);
+            }
+            if client_session.is_started() {
+                backoff.reset();
+            }
+            publication = client_session.take_relay_publication();
+
+            loop {
+                let delay = match backoff.next_delay() {
+                    Some(delay) if *is_running.borrow() => delay,
+                    _ => {
+                        log::warn!(
+                            "pull client stops reconnecting to {} after {} retries, app_name: {}, stream_name: {}",
+                            address,
+                            backoff.retries(),
+                            app_name,
+                            stream_name
+                        );
+                        //it is stopped by the api if it is not running
+                        let gives_up = is_running.send_replace(false);
+                        if let Some(publication) = publication {
+                            if let Err(err) = publication.unpublish(&producer) {
+                                log::error!("pull client unpublish error: {}", err);
+                            }
+                        }
+                        return gives_up;
+                    }
+                };
+
                 log::info!(
-                    "receive pull event, app_name :{}, stream_name: {}",
+                    "pull client reconnects to {} in {:?}, app_name: {}, stream_name: {}",
+                    address,
+                    delay,
                     app_name,
                     stream_name
                 );
-                let stream = TcpStream::connect(self.address.clone()).await?;
-
-                let mut client_session = ClientSession::new(
-                    stream,
-                    ClientSessionType::Pull,
-                    self.address.clone(),
-                    app_name.clone(),
-                    stream_name.clone(),
-                    self.channel_event_producer.clone(),
-                    0,
-                );
+                //a stopped relay does not wait for the reconnection
+                tokio::select! {
+                    _ = sleep(delay) => {}
+                    _ = Self::wait_stopped(&mut state_receiver) => continue,
+                }
 
-                tokio::spawn(async move {
-                    if let Err(err) = client_session.run().await {
-                        log::error!("client_session as pull client run error: {}", err);
+                match TcpStream::connect(address.clone()).await {
+                    Ok(stream) => {
+                        client_session = ClientSession::new(
+                            stream,
+                            ClientSessionType::Pull,
+                            address.clone(),
+                            app_name.clone(),
+                            stream_name.clone(),
+                            producer.clone(),
+                            0,
+                        );
+                        if let Some(publication) = publication.take() {
+                            client_session.resume_relay_publication(publication);
+                        }
+                        break;
+                    }
+                    Err(err) => {
+                        log::error!("pull client connect {} error: {}", address, err);
                     }
-                });
+                }
             }
         }
     }
 }
+
+fn relay_error(desp: &str) -> StreamHubError {
+    StreamHubError {
+        value: StreamHubErrorValue::RtmpRelayError(String::from(desp)),
+    }
+}
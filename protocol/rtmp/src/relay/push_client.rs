@@ -1,17 +1,28 @@
 use {
     super::errors::ClientError,
     crate::session::client_session::{ClientSession, ClientSessionType},
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    },
     streamhub::{
-        define::{StreamHubEventSender, BroadcastEvent, BroadcastEventReceiver},
+        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
         stream::StreamIdentifier,
+        utils::Backoff,
     },
-    tokio::net::TcpStream,
+    tokio::{net::TcpStream, time::sleep},
 };
 
 pub struct PushClient {
     address: String,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    backoff: Backoff,
+    /* the local streams being pushed, the flag is cleared when the stream is unpublished */
+    pushing_streams: HashMap<StreamIdentifier, Arc<AtomicBool>>,
 }
 
 impl PushClient {
@@ -19,12 +30,15 @@ impl PushClient {
         address: String,
         consumer: BroadcastEventReceiver,
         producer: StreamHubEventSender,
+        backoff: Backoff,
     ) -> Self {
         Self {
             address,
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            backoff,
+            pushing_streams: HashMap::new(),
         }
     }
 
@@ -39,7 +53,7 @@ impl PushClient {
                     if let StreamIdentifier::Rtmp {
                         app_name,
                         stream_name,
-                    } = identifier
+                    } = identifier.clone()
                     {
                         log::info!(
                             "publish app_name: {} stream_name: {} address: {}",
@@ -47,25 +61,36 @@ impl PushClient {
                             stream_name.clone(),
                             self.address.clone()
                         );
-                        let stream = TcpStream::connect(self.address.clone()).await?;
 
-                        let mut client_session = ClientSession::new(
-                            stream,
-                            ClientSessionType::Push,
-                            self.address.clone(),
-                            app_name,
-                            stream_name,
-                            self.channel_event_producer.clone(),
-                            0,
-                        );
+                        let is_pushing = Arc::new(AtomicBool::new(true));
+                        if let Some(previous) =
+                            self.pushing_streams.insert(identifier, is_pushing.clone())
+                        {
+                            previous.store(false, Ordering::Release);
+                        }
+
+                        let address = self.address.clone();
+                        let producer = self.channel_event_producer.clone();
+                        let backoff = self.backoff.clone();
 
                         tokio::spawn(async move {
-                            if let Err(err) = client_session.run().await {
-                                log::error!("client_session as push client run error: {}", err);
-                            }
+                            Self::supervise(
+                                address,
+                                app_name,
+                                stream_name,
+                                producer,
+                                backoff,
+                                is_pushing,
+                            )
+                            .await;
                         });
                     }
                 }
+                BroadcastEvent::UnPublish { identifier } => {
+                    if let Some(is_pushing) = self.pushing_streams.remove(&identifier) {
+                        is_pushing.store(false, Ordering::Release);
+                    }
+                }
 
                 _ => {
                     log::info!("push client receive other events");
@@ -73,4 +98,67 @@ impl PushClient {
             }
         }
     }
+
+    /* Push the local stream to the remote server, reconnect with backoff when the
+    session fails until the local stream is unpublished. */
+    async fn supervise(
+        address: String,
+        app_name: String,
+        stream_name: String,
+        producer: StreamHubEventSender,
+        mut backoff: Backoff,
+        is_pushing: Arc<AtomicBool>,
+    ) {
+        while is_pushing.load(Ordering::Acquire) {
+            match TcpStream::connect(address.clone()).await {
+                Ok(stream) => {
+                    let mut client_session = ClientSession::new(
+                        stream,
+                        ClientSessionType::Push,
+                        address.clone(),
+                        app_name.clone(),
+                        stream_name.clone(),
+                        producer.clone(),
+                        0,
+                    );
+
+                    if let Err(err) = client_session.run().await {
+                        log::error!("client_session as push client run error: {}", err);
+                    }
+                    if client_session.is_started() {
+                        backoff.reset();
+                    }
+                    if let Err(err) = client_session.exit().await {
+                        log::error!("client_session as push client exit error: {}", err);
+                    }
+                }
+                Err(err) => {
+                    log::error!("push client connect {} error: {}", address, err);
+                }
+            }
+
+            if !is_pushing.load(Ordering::Acquire) {
+                break;
+            }
+
+            let Some(delay) = backoff.next_delay() else {
+                log::error!(
+                    "push client gives up reconnecting to {} after {} retries, app_name: {}, stream_name: {}",
+                    address,
+                    backoff.retries(),
+                    app_name,
+                    stream_name
+                );
+                break;
+            };
+            log::info!(
+                "push client reconnects to {} in {:?}, app_name: {}, stream_name: {}",
+                address,
+                delay,
+                app_name,
+                stream_name
+            );
+            sleep(delay).await;
+        }
+    }
 }
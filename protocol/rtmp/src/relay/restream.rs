@@ -0,0 +1,415 @@
+use {
+    super::errors::ClientError,
+    crate::{
+        session::client_session::{ClientSession, ClientSessionType},
+        utils::RtmpUrlParser,
+    },
+    bytesio::bytesio::{TNetIO, TcpIO, TlsIO},
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    },
+    streamhub::{
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            StatisticData, StreamHubEvent, StreamHubEventSender,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        statistics::RestreamState,
+        stream::StreamIdentifier,
+        utils::{Backoff, Uuid},
+    },
+    tokio::{net::TcpStream, time::sleep},
+};
+
+#[derive(Debug, Clone)]
+pub struct RestreamTarget {
+    pub id: String,
+    //rtmp:// or rtmps:// url with the stream key
+    pub url: String,
+}
+
+/* The targets are restreamed when a stream matching the rule is published. */
+#[derive(Debug, Clone)]
+pub struct RestreamRule {
+    pub app_name: String,
+    //match all the streams of the app if it is none
+    pub stream_name: Option<String>,
+    pub targets: Vec<RestreamTarget>,
+}
+
+impl RestreamRule {
+    fn is_match(&self, app_name: &str, stream_name: &str) -> bool {
+        self.app_name == app_name
+            && self
+                .stream_name
+                .as_ref()
+                .is_none_or(|name| name == stream_name)
+    }
+}
+
+/* Push the published rtmp streams to several platforms at once. Every target is
+pushed by an individual push session(a subscriber of the local stream), which is
+reconnected with backoff when it is interrupted. */
+pub struct RestreamClient {
+    client_event_consumer: BroadcastEventReceiver,
+    channel_event_producer: StreamHubEventSender,
+    backoff: Backoff,
+    rules: Vec<RestreamRule>,
+    /* the targets added by the api, they are removed when the stream is unpublished */
+    api_targets: HashMap<StreamIdentifier, Vec<RestreamTarget>>,
+    published_streams: HashSet<StreamIdentifier>,
+    /* (stream, target id) to the running flag of the push session */
+    running_targets: HashMap<(StreamIdentifier, String), Arc<AtomicBool>>,
+}
+
+impl RestreamClient {
+    pub fn new(
+        consumer: BroadcastEventReceiver,
+        producer: StreamHubEventSender,
+        backoff: Backoff,
+        rules: Vec<RestreamRule>,
+    ) -> Self {
+        Self {
+            client_event_consumer: consumer,
+            channel_event_producer: producer,
+            backoff,
+            rules,
+            api_targets: HashMap::new(),
+            published_streams: HashSet::new(),
+            running_targets: HashMap::new(),
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), ClientError> {
+        log::info!("restream client run...");
+
+        loop {
+            let event = self.client_event_consumer.recv().await?;
+
+            match event {
+                BroadcastEvent::Publish { identifier } => {
+                    if let StreamIdentifier::Rtmp {
+                        app_name,
+                        stream_name,
+                    } = &identifier
+                    {
+                        let mut targets: Vec<RestreamTarget> = self
+                            .rules
+                            .iter()
+                            .filter(|rule| rule.is_match(app_name, stream_name))
+                            .flat_map(|rule| rule.targets.clone())
+                            .collect();
+                        if let Some(api_targets) = self.api_targets.get(&identifier) {
+                            targets.extend(api_targets.iter().cloned());
+                        }
+
+                        self.published_streams.insert(identifier.clone());
+                        for target in targets {
+                            self.start_target(identifier.clone(), target);
+                        }
+                    }
+                }
+                BroadcastEvent::UnPublish { identifier } => {
+                    self.published_streams.remove(&identifier);
+                    //the ids of the api targets are released in the stream hub
+                    for target in self.api_targets.remove(&identifier).unwrap_or_default() {
+                        let relay_stopped = StreamHubEvent::RelayStopped { id: target.id };
+                        if let Err(err) = self.channel_event_producer.send(relay_stopped) {
+                            log::error!("restream client send relay stopped error: {}", err);
+                        }
+                    }
+                    self.running_targets
+                        .retain(|(running_identifier, _), is_running| {
+                            if *running_identifier == identifier {
+                                is_running.store(false, Ordering::Release);
+                                return false;
+                            }
+                            true
+                        });
+                }
+                BroadcastEvent::StartRestream {
+                    id,
+                    identifier,
+                    url,
+                    token: _,
+                    result_sender,
+                } => {
+                    //the webrtc streams are pushed by the WHIP relay clients
+                    if matches!(identifier, StreamIdentifier::WebRTC { .. }) {
+                        continue;
+                    }
+                    let result = self.on_start_restream(id, identifier, url);
+                    Self::send_result(result_sender, result).await;
+                }
+                BroadcastEvent::StopRestream { id, result_sender } => {
+                    //the ids of the other relay clients are ignored
+                    let found = self.on_stop_restream(&id);
+                    if found {
+                        Self::send_result(result_sender, Ok(())).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_start_restream(
+        &mut self,
+        id: String,
+        identifier: StreamIdentifier,
+        url: String,
+    ) -> Result<(), StreamHubError> {
+        if !matches!(identifier, StreamIdentifier::Rtmp { .. }) {
+            return Err(restream_error("only rtmp streams can be restreamed"));
+        }
+        if RtmpUrlParser::new(url.clone()).parse_url().is_err() {
+            return Err(restream_error("the url is not a valid rtmp or rtmps url"));
+        }
+        if let Some(is_running) = self.running_targets.get(&(identifier.clone(), id.clone())) {
+            if is_running.load(Ordering::Acquire) {
+                return Err(restream_error("the restream id exists"));
+            }
+        }
+
+        let target = RestreamTarget { id, url };
+        let api_targets = self.api_targets.entry(identifier.clone()).or_default();
+        api_targets.retain(|api_target| api_target.id != target.id);
+        api_targets.push(target.clone());
+
+        //the target starts when the stream is published if it is not published yet
+        if self.published_streams.contains(&identifier) {
+            self.start_target(identifier, target);
+        }
+
+        Ok(())
+    }
+
+    //return false if the restream id is not found
+    fn on_stop_restream(&mut self, id: &str) -> bool {
+        let mut found = false;
+
+        for targets in self.api_targets.values_mut() {
+            let count = targets.len();
+            targets.retain(|target| target.id != id);
+            found |= targets.len() != count;
+        }
+        self.running_targets.retain(|(_, target_id), is_running| {
+            if target_id == id {
+                is_running.store(false, Ordering::Release);
+                found = true;
+                return false;
+            }
+            true
+        });
+
+        found
+    }
+
+    async fn send_result(
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+        result: Result<(), StreamHubError>,
+    ) {
+        if let Some(sender) = result_sender {
+            if let Err(err) = sender.send(result).await {
+                log::error!("restream client send result error: {}", err);
+            }
+        }
+    }
+
+    fn start_target(&mut self, identifier: StreamIdentifier, target: RestreamTarget) {
+        let StreamIdentifier::Rtmp {
+            app_name,
+            stream_name,
+        } = identifier.clone()
+        else {
+            return;
+        };
+
+        let mut parser = RtmpUrlParser::new(target.url.clone());
+        if let Err(err) = parser.parse_url() {
+            log::error!("restream target: {} url parse error: {}", target.id, err);
+            return;
+        }
+        if parser.is_tls {
+            parser.append_port(String::from("443"));
+        } else {
+            parser.append_port(String::from("1935"));
+        }
+
+        let is_running = Arc::new(AtomicBool::new(true));
+        if let Some(previous) = self
+            .running_targets
+            .insert((identifier.clone(), target.id.clone()), is_running.clone())
+        {
+            previous.store(false, Ordering::Release);
+        }
+
+        log::info!(
+            "start restream target: {}, app_name: {} stream_name: {}",
+            target.id,
+            app_name,
+            stream_name
+        );
+
+        let target_push = TargetPush {
+            id: target.id,
+            identifier,
+            app_name,
+            stream_name,
+            parser,
+            producer: self.channel_event_producer.clone(),
+            reconnect_count: 0,
+        };
+        let backoff = self.backoff.clone();
+
+        tokio::spawn(async move {
+            target_push.supervise(backoff, is_running).await;
+        });
+    }
+}
+
+fn restream_error(desp: &str) -> StreamHubError {
+    StreamHubError {
+        value: StreamHubErrorValue::RestreamError(String::from(desp)),
+    }
+}
+
+/* Push a local stream to one restream target. */
+struct TargetPush {
+    id: String,
+    identifier: StreamIdentifier,
+    //the local stream names
+    app_name: String,
+    stream_name: String,
+    //the parsed target url
+    parser: RtmpUrlParser,
+    producer: StreamHubEventSender,
+    reconnect_count: usize,
+}
+
+impl TargetPush {
+    async fn supervise(mut self, mut backoff: Backoff, is_running: Arc<AtomicBool>) {
+        self.report_state(RestreamState::Connecting, None);
+
+        while is_running.load(Ordering::Acquire) {
+            match self.connect().await {
+                Ok(mut client_session) => {
+                    client_session.subscribe(self.app_name.clone(), self.stream_name.clone());
+                    let session_id = client_session.get_session_id();
+                    let mut publish_start = client_session.subscribe_publish_start();
+                    let mut is_pushing = false;
+
+                    //it is pushing after the remote server accepts the publishing
+                    let result = {
+                        let run = client_session.run();
+                        tokio::pin!(run);
+                        loop {
+                            tokio::select! {
+                                result = &mut run => break result,
+                                Ok(()) = &mut publish_start, if !is_pushing => {
+                                    is_pushing = true;
+                                    self.report_state(RestreamState::Pushing, Some(session_id));
+                                }
+                            }
+                        }
+                    };
+                    if let Err(err) = result {
+                        log::error!("restream target: {} push error: {}", self.id, err);
+                    }
+                    if client_session.is_started() {
+                        backoff.reset();
+                    }
+                    if let Err(err) = client_session.exit().await {
+                        log::error!("restream target: {} exit error: {}", self.id, err);
+                    }
+                }
+                Err(err) => {
+                    log::error!("restream target: {} connect error: {}", self.id, err);
+                }
+            }
+
+            if !is_running.load(Ordering::Acquire) {
+                break;
+            }
+
+            let Some(delay) = backoff.next_delay() else {
+                log::error!(
+                    "restream target: {} gives up reconnecting after {} retries",
+                    self.id,
+                    backoff.retries()
+                );
+                //it is stopped by the api if it is not running
+                if is_running.swap(false, Ordering::AcqRel) {
+                    self.report_state(RestreamState::Failed, None);
+                    let relay_stopped = StreamHubEvent::RelayStopped {
+                        id: self.id.clone(),
+                    };
+                    if let Err(err) = self.producer.send(relay_stopped) {
+                        log::error!(
+                            "restream target: {} send relay stopped error: {}",
+                            self.id,
+                            err
+                        );
+                    }
+                    return;
+                }
+                break;
+            };
+
+            self.reconnect_count += 1;
+            self.report_state(RestreamState::Reconnecting, None);
+            log::info!("restream target: {} reconnects in {:?}", self.id, delay);
+            sleep(delay).await;
+        }
+
+        self.report_state(RestreamState::Stopped, None);
+    }
+
+    async fn connect(&self) -> Result<ClientSession, ClientError> {
+        let stream = TcpStream::connect(self.parser.host_with_port.clone()).await?;
+        let remote_addr = stream.peer_addr().ok();
+
+        let net_io: Box<dyn TNetIO + Send + Sync> = if self.parser.is_tls {
+            Box::new(TlsIO::connect(&self.parser.host, stream).await?)
+        } else {
+            Box::new(TcpIO::new(stream))
+        };
+
+        Ok(ClientSession::new_with_net_io(
+            net_io,
+            remote_addr,
+            ClientSessionType::Push,
+            self.parser.host_with_port.clone(),
+            self.parser.app_name.clone(),
+            self.parser.stream_name_with_query.clone(),
+            self.producer.clone(),
+            0,
+        ))
+    }
+
+    fn report_state(&self, state: RestreamState, subscriber_id: Option<Uuid>) {
+        let scheme = if self.parser.is_tls { "rtmps" } else { "rtmp" };
+        let statistic_event = StreamHubEvent::Statistic {
+            identifier: self.identifier.clone(),
+            data: StatisticData::Restream {
+                id: self.id.clone(),
+                //the stream key is not exposed
+                url: format!(
+                    "{}://{}/{}",
+                    scheme, self.parser.host_with_port, self.parser.app_name
+                ),
+                state,
+                reconnect_count: self.reconnect_count,
+                subscriber_id,
+            },
+        };
+
+        if let Err(err) = self.producer.send(statistic_event) {
+            log::error!("restream target: {} report state error: {}", self.id, err);
+        }
+    }
+}
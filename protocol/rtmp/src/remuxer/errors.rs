@@ -3,6 +3,7 @@ use {
     crate::{cache::errors::MetadataError, session::errors::SessionError},
     bytesio::bytes_errors::{BytesReadError, BytesWriteError},
     failure::Fail,
+    fdk_aac::enc::EncoderError as AacEncoderError,
     std::fmt,
     streamhub::errors::StreamHubError,
     tokio::sync::broadcast::error::RecvError,
@@ -130,3 +131,22 @@ impl fmt::Display for RtmpRemuxerError {
         fmt::Display::fmt(&self.value, f)
     }
 }
+
+#[derive(Debug)]
+pub struct G7112AacError {
+    pub value: G7112AacErrorValue,
+}
+
+#[derive(Debug)]
+pub enum G7112AacErrorValue {
+    AacEncoderError(AacEncoderError),
+    NotSupportedCodec,
+}
+
+impl From<AacEncoderError> for G7112AacError {
+    fn from(error: AacEncoderError) -> Self {
+        G7112AacError {
+            value: G7112AacErrorValue::AacEncoderError(error),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use super::errors::{G7112AacError, G7112AacErrorValue};
+use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder as AacEncoder, EncoderParams};
+use streamhub::define::AudioCodecType;
+
+//the samples of one channel in an aac frame
+const AAC_FRAME_SAMPLES: usize = 1024;
+
+//https://www.itu.int/rec/T-REC-G.711
+pub fn alaw_to_linear(alaw: u8) -> i16 {
+    let alaw = alaw ^ 0x55;
+    let segment = (alaw & 0x70) >> 4;
+    let mut linear = ((alaw & 0x0F) as i32) << 4;
+    linear += match segment {
+        0 => 8,
+        _ => 0x108,
+    };
+    if segment > 1 {
+        linear <<= segment - 1;
+    }
+
+    if alaw & 0x80 > 0 {
+        linear as i16
+    } else {
+        -linear as i16
+    }
+}
+
+pub fn ulaw_to_linear(ulaw: u8) -> i16 {
+    let ulaw = !ulaw;
+    let mut linear = (((ulaw & 0x0F) as i32) << 3) + 0x84;
+    linear <<= (ulaw & 0x70) >> 4;
+
+    if ulaw & 0x80 > 0 {
+        (0x84 - linear) as i16
+    } else {
+        (linear - 0x84) as i16
+    }
+}
+
+// Resample by the linear interpolation, it is good enough for the narrow band speech.
+struct LinearResampler {
+    input_sample_rate: u32,
+    output_sample_rate: u32,
+    //the position of the next output sample in 1/output_sample_rate input samples,
+    //0 is the last sample of the previous input
+    offset: u64,
+    last_sample: i16,
+}
+
+impl LinearResampler {
+    fn new(input_sample_rate: u32, output_sample_rate: u32) -> Self {
+        Self {
+            input_sample_rate,
+            output_sample_rate,
+            offset: output_sample_rate as u64,
+            last_sample: 0,
+        }
+    }
+
+    fn resample(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let output_sample_rate = self.output_sample_rate as u64;
+        let end = input.len() as u64 * output_sample_rate;
+        let last_sample = self.last_sample as i64;
+        let sample = |index: u64| match index {
+            0 => last_sample,
+            _ => input[index as usize - 1] as i64,
+        };
+
+        while self.offset < end {
+            let index = self.offset / output_sample_rate;
+            let fraction = (self.offset % output_sample_rate) as i64;
+            let (left, right) = (sample(index), sample(index + 1));
+            output.push((left + (right - left) * fraction / output_sample_rate as i64) as i16);
+            self.offset += self.input_sample_rate as u64;
+        }
+
+        if let Some(last_sample) = input.last() {
+            self.offset -= end;
+            self.last_sample = *last_sample;
+        }
+    }
+}
+
+// Transcode the mono G.711 A-law/µ-law samples to the raw aac frames.
+pub struct G7112AacTranscoder {
+    codec: AudioCodecType,
+    input_sample_rate: u32,
+    output_sample_rate: u32,
+    resampler: LinearResampler,
+    encoder: AacEncoder,
+    pcm_data: Vec<i16>,
+}
+
+impl G7112AacTranscoder {
+    pub fn new(
+        codec: AudioCodecType,
+        input_sample_rate: u32,
+        output_sample_rate: u32,
+    ) -> Result<Self, G7112AacError> {
+        let is_g711 = matches!(codec, AudioCodecType::G711A | AudioCodecType::G711U);
+        if !is_g711 || input_sample_rate == 0 {
+            return Err(G7112AacError {
+                value: G7112AacErrorValue::NotSupportedCodec,
+            });
+        }
+
+        let encoder = AacEncoder::new(EncoderParams {
+            bit_rate: BitRate::VbrMedium,
+            transport: fdk_aac::enc::Transport::Raw,
+            channels: ChannelMode::Mono,
+            sample_rate: output_sample_rate,
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+        })?;
+
+        Ok(G7112AacTranscoder {
+            codec,
+            input_sample_rate,
+            output_sample_rate,
+            resampler: LinearResampler::new(input_sample_rate, output_sample_rate),
+            encoder,
+            pcm_data: Vec::new(),
+        })
+    }
+
+    //the timestamps of the input and the returned aac frames are in the input sample rate
+    pub fn transcode(
+        &mut self,
+        input: &[u8],
+        timestamp: u32,
+    ) -> Result<Vec<(u32, Vec<u8>)>, G7112AacError> {
+        let decode = match self.codec {
+            AudioCodecType::G711A => alaw_to_linear,
+            _ => ulaw_to_linear,
+        };
+        let pcm: Vec<i16> = input.iter().map(|sample| decode(*sample)).collect();
+        self.resampler.resample(&pcm, &mut self.pcm_data);
+
+        //the end of the buffered samples is the end of this input
+        let end_timestamp = timestamp.wrapping_add(input.len() as u32);
+        let (input_sample_rate, output_sample_rate) = (
+            self.input_sample_rate as u64,
+            self.output_sample_rate as u64,
+        );
+        let buffered_duration = |buffered_samples: usize| {
+            (buffered_samples as u64 * input_sample_rate / output_sample_rate) as u32
+        };
+
+        let mut aac_output: Vec<u8> = vec![0; AAC_FRAME_SAMPLES * 2];
+        let mut result = Vec::new();
+        while self.pcm_data.len() >= AAC_FRAME_SAMPLES {
+            let frame_timestamp =
+                end_timestamp.wrapping_sub(buffered_duration(self.pcm_data.len()));
+
+            let encoder_info = self
+                .encoder
+                .encode(&self.pcm_data[..AAC_FRAME_SAMPLES], &mut aac_output)?;
+            self.pcm_data.drain(..AAC_FRAME_SAMPLES);
+
+            if encoder_info.output_size > 0 {
+                result.push((
+                    frame_timestamp,
+                    aac_output[..encoder_info.output_size].to_vec(),
+                ));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alaw_to_linear, ulaw_to_linear, G7112AacTranscoder, LinearResampler};
+    use streamhub::define::AudioCodecType;
+
+    #[test]
+    fn test_g711_decode() {
+        assert_eq!(alaw_to_linear(0xD5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+        assert_eq!(alaw_to_linear(0x2A), -32256);
+
+        assert_eq!(ulaw_to_linear(0xFF), 0);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+    }
+
+    #[test]
+    fn test_resample() {
+        let mut resampler = LinearResampler::new(8000, 16000);
+        let mut output = Vec::new();
+        resampler.resample(&[100, 200, 300], &mut output);
+        resampler.resample(&[400], &mut output);
+        //the last sample is kept for the interpolation with the next input
+        assert_eq!(output, vec![100, 150, 200, 250, 300, 350]);
+
+        //a 20ms G.711 packet is 160 samples
+        let mut resampler = LinearResampler::new(8000, 44100);
+        let mut output = Vec::new();
+        for _ in 0..50 {
+            resampler.resample(&[0; 160], &mut output);
+        }
+        assert!((44090..=44100).contains(&output.len()));
+    }
+
+    #[test]
+    fn test_transcode() {
+        let mut transcoder = G7112AacTranscoder::new(AudioCodecType::G711U, 8000, 44100).unwrap();
+
+        let mut frames = Vec::new();
+        for i in 0..50 {
+            let input: Vec<u8> = (0..160).map(|n| ((n * 7 + i) % 256) as u8).collect();
+            frames.extend(transcoder.transcode(&input, 8000 + i * 160).unwrap());
+        }
+
+        //the encoder keeps a few frames for the lookahead
+        assert!(frames.len() > 35 && frames.len() <= 43);
+        //an aac frame is 1024 samples at 44100 Hz, about 185.8 samples at 8000 Hz
+        assert!(frames
+            .windows(2)
+            .all(|frames| (185..=186).contains(&(frames[1].0 - frames[0].0))));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod errors;
 
+pub mod g7112aac;
 pub mod rtmp_cooker;
 pub mod rtsp2rtmp;
 pub mod whip2rtmp;
@@ -1,17 +1,22 @@
 use bytes::BytesMut;
 use bytesio::bytes_reader::BytesReader;
 use h264_decoder::sps::SpsParser;
-use streamhub::define::VideoCodecType;
+use streamhub::define::{AudioCodecType, VideoCodecType};
 use tokio::sync::oneshot;
 use xflv::define::h264_nal_type::{H264_NAL_IDR, H264_NAL_PPS, H264_NAL_SPS};
+use xflv::mpeg4_aac::Mpeg4Aac;
 
 use crate::session::define::SessionType;
 
 use super::{
     errors::{RtmpRemuxerError, RtmpRemuxerErrorValue},
+    g7112aac::G7112AacTranscoder,
     rtmp_cooker::RtmpCooker,
 };
 
+//the sample rate of the aac transcoded from G.711
+const TRANSCODED_AAC_SAMPLE_RATE: u32 = 44100;
+
 use {
     crate::session::common::Common,
     std::time::Duration,
@@ -42,6 +47,11 @@ pub struct Rtsp2RtmpRemuxerSession {
 
     rtmp_handler: Common,
     rtmp_cooker: RtmpCooker,
+    //the G.711 audio is transcoded to aac
+    g7112aac_transcoder: Option<G7112AacTranscoder>,
+    //the tracks which cannot be carried by rtmp are dropped
+    drop_audio: bool,
+    drop_video: bool,
 }
 
 pub fn find_start_code(nalus: &[u8]) -> Option<usize> {
@@ -79,6 +89,9 @@ impl Rtsp2RtmpRemuxerSession {
             base_video_timestamp: 0,
             rtmp_handler: Common::new(None, event_producer, SessionType::Server, None),
             rtmp_cooker: RtmpCooker::default(),
+            g7112aac_transcoder: None,
+            drop_audio: false,
+            drop_video: false,
         }
     }
 
@@ -183,6 +196,24 @@ impl Rtsp2RtmpRemuxerSession {
                             self.video_clock_rate
                         );
 
+                        match media_info.acodec {
+                            AudioCodecType::AAC => {}
+                            AudioCodecType::G711A | AudioCodecType::G711U => {
+                                self.start_g7112aac_transcoder(media_info.acodec).await?;
+                            }
+                            AudioCodecType::Unsupported => {
+                                log::warn!(
+                                    "the audio codec is not supported, the audio is dropped"
+                                );
+                                self.drop_audio = true;
+                            }
+                        }
+
+                        if media_info.vcodec == VideoCodecType::Unsupported {
+                            log::warn!("the video codec is not supported, the video is dropped");
+                            self.drop_video = true;
+                        }
+
                         if media_info.vcodec == VideoCodecType::H265 {
                             log::warn!(
                                 "h265 rtsp to rtmp is not supported now!!! will come soon!!"
@@ -190,6 +221,9 @@ impl Rtsp2RtmpRemuxerSession {
                             break;
                         }
                     }
+                    FrameData::TextData { data } => {
+                        self.rtmp_handler.on_text_data(data).await?;
+                    }
                     _ => continue,
                 };
                 retry_count = 0;
@@ -207,10 +241,74 @@ impl Rtsp2RtmpRemuxerSession {
         self.unpublish_rtmp().await
     }
 
+    async fn start_g7112aac_transcoder(
+        &mut self,
+        acodec: AudioCodecType,
+    ) -> Result<(), RtmpRemuxerError> {
+        let transcoder = match G7112AacTranscoder::new(
+            acodec,
+            self.audio_clock_rate,
+            TRANSCODED_AAC_SAMPLE_RATE,
+        ) {
+            Ok(transcoder) => transcoder,
+            Err(err) => {
+                log::error!(
+                    "create g711 to aac transcoder error: {:?}, the audio is dropped",
+                    err
+                );
+                self.drop_audio = true;
+                return Ok(());
+            }
+        };
+        self.g7112aac_transcoder = Some(transcoder);
+
+        //the aac sequence header is sent before the transcoded frames
+        match Mpeg4Aac::new(2, TRANSCODED_AAC_SAMPLE_RATE, 1)
+            .and_then(|aac| aac.gen_audio_specific_config())
+        {
+            Ok(asc) => {
+                let mut seq_header = self.rtmp_cooker.gen_audio_frame_data(&asc)?;
+                self.rtmp_handler.on_audio_data(&mut seq_header, &0).await?;
+            }
+            Err(err) => {
+                log::error!("generate aac sequence header error: {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
     async fn on_rtsp_audio(
         &mut self,
         audio_data: &BytesMut,
         timestamp: u32,
+    ) -> Result<(), RtmpRemuxerError> {
+        if self.drop_audio {
+            return Ok(());
+        }
+        let Some(transcoder) = &mut self.g7112aac_transcoder else {
+            return self.send_rtmp_audio(audio_data, timestamp).await;
+        };
+
+        match transcoder.transcode(&audio_data[..], timestamp) {
+            Ok(aac_frames) => {
+                for (timestamp, aac_frame) in aac_frames {
+                    self.send_rtmp_audio(&BytesMut::from(&aac_frame[..]), timestamp)
+                        .await?;
+                }
+            }
+            Err(err) => {
+                log::error!("g711 to aac transcode error: {:?}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send_rtmp_audio(
+        &mut self,
+        audio_data: &BytesMut,
+        timestamp: u32,
     ) -> Result<(), RtmpRemuxerError> {
         if self.base_audio_timestamp == 0 {
             self.base_audio_timestamp = timestamp;
@@ -232,6 +330,9 @@ impl Rtsp2RtmpRemuxerSession {
         nalus: &mut BytesMut,
         timestamp: u32,
     ) -> Result<(), RtmpRemuxerError> {
+        if self.drop_video {
+            return Ok(());
+        }
         if self.base_video_timestamp == 0 {
             self.base_video_timestamp = timestamp;
         }
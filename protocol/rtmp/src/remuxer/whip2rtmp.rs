@@ -206,6 +206,9 @@ impl Whip2RtmpRemuxerSession {
                             break;
                         }
                     }
+                    FrameData::TextData { data } => {
+                        self.rtmp_handler.on_text_data(data).await?;
+                    }
                     _ => continue,
                 };
                 retry_count = 0;
@@ -3,6 +3,7 @@ use streamhub::define::StreamHubEventSender;
 use super::session::server_session;
 use commonlib::auth::Auth;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::io::Error;
 use tokio::net::TcpListener;
 
@@ -11,6 +12,7 @@ pub struct RtmpServer {
     event_producer: StreamHubEventSender,
     gop_num: usize,
     auth: Option<Auth>,
+    read_timeout: Duration,
 }
 
 impl RtmpServer {
@@ -19,12 +21,14 @@ impl RtmpServer {
         event_producer: StreamHubEventSender,
         gop_num: usize,
         auth: Option<Auth>,
+        read_timeout: Duration,
     ) -> Self {
         Self {
             address,
             event_producer,
             gop_num,
             auth,
+            read_timeout,
         }
     }
 
@@ -42,6 +46,7 @@ impl RtmpServer {
                 self.event_producer.clone(),
                 self.gop_num,
                 self.auth.clone(),
+                self.read_timeout,
             );
             tokio::spawn(async move {
                 if let Err(err) = session.run().await {
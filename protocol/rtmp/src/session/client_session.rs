@@ -2,7 +2,7 @@ use crate::chunk::packetizer::ChunkPacketizer;
 
 use {
     super::{
-        common::Common,
+        common::{Common, RelayPublication},
         define,
         define::SessionType,
         errors::{SessionError, SessionErrorValue},
@@ -26,12 +26,16 @@ use {
         bytesio::{TNetIO, TcpIO},
     },
     indexmap::IndexMap,
+    std::net::SocketAddr,
     std::sync::Arc,
     std::time::Duration,
     //crate::utils::print::print,
-    streamhub::define::StreamHubEventSender,
-    tokio::{net::TcpStream, sync::Mutex},
-    xflv::amf0::Amf0ValueType,
+    streamhub::{define::StreamHubEventSender, utils::Uuid},
+    tokio::{
+        net::TcpStream,
+        sync::{oneshot, Mutex},
+    },
+    xflv::{amf0::Amf0ValueType, script_data::unmarshal_text_data},
 };
 
 #[allow(dead_code)]
@@ -84,6 +88,12 @@ pub struct ClientSession {
     sub_stream_name: Option<String>,
     /*configure how many gops will be cached.*/
     gop_num: usize,
+    /*the publication handed over from the previous relay session*/
+    relay_publication: Option<RelayPublication>,
+    is_published: bool,
+    is_subscribed: bool,
+    /*notified when the remote server accepts the publishing*/
+    publish_start_sender: Option<oneshot::Sender<()>>,
 }
 
 impl ClientSession {
@@ -104,7 +114,32 @@ impl ClientSession {
         };
 
         let tcp_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
-        let net_io = Arc::new(Mutex::new(tcp_io));
+
+        Self::new_with_net_io(
+            tcp_io,
+            remote_addr,
+            client_type,
+            raw_domain_name,
+            app_name,
+            raw_stream_name,
+            event_producer,
+            gop_num,
+        )
+    }
+
+    /* Create a session on an established connection, e.g. a TLS connection for rtmps. */
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_net_io(
+        net_io: Box<dyn TNetIO + Send + Sync>,
+        remote_addr: Option<SocketAddr>,
+        client_type: ClientSessionType,
+        raw_domain_name: String,
+        app_name: String,
+        raw_stream_name: String,
+        event_producer: StreamHubEventSender,
+        gop_num: usize,
+    ) -> Self {
+        let net_io = Arc::new(Mutex::new(net_io));
 
         let packetizer = if client_type == ClientSessionType::Push {
             Some(ChunkPacketizer::new(Arc::clone(&net_io)))
@@ -130,6 +165,10 @@ impl ClientSession {
             sub_app_name: None,
             sub_stream_name: None,
             gop_num,
+            relay_publication: None,
+            is_published: false,
+            is_subscribed: false,
+            publish_start_sender: None,
         }
     }
     
@@ -137,6 +176,68 @@ impl ClientSession {
         self.timeout = Some(timeout)
     }
 
+    /* Resume the publication of a previous pull session after reconnecting. */
+    pub fn resume_relay_publication(&mut self, publication: RelayPublication) {
+        self.relay_publication = Some(publication);
+    }
+
+    /* Take the publication out so that it is kept alive after this session ends. */
+    pub fn take_relay_publication(&mut self) -> Option<RelayPublication> {
+        if self.is_published {
+            self.is_published = false;
+            return Some(
+                self.common
+                    .take_relay_publication(self.app_name.clone(), self.stream_name.clone()),
+            );
+        }
+        self.relay_publication.take()
+    }
+
+    /* The receiver is notified after NetStream.Publish.Start is received. */
+    pub fn subscribe_publish_start(&mut self) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.publish_start_sender = Some(sender);
+        receiver
+    }
+
+    pub fn get_session_id(&self) -> Uuid {
+        self.common.get_session_id()
+    }
+
+    /* Whether the session has started to play or publish the stream. */
+    pub fn is_started(&self) -> bool {
+        self.is_published || self.is_subscribed
+    }
+
+    /* Release the stream hub resources held by this session. */
+    pub async fn exit(&mut self) -> Result<(), SessionError> {
+        if self.is_subscribed {
+            self.is_subscribed = false;
+            let (app_name, stream_name) = self.get_subscribe_names();
+            self.common
+                .unsubscribe_from_stream_hub(app_name, stream_name)
+                .await?;
+        }
+
+        //a publication which is not resumed yet is also released here
+        if self.is_published || self.relay_publication.take().is_some() {
+            self.is_published = false;
+            self.common
+                .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn get_subscribe_names(&self) -> (String, String) {
+        if let (Some(app_name), Some(stream_name)) = (&self.sub_app_name, &self.sub_stream_name) {
+            (app_name.clone(), stream_name.clone())
+        } else {
+            (self.app_name.clone(), self.stream_name.clone())
+        }
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
@@ -268,7 +369,11 @@ impl ClientSession {
                 self.common.on_video_data(data, timestamp).await?
             }
             RtmpMessageData::AmfData { raw_data } => {
-                self.common.on_meta_data(raw_data, timestamp).await?;
+                if let Ok(Some(text)) = unmarshal_text_data(raw_data) {
+                    self.common.on_text_data(text).await?;
+                } else {
+                    self.common.on_meta_data(raw_data, timestamp).await?;
+                }
             }
 
             _ => {}
@@ -466,10 +571,16 @@ impl ClientSession {
 
         let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
         netstream
-            .write_release_stream(&(define::TRANSACTION_ID_CONNECT as f64), &self.stream_name)
+            .write_release_stream(
+                &(define::TRANSACTION_ID_RELEASE_STREAM as f64),
+                &self.stream_name,
+            )
             .await?;
         netstream
-            .write_fcpublish(&(define::TRANSACTION_ID_CONNECT as f64), &self.stream_name)
+            .write_fcpublish(
+                &(define::TRANSACTION_ID_FCPUBLISH as f64),
+                &self.stream_name,
+            )
             .await?;
 
         self.state = ClientSessionState::CreateStream;
@@ -524,31 +635,30 @@ impl ClientSession {
                 "NetStream.Publish.Start" => {
                     self.state = ClientSessionState::StartPublish;
                     //subscribe from local session and publish to remote rtmp server
-                    if let (Some(app_name), Some(stream_name)) =
-                        (&self.sub_app_name, &self.sub_stream_name)
-                    {
-                        self.common
-                            .subscribe_from_stream_hub(app_name.clone(), stream_name.clone())
-                            .await?;
+                    let (app_name, stream_name) = self.get_subscribe_names();
+                    self.common
+                        .subscribe_from_stream_hub(app_name, stream_name)
+                        .await?;
+                    self.is_subscribed = true;
+                    if let Some(sender) = self.publish_start_sender.take() {
+                        let _ = sender.send(());
+                    }
+                }
+                "NetStream.Publish.Reset" => {}
+                "NetStream.Play.Start" => {
+                    //pull from remote rtmp server and publish to local session
+                    if let Some(publication) = self.relay_publication.take() {
+                        self.common.resume_relay_publication(publication)?;
                     } else {
                         self.common
-                            .subscribe_from_stream_hub(
+                            .publish_to_stream_hub(
                                 self.app_name.clone(),
                                 self.stream_name.clone(),
+                                self.gop_num,
                             )
                             .await?;
                     }
-                }
-                "NetStream.Publish.Reset" => {}
-                "NetStream.Play.Start" => {
-                    //pull from remote rtmp server and publish to local session
-                    self.common
-                        .publish_to_stream_hub(
-                            self.app_name.clone(),
-                            self.stream_name.clone(),
-                            self.gop_num,
-                        )
-                        .await?
+                    self.is_published = true;
                 }
                 _ => {}
             }
@@ -29,9 +29,10 @@ use {
         errors::{StreamHubError, StreamHubErrorValue},
         statistics::StatisticsStream,
         stream::StreamIdentifier,
-        utils::Uuid,
+        utils::{TimestampRebaser, Uuid},
     },
     tokio::sync::{mpsc, Mutex},
+    xflv::script_data::marshal_text_data,
 };
 
 pub struct Common {
@@ -55,6 +56,38 @@ pub struct Common {
     pub stream_handler: Arc<RtmpStreamHandler>,
     /* now used for subscriber session */
     statistic_data_sender: Option<StatisticDataSender>,
+    /* keep the published timestamps continuous after a relay session resumes a publication */
+    timestamp_rebaser: TimestampRebaser,
+    /* the timestamp of the last sent audio/video frame, the text data is stamped with it */
+    last_timestamp: u32,
+}
+
+/* The stream hub publication of a relay(pull) session. It outlives the session,
+so the next session can resume it after reconnecting to the remote server and
+the subscribers of the stream are not disconnected. */
+pub struct RelayPublication {
+    identifier: StreamIdentifier,
+    info: PublisherInfo,
+    data_sender: FrameDataSender,
+    stream_handler: Arc<RtmpStreamHandler>,
+    timestamp_rebaser: TimestampRebaser,
+}
+
+impl RelayPublication {
+    /* Give up the publication when the remote server cannot be reconnected. */
+    pub fn unpublish(self, event_producer: &StreamHubEventSender) -> Result<(), SessionError> {
+        log::info!("unpublish relay publication: {}", self.identifier);
+        let unpublish_event = StreamHubEvent::UnPublish {
+            identifier: self.identifier,
+            info: self.info,
+        };
+
+        event_producer
+            .send(unpublish_event)
+            .map_err(|_| SessionError {
+                value: SessionErrorValue::StreamHubEventSendErr,
+            })
+    }
 }
 
 impl Common {
@@ -80,52 +113,20 @@ impl Common {
             request_url: String::default(),
             stream_handler: Arc::new(RtmpStreamHandler::new()),
             statistic_data_sender: None,
+            timestamp_rebaser: TimestampRebaser::new(),
+            last_timestamp: 0,
             //cache: None,
         }
     }
+    pub fn get_session_id(&self) -> Uuid {
+        self.session_id
+    }
+
     pub async fn send_channel_data(&mut self) -> Result<(), SessionError> {
         let mut retry_times = 0;
         loop {
             if let Some(data) = self.data_receiver.recv().await {
-                match data {
-                    FrameData::Audio { timestamp, data } => {
-                        let data_size = data.len();
-                        self.send_audio(data, timestamp).await?;
-
-                        if let Some(sender) = &self.statistic_data_sender {
-                            let statistic_audio_data = StatisticData::Audio {
-                                uuid: Some(self.session_id),
-                                aac_packet_type: 1,
-                                data_size,
-                                duration: 0,
-                            };
-                            if let Err(err) = sender.send(statistic_audio_data) {
-                                log::error!("send statistic_data err: {}", err);
-                            }
-                        }
-                    }
-                    FrameData::Video { timestamp, data } => {
-                        let data_size = data.len();
-                        self.send_video(data, timestamp).await?;
-
-                        if let Some(sender) = &self.statistic_data_sender {
-                            let statistic_video_data = StatisticData::Video {
-                                uuid: Some(self.session_id),
-                                frame_count: 1,
-                                data_size,
-                                is_key_frame: None,
-                                duration: 0,
-                            };
-                            if let Err(err) = sender.send(statistic_video_data) {
-                                log::error!("send statistic_data err: {}", err);
-                            }
-                        }
-                    }
-                    FrameData::MetaData { timestamp, data } => {
-                        self.send_metadata(data, timestamp).await?;
-                    }
-                    _ => {}
-                }
+                self.send_frame_data(data).await?;
             } else {
                 retry_times += 1;
                 log::debug!(
@@ -142,6 +143,63 @@ impl Common {
         }
     }
 
+    pub async fn recv_channel_data(&mut self) -> Option<FrameData> {
+        self.data_receiver.recv().await
+    }
+
+    pub async fn send_frame_data(&mut self, data: FrameData) -> Result<(), SessionError> {
+        match data {
+            FrameData::Audio { timestamp, data } => {
+                let data_size = data.len();
+                self.send_audio(data, timestamp).await?;
+                self.last_timestamp = timestamp;
+
+                if let Some(sender) = &self.statistic_data_sender {
+                    let statistic_audio_data = StatisticData::Audio {
+                        uuid: Some(self.session_id),
+                        aac_packet_type: 1,
+                        data_size,
+                        duration: 0,
+                    };
+                    if let Err(err) = sender.send(statistic_audio_data) {
+                        log::error!("send statistic_data err: {}", err);
+                    }
+                }
+            }
+            FrameData::Video { timestamp, data } => {
+                let data_size = data.len();
+                self.send_video(data, timestamp).await?;
+                self.last_timestamp = timestamp;
+
+                if let Some(sender) = &self.statistic_data_sender {
+                    let statistic_video_data = StatisticData::Video {
+                        uuid: Some(self.session_id),
+                        frame_count: 1,
+                        data_size,
+                        is_key_frame: None,
+                        duration: 0,
+                    };
+                    if let Err(err) = sender.send(statistic_video_data) {
+                        log::error!("send statistic_data err: {}", err);
+                    }
+                }
+            }
+            FrameData::MetaData { timestamp, data } => {
+                self.send_metadata(data, timestamp).await?;
+            }
+            FrameData::TextData { data } => match marshal_text_data(&data) {
+                Ok(text_data) => {
+                    self.send_metadata(text_data, self.last_timestamp).await?;
+                }
+                Err(err) => {
+                    log::error!("marshal text data err: {}", err);
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub async fn send_audio(&mut self, data: BytesMut, timestamp: u32) -> Result<(), SessionError> {
         let mut chunk_info = ChunkInfo::new(
             csid_type::AUDIO,
@@ -205,6 +263,7 @@ impl Common {
         data: &mut BytesMut,
         timestamp: &u32,
     ) -> Result<(), SessionError> {
+        let timestamp = &self.timestamp_rebaser.rebase(*timestamp);
         let channel_data = FrameData::Video {
             timestamp: *timestamp,
             data: data.clone(),
@@ -232,6 +291,7 @@ impl Common {
         data: &mut BytesMut,
         timestamp: &u32,
     ) -> Result<(), SessionError> {
+        let timestamp = &self.timestamp_rebaser.rebase(*timestamp);
         let channel_data = FrameData::Audio {
             timestamp: *timestamp,
             data: data.clone(),
@@ -259,6 +319,7 @@ impl Common {
         data: &mut BytesMut,
         timestamp: &u32,
     ) -> Result<(), SessionError> {
+        let timestamp = &self.timestamp_rebaser.rebase(*timestamp);
         let channel_data = FrameData::MetaData {
             timestamp: *timestamp,
             data: data.clone(),
@@ -278,6 +339,17 @@ impl Common {
         Ok(())
     }
 
+    /* the text data is not cached, only the current subscribers receive it */
+    pub async fn on_text_data(&mut self, data: String) -> Result<(), SessionError> {
+        if self.data_sender.send(FrameData::TextData { data }).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::SendFrameDataErr,
+            });
+        }
+
+        Ok(())
+    }
+
     fn get_subscriber_info(&mut self) -> SubscriberInfo {
         let remote_addr = if let Some(addr) = self.remote_addr {
             addr.to_string()
@@ -365,11 +437,12 @@ impl Common {
         let statistic_data_sender: Option<StatisticDataSender> = result.1;
 
         if let Some(sender) = &statistic_data_sender {
+            let info = self.get_subscriber_info();
             let statistic_subscriber = StatisticData::Subscriber {
                 id: self.session_id,
-                remote_addr: self.remote_addr.unwrap().to_string(),
+                remote_addr: info.notify_info.remote_addr,
                 start_time: chrono::Local::now(),
-                sub_type: SubscribeType::RtmpPull,
+                sub_type: info.sub_type,
             };
             if let Err(err) = sender.send(statistic_subscriber) {
                 log::error!("send statistic_subscriber err: {}", err);
@@ -451,6 +524,44 @@ impl Common {
         Ok(())
     }
 
+    /* Hand over the stream hub publication, it must have been published by this session. */
+    pub fn take_relay_publication(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+    ) -> RelayPublication {
+        RelayPublication {
+            identifier: StreamIdentifier::Rtmp {
+                app_name,
+                stream_name,
+            },
+            info: self.get_publisher_info(),
+            data_sender: self.data_sender.clone(),
+            stream_handler: self.stream_handler.clone(),
+            timestamp_rebaser: self.timestamp_rebaser.clone(),
+        }
+    }
+
+    /* Resume a publication of the previous relay session instead of publishing a new one,
+    a discontinuity is inserted so the subscribers know the source is switched. */
+    pub fn resume_relay_publication(
+        &mut self,
+        publication: RelayPublication,
+    ) -> Result<(), SessionError> {
+        log::info!("resume relay publication: {}", publication.identifier);
+        self.session_id = publication.info.id;
+        self.data_sender = publication.data_sender;
+        self.stream_handler = publication.stream_handler;
+        self.timestamp_rebaser = publication.timestamp_rebaser;
+        self.timestamp_rebaser.discontinue();
+
+        self.data_sender
+            .send(FrameData::Discontinuity {})
+            .map_err(|_| SessionError {
+                value: SessionErrorValue::SendFrameDataErr,
+            })
+    }
+
     pub async fn unpublish_to_stream_hub(
         &mut self,
         app_name: String,
@@ -579,7 +690,9 @@ impl TStreamHandler for RtmpStreamHandler {
             match sub_type {
                 SubscribeType::RtmpPull
                 | SubscribeType::RtmpRemux2HttpFlv
-                | SubscribeType::RtmpRemux2Hls => {
+                | SubscribeType::RtmpRemux2Hls
+                | SubscribeType::RtmpRemux2Rtsp
+                | SubscribeType::WhepPull => {
                     if let Some(gops_data) = cache.get_gops_data() {
                         for gop in gops_data {
                             for channel_data in gop.get_frame_data() {
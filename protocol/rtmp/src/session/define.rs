@@ -20,6 +20,8 @@ pub const STREAM_ID: f64 = 1.0;
 
 pub const TRANSACTION_ID_CONNECT: u8 = 1;
 pub const TRANSACTION_ID_CREATE_STREAM: u8 = 2;
+pub const TRANSACTION_ID_RELEASE_STREAM: u8 = 4;
+pub const TRANSACTION_ID_FCPUBLISH: u8 = 5;
 
 //pub mod
 pub const RTMP_LEVEL_WARNING: &str = "warning";
@@ -14,7 +14,7 @@ use {
     commonlib::errors::AuthError,
     failure::{Backtrace, Fail},
     std::fmt,
-    streamhub::errors::StreamHubError,
+    streamhub::errors::{StreamHubError, StreamHubErrorValue},
     tokio::sync::oneshot::error::RecvError,
     xflv::amf0::errors::Amf0WriteError,
 };
@@ -85,6 +85,18 @@ pub enum SessionErrorValue {
     AuthError(#[cause] AuthError),
 }
 
+impl SessionError {
+    /* The stream has been published by another session, e.g. the pull of another subscriber. */
+    pub fn is_stream_exists(&self) -> bool {
+        matches!(
+            self.value,
+            SessionErrorValue::ChannelError(StreamHubError {
+                value: StreamHubErrorValue::Exists
+            })
+        )
+    }
+}
+
 impl From<Amf0WriteError> for SessionError {
     fn from(error: Amf0WriteError) -> Self {
         SessionError {
@@ -0,0 +1,167 @@
+use {
+    bytesio::bytes_reader::BytesReader,
+    streamhub::define::FrameData,
+    xflv::{
+        define,
+        flv_tag_header::{AudioTagHeader, VideoTagHeader},
+        Unmarshal,
+    },
+};
+
+/*the playback state changed by the pause/seek/receiveAudio/receiveVideo
+commands of a player, it decides which frames are sent to the player.*/
+pub struct PlaybackControl {
+    is_paused: bool,
+    receive_audio: bool,
+    receive_video: bool,
+    /*the video is resumed from a key frame to avoid the decoding artifacts*/
+    wait_key_frame: bool,
+}
+
+impl Default for PlaybackControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaybackControl {
+    pub fn new() -> Self {
+        Self {
+            is_paused: false,
+            receive_audio: true,
+            receive_video: true,
+            wait_key_frame: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn pause(&mut self, is_paused: bool) {
+        if self.is_paused && !is_paused {
+            self.wait_key_frame = true;
+        }
+        self.is_paused = is_paused;
+    }
+
+    /*the live streams can only be seeked to the live edge*/
+    pub fn seek(&mut self) {
+        self.wait_key_frame = true;
+    }
+
+    pub fn set_receive_audio(&mut self, enable: bool) {
+        self.receive_audio = enable;
+    }
+
+    pub fn set_receive_video(&mut self, enable: bool) {
+        if !self.receive_video && enable {
+            self.wait_key_frame = true;
+        }
+        self.receive_video = enable;
+    }
+
+    /*return true if the frame should be sent to the player*/
+    pub fn filter(&mut self, data: &FrameData) -> bool {
+        match data {
+            FrameData::Video { data, .. } => {
+                let mut reader = BytesReader::new(data.clone());
+                let (is_key_frame, is_seq_header) = match VideoTagHeader::unmarshal(&mut reader) {
+                    Ok(tag_header) => (
+                        tag_header.frame_type == define::frame_type::KEY_FRAME,
+                        tag_header.avc_packet_type == define::avc_packet_type::AVC_SEQHDR,
+                    ),
+                    Err(_) => (false, false),
+                };
+
+                //the decoder config is always needed whenever the video is resumed
+                if is_key_frame && is_seq_header {
+                    return true;
+                }
+                if self.is_paused || !self.receive_video {
+                    return false;
+                }
+                if self.wait_key_frame {
+                    if !is_key_frame {
+                        return false;
+                    }
+                    self.wait_key_frame = false;
+                }
+                true
+            }
+            FrameData::Audio { data, .. } => {
+                let mut reader = BytesReader::new(data.clone());
+                if let Ok(tag_header) = AudioTagHeader::unmarshal(&mut reader) {
+                    if tag_header.sound_format == define::SoundFormat::AAC as u8
+                        && tag_header.aac_packet_type == define::aac_packet_type::AAC_SEQHDR
+                    {
+                        return true;
+                    }
+                }
+                !self.is_paused && self.receive_audio
+            }
+            FrameData::TextData { .. } => !self.is_paused,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlaybackControl;
+    use bytes::BytesMut;
+    use streamhub::define::FrameData;
+
+    fn video(data: &[u8]) -> FrameData {
+        FrameData::Video {
+            timestamp: 0,
+            data: BytesMut::from(data),
+        }
+    }
+
+    fn audio(data: &[u8]) -> FrameData {
+        FrameData::Audio {
+            timestamp: 0,
+            data: BytesMut::from(data),
+        }
+    }
+
+    #[test]
+    fn test_playback_control() {
+        let seq_header = video(&[0x17, 0x00, 0x00, 0x00, 0x00]);
+        let key_frame = video(&[0x17, 0x01, 0x00, 0x00, 0x00]);
+        let inter_frame = video(&[0x27, 0x01, 0x00, 0x00, 0x00]);
+        let aac_header = audio(&[0xaf, 0x00, 0x12, 0x10]);
+        let aac_raw = audio(&[0xaf, 0x01, 0x21, 0x00]);
+
+        let mut control = PlaybackControl::new();
+        assert!(control.filter(&inter_frame));
+        assert!(control.filter(&aac_raw));
+
+        control.pause(true);
+        assert!(control.is_paused());
+        assert!(!control.filter(&key_frame));
+        assert!(!control.filter(&aac_raw));
+        assert!(control.filter(&seq_header));
+        assert!(control.filter(&aac_header));
+
+        //resumed from the next key frame
+        control.pause(false);
+        assert!(!control.filter(&inter_frame));
+        assert!(control.filter(&aac_raw));
+        assert!(control.filter(&key_frame));
+        assert!(control.filter(&inter_frame));
+
+        //audio only mode
+        control.set_receive_video(false);
+        assert!(!control.filter(&key_frame));
+        assert!(control.filter(&aac_raw));
+        control.set_receive_video(true);
+        assert!(!control.filter(&inter_frame));
+        assert!(control.filter(&key_frame));
+
+        control.set_receive_audio(false);
+        assert!(!control.filter(&aac_raw));
+        assert!(control.filter(&inter_frame));
+    }
+}
@@ -8,6 +8,7 @@ use {
         define,
         define::SessionType,
         errors::{SessionError, SessionErrorValue},
+        playback::PlaybackControl,
     },
     crate::{
         chunk::{
@@ -33,7 +34,7 @@ use {
     std::{sync::Arc, time::Duration},
     streamhub::define::StreamHubEventSender,
     tokio::{net::TcpStream, sync::Mutex},
-    xflv::amf0::Amf0ValueType,
+    xflv::{amf0::Amf0ValueType, script_data::unmarshal_text_data},
 };
 
 enum ServerSessionState {
@@ -61,6 +62,9 @@ pub struct ServerSession {
     /*configure how many gops will be cached.*/
     gop_num: usize,
     auth: Option<Auth>,
+    /*the session is closed if no data is received in time.*/
+    read_timeout: Duration,
+    playback: PlaybackControl,
 }
 
 impl ServerSession {
@@ -69,6 +73,7 @@ impl ServerSession {
         event_producer: StreamHubEventSender,
         gop_num: usize,
         auth: Option<Auth>,
+        read_timeout: Duration,
     ) -> Self {
         let remote_addr = if let Ok(addr) = stream.peer_addr() {
             log::info!("server session: {}", addr.to_string());
@@ -100,6 +105,8 @@ impl ServerSession {
             connect_properties: ConnectProperties::default(),
             gop_num,
             auth,
+            read_timeout,
+            playback: PlaybackControl::new(),
         }
     }
 
@@ -156,7 +163,7 @@ impl ServerSession {
                 .io
                 .lock()
                 .await
-                .read_timeout(Duration::from_secs(2))
+                .read_timeout(self.read_timeout)
                 .await
             {
                 Ok(data) => {
@@ -177,7 +184,10 @@ impl ServerSession {
         }
 
         self.has_remaing_data = false;
+        self.parse_chunks().await
+    }
 
+    async fn parse_chunks(&mut self) -> Result<(), SessionError> {
         loop {
             match self.unpacketizer.read_chunks() {
                 Ok(rv) => {
@@ -208,14 +218,49 @@ impl ServerSession {
     }
 
     async fn play(&mut self) -> Result<(), SessionError> {
-        match self.common.send_channel_data().await {
-            Ok(_) => {}
-            Err(err) => {
-                self.common
-                    .unsubscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
-                    .await?;
-                return Err(err);
+        /*keep reading the commands(pause/seek/receiveVideo...) from the player while sending*/
+        let io = Arc::clone(&self.io);
+        let rv = loop {
+            tokio::select! {
+                data = self.common.recv_channel_data() => {
+                    let Some(data) = data else {
+                        break Err(SessionError {
+                            value: SessionErrorValue::NoMediaDataReceived,
+                        });
+                    };
+                    if self.playback.filter(&data) {
+                        if let Err(err) = self.common.send_frame_data(data).await {
+                            break Err(err);
+                        }
+                    }
+                }
+                data = async { io.lock().await.read().await } => {
+                    match data {
+                        Ok(data) => {
+                            self.unpacketizer.extend_data(&data[..]);
+                            if let Err(err) = self.parse_chunks().await {
+                                break Err(err);
+                            }
+                        }
+                        Err(err) => {
+                            break Err(SessionError {
+                                value: SessionErrorValue::BytesIOError(err),
+                            });
+                        }
+                    }
+                }
+            }
+
+            if !matches!(self.state, ServerSessionState::Play) {
+                break Ok(());
             }
+        };
+
+        if let Err(err) = rv {
+            self.common
+                .unsubscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                .await?;
+            return Err(err);
         }
 
         Ok(())
@@ -261,7 +306,12 @@ impl ServerSession {
                 self.common.on_video_data(data, timestamp).await?;
             }
             RtmpMessageData::AmfData { raw_data } => {
-                self.common.on_meta_data(raw_data, timestamp).await?;
+                //the onTextData messages are relayed instead of being cached as the metadata
+                if let Ok(Some(text)) = unmarshal_text_data(raw_data) {
+                    self.common.on_text_data(text).await?;
+                } else {
+                    self.common.on_meta_data(raw_data, timestamp).await?;
+                }
             }
 
             _ => {}
@@ -333,6 +383,42 @@ impl ServerSession {
                 self.unpacketizer.session_type = config::SERVER_PUSH;
                 self.on_publish(transaction_id, stream_id, others).await?;
             }
+            "pause" => {
+                let is_paused = match others.first() {
+                    Some(Amf0ValueType::Boolean(is_paused)) => *is_paused,
+                    _ => !self.playback.is_paused(),
+                };
+                self.on_pause(transaction_id, stream_id, is_paused).await?;
+            }
+            "seek" => {
+                let ms = match others.first() {
+                    Some(Amf0ValueType::Number(ms)) => *ms,
+                    _ => 0.0,
+                };
+                self.on_seek(transaction_id, ms).await?;
+            }
+            "receiveAudio" | "receiveVideo" => {
+                let enable = match others.first() {
+                    Some(Amf0ValueType::Boolean(enable)) => *enable,
+                    _ => true,
+                };
+                self.on_receive(transaction_id, cmd_name, enable).await?;
+            }
+            "releaseStream" => {
+                log::info!("[ S<-C ] [release stream] ");
+                NetConnection::new(Arc::clone(&self.io))
+                    .write_result(transaction_id, &Amf0ValueType::Undefined)
+                    .await?;
+            }
+            "FCPublish" | "FCUnpublish" => {
+                self.on_fc_publish(transaction_id, cmd_name, others).await?;
+            }
+            "getStreamLength" => {
+                //only the live streams are served, which have no length
+                NetConnection::new(Arc::clone(&self.io))
+                    .write_result(transaction_id, &Amf0ValueType::Number(0.0))
+                    .await?;
+            }
             _ => {}
         }
 
@@ -432,11 +518,16 @@ impl ServerSession {
             )
             .await?;
 
+        //the amf3 clients also read the amf0 responses, only the encodings
+        //which are able to be parsed are accepted
         let obj_encoding = command_obj.get("objectEncoding");
         let encoding = match obj_encoding {
-            Some(Amf0ValueType::Number(encoding)) => encoding,
+            Some(Amf0ValueType::Number(encoding)) if *encoding == define::OBJENCODING_AMF3 => {
+                &define::OBJENCODING_AMF3
+            }
             _ => &define::OBJENCODING_AMF0,
         };
+        self.connect_properties.object_encoding = Some(*encoding);
 
         let app_name = command_obj.get("app");
         self.app_name = match app_name {
@@ -488,9 +579,15 @@ impl ServerSession {
         transaction_id: &f64,
         stream_id: &f64,
     ) -> Result<(), SessionError> {
-        self.common
-            .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
-            .await?;
+        if let ServerSessionState::Play = self.state {
+            self.common
+                .unsubscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                .await?;
+        } else {
+            self.common
+                .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                .await?;
+        }
 
         let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
         netstream
@@ -513,6 +610,141 @@ impl ServerSession {
         Ok(())
     }
 
+    pub async fn on_pause(
+        &mut self,
+        transaction_id: &f64,
+        stream_id: &u32,
+        is_paused: bool,
+    ) -> Result<(), SessionError> {
+        log::info!(
+            "[ S<-C ] [pause] app_name: {}, stream_name: {}, pause: {}",
+            self.app_name,
+            self.stream_name,
+            is_paused
+        );
+        self.playback.pause(is_paused);
+
+        let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        if is_paused {
+            event_messages.write_stream_eof(*stream_id).await?;
+            netstream
+                .write_on_status(transaction_id, "status", "NetStream.Pause.Notify", "paused")
+                .await?;
+        } else {
+            event_messages.write_stream_begin(*stream_id).await?;
+            netstream
+                .write_on_status(
+                    transaction_id,
+                    "status",
+                    "NetStream.Unpause.Notify",
+                    "unpaused",
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn on_seek(&mut self, transaction_id: &f64, ms: f64) -> Result<(), SessionError> {
+        log::info!(
+            "[ S<-C ] [seek] app_name: {}, stream_name: {}, ms: {}",
+            self.app_name,
+            self.stream_name,
+            ms
+        );
+        /*there is no DVR/VOD source for the rtmp streams,
+        the player is moved to the live edge from the next key frame.*/
+        self.playback.seek();
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_status(transaction_id, "status", "NetStream.Seek.Notify", "seek")
+            .await?;
+        netstream
+            .write_on_status(
+                transaction_id,
+                "status",
+                "NetStream.Play.Start",
+                "play start",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn on_receive(
+        &mut self,
+        transaction_id: &f64,
+        cmd_name: &str,
+        enable: bool,
+    ) -> Result<(), SessionError> {
+        log::info!(
+            "[ S<-C ] [{}] app_name: {}, stream_name: {}, enable: {}",
+            cmd_name,
+            self.app_name,
+            self.stream_name,
+            enable
+        );
+        if cmd_name == "receiveAudio" {
+            self.playback.set_receive_audio(enable);
+        } else {
+            self.playback.set_receive_video(enable);
+        }
+
+        //only the enabling is answered with the status messages
+        if enable {
+            let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+            netstream
+                .write_on_status(transaction_id, "status", "NetStream.Seek.Notify", "seek")
+                .await?;
+            netstream
+                .write_on_status(
+                    transaction_id,
+                    "status",
+                    "NetStream.Play.Start",
+                    "play start",
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn on_fc_publish(
+        &mut self,
+        transaction_id: &f64,
+        cmd_name: &str,
+        other_values: &[Amf0ValueType],
+    ) -> Result<(), SessionError> {
+        let stream_name = match other_values.first() {
+            Some(Amf0ValueType::UTF8String(stream_name)) => stream_name.clone(),
+            _ => String::new(),
+        };
+        log::info!(
+            "[ S<-C ] [{}] app_name: {}, stream_name: {}",
+            cmd_name,
+            self.app_name,
+            stream_name
+        );
+
+        let (status_name, code) = if cmd_name == "FCPublish" {
+            ("onFCPublish", "NetStream.Publish.Start")
+        } else {
+            ("onFCUnpublish", "NetStream.Unpublish.Success")
+        };
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_fc_status(status_name, code, &stream_name)
+            .await?;
+
+        NetConnection::new(Arc::clone(&self.io))
+            .write_result(transaction_id, &Amf0ValueType::Undefined)
+            .await?;
+
+        Ok(())
+    }
+
     fn get_request_url(&mut self, raw_stream_name: String) -> String {
         if let Some(tc_url) = &self.connect_properties.tc_url {
             format!("{tc_url}/{raw_stream_name}")
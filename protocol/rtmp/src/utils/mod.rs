@@ -18,6 +18,8 @@ pub struct RtmpUrlParser {
     pub stream_name_with_query: String,
     pub stream_name: String,
     pub query: Option<String>,
+    // the url starts with rtmps://
+    pub is_tls: bool,
 }
 
 impl RtmpUrlParser {
@@ -37,10 +39,18 @@ impl RtmpUrlParser {
      stream_name_with_query: stream_name?auth_key=test_Key
      stream_name: stream_name
      query: auth_key=test_Key
+     rtmps://domain.name.cn/app_name/stream_name is also supported.
     */
     pub fn parse_url(&mut self) -> Result<(), RtmpUrlParseError> {
-        if let Some(idx) = self.url.find("rtmp://") {
-            let remove_header_left = &self.url[idx + 7..];
+        let header = if let Some(idx) = self.url.find("rtmps://") {
+            self.is_tls = true;
+            Some(idx + 8)
+        } else {
+            self.url.find("rtmp://").map(|idx| idx + 7)
+        };
+
+        if let Some(header_len) = header {
+            let remove_header_left = &self.url[header_len..];
             let url_parts: Vec<&str> = remove_header_left.split('/').collect();
             if url_parts.len() != 3 {
                 return Err(RtmpUrlParseError {
@@ -146,4 +156,19 @@ mod tests {
             println!(" query: {}", parser.query.unwrap());
         }
     }
+    #[test]
+    fn test_rtmps_url_parser() {
+        let mut parser = RtmpUrlParser::new(String::from(
+            "rtmps://live-api-s.facebook.com:443/rtmp/FB-123?s_bl=1",
+        ));
+
+        parser.parse_url().unwrap();
+
+        assert!(parser.is_tls);
+        assert_eq!(parser.host, "live-api-s.facebook.com");
+        assert_eq!(parser.port, Some(String::from("443")));
+        assert_eq!(parser.app_name, "rtmp");
+        assert_eq!(parser.stream_name, "FB-123");
+        assert_eq!(parser.stream_name_with_query, "FB-123?s_bl=1");
+    }
 }
@@ -1,33 +1,239 @@
 use {
     super::errors::RelayError,
     crate::{
+        rtsp_auth::RtspCredential,
         rtsp_transport::ProtocolType,
-        session::{client_session::RtspClientSession, define::ClientSessionType},
+        session::{
+            client_session::{RtspClientSession, RtspRelayPublication},
+            define::ClientSessionType,
+        },
     },
     std::{
         collections::HashMap,
-        sync::{atomic::AtomicBool, Arc},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
     },
     streamhub::{
-        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            StreamHubEvent, StreamHubEventSender,
+        },
         errors::{StreamHubError, StreamHubErrorValue},
         stream::StreamIdentifier,
+        utils::Backoff,
     },
-    tokio::sync::Mutex,
+    tokio::time::sleep,
 };
 
 pub struct RtspPullClientManager {
     clients: HashMap<String, Arc<AtomicBool>>,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    backoff: Backoff,
 }
 
 impl RtspPullClientManager {
-    pub fn new(consumer: BroadcastEventReceiver, producer: StreamHubEventSender) -> Self {
+    pub fn new(
+        consumer: BroadcastEventReceiver,
+        producer: StreamHubEventSender,
+        backoff: Backoff,
+    ) -> Self {
         Self {
             clients: HashMap::new(),
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            backoff,
+        }
+    }
+
+    /* Run the pull session and reconnect it when it fails until the relay is stopped.
+    The local publication is handed over between the sessions, so the subscribers
+    stay connected during a short upstream outage. */
+    async fn supervise(
+        id: String,
+        mut client_session: RtspClientSession,
+        server_address: String,
+        stream_path: String,
+        producer: StreamHubEventSender,
+        mut backoff: Backoff,
+        is_running: Arc<AtomicBool>,
+    ) {
+        let mut publication: Option<RtspRelayPublication>;
+        //the user info of the server address is not logged
+        let (_, log_address) = RtspCredential::split_address(&server_address);
+
+        loop {
+            match client_session.run().await {
+                Ok(()) => {
+                    //the relay is stopped and the session has unpublished the stream
+                    return;
+                }
+                Err(err) if err.is_stream_exists() => {
+                    //the stream is pulled by another relay, reconnecting cannot publish it either
+                    log::warn!(
+                        "rtsp pull client stops pulling from {}, the stream exists, stream path: {}",
+                        log_address,
+                        stream_path
+                    );
+                    Self::release_id(id, &producer, &is_running);
+                    return;
+                }
+                Err(err) => {
+                    log::error!("client_session as pull client run error: {}", err);
+                }
+            }
+
+            publication = client_session.take_relay_publication();
+            if publication.is_some() {
+                backoff.reset();
+            }
+
+            loop {
+                let delay = match backoff.next_delay() {
+                    Some(delay) if is_running.load(Ordering::Acquire) => delay,
+                    _ => {
+                        log::warn!(
+                            "rtsp pull client stops reconnecting to {} after {} retries, stream path: {}",
+                            log_address,
+                            backoff.retries(),
+                            stream_path
+                        );
+                        if let Some(publication) = publication {
+                            if let Err(err) = publication.unpublish(&producer) {
+                                log::error!("rtsp pull client unpublish error: {}", err);
+                            }
+                        }
+                        Self::release_id(id, &producer, &is_running);
+                        return;
+                    }
+                };
+
+                log::info!(
+                    "rtsp pull client reconnects to {} in {:?}, stream path: {}",
+                    log_address,
+                    delay,
+                    stream_path
+                );
+                sleep(delay).await;
+
+                match RtspClientSession::new(
+                    server_address.clone(),
+                    stream_path.clone(),
+                    ProtocolType::TCP,
+                    producer.clone(),
+                    ClientSessionType::Pull,
+                )
+                .await
+                {
+                    Ok(session) => {
+                        client_session = session;
+                        client_session.is_running = is_running.clone();
+                        if let Some(publication) = publication.take() {
+                            client_session.resume_relay_publication(publication);
+                        }
+                        break;
+                    }
+                    Err(err) => {
+                        log::error!("rtsp pull client connect {} error: {}", log_address, err);
+                    }
+                }
+            }
+        }
+    }
+
+    //release the id in the stream hub if the client is not stopped by the api
+    fn release_id(id: String, producer: &StreamHubEventSender, is_running: &AtomicBool) {
+        if is_running.swap(false, Ordering::AcqRel) {
+            if let Err(err) = producer.send(StreamHubEvent::RelayStopped { id }) {
+                log::error!("rtsp pull client send relay stopped error: {}", err);
+            }
+        }
+    }
+
+    async fn start_client(
+        &mut self,
+        id: String,
+        stream_path: String,
+        server_address: Option<String>,
+    ) -> Result<(), StreamHubError> {
+        let Some(server_address) = server_address else {
+            log::error!(
+                "The Rtsp subscribe parameters does not contain server address: {}",
+                stream_path
+            );
+            return Err(StreamHubError {
+                value: StreamHubErrorValue::RtspClientSessionError(String::from(
+                    "The Rtsp subscribe parameters does not contain server address",
+                )),
+            });
+        };
+        log::info!("publish stream_path: {}", stream_path.clone());
+
+        /* judge if the server address / stream path exists, the id of a client
+        which gave up reconnecting can be reused */
+        if self
+            .clients
+            .get(&id)
+            .is_some_and(|is_running| is_running.load(Ordering::Acquire))
+        {
+            log::warn!("the client session with id:{} exists", id);
+            return Err(StreamHubError {
+                value: StreamHubErrorValue::RtspClientSessionError(format!(
+                    "stream {} exists.",
+                    stream_path
+                )),
+            });
+        }
+
+        /* new and run a client, save the client handler for exit */
+        match RtspClientSession::new(
+            server_address.clone(),
+            stream_path.clone(),
+            ProtocolType::TCP,
+            self.channel_event_producer.clone(),
+            ClientSessionType::Pull,
+        )
+        .await
+        {
+            Ok(client_session) => {
+                let is_running = client_session.is_running.clone();
+                self.clients.insert(id.clone(), is_running.clone());
+
+                let producer = self.channel_event_producer.clone();
+                let backoff = self.backoff.clone();
+                tokio::spawn(async move {
+                    Self::supervise(
+                        id,
+                        client_session,
+                        server_address,
+                        stream_path,
+                        producer,
+                        backoff,
+                        is_running,
+                    )
+                    .await;
+                });
+                Ok(())
+            }
+            Err(err) => {
+                log::error!("new client session err: {}", err);
+                Err(StreamHubError {
+                    value: StreamHubErrorValue::RtspClientSessionError(err.to_string()),
+                })
+            }
+        }
+    }
+
+    async fn send_result(
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+        result: Result<(), StreamHubError>,
+    ) {
+        if let Some(sender) = result_sender {
+            if let Err(err) = sender.send(result).await {
+                log::error!("rtsp pull client send result error: {}", err);
+            }
         }
     }
 
@@ -42,118 +248,23 @@ impl RtspPullClientManager {
                     id,
                     identifier,
                     server_address,
+                    token: _,
                     result_sender,
                 } => {
-                    let sender = result_sender.unwrap();
-
+                    //the subscribes of the other protocols, e.g. the cluster pulls, are not replied
                     if let StreamIdentifier::Rtsp { stream_path } = identifier {
-                        if let Some(server_address) = server_address {
-                            log::info!("publish stream_path: {}", stream_path.clone());
-
-                            /* judge if the server address / stream path exists */
-                            if self.clients.get_mut(&id).is_some() {
-                                log::warn!("the client session with id:{} exists", id);
-
-                                let err = Err(StreamHubError {
-                                    value: StreamHubErrorValue::RtspClientSessionError(format!(
-                                        "stream {} exists.",
-                                        stream_path
-                                    )),
-                                });
-                                if let Err(send_err) = sender.send(err).await {
-                                    log::error!("sender error: {}", send_err);
-                                }
-                                continue;
-                            }
-
-                            /* new and run a client, save the client handler for exit */
-                            match RtspClientSession::new(
-                                server_address.clone(),
-                                stream_path.clone(),
-                                ProtocolType::TCP,
-                                self.channel_event_producer.clone(),
-                                ClientSessionType::Pull,
-                            )
-                            .await
-                            {
-                                Ok(client_session) => {
-                                    self.clients.insert(id, client_session.is_running.clone());
-                                    let arc_client_session = Arc::new(Mutex::new(client_session));
-
-                                    tokio::spawn(async move {
-                                        if let Err(err) =
-                                            arc_client_session.lock().await.run().await
-                                        {
-                                            log::error!(
-                                                "client_session as push client run error: {}",
-                                                err
-                                            );
-
-                                            //let err = Err(StreamHubError {
-                                            //    value: StreamHubErrorValue::RtspClientSessionError(
-                                            //        err.to_string(),
-                                            //    ),
-                                            //});
-                                            //if let Err(send_err) = sender.send(err).await {
-                                            //    log::error!("sender error: {}", send_err);
-                                            //}
-                                        }
-                                    });
-
-                                    if let Err(send_err) = sender.send(Ok(())).await {
-                                        log::error!("sender error: {}", send_err);
-                                    }
-                                }
-                                Err(err) => {
-                                    log::error!("new client session err: {}", err);
-
-                                    let err = Err(StreamHubError {
-                                        value: StreamHubErrorValue::RtspClientSessionError(
-                                            err.to_string(),
-                                        ),
-                                    });
-                                    if let Err(send_err) = sender.send(err).await {
-                                        log::error!("sender error: {}", send_err);
-                                    }
-                                    continue;
-                                }
-                            }
-                        } else {
-                            log::error!(
-                                "The Rtsp subscribe parameters does not contain server address: {}",
-                                stream_path
-                            );
-
-                            let err = Err(StreamHubError {
-                                value: StreamHubErrorValue::RtspClientSessionError(String::from(
-                                    "The Rtsp subscribe parameters does not contain server address",
-                                )),
-                            });
-                            if let Err(send_err) = sender.send(err).await {
-                                log::error!("sender error: {}", send_err);
-                            }
-                            continue;
-                        }
+                        let result = self.start_client(id, stream_path, server_address).await;
+                        Self::send_result(result_sender, result).await;
                     }
                 }
 
                 BroadcastEvent::UnSubscribe { id, result_sender } => {
-                    let sender = result_sender.unwrap();
-                    /* judge if the server address / stream path exists */
-                    if let Some(client) = self.clients.get_mut(&id) {
+                    /* judge if the server address / stream path exists, the ids of the
+                    other relay clients are ignored */
+                    if let Some(client) = self.clients.remove(&id) {
                         client.store(false, std::sync::atomic::Ordering::Release);
-                        self.clients.remove(&id);
-                    } else {
-                        log::warn!("the client session with id:{} not exists", id);
-
-                        let err = Err(StreamHubError {
-                            value: StreamHubErrorValue::RtspClientSessionError(String::from(
-                                "the client session not exists",
-                            )),
-                        });
-                        if let Err(send_err) = sender.send(err).await {
-                            log::error!("sender error: {}", send_err);
-                        }
+
+                        Self::send_result(result_sender, Ok(())).await;
                     }
                 }
 
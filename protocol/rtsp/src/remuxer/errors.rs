@@ -0,0 +1,74 @@
+#![allow(non_local_definitions)]
+use {
+    failure::Fail,
+    std::fmt,
+    streamhub::errors::StreamHubError,
+    tokio::sync::oneshot::error::RecvError as OneshotRecvError,
+    xflv::errors::{FlvDemuxerError, Mpeg4AvcHevcError, MpegAacError},
+};
+
+pub struct RtspRemuxerError {
+    pub value: RtspRemuxerErrorValue,
+}
+
+#[derive(Debug, Fail)]
+pub enum RtspRemuxerErrorValue {
+    #[fail(display = "flv demuxer error:{}", _0)]
+    FlvDemuxerError(#[cause] FlvDemuxerError),
+    #[fail(display = "mpeg avc error:{}", _0)]
+    MpegAvcError(#[cause] Mpeg4AvcHevcError),
+    #[fail(display = "mpeg aac error:{}", _0)]
+    MpegAacError(#[cause] MpegAacError),
+    #[fail(display = "stream hub event send error")]
+    StreamHubEventSendErr,
+    #[fail(display = "event execute error: {}", _0)]
+    ChannelError(#[cause] StreamHubError),
+    #[fail(display = "tokio: oneshot receiver err: {}", _0)]
+    OneshotRecvError(#[cause] OneshotRecvError),
+}
+
+impl From<FlvDemuxerError> for RtspRemuxerError {
+    fn from(error: FlvDemuxerError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::FlvDemuxerError(error),
+        }
+    }
+}
+
+impl From<Mpeg4AvcHevcError> for RtspRemuxerError {
+    fn from(error: Mpeg4AvcHevcError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::MpegAvcError(error),
+        }
+    }
+}
+
+impl From<MpegAacError> for RtspRemuxerError {
+    fn from(error: MpegAacError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::MpegAacError(error),
+        }
+    }
+}
+
+impl From<StreamHubError> for RtspRemuxerError {
+    fn from(error: StreamHubError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::ChannelError(error),
+        }
+    }
+}
+
+impl From<OneshotRecvError> for RtspRemuxerError {
+    fn from(error: OneshotRecvError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::OneshotRecvError(error),
+        }
+    }
+}
+
+impl fmt::Display for RtspRemuxerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
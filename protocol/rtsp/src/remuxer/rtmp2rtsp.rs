@@ -0,0 +1,449 @@
+use super::errors::{RtspRemuxerError, RtspRemuxerErrorValue};
+use crate::global_trait::Unmarshal as SdpUnmarshal;
+use crate::rtsp_vod::{media_sdp, AacConfig, H264Config};
+use crate::sdp::Sdp;
+use crate::session::server_session::RtspStreamHandler;
+use bytes::BytesMut;
+use bytesio::bytes_reader::BytesReader;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use streamhub::{
+    define::{
+        FrameData, FrameDataReceiver, FrameDataSender, NotifyInfo, PubDataType, PublishType,
+        PublisherInfo, StreamHubEvent, StreamHubEventSender, SubDataType, SubscribeType,
+        SubscriberInfo,
+    },
+    stream::StreamIdentifier,
+    utils::{RandomDigitCount, Uuid},
+};
+use tokio::sync::{oneshot, watch};
+use xflv::{
+    define::{aac_packet_type, avc_packet_type, AvcCodecId, SoundFormat},
+    flv_tag_header::{AudioTagHeader, VideoTagHeader},
+    mpeg4_aac::Mpeg4AacProcessor,
+    mpeg4_avc::Mpeg4AvcProcessor,
+    Unmarshal as FlvUnmarshal,
+};
+
+const H264_CLOCK_RATE: u32 = 90000;
+//the remuxer stops if the rtsp stream has no players in time
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/* The remuxers started by the rtsp sessions, the concurrent DESCRIBE requests of
+the same path wait for the same remuxer instead of starting their own. */
+#[derive(Default, Clone)]
+pub struct Rtmp2RtspRemuxers {
+    ready_receivers: Arc<std::sync::Mutex<HashMap<String, watch::Receiver<bool>>>>,
+}
+
+impl Rtmp2RtspRemuxers {
+    //start a remuxer if there is none, and wait until the remuxed stream is published
+    pub async fn remux(
+        &self,
+        stream_path: &str,
+        event_producer: StreamHubEventSender,
+        ready_timeout: Duration,
+    ) -> bool {
+        let mut ready_receiver = {
+            let mut ready_receivers = self.ready_receivers.lock().unwrap();
+            if let Some(ready_receiver) = ready_receivers.get(stream_path) {
+                ready_receiver.clone()
+            } else {
+                let (ready_sender, ready_receiver) = watch::channel(false);
+                ready_receivers.insert(stream_path.to_string(), ready_receiver.clone());
+
+                let mut remuxer_session = Rtmp2RtspRemuxerSession::new(
+                    stream_path.to_string(),
+                    event_producer,
+                    ready_sender,
+                );
+                let ready_receivers = self.ready_receivers.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = remuxer_session.run().await {
+                        log::error!("rtmp2rtsp session error: {}", err);
+                    }
+                    ready_receivers
+                        .lock()
+                        .unwrap()
+                        .remove(&remuxer_session.stream_path);
+                });
+                ready_receiver
+            }
+        };
+
+        let is_ready =
+            tokio::time::timeout(ready_timeout, ready_receiver.wait_for(|ready| *ready)).await;
+        matches!(is_ready, Ok(Ok(_)))
+    }
+}
+
+// Remux a RTMP stream to a RTSP stream when it is played by a rtsp client, the
+// sdp is built from the AVC/AAC sequence headers and the frames are packetized
+// by the rtp packers of the rtsp sessions which play it.
+pub struct Rtmp2RtspRemuxerSession {
+    event_producer: StreamHubEventSender,
+    //RTMP
+    app_name: String,
+    stream_name: String,
+    subscribe_id: Uuid,
+    avc_processor: Mpeg4AvcProcessor,
+    aac_processor: Mpeg4AacProcessor,
+
+    //RTSP
+    stream_path: String,
+    publish_id: Uuid,
+    stream_handler: Arc<RtspStreamHandler>,
+    frame_sender: Option<FrameDataSender>,
+    h264_config: Option<H264Config>,
+    aac_config: Option<AacConfig>,
+    //notify the rtsp sessions when the sdp of the remuxed stream is ready
+    ready_sender: watch::Sender<bool>,
+}
+
+impl Rtmp2RtspRemuxerSession {
+    pub fn new(
+        stream_path: String,
+        event_producer: StreamHubEventSender,
+        ready_sender: watch::Sender<bool>,
+    ) -> Self {
+        //the reverse of the path mapping in rtsp2rtmp
+        let eles: Vec<&str> = stream_path.splitn(2, '/').collect();
+        let (app_name, stream_name) = if eles.len() < 2 {
+            (String::from("rtsp"), String::from(eles[0]))
+        } else {
+            (String::from(eles[0]), String::from(eles[1]))
+        };
+
+        Self {
+            event_producer,
+            app_name,
+            stream_name,
+            subscribe_id: Uuid::new(RandomDigitCount::Four),
+            avc_processor: Mpeg4AvcProcessor::new(),
+            aac_processor: Mpeg4AacProcessor::new(),
+            stream_path,
+            publish_id: Uuid::new(RandomDigitCount::Four),
+            stream_handler: Arc::new(RtspStreamHandler::new()),
+            frame_sender: None,
+            h264_config: None,
+            aac_config: None,
+            ready_sender,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), RtspRemuxerError> {
+        let receiver = self.subscribe_rtmp().await?;
+        let rv = self.receive_rtmp_data(receiver).await;
+
+        self.unsubscribe_rtmp();
+        self.unpublish_rtsp();
+        rv
+    }
+
+    fn get_subscriber_info(&self) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.subscribe_id,
+            sub_type: SubscribeType::RtmpRemux2Rtsp,
+            sub_data_type: SubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+
+    fn get_publisher_info(&self) -> PublisherInfo {
+        PublisherInfo {
+            id: self.publish_id,
+            pub_type: PublishType::RtmpRemux2Rtsp,
+            pub_data_type: PubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+
+    async fn subscribe_rtmp(&mut self) -> Result<FrameDataReceiver, RtspRemuxerError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+            result_sender: event_result_sender,
+        };
+
+        if self.event_producer.send(subscribe_event).is_err() {
+            return Err(RtspRemuxerError {
+                value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        let receiver = event_result_receiver.await??.0;
+        receiver.frame_receiver.ok_or(RtspRemuxerError {
+            value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+        })
+    }
+
+    fn unsubscribe_rtmp(&mut self) {
+        let unsubscribe_event = StreamHubEvent::UnSubscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+        };
+        if let Err(err) = self.event_producer.send(unsubscribe_event) {
+            log::error!("rtmp2rtsp unsubscribe_rtmp err {}", err);
+        }
+    }
+
+    fn gen_sdp(&self) -> String {
+        String::from("v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=xiu\r\nc=IN IP4 0.0.0.0\r\nt=0 0\r\n")
+            + &media_sdp(&self.h264_config, &self.aac_config)
+    }
+
+    async fn publish_rtsp(&mut self) -> Result<(), RtspRemuxerError> {
+        if let Some(sdp) = Sdp::unmarshal(&self.gen_sdp()) {
+            self.stream_handler.set_sdp(sdp).await;
+        }
+
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+        let publish_event = StreamHubEvent::Publish {
+            identifier: StreamIdentifier::Rtsp {
+                stream_path: self.stream_path.clone(),
+            },
+            result_sender: event_result_sender,
+            info: self.get_publisher_info(),
+            stream_handler: self.stream_handler.clone(),
+        };
+
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(RtspRemuxerError {
+                value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        self.frame_sender = event_result_receiver.await??.0;
+        log::info!(
+            "rtmp2rtsp: remux rtmp stream {}/{} to rtsp stream {}",
+            self.app_name,
+            self.stream_name,
+            self.stream_path
+        );
+
+        self.ready_sender.send_replace(true);
+
+        Ok(())
+    }
+
+    fn unpublish_rtsp(&mut self) {
+        if self.frame_sender.take().is_none() {
+            return;
+        }
+
+        let unpublish_event = StreamHubEvent::UnPublish {
+            identifier: StreamIdentifier::Rtsp {
+                stream_path: self.stream_path.clone(),
+            },
+            info: self.get_publisher_info(),
+        };
+        if let Err(err) = self.event_producer.send(unpublish_event) {
+            log::error!("rtmp2rtsp unpublish_rtsp err {}", err);
+        }
+    }
+
+    async fn receive_rtmp_data(
+        &mut self,
+        mut receiver: FrameDataReceiver,
+    ) -> Result<(), RtspRemuxerError> {
+        let idle = Self::wait_idle(self.stream_handler.subscribe_subscriber_count());
+        tokio::pin!(idle);
+
+        loop {
+            tokio::select! {
+                data = receiver.recv() => {
+                    match data {
+                        Some(FrameData::Video { timestamp, data }) => {
+                            self.on_rtmp_video(timestamp, data).await?;
+                        }
+                        Some(FrameData::Audio { timestamp, data }) => {
+                            self.on_rtmp_audio(timestamp, data).await?;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                _ = &mut idle => {
+                    log::info!("rtmp2rtsp: no rtsp player of {}, stop remuxing", self.stream_path);
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    //return when the rtsp stream has no subscribers for the idle timeout
+    async fn wait_idle(mut subscriber_count: watch::Receiver<usize>) {
+        loop {
+            if *subscriber_count.borrow_and_update() == 0 {
+                match tokio::time::timeout(IDLE_TIMEOUT, subscriber_count.changed()).await {
+                    Ok(Ok(())) => continue,
+                    _ => return,
+                }
+            }
+            if subscriber_count.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn on_rtmp_video(
+        &mut self,
+        timestamp: u32,
+        data: BytesMut,
+    ) -> Result<(), RtspRemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = VideoTagHeader::unmarshal(&mut reader)?;
+        if tag_header.codec_id != AvcCodecId::H264 as u8 {
+            return Ok(());
+        }
+
+        match tag_header.avc_packet_type {
+            avc_packet_type::AVC_SEQHDR => {
+                self.avc_processor
+                    .decoder_configuration_record_load(&mut reader)?;
+
+                let mpeg4_avc = &self.avc_processor.mpeg4_avc;
+                if let (Some(sps), Some(pps)) = (mpeg4_avc.sps.first(), mpeg4_avc.pps.first()) {
+                    self.h264_config = Some(H264Config {
+                        sps: sps.data.clone(),
+                        pps: pps.data.clone(),
+                    });
+                }
+            }
+            avc_packet_type::AVC_NALU => {
+                //the sps and pps are inserted before the idr frames
+                let nalus = self.avc_processor.h264_mp4toannexb(&mut reader)?;
+                let pts = timestamp.wrapping_add(tag_header.composition_time as u32);
+                self.send_frame(FrameData::Video {
+                    timestamp: pts.wrapping_mul(H264_CLOCK_RATE / 1000),
+                    data: nalus,
+                })
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn on_rtmp_audio(
+        &mut self,
+        timestamp: u32,
+        data: BytesMut,
+    ) -> Result<(), RtspRemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = AudioTagHeader::unmarshal(&mut reader)?;
+        if tag_header.sound_format != SoundFormat::AAC as u8 {
+            return Ok(());
+        }
+
+        match tag_header.aac_packet_type {
+            aac_packet_type::AAC_SEQHDR => {
+                self.aac_processor
+                    .extend_data(reader.extract_remaining_bytes())
+                    .audio_specific_config_load()?;
+
+                let mpeg4_aac = &self.aac_processor.mpeg4_aac;
+                self.aac_config = Some(AacConfig {
+                    object_type: mpeg4_aac.object_type,
+                    sampling_frequency_index: mpeg4_aac.sampling_frequency_index,
+                    channel_configuration: mpeg4_aac.channel_configuration,
+                });
+            }
+            aac_packet_type::AAC_RAW => {
+                let Some(aac_config) = &self.aac_config else {
+                    return Ok(());
+                };
+                let sample_rate = aac_config.sample_rate() as u64;
+                self.send_frame(FrameData::Audio {
+                    timestamp: (timestamp as u64 * sample_rate / 1000) as u32,
+                    data: reader.extract_remaining_bytes(),
+                })
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    //the rtsp stream is published with the sequence headers received before the first frame
+    async fn send_frame(&mut self, frame: FrameData) -> Result<(), RtspRemuxerError> {
+        if self.frame_sender.is_none() {
+            self.publish_rtsp().await?;
+        }
+
+        if let Some(sender) = &self.frame_sender {
+            if sender.send(frame).is_err() {
+                return Err(RtspRemuxerError {
+                    value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rtmp2RtspRemuxerSession;
+    use crate::global_trait::Unmarshal;
+    use crate::rtsp_vod::{AacConfig, H264Config};
+    use crate::sdp::Sdp;
+    use bytes::BytesMut;
+    use tokio::sync::{mpsc, watch};
+
+    #[test]
+    fn test_remux_path_and_sdp() {
+        let (event_producer, _) = mpsc::unbounded_channel();
+
+        let session = Rtmp2RtspRemuxerSession::new(
+            String::from("test"),
+            event_producer.clone(),
+            watch::channel(false).0,
+        );
+        assert_eq!(session.app_name, "rtsp");
+        assert_eq!(session.stream_name, "test");
+
+        let mut session = Rtmp2RtspRemuxerSession::new(
+            String::from("live/test/1"),
+            event_producer,
+            watch::channel(false).0,
+        );
+        assert_eq!(session.app_name, "live");
+        assert_eq!(session.stream_name, "test/1");
+
+        session.h264_config = Some(H264Config {
+            sps: BytesMut::from(&[0x67, 0x64, 0x00, 0x1F, 0xAC][..]),
+            pps: BytesMut::from(&[0x68, 0xEE, 0x3C, 0x80][..]),
+        });
+        session.aac_config = Some(AacConfig {
+            object_type: 2,
+            sampling_frequency_index: 4,
+            channel_configuration: 2,
+        });
+
+        let sdp = Sdp::unmarshal(&session.gen_sdp()).unwrap();
+        assert_eq!(sdp.medias.len(), 2);
+        assert_eq!(sdp.medias[0].rtpmap.encoding_name, "H264");
+        assert_eq!(sdp.medias[1].rtpmap.clock_rate, 44100);
+    }
+}
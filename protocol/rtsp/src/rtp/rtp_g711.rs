@@ -0,0 +1,150 @@
+use super::define;
+use super::errors::PackerError;
+use super::errors::UnPackerError;
+use super::utils::OnFrameFn;
+use super::utils::OnRtpPacketFn;
+use super::utils::OnRtpPacketFn2;
+use super::utils::TPacker;
+use super::utils::TRtpReceiverForRtcp;
+use super::utils::TUnPacker;
+use super::utils::Unmarshal;
+use super::RtpHeader;
+use super::RtpPacket;
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use bytesio::bytes_reader::BytesReader;
+use bytesio::bytesio::TNetIO;
+use std::sync::Arc;
+use streamhub::define::FrameData;
+use tokio::sync::Mutex;
+
+// G.711 PCMU/PCMA(RFC 3551 4.5.14), one byte is one sample of a channel,
+// the samples are put into the rtp payload directly.
+pub struct RtpG711Packer {
+    header: RtpHeader,
+    mtu: usize,
+    channel_count: u8,
+    on_packet_handler: Option<OnRtpPacketFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+    io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+}
+
+impl RtpG711Packer {
+    pub fn new(
+        payload_type: u8,
+        ssrc: u32,
+        init_seq: u16,
+        mtu: usize,
+        channel_count: u8,
+        io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+    ) -> Self {
+        RtpG711Packer {
+            header: RtpHeader {
+                payload_type,
+                seq_number: init_seq,
+                ssrc,
+                version: 2,
+                ..Default::default()
+            },
+            mtu,
+            channel_count: channel_count.max(1),
+            io,
+            on_packet_handler: None,
+            on_packet_for_rtcp_handler: None,
+        }
+    }
+}
+
+#[async_trait]
+impl TPacker for RtpG711Packer {
+    async fn pack(&mut self, data: &mut BytesMut, timestamp: u32) -> Result<(), PackerError> {
+        let channel_count = self.channel_count as usize;
+        //do not split the samples of different channels into two packets
+        let max_payload_len =
+            (self.mtu - define::RTP_FIXED_HEADER_LEN) / channel_count * channel_count;
+
+        let mut sample_offset: u32 = 0;
+        while !data.is_empty() {
+            let payload_len = data.len().min(max_payload_len);
+
+            self.header.timestamp = timestamp.wrapping_add(sample_offset);
+            let mut packet = RtpPacket::new(self.header.clone());
+            packet.payload.put(data.split_to(payload_len));
+
+            if let Some(f) = &self.on_packet_for_rtcp_handler {
+                f(packet.clone()).await;
+            }
+
+            if let Some(f) = &self.on_packet_handler {
+                f(self.io.clone(), packet).await?;
+            }
+
+            self.header.seq_number = self.header.seq_number.wrapping_add(1);
+            sample_offset += (payload_len / channel_count) as u32;
+        }
+
+        Ok(())
+    }
+
+    fn on_packet_handler(&mut self, f: OnRtpPacketFn) {
+        self.on_packet_handler = Some(f);
+    }
+
+    fn seq_number(&self) -> u16 {
+        self.header.seq_number
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpG711Packer {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
+
+#[derive(Default)]
+pub struct RtpG711UnPacker {
+    on_frame_handler: Option<OnFrameFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+}
+
+impl RtpG711UnPacker {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl TUnPacker for RtpG711UnPacker {
+    async fn unpack(&mut self, reader: &mut BytesReader) -> Result<(), UnPackerError> {
+        let rtp_packet = RtpPacket::unmarshal(reader)?;
+
+        if let Some(f) = &self.on_packet_for_rtcp_handler {
+            f(rtp_packet.clone()).await;
+        }
+
+        if rtp_packet.payload.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(f) = &self.on_frame_handler {
+            f(FrameData::Audio {
+                timestamp: rtp_packet.header.timestamp,
+                data: rtp_packet.payload,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn on_frame_handler(&mut self, f: OnFrameFn) {
+        self.on_frame_handler = Some(f);
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpG711UnPacker {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
@@ -131,6 +131,10 @@ impl TPacker for RtpH264Packer {
     fn on_packet_handler(&mut self, f: OnRtpPacketFn) {
         self.on_packet_handler = Some(f);
     }
+
+    fn seq_number(&self) -> u16 {
+        self.header.seq_number
+    }
 }
 
 impl TRtpReceiverForRtcp for RtpH264Packer {
@@ -148,6 +148,10 @@ impl TPacker for RtpH265Packer {
     fn on_packet_handler(&mut self, f: OnRtpPacketFn) {
         self.on_packet_handler = Some(f);
     }
+
+    fn seq_number(&self) -> u16 {
+        self.header.seq_number
+    }
 }
 
 impl TRtpReceiverForRtcp for RtpH265Packer {
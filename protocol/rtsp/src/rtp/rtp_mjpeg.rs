@@ -0,0 +1,533 @@
+use super::define;
+use super::errors::PackerError;
+use super::errors::UnPackerError;
+use super::utils::OnFrameFn;
+use super::utils::OnRtpPacketFn;
+use super::utils::OnRtpPacketFn2;
+use super::utils::TPacker;
+use super::utils::TRtpReceiverForRtcp;
+use super::utils::TUnPacker;
+use super::utils::Unmarshal;
+use super::RtpHeader;
+use super::RtpPacket;
+use async_trait::async_trait;
+use byteorder::BigEndian;
+use bytes::{BufMut, BytesMut};
+use bytesio::bytes_reader::BytesReader;
+use bytesio::bytesio::TNetIO;
+use std::sync::Arc;
+use streamhub::define::FrameData;
+use tokio::sync::Mutex;
+
+// JPEG(RFC 2435), only the entropy-coded scan data of a baseline JPEG frame is
+// sent, the receiver rebuilds the JPEG headers from the following header:
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Type-specific |              Fragment Offset                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Type     |       Q       |     Width     |     Height    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+const JPEG_HEADER_LEN: usize = 8;
+const RESTART_MARKER_HEADER_LEN: usize = 4;
+//the types 64-127 are the types 0-63 with restart markers
+const JPEG_TYPE_RESTART_MARKER: u8 = 64;
+//the quantization tables are sent in the first packet of a frame if Q >= 128
+const JPEG_Q_INBAND_TABLES: u8 = 255;
+
+const MARKER_SOI: u8 = 0xD8;
+const MARKER_EOI: u8 = 0xD9;
+const MARKER_SOF0: u8 = 0xC0;
+const MARKER_DHT: u8 = 0xC4;
+const MARKER_DQT: u8 = 0xDB;
+const MARKER_DRI: u8 = 0xDD;
+const MARKER_SOS: u8 = 0xDA;
+
+//the natural position of the nth coefficient in zigzag order
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+//RFC 2435 Appendix A, in natural order
+const LUMA_QUANTIZER: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+const CHROMA_QUANTIZER: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+//RFC 2435 Appendix B, the huffman tables every RTP/JPEG frame is encoded with
+const LUMA_DC_CODE_LENS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const LUMA_DC_SYMBOLS: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const LUMA_AC_CODE_LENS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const LUMA_AC_SYMBOLS: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+const CHROMA_DC_CODE_LENS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const CHROMA_DC_SYMBOLS: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const CHROMA_AC_CODE_LENS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMA_AC_SYMBOLS: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+// The information of a baseline JPEG frame carried by the RTP/JPEG headers.
+#[derive(Debug, Default, PartialEq)]
+struct JpegFrame {
+    //0: YUV 4:2:2, 1: YUV 4:2:0
+    jpeg_type: u8,
+    width: u16,
+    height: u16,
+    restart_interval: u16,
+    //the 64 bytes tables in zigzag order, luma first
+    quant_tables: BytesMut,
+    scan: BytesMut,
+}
+
+impl JpegFrame {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 4 || data[0] != 0xFF || data[1] != MARKER_SOI {
+            return None;
+        }
+
+        let mut frame = JpegFrame::default();
+        let mut quant_tables: [Option<&[u8]>; 4] = [None; 4];
+        let mut has_sof = false;
+
+        let mut pos = 2;
+        while pos + 4 <= data.len() {
+            if data[pos] != 0xFF {
+                return None;
+            }
+            let marker = data[pos + 1];
+            //fill bytes
+            if marker == 0xFF {
+                pos += 1;
+                continue;
+            }
+            let segment_len = ((data[pos + 2] as usize) << 8) | data[pos + 3] as usize;
+            let segment = data.get(pos + 4..pos + 2 + segment_len)?;
+
+            match marker {
+                MARKER_DQT => {
+                    for table in segment.chunks(65) {
+                        //only the 8 bits precision tables can be carried
+                        if table.len() != 65 || table[0] >> 4 != 0 {
+                            return None;
+                        }
+                        *quant_tables.get_mut((table[0] & 0x0F) as usize)? = Some(&table[1..]);
+                    }
+                }
+                MARKER_SOF0 => {
+                    //precision(1) height(2) width(2) component count(1)
+                    //and id(1) sampling factors(1) table(1) of each component
+                    if segment.len() < 15 || segment[5] != 3 {
+                        return None;
+                    }
+                    frame.height = ((segment[1] as u16) << 8) | segment[2] as u16;
+                    frame.width = ((segment[3] as u16) << 8) | segment[4] as u16;
+                    frame.jpeg_type = match (segment[7], segment[10], segment[13]) {
+                        (0x21, 0x11, 0x11) => 0,
+                        (0x22, 0x11, 0x11) => 1,
+                        _ => return None,
+                    };
+                    has_sof = true;
+                }
+                MARKER_DRI => {
+                    frame.restart_interval =
+                        ((*segment.first()? as u16) << 8) | *segment.get(1)? as u16;
+                }
+                MARKER_SOS => {
+                    let mut scan = &data[pos + 2 + segment_len..];
+                    if scan.ends_with(&[0xFF, MARKER_EOI]) {
+                        scan = &scan[..scan.len() - 2];
+                    }
+                    frame.scan = BytesMut::from(scan);
+                    break;
+                }
+                //the huffman tables must be the default ones
+                _ => {}
+            }
+            pos += 2 + segment_len;
+        }
+
+        //the width and height are carried in 8 pixel multiples by one byte
+        if !has_sof || frame.scan.is_empty() || frame.width > 2040 || frame.height > 2040 {
+            return None;
+        }
+        for table in quant_tables.iter().flatten() {
+            frame.quant_tables.put(*table);
+        }
+
+        Some(frame)
+    }
+
+    //rebuild a complete JPEG frame with the default huffman tables
+    fn marshal(&self) -> BytesMut {
+        let mut data = BytesMut::new();
+        data.put_u8(0xFF);
+        data.put_u8(MARKER_SOI);
+
+        let table_count = self.quant_tables.len() / 64;
+        for (id, table) in self.quant_tables.chunks_exact(64).enumerate() {
+            data.put_u8(0xFF);
+            data.put_u8(MARKER_DQT);
+            data.put_u16(67);
+            data.put_u8(id as u8);
+            data.put(table);
+        }
+
+        if self.restart_interval > 0 {
+            data.put_u8(0xFF);
+            data.put_u8(MARKER_DRI);
+            data.put_u16(4);
+            data.put_u16(self.restart_interval);
+        }
+
+        let chroma_table_id = if table_count > 1 { 1 } else { 0 };
+        data.put_u8(0xFF);
+        data.put_u8(MARKER_SOF0);
+        data.put_u16(17);
+        data.put_u8(8);
+        data.put_u16(self.height);
+        data.put_u16(self.width);
+        data.put_u8(3);
+        data.put_u8(1);
+        data.put_u8(if self.jpeg_type == 0 { 0x21 } else { 0x22 });
+        data.put_u8(0);
+        data.put_u8(2);
+        data.put_u8(0x11);
+        data.put_u8(chroma_table_id);
+        data.put_u8(3);
+        data.put_u8(0x11);
+        data.put_u8(chroma_table_id);
+
+        Self::put_huffman_table(&mut data, 0x00, &LUMA_DC_CODE_LENS, &LUMA_DC_SYMBOLS);
+        Self::put_huffman_table(&mut data, 0x10, &LUMA_AC_CODE_LENS, &LUMA_AC_SYMBOLS);
+        Self::put_huffman_table(&mut data, 0x01, &CHROMA_DC_CODE_LENS, &CHROMA_DC_SYMBOLS);
+        Self::put_huffman_table(&mut data, 0x11, &CHROMA_AC_CODE_LENS, &CHROMA_AC_SYMBOLS);
+
+        data.put_u8(0xFF);
+        data.put_u8(MARKER_SOS);
+        data.put_u16(12);
+        data.put_u8(3);
+        data.put(&[0x01, 0x00, 0x02, 0x11, 0x03, 0x11][..]);
+        data.put(&[0x00, 0x3F, 0x00][..]);
+
+        data.put(&self.scan[..]);
+        data.put_u8(0xFF);
+        data.put_u8(MARKER_EOI);
+
+        data
+    }
+
+    fn put_huffman_table(data: &mut BytesMut, class_and_id: u8, code_lens: &[u8], symbols: &[u8]) {
+        data.put_u8(0xFF);
+        data.put_u8(MARKER_DHT);
+        data.put_u16((3 + code_lens.len() + symbols.len()) as u16);
+        data.put_u8(class_and_id);
+        data.put(code_lens);
+        data.put(symbols);
+    }
+}
+
+//RFC 2435 4.2, the tables for the Q values 1-99 are scaled from the example tables
+fn default_quant_tables(q: u8) -> BytesMut {
+    let q = q.clamp(1, 99) as u32;
+    let factor = if q < 50 { 5000 / q } else { 200 - q * 2 };
+
+    let mut tables = BytesMut::with_capacity(128);
+    for quantizer in [&LUMA_QUANTIZER, &CHROMA_QUANTIZER] {
+        for position in ZIGZAG {
+            let value = (quantizer[position] as u32 * factor + 50) / 100;
+            tables.put_u8(value.clamp(1, 255) as u8);
+        }
+    }
+    tables
+}
+
+pub struct RtpMjpegPacker {
+    header: RtpHeader,
+    mtu: usize,
+    on_packet_handler: Option<OnRtpPacketFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+    io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+}
+
+impl RtpMjpegPacker {
+    pub fn new(
+        payload_type: u8,
+        ssrc: u32,
+        init_seq: u16,
+        mtu: usize,
+        io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+    ) -> Self {
+        RtpMjpegPacker {
+            header: RtpHeader {
+                payload_type,
+                seq_number: init_seq,
+                ssrc,
+                version: 2,
+                ..Default::default()
+            },
+            mtu,
+            io,
+            on_packet_handler: None,
+            on_packet_for_rtcp_handler: None,
+        }
+    }
+}
+
+#[async_trait]
+impl TPacker for RtpMjpegPacker {
+    async fn pack(&mut self, data: &mut BytesMut, timestamp: u32) -> Result<(), PackerError> {
+        let Some(frame) = JpegFrame::parse(&data[..]) else {
+            log::warn!("mjpeg packer: not a baseline jpeg frame, drop it");
+            return Ok(());
+        };
+        self.header.timestamp = timestamp;
+
+        let mut jpeg_type = frame.jpeg_type;
+        if frame.restart_interval > 0 {
+            jpeg_type |= JPEG_TYPE_RESTART_MARKER;
+        }
+
+        let mut offset = 0;
+        while offset < frame.scan.len() {
+            let mut packet = RtpPacket::new(self.header.clone());
+            packet.payload.put_u8(0);
+            packet.payload.put_uint(offset as u64, 3);
+            packet.payload.put_u8(jpeg_type);
+            packet.payload.put_u8(JPEG_Q_INBAND_TABLES);
+            packet.payload.put_u8((frame.width / 8) as u8);
+            packet.payload.put_u8((frame.height / 8) as u8);
+
+            if frame.restart_interval > 0 {
+                packet.payload.put_u16(frame.restart_interval);
+                //F = 1, L = 1, Restart Count = 0x3FFF: the scan is not split at restart markers
+                packet.payload.put_u16(0xFFFF);
+            }
+
+            if offset == 0 {
+                packet.payload.put_u8(0);
+                packet.payload.put_u8(0);
+                packet.payload.put_u16(frame.quant_tables.len() as u16);
+                packet.payload.put(&frame.quant_tables[..]);
+            }
+
+            let payload_len = (frame.scan.len() - offset)
+                .min(self.mtu - define::RTP_FIXED_HEADER_LEN - packet.payload.len());
+            packet
+                .payload
+                .put(&frame.scan[offset..offset + payload_len]);
+            offset += payload_len;
+
+            if offset == frame.scan.len() {
+                packet.header.marker = 1;
+            }
+
+            if let Some(f) = &self.on_packet_for_rtcp_handler {
+                f(packet.clone()).await;
+            }
+
+            if let Some(f) = &self.on_packet_handler {
+                f(self.io.clone(), packet).await?;
+            }
+
+            self.header.seq_number = self.header.seq_number.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    fn on_packet_handler(&mut self, f: OnRtpPacketFn) {
+        self.on_packet_handler = Some(f);
+    }
+
+    fn seq_number(&self) -> u16 {
+        self.header.seq_number
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpMjpegPacker {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
+
+#[derive(Default)]
+pub struct RtpMjpegUnPacker {
+    frame: JpegFrame,
+    timestamp: u32,
+    //the in-band tables may be omitted in the following frames if not changed
+    inband_quant_tables: BytesMut,
+    on_frame_handler: Option<OnFrameFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+}
+
+impl RtpMjpegUnPacker {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl TUnPacker for RtpMjpegUnPacker {
+    async fn unpack(&mut self, reader: &mut BytesReader) -> Result<(), UnPackerError> {
+        let rtp_packet = RtpPacket::unmarshal(reader)?;
+
+        if let Some(f) = &self.on_packet_for_rtcp_handler {
+            f(rtp_packet.clone()).await;
+        }
+
+        let mut payload_reader = BytesReader::new(rtp_packet.payload);
+        if payload_reader.len() < JPEG_HEADER_LEN {
+            return Ok(());
+        }
+        let _type_specific = payload_reader.read_u8()?;
+        let fragment_offset = payload_reader.read_u24::<BigEndian>()? as usize;
+        let jpeg_type = payload_reader.read_u8()?;
+        let q = payload_reader.read_u8()?;
+        let width = payload_reader.read_u8()? as u16 * 8;
+        let height = payload_reader.read_u8()? as u16 * 8;
+
+        let mut restart_interval = 0;
+        if jpeg_type & JPEG_TYPE_RESTART_MARKER > 0 {
+            if payload_reader.len() < RESTART_MARKER_HEADER_LEN {
+                return Ok(());
+            }
+            restart_interval = payload_reader.read_u16::<BigEndian>()?;
+            let _restart_count = payload_reader.read_u16::<BigEndian>()?;
+        }
+
+        if fragment_offset == 0 {
+            let quant_tables = if q >= 128 {
+                let _mbz = payload_reader.read_u8()?;
+                let _precision = payload_reader.read_u8()?;
+                let length = payload_reader.read_u16::<BigEndian>()? as usize;
+                if length > 0 {
+                    self.inband_quant_tables = payload_reader.read_bytes(length)?;
+                }
+                self.inband_quant_tables.clone()
+            } else {
+                default_quant_tables(q)
+            };
+
+            self.timestamp = rtp_packet.header.timestamp;
+            self.frame = JpegFrame {
+                jpeg_type: jpeg_type & !JPEG_TYPE_RESTART_MARKER,
+                width,
+                height,
+                restart_interval,
+                quant_tables,
+                scan: BytesMut::new(),
+            };
+        } else if fragment_offset != self.frame.scan.len()
+            || rtp_packet.header.timestamp != self.timestamp
+        {
+            //a fragment is lost, drop the frame
+            self.frame.scan.clear();
+            return Ok(());
+        }
+
+        if fragment_offset == 0 || !self.frame.scan.is_empty() {
+            self.frame
+                .scan
+                .extend_from_slice(&payload_reader.extract_remaining_bytes()[..]);
+        }
+
+        if rtp_packet.header.marker == 1 && !self.frame.scan.is_empty() {
+            let data = self.frame.marshal();
+            self.frame.scan.clear();
+
+            if let Some(f) = &self.on_frame_handler {
+                f(FrameData::Video {
+                    timestamp: self.timestamp,
+                    data,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_frame_handler(&mut self, f: OnFrameFn) {
+        self.on_frame_handler = Some(f);
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpMjpegUnPacker {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_quant_tables, JpegFrame, CHROMA_AC_CODE_LENS, LUMA_AC_CODE_LENS};
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_jpeg_frame_marshal_parse() {
+        let code_count =
+            |code_lens: &[u8]| code_lens.iter().map(|len| *len as usize).sum::<usize>();
+        assert_eq!(code_count(&LUMA_AC_CODE_LENS), 162);
+        assert_eq!(code_count(&CHROMA_AC_CODE_LENS), 162);
+
+        //Q = 50 is the example tables of the JPEG specification
+        let quant_tables = default_quant_tables(50);
+        assert_eq!(quant_tables.len(), 128);
+        assert_eq!(&quant_tables[..4], &[16, 11, 12, 14]);
+        assert_eq!(&quant_tables[64..68], &[17, 18, 18, 24]);
+
+        let frame = JpegFrame {
+            jpeg_type: 1,
+            width: 640,
+            height: 480,
+            restart_interval: 40,
+            quant_tables,
+            scan: BytesMut::from(&[0x12, 0x34, 0xFF, 0x00, 0x56][..]),
+        };
+
+        let data = frame.marshal();
+        assert!(data.ends_with(&[0x56, 0xFF, 0xD9]));
+        assert_eq!(JpegFrame::parse(&data[..]), Some(frame));
+
+        //progressive frames cannot be carried
+        let mut progressive = data.clone();
+        let sof = progressive
+            .windows(2)
+            .position(|marker| marker == [0xFF, 0xC0])
+            .unwrap();
+        progressive[sof + 1] = 0xC2;
+        assert_eq!(JpegFrame::parse(&progressive[..]), None);
+    }
+}
@@ -0,0 +1,134 @@
+use super::errors::PackerError;
+use super::errors::UnPackerError;
+use super::utils::OnFrameFn;
+use super::utils::OnRtpPacketFn;
+use super::utils::OnRtpPacketFn2;
+use super::utils::TPacker;
+use super::utils::TRtpReceiverForRtcp;
+use super::utils::TUnPacker;
+use super::utils::Unmarshal;
+use super::RtpHeader;
+use super::RtpPacket;
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use bytesio::bytes_reader::BytesReader;
+use bytesio::bytesio::TNetIO;
+use std::sync::Arc;
+use streamhub::define::FrameData;
+use tokio::sync::Mutex;
+
+// Opus(RFC 7587), one rtp packet carries exactly one opus packet which is
+// never fragmented, and the rtp clock rate is always 48000.
+pub struct RtpOpusPacker {
+    header: RtpHeader,
+    on_packet_handler: Option<OnRtpPacketFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+    io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+}
+
+impl RtpOpusPacker {
+    pub fn new(
+        payload_type: u8,
+        ssrc: u32,
+        init_seq: u16,
+        io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+    ) -> Self {
+        RtpOpusPacker {
+            header: RtpHeader {
+                payload_type,
+                seq_number: init_seq,
+                ssrc,
+                version: 2,
+                ..Default::default()
+            },
+            io,
+            on_packet_handler: None,
+            on_packet_for_rtcp_handler: None,
+        }
+    }
+}
+
+#[async_trait]
+impl TPacker for RtpOpusPacker {
+    async fn pack(&mut self, data: &mut BytesMut, timestamp: u32) -> Result<(), PackerError> {
+        self.header.timestamp = timestamp;
+
+        let mut packet = RtpPacket::new(self.header.clone());
+        packet.payload.put(data);
+
+        if let Some(f) = &self.on_packet_for_rtcp_handler {
+            f(packet.clone()).await;
+        }
+
+        if let Some(f) = &self.on_packet_handler {
+            f(self.io.clone(), packet).await?;
+        }
+
+        self.header.seq_number = self.header.seq_number.wrapping_add(1);
+
+        Ok(())
+    }
+
+    fn on_packet_handler(&mut self, f: OnRtpPacketFn) {
+        self.on_packet_handler = Some(f);
+    }
+
+    fn seq_number(&self) -> u16 {
+        self.header.seq_number
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpOpusPacker {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
+
+#[derive(Default)]
+pub struct RtpOpusUnPacker {
+    on_frame_handler: Option<OnFrameFn>,
+    on_packet_for_rtcp_handler: Option<OnRtpPacketFn2>,
+}
+
+impl RtpOpusUnPacker {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl TUnPacker for RtpOpusUnPacker {
+    async fn unpack(&mut self, reader: &mut BytesReader) -> Result<(), UnPackerError> {
+        let rtp_packet = RtpPacket::unmarshal(reader)?;
+
+        if let Some(f) = &self.on_packet_for_rtcp_handler {
+            f(rtp_packet.clone()).await;
+        }
+
+        //an empty payload is used for the discontinuous transmission(DTX)
+        if rtp_packet.payload.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(f) = &self.on_frame_handler {
+            f(FrameData::Audio {
+                timestamp: rtp_packet.header.timestamp,
+                data: rtp_packet.payload,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn on_frame_handler(&mut self, f: OnFrameFn) {
+        self.on_frame_handler = Some(f);
+    }
+}
+
+impl TRtpReceiverForRtcp for RtpOpusUnPacker {
+    fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
+        self.on_packet_for_rtcp_handler = Some(f);
+    }
+}
@@ -50,6 +50,8 @@ pub trait TPacker: TRtpReceiverForRtcp + Send + Sync {
     async fn pack(&mut self, nalus: &mut BytesMut, timestamp: u32) -> Result<(), PackerError>;
     /*Call back function used for processing a rtp packet.*/
     fn on_packet_handler(&mut self, f: OnRtpPacketFn);
+    /*The sequence number of the next rtp packet.*/
+    fn seq_number(&self) -> u16;
 }
 
 #[async_trait]
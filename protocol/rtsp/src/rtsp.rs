@@ -1,8 +1,13 @@
 use streamhub::define::StreamHubEventSender;
 
+use super::remuxer::rtmp2rtsp::Rtmp2RtspRemuxers;
+use super::rtsp_auth::RtspAuthenticator;
+use super::rtsp_multicast::RtspMulticastManager;
 use super::session::server_session::RtspServerSession;
 use commonlib::auth::Auth;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::Error;
 use tokio::net::TcpListener;
 
@@ -10,14 +15,37 @@ pub struct RtspServer {
     address: String,
     event_producer: StreamHubEventSender,
     auth: Option<Auth>,
+    read_timeout: Option<Duration>,
+    authenticator: Option<Arc<RtspAuthenticator>>,
+    multicast: Option<Arc<std::sync::Mutex<RtspMulticastManager>>>,
+    session_timeout: Duration,
+    //the folder of the hls recordings played by rtsp
+    vod_path: Option<String>,
+    remuxers: Rtmp2RtspRemuxers,
 }
 
 impl RtspServer {
-    pub fn new(address: String, event_producer: StreamHubEventSender, auth: Option<Auth>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: String,
+        event_producer: StreamHubEventSender,
+        auth: Option<Auth>,
+        read_timeout: Option<Duration>,
+        authenticator: Option<RtspAuthenticator>,
+        multicast: Option<RtspMulticastManager>,
+        session_timeout: Duration,
+        vod_path: Option<String>,
+    ) -> Self {
         Self {
             address,
             event_producer,
             auth,
+            read_timeout,
+            authenticator: authenticator.map(Arc::new),
+            multicast: multicast.map(|manager| Arc::new(std::sync::Mutex::new(manager))),
+            session_timeout,
+            vod_path,
+            remuxers: Rtmp2RtspRemuxers::default(),
         }
     }
 
@@ -28,8 +56,17 @@ impl RtspServer {
         log::info!("Rtsp server listening on tcp://{}", socket_addr);
         loop {
             let (tcp_stream, _) = listener.accept().await?;
-            let mut session =
-                RtspServerSession::new(tcp_stream, self.event_producer.clone(), self.auth.clone());
+            let mut session = RtspServerSession::new(
+                tcp_stream,
+                self.event_producer.clone(),
+                self.auth.clone(),
+                self.read_timeout,
+                self.authenticator.clone(),
+                self.multicast.clone(),
+                self.session_timeout,
+                self.vod_path.clone(),
+                self.remuxers.clone(),
+            );
             tokio::spawn(async move {
                 if let Err(err) = session.run().await {
                     let session_id = if let Some(id) = session.session_id {
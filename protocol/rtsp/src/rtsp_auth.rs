@@ -0,0 +1,466 @@
+use base64::{engine::general_purpose, Engine as _};
+use indexmap::IndexMap;
+use rand::RngExt;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+//RFC 2617: HTTP Authentication: Basic and Digest Access Authentication
+//RFC 7616: HTTP Digest Access Authentication(SHA-256)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum AuthScheme {
+    Basic,
+    #[default]
+    Digest,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DigestAlgorithm {
+    #[default]
+    Md5,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "MD5" => Some(DigestAlgorithm::Md5),
+            "SHA-256" => Some(DigestAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 => format!("{:x}", md5::compute(data)),
+            DigestAlgorithm::Sha256 => hex::encode(Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtspCredential {
+    pub username: String,
+    pub password: String,
+}
+
+impl RtspCredential {
+    /* Split the user info from an address like user:password@host:port. */
+    pub fn split_address(address: &str) -> (Option<Self>, String) {
+        if let Some((user_info, host_with_port)) = address.rsplit_once('@') {
+            let (username, password) = user_info.split_once(':').unwrap_or((user_info, ""));
+            let credential = RtspCredential {
+                username: username.to_string(),
+                password: password.to_string(),
+            };
+            return (Some(credential), host_with_port.to_string());
+        }
+        (None, address.to_string())
+    }
+}
+
+pub fn get_header<'a>(headers: &'a IndexMap<String, String>, name: &str) -> Option<&'a String> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/* Parse the parameters of the WWW-Authenticate/Authorization header value:
+Digest realm="xiu", nonce="4ae1e5cb", algorithm=MD5 */
+fn parse_params(data: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = data.trim();
+
+    while !rest.is_empty() {
+        let Some(eq_index) = rest.find('=') else {
+            break;
+        };
+        let key = rest[..eq_index].trim().to_lowercase();
+        rest = rest[eq_index + 1..].trim_start();
+
+        let value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            value = quoted[..end].to_string();
+            rest = quoted.get(end + 1..).unwrap_or("");
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            value = rest[..end].trim().to_string();
+            rest = &rest[end..];
+        }
+        params.insert(key, value);
+
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    params
+}
+
+fn split_scheme(header: &str) -> (&str, &str) {
+    let header = header.trim();
+    header.split_once(' ').unwrap_or((header, ""))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn digest_response(
+    algorithm: DigestAlgorithm,
+    credential: &RtspCredential,
+    realm: &str,
+    nonce: &str,
+    method: &str,
+    uri: &str,
+    qop: Option<&str>,
+    nc: &str,
+    cnonce: &str,
+) -> String {
+    let ha1 = algorithm.hash(&format!(
+        "{}:{}:{}",
+        credential.username, realm, credential.password
+    ));
+    let ha2 = algorithm.hash(&format!("{}:{}", method, uri));
+
+    match qop {
+        Some(qop) => algorithm.hash(&format!(
+            "{}:{}:{}:{}:{}:{}",
+            ha1, nonce, nc, cnonce, qop, ha2
+        )),
+        None => algorithm.hash(&format!("{}:{}:{}", ha1, nonce, ha2)),
+    }
+}
+
+pub fn gen_nonce() -> String {
+    let bytes: [u8; 16] = rand::rng().random();
+    hex::encode(bytes)
+}
+
+/* The server side authentication with the configured users. */
+#[derive(Debug, Clone)]
+pub struct RtspAuthenticator {
+    scheme: AuthScheme,
+    realm: String,
+    //username to password
+    users: HashMap<String, String>,
+    pull_enabled: bool,
+    push_enabled: bool,
+}
+
+impl RtspAuthenticator {
+    pub fn new(
+        scheme: AuthScheme,
+        realm: String,
+        users: HashMap<String, String>,
+        pull_enabled: bool,
+        push_enabled: bool,
+    ) -> Self {
+        Self {
+            scheme,
+            realm,
+            users,
+            pull_enabled,
+            push_enabled,
+        }
+    }
+
+    pub fn is_required(&self, is_pull: bool) -> bool {
+        if is_pull {
+            self.pull_enabled
+        } else {
+            self.push_enabled
+        }
+    }
+
+    /* the WWW-Authenticate header value of the 401 response */
+    pub fn challenge(&self, nonce: &str) -> String {
+        match self.scheme {
+            AuthScheme::Basic => format!("Basic realm=\"{}\"", self.realm),
+            AuthScheme::Digest => format!(
+                "Digest realm=\"{}\", nonce=\"{}\", algorithm=MD5",
+                self.realm, nonce
+            ),
+        }
+    }
+
+    pub fn authenticate(&self, method: &str, authorization: Option<&String>, nonce: &str) -> bool {
+        let Some(authorization) = authorization else {
+            return false;
+        };
+        let (scheme, data) = split_scheme(authorization);
+
+        match self.scheme {
+            AuthScheme::Basic if scheme.eq_ignore_ascii_case("Basic") => {
+                let Ok(decoded) = general_purpose::STANDARD.decode(data.trim()) else {
+                    return false;
+                };
+                let Ok(user_pass) = String::from_utf8(decoded) else {
+                    return false;
+                };
+                match user_pass.split_once(':') {
+                    Some((username, password)) => {
+                        self.users.get(username) == Some(&password.to_string())
+                    }
+                    None => false,
+                }
+            }
+            AuthScheme::Digest if scheme.eq_ignore_ascii_case("Digest") => {
+                let params = parse_params(data);
+                self.check_digest(method, &params, nonce)
+            }
+            _ => false,
+        }
+    }
+
+    fn check_digest(&self, method: &str, params: &HashMap<String, String>, nonce: &str) -> bool {
+        let (Some(username), Some(uri), Some(response)) = (
+            params.get("username"),
+            params.get("uri"),
+            params.get("response"),
+        ) else {
+            return false;
+        };
+        let Some(password) = self.users.get(username) else {
+            log::warn!("rtsp digest auth: unknown user: {}", username);
+            return false;
+        };
+        if params.get("realm") != Some(&self.realm)
+            || params.get("nonce").map(|v| v.as_str()) != Some(nonce)
+        {
+            return false;
+        }
+        let algorithm = match params.get("algorithm") {
+            Some(name) => match DigestAlgorithm::from_name(name) {
+                Some(algorithm) => algorithm,
+                None => return false,
+            },
+            None => DigestAlgorithm::Md5,
+        };
+
+        let credential = RtspCredential {
+            username: username.clone(),
+            password: password.clone(),
+        };
+        let expected = digest_response(
+            algorithm,
+            &credential,
+            &self.realm,
+            nonce,
+            method,
+            uri,
+            params.get("qop").map(|v| v.as_str()),
+            params.get("nc").map(|v| v.as_str()).unwrap_or(""),
+            params.get("cnonce").map(|v| v.as_str()).unwrap_or(""),
+        );
+        expected.eq_ignore_ascii_case(response)
+    }
+}
+
+/* The client side authentication, it answers the challenge of the server. */
+#[derive(Debug, Clone)]
+pub struct RtspAuthorization {
+    credential: RtspCredential,
+    //the WWW-Authenticate header value
+    challenge: String,
+    nonce_count: u32,
+}
+
+impl RtspAuthorization {
+    pub fn new(credential: RtspCredential, challenge: String) -> Self {
+        Self {
+            credential,
+            challenge,
+            nonce_count: 0,
+        }
+    }
+
+    /* the Authorization header value of a request */
+    pub fn authorization(&mut self, method: &str, uri: &str) -> Option<String> {
+        let (scheme, data) = split_scheme(&self.challenge);
+
+        if scheme.eq_ignore_ascii_case("Basic") {
+            let user_pass = format!("{}:{}", self.credential.username, self.credential.password);
+            return Some(format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode(user_pass)
+            ));
+        }
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            log::error!("unsupported rtsp authentication scheme: {}", scheme);
+            return None;
+        }
+
+        let params = parse_params(data);
+        let realm = params.get("realm").cloned().unwrap_or_default();
+        let nonce = params.get("nonce").cloned().unwrap_or_default();
+        let algorithm = match params.get("algorithm") {
+            Some(name) => DigestAlgorithm::from_name(name)?,
+            None => DigestAlgorithm::Md5,
+        };
+        //only qop=auth is supported
+        let qop = params
+            .get("qop")
+            .filter(|qop| qop.split(',').any(|v| v.trim() == "auth"))
+            .map(|_| "auth");
+
+        let mut authorization = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\"",
+            self.credential.username, realm, nonce, uri
+        );
+
+        let response = if let Some(qop) = qop {
+            self.nonce_count += 1;
+            let nc = format!("{:08x}", self.nonce_count);
+            let cnonce = gen_nonce();
+            let response = digest_response(
+                algorithm,
+                &self.credential,
+                &realm,
+                &nonce,
+                method,
+                uri,
+                Some(qop),
+                &nc,
+                &cnonce,
+            );
+            authorization += &format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce);
+            response
+        } else {
+            digest_response(
+                algorithm,
+                &self.credential,
+                &realm,
+                &nonce,
+                method,
+                uri,
+                None,
+                "",
+                "",
+            )
+        };
+
+        authorization += &format!(
+            ", response=\"{}\", algorithm={}",
+            response,
+            algorithm.name()
+        );
+        if let Some(opaque) = params.get("opaque") {
+            authorization += &format!(", opaque=\"{}\"", opaque);
+        }
+
+        Some(authorization)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthScheme, RtspAuthenticator, RtspAuthorization, RtspCredential};
+    use std::collections::HashMap;
+
+    fn authenticator(
+        scheme: AuthScheme,
+        realm: &str,
+        username: &str,
+        password: &str,
+    ) -> RtspAuthenticator {
+        let mut users = HashMap::new();
+        users.insert(username.to_string(), password.to_string());
+        RtspAuthenticator::new(scheme, realm.to_string(), users, true, true)
+    }
+
+    #[test]
+    fn test_digest_rfc2617_example() {
+        let authenticator = authenticator(
+            AuthScheme::Digest,
+            "testrealm@host.com",
+            "Mufasa",
+            "Circle Of Life",
+        );
+        let authorization = String::from(
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+             qop=auth, nc=00000001, cnonce=\"0a4f113b\", \
+             response=\"6629fae49393a05397450978507c4ef1\", \
+             opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        );
+
+        assert!(authenticator.authenticate(
+            "GET",
+            Some(&authorization),
+            "dcd98b7102dd2f0e8b11d0f600bfb0c093"
+        ));
+        assert!(!authenticator.authenticate(
+            "POST",
+            Some(&authorization),
+            "dcd98b7102dd2f0e8b11d0f600bfb0c093"
+        ));
+        assert!(!authenticator.authenticate("GET", Some(&authorization), "another nonce"));
+    }
+
+    #[test]
+    fn test_challenge_and_response() {
+        let uri = "rtsp://127.0.0.1:554/live/test";
+        let credential = RtspCredential {
+            username: String::from("admin"),
+            password: String::from("12345"),
+        };
+
+        for scheme in [AuthScheme::Basic, AuthScheme::Digest] {
+            let authenticator = authenticator(scheme, "xiu", "admin", "12345");
+            let challenge = authenticator.challenge("4ae1e5cb");
+
+            let mut client = RtspAuthorization::new(credential.clone(), challenge.clone());
+            let authorization = client.authorization("DESCRIBE", uri).unwrap();
+            assert!(authenticator.authenticate("DESCRIBE", Some(&authorization), "4ae1e5cb"));
+
+            let wrong_credential = RtspCredential {
+                username: String::from("admin"),
+                password: String::from("54321"),
+            };
+            let mut client = RtspAuthorization::new(wrong_credential, challenge);
+            let authorization = client.authorization("DESCRIBE", uri).unwrap();
+            assert!(!authenticator.authenticate("DESCRIBE", Some(&authorization), "4ae1e5cb"));
+        }
+    }
+
+    #[test]
+    fn test_sha256_with_qop() {
+        let authenticator = authenticator(AuthScheme::Digest, "xiu", "admin", "12345");
+        let challenge = String::from(
+            "Digest realm=\"xiu\", nonce=\"4ae1e5cb\", qop=\"auth,auth-int\", algorithm=SHA-256",
+        );
+        let credential = RtspCredential {
+            username: String::from("admin"),
+            password: String::from("12345"),
+        };
+
+        let mut client = RtspAuthorization::new(credential, challenge);
+        let authorization = client
+            .authorization("PLAY", "rtsp://127.0.0.1/live")
+            .unwrap();
+        assert!(authorization.contains("algorithm=SHA-256"));
+        assert!(authorization.contains("nc=00000001"));
+        assert!(authenticator.authenticate("PLAY", Some(&authorization), "4ae1e5cb"));
+    }
+
+    #[test]
+    fn test_split_address() {
+        let (credential, address) = RtspCredential::split_address("admin:12345@192.168.0.64:554");
+        assert_eq!(address, "192.168.0.64:554");
+        assert_eq!(
+            credential,
+            Some(RtspCredential {
+                username: String::from("admin"),
+                password: String::from("12345"),
+            })
+        );
+
+        let (credential, address) = RtspCredential::split_address("192.168.0.64:554");
+        assert_eq!(address, "192.168.0.64:554");
+        assert_eq!(credential, None);
+    }
+}
@@ -9,12 +9,18 @@ use crate::rtp::utils::OnRtpPacketFn2;
 use crate::rtp::RtpPacket;
 
 use super::rtp::rtp_aac::RtpAacPacker;
+use super::rtp::rtp_g711::RtpG711Packer;
 use super::rtp::rtp_h264::RtpH264Packer;
 use super::rtp::rtp_h265::RtpH265Packer;
+use super::rtp::rtp_mjpeg::RtpMjpegPacker;
+use super::rtp::rtp_opus::RtpOpusPacker;
 
 use super::rtp::rtp_aac::RtpAacUnPacker;
+use super::rtp::rtp_g711::RtpG711UnPacker;
 use super::rtp::rtp_h264::RtpH264UnPacker;
 use super::rtp::rtp_h265::RtpH265UnPacker;
+use super::rtp::rtp_mjpeg::RtpMjpegUnPacker;
+use super::rtp::rtp_opus::RtpOpusUnPacker;
 
 use super::rtp::rtcp::rtcp_context::RtcpContext;
 use super::rtp::rtcp::rtcp_sr::RtcpSenderReport;
@@ -68,6 +74,10 @@ impl RtpChannel {
         rtp_channel
     }
 
+    pub fn codec_info(&self) -> &RtspCodecInfo {
+        &self.codec_info
+    }
+
     //Receive av frame from network -> pack AV frame to RTP packet -> send to stream hub
     pub async fn on_packet(&mut self, reader: &mut BytesReader) -> Result<(), UnPackerError> {
         if let Some(unpacker) = &mut self.rtp_unpacker {
@@ -103,6 +113,11 @@ impl RtpChannel {
         }
     }
 
+    //The sequence number of the next rtp packet sent out
+    pub fn seq_number(&self) -> Option<u16> {
+        self.rtp_packer.as_ref().map(|packer| packer.seq_number())
+    }
+
     //Set handler for processing received AV rtp packet from network
     pub fn on_packet_for_rtcp_handler(&mut self, f: OnRtpPacketFn2) {
         if let Some(packer) = &mut self.rtp_packer {
@@ -123,7 +138,15 @@ impl TRtpFunc for RtpChannel {
             RtspCodecId::AAC => {
                 self.rtp_unpacker = Some(Box::new(RtpAacUnPacker::new()));
             }
-            RtspCodecId::G711A => {}
+            RtspCodecId::G711A | RtspCodecId::G711U => {
+                self.rtp_unpacker = Some(Box::new(RtpG711UnPacker::new()));
+            }
+            RtspCodecId::OPUS => {
+                self.rtp_unpacker = Some(Box::new(RtpOpusUnPacker::new()));
+            }
+            RtspCodecId::MJPEG => {
+                self.rtp_unpacker = Some(Box::new(RtpMjpegUnPacker::new()));
+            }
         }
     }
     fn create_packer(&mut self, io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>) {
@@ -154,7 +177,33 @@ impl TRtpFunc for RtpChannel {
                     io,
                 )));
             }
-            RtspCodecId::G711A => {}
+            RtspCodecId::G711A | RtspCodecId::G711U => {
+                self.rtp_packer = Some(Box::new(RtpG711Packer::new(
+                    self.codec_info.payload_type,
+                    self.ssrc,
+                    self.init_sequence,
+                    1400,
+                    self.codec_info.channel_count,
+                    io,
+                )));
+            }
+            RtspCodecId::OPUS => {
+                self.rtp_packer = Some(Box::new(RtpOpusPacker::new(
+                    self.codec_info.payload_type,
+                    self.ssrc,
+                    self.init_sequence,
+                    io,
+                )));
+            }
+            RtspCodecId::MJPEG => {
+                self.rtp_packer = Some(Box::new(RtpMjpegPacker::new(
+                    self.codec_info.payload_type,
+                    self.ssrc,
+                    self.init_sequence,
+                    1400,
+                    io,
+                )));
+            }
         }
     }
 }
@@ -0,0 +1,308 @@
+use crate::rtp::utils::Marshal as RtpMarshal;
+use crate::rtp::RtpPacket;
+use crate::rtsp_channel::TRtpFunc;
+use crate::rtsp_codec::RtspCodecInfo;
+use crate::rtsp_track::{RtspTrack, TrackType};
+use crate::session::errors::{SessionError, SessionErrorValue};
+
+use bytesio::bytes_writer::AsyncBytesWriter;
+use bytesio::bytesio::{TNetIO, UdpIO};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use streamhub::{
+    define::{
+        FrameData, NotifyInfo, StreamHubEvent, StreamHubEventSender, SubDataType, SubscribeType,
+        SubscriberInfo,
+    },
+    stream::StreamIdentifier,
+    utils::{RandomDigitCount, Uuid},
+};
+use tokio::sync::{oneshot, Mutex};
+
+pub const DEFAULT_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 0, 0, 1);
+pub const DEFAULT_MULTICAST_PORT: u16 = 5000;
+pub const DEFAULT_MULTICAST_TTL: u32 = 16;
+
+// The group of a played stream, the video RTP/RTCP use port and port + 1,
+// the audio RTP/RTCP use port + 2 and port + 3.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MulticastGroup {
+    pub address: Ipv4Addr,
+    pub port: u16,
+    pub ttl: u32,
+}
+
+impl MulticastGroup {
+    pub fn track_ports(&self, track_type: &TrackType) -> [u16; 2] {
+        let port = match track_type {
+            TrackType::Video => self.port,
+            _ => self.port + 2,
+        };
+        [port, port + 1]
+    }
+}
+
+struct MulticastStream {
+    index: u32,
+    viewers: usize,
+    //dropped to stop the sender when the last viewer leaves
+    exit_sender: Option<oneshot::Sender<()>>,
+}
+
+// All the viewers of a stream share one multicast group, so the stream is
+// packed and sent only once no matter how many viewers there are on the LAN.
+pub struct RtspMulticastManager {
+    base_address: Ipv4Addr,
+    port: u16,
+    ttl: u32,
+    streams: HashMap<StreamIdentifier, MulticastStream>,
+}
+
+impl RtspMulticastManager {
+    pub fn new(base_address: Ipv4Addr, port: u16, ttl: u32) -> Self {
+        Self {
+            base_address,
+            port,
+            ttl,
+            streams: HashMap::new(),
+        }
+    }
+
+    fn group(&self, index: u32) -> MulticastGroup {
+        MulticastGroup {
+            address: Ipv4Addr::from(u32::from(self.base_address).wrapping_add(index)),
+            port: self.port,
+            ttl: self.ttl,
+        }
+    }
+
+    //a viewer joins the group of the stream, the group is allocated for the first viewer
+    pub fn join(&mut self, identifier: &StreamIdentifier) -> MulticastGroup {
+        if let Some(stream) = self.streams.get_mut(identifier) {
+            stream.viewers += 1;
+            let index = stream.index;
+            return self.group(index);
+        }
+
+        let mut index = 0;
+        while self.streams.values().any(|stream| stream.index == index) {
+            index += 1;
+        }
+
+        self.streams.insert(
+            identifier.clone(),
+            MulticastStream {
+                index,
+                viewers: 1,
+                exit_sender: None,
+            },
+        );
+        self.group(index)
+    }
+
+    //the group is released and the sender stops when the last viewer leaves
+    pub fn leave(&mut self, identifier: &StreamIdentifier) {
+        if let Some(stream) = self.streams.get_mut(identifier) {
+            stream.viewers -= 1;
+            if stream.viewers == 0 {
+                self.streams.remove(identifier);
+            }
+        }
+    }
+
+    //returns the exit receiver if no sender is running for the stream
+    fn start_sender(&mut self, identifier: &StreamIdentifier) -> Option<oneshot::Receiver<()>> {
+        let stream = self.streams.get_mut(identifier)?;
+        if let Some(exit_sender) = &stream.exit_sender {
+            if !exit_sender.is_closed() {
+                return None;
+            }
+        }
+
+        let (exit_sender, exit_receiver) = oneshot::channel();
+        stream.exit_sender = Some(exit_sender);
+        Some(exit_receiver)
+    }
+}
+
+// Start sending the stream to its group if it is the first viewer who plays.
+pub fn start_multicast_sender(
+    manager: &std::sync::Mutex<RtspMulticastManager>,
+    identifier: StreamIdentifier,
+    codec_infos: Vec<(TrackType, RtspCodecInfo)>,
+    event_producer: StreamHubEventSender,
+) {
+    let (group, exit_receiver) = {
+        let mut manager = manager.lock().unwrap();
+        let Some(exit_receiver) = manager.start_sender(&identifier) else {
+            return;
+        };
+        let index = manager.streams[&identifier].index;
+        (manager.group(index), exit_receiver)
+    };
+
+    tokio::spawn(async move {
+        log::info!("multicast sender start: {} to {:?}", identifier, group);
+        if let Err(err) = multicast_send_loop(
+            identifier.clone(),
+            codec_infos,
+            group,
+            event_producer,
+            exit_receiver,
+        )
+        .await
+        {
+            log::error!("multicast sender error: {} {}", identifier, err);
+        }
+        log::info!("multicast sender exit: {}", identifier);
+    });
+}
+
+async fn multicast_send_loop(
+    identifier: StreamIdentifier,
+    codec_infos: Vec<(TrackType, RtspCodecInfo)>,
+    group: MulticastGroup,
+    event_producer: StreamHubEventSender,
+    mut exit_receiver: oneshot::Receiver<()>,
+) -> Result<(), SessionError> {
+    let mut tracks: HashMap<TrackType, RtspTrack> = HashMap::new();
+
+    for (track_type, codec_info) in codec_infos {
+        let ports = group.track_ports(&track_type);
+        let Some(rtp_io) = UdpIO::new(group.address.to_string(), ports[0], 0).await else {
+            log::error!(
+                "cannot create the multicast socket: {}:{}",
+                group.address,
+                ports[0]
+            );
+            continue;
+        };
+        rtp_io.set_multicast_ttl(group.ttl);
+
+        let track = RtspTrack::new(track_type.clone(), codec_info, String::default());
+        let box_udp_io: Box<dyn TNetIO + Send + Sync> = Box::new(rtp_io);
+        let mut rtp_channel = track.rtp_channel.lock().await;
+        rtp_channel.create_packer(Arc::new(Mutex::new(box_udp_io)));
+        rtp_channel.on_packet_handler(Box::new(
+            move |io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>, packet: RtpPacket| {
+                Box::pin(async move {
+                    let mut bytes_writer = AsyncBytesWriter::new(io);
+
+                    let msg = packet.marshal()?;
+                    bytes_writer.write(&msg)?;
+                    bytes_writer.flush().await?;
+                    Ok(())
+                })
+            },
+        ));
+        drop(rtp_channel);
+        tracks.insert(track_type, track);
+    }
+
+    let subscriber_info = SubscriberInfo {
+        id: Uuid::new(RandomDigitCount::Zero),
+        sub_type: SubscribeType::RtspPull,
+        sub_data_type: SubDataType::Frame,
+        notify_info: NotifyInfo {
+            request_url: String::from(""),
+            remote_addr: group.address.to_string(),
+        },
+    };
+
+    let (event_result_sender, event_result_receiver) = oneshot::channel();
+    let subscribe_event = StreamHubEvent::Subscribe {
+        identifier: identifier.clone(),
+        info: subscriber_info.clone(),
+        result_sender: event_result_sender,
+    };
+    if event_producer.send(subscribe_event).is_err() {
+        return Err(SessionError {
+            value: SessionErrorValue::StreamHubEventSendErr,
+        });
+    }
+    let mut receiver = event_result_receiver.await??.0.frame_receiver.unwrap();
+
+    let result = loop {
+        let frame_data = tokio::select! {
+            frame_data = receiver.recv() => frame_data,
+            _ = &mut exit_receiver => break Ok(()),
+        };
+
+        let (track_type, mut data, timestamp) = match frame_data {
+            Some(FrameData::Audio { timestamp, data }) => (TrackType::Audio, data, timestamp),
+            Some(FrameData::Video { timestamp, data }) => (TrackType::Video, data, timestamp),
+            Some(_) => continue,
+            None => {
+                break Err(SessionError {
+                    value: SessionErrorValue::CannotReceiveFrameData,
+                })
+            }
+        };
+
+        if let Some(track) = tracks.get_mut(&track_type) {
+            if let Err(err) = track
+                .rtp_channel
+                .lock()
+                .await
+                .on_frame(&mut data, timestamp)
+                .await
+            {
+                log::error!("multicast sender pack error: {}", err);
+            }
+        }
+    };
+
+    let unsubscribe_event = StreamHubEvent::UnSubscribe {
+        identifier,
+        info: subscriber_info,
+    };
+    if event_producer.send(unsubscribe_event).is_err() {
+        log::error!("multicast sender: send unsubscribe event error");
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MulticastGroup, RtspMulticastManager};
+    use crate::rtsp_track::TrackType;
+    use std::net::Ipv4Addr;
+    use streamhub::stream::StreamIdentifier;
+
+    #[test]
+    fn test_multicast_group_allocation() {
+        let mut manager = RtspMulticastManager::new(Ipv4Addr::new(239, 0, 0, 1), 5000, 16);
+        let first = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/first"),
+        };
+        let second = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/second"),
+        };
+
+        let group = manager.join(&first);
+        assert_eq!(
+            group,
+            MulticastGroup {
+                address: Ipv4Addr::new(239, 0, 0, 1),
+                port: 5000,
+                ttl: 16,
+            }
+        );
+        assert_eq!(group.track_ports(&TrackType::Audio), [5002, 5003]);
+        //the viewers of the same stream share the group
+        assert_eq!(manager.join(&first), group);
+        assert_eq!(manager.join(&second).address, Ipv4Addr::new(239, 0, 0, 2));
+
+        //only one sender runs for a stream
+        let exit_receiver = manager.start_sender(&first);
+        assert!(exit_receiver.is_some());
+        assert!(manager.start_sender(&first).is_none());
+
+        manager.leave(&first);
+        manager.leave(&first);
+        //the released group is reused
+        assert_eq!(manager.join(&first).address, Ipv4Addr::new(239, 0, 0, 1));
+    }
+}
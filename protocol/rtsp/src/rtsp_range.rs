@@ -1,7 +1,6 @@
 use crate::global_trait::Marshal;
 
 use super::global_trait::Unmarshal;
-use super::rtsp_utils;
 
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum RtspRangeType {
@@ -12,9 +11,10 @@ pub enum RtspRangeType {
 
 #[derive(Debug, Clone, Default)]
 pub struct RtspRange {
-    range_type: RtspRangeType,
-    begin: i64,
-    end: Option<i64>,
+    pub range_type: RtspRangeType,
+    //milliseconds for npt and seconds for clock
+    pub begin: i64,
+    pub end: Option<i64>,
 }
 
 impl Unmarshal for RtspRange {
@@ -44,7 +44,7 @@ impl Unmarshal for RtspRange {
                 };
 
                 rtsp_range.begin = get_clock_time(ranges[0]);
-                if ranges.len() > 1 {
+                if ranges.len() > 1 && !ranges[1].is_empty() {
                     rtsp_range.end = Some(get_clock_time(ranges[1]));
                 }
             }
@@ -52,18 +52,13 @@ impl Unmarshal for RtspRange {
                 rtsp_range.range_type = RtspRangeType::NPT;
                 let ranges: Vec<&str> = kv[1].split('-').collect();
 
+                //npt-hhmmss(h:m:s.fraction) or npt-sec(seconds.fraction)
                 let get_npt_time = |range_time: &str| -> i64 {
-                    if let (Some(hour), Some(minute), Some(second), mill) =
-                        rtsp_utils::scanf!(range_time, |c| c == ':' || c == '.', i64, i64, i64, i64)
-                    {
-                        let mut result = (hour * 3600 + minute * 60 + second) * 1000;
-                        if let Some(m) = mill {
-                            result += m;
-                        }
-                        result
-                    } else {
-                        0
+                    let mut result = 0.0;
+                    for part in range_time.split(':') {
+                        result = result * 60.0 + part.trim().parse::<f64>().unwrap_or(0.0);
                     }
+                    (result * 1000.0) as i64
                 };
 
                 match ranges[0] {
@@ -90,7 +85,22 @@ impl Unmarshal for RtspRange {
 
 impl Marshal for RtspRange {
     fn marshal(&self) -> String {
-        String::default()
+        match self.range_type {
+            RtspRangeType::NPT => {
+                let npt_time = |time: i64| format!("{:.3}", time as f64 / 1000.0);
+                let end = self.end.map(npt_time).unwrap_or_default();
+                format!("npt={}-{}", npt_time(self.begin), end)
+            }
+            RtspRangeType::CLOCK => {
+                let clock_time = |time: i64| {
+                    chrono::DateTime::from_timestamp(time, 0)
+                        .map(|datetime| datetime.format("%Y%m%dT%H%M%SZ").to_string())
+                        .unwrap_or_default()
+                };
+                let end = self.end.map(clock_time).unwrap_or_default();
+                format!("clock={}-{}", clock_time(self.begin), end)
+            }
+        }
     }
 }
 
@@ -98,7 +108,7 @@ impl Marshal for RtspRange {
 mod tests {
 
     use super::RtspRange;
-    use crate::global_trait::Unmarshal;
+    use crate::global_trait::{Marshal, Unmarshal};
 
     #[test]
     fn test_parse_transport() {
@@ -116,4 +126,19 @@ mod tests {
         let parser2 = RtspRange::unmarshal("npt=0-").unwrap();
         println!(" parser2: {:?}, {}", parser2, parser2.end.is_none());
     }
+
+    #[test]
+    fn test_npt_range() {
+        let range = RtspRange::unmarshal("npt=120.5-").unwrap();
+        assert_eq!(range.begin, 120500);
+        assert_eq!(range.end, None);
+        assert_eq!(range.marshal(), "npt=120.500-");
+
+        let range = RtspRange::unmarshal("npt=0:02:00-1:00:00.25").unwrap();
+        assert_eq!(range.begin, 120000);
+        assert_eq!(range.end, Some(3600250));
+
+        let range = RtspRange::unmarshal("clock=20220520T064812Z-").unwrap();
+        assert_eq!(range.marshal(), "clock=20220520T064812Z-");
+    }
 }
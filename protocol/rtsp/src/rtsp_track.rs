@@ -8,6 +8,7 @@ use bytes::BytesMut;
 use bytesio::bytes_reader::BytesReader;
 use bytesio::bytesio::TNetIO;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
@@ -38,6 +39,8 @@ pub struct RtspTrack {
 
     pub rtp_channel: Arc<Mutex<RtpChannel>>,
     pub rtcp_channel: Arc<Mutex<RtcpChannel>>,
+    //the time of the latest rtp or rtcp packet received over UDP
+    pub udp_activity: Arc<std::sync::Mutex<Instant>>,
 }
 
 impl RtspTrack {
@@ -51,11 +54,13 @@ impl RtspTrack {
             uri: String::default(),
             rtp_channel: Arc::new(Mutex::new(rtp_channel)),
             rtcp_channel: Arc::new(Mutex::default()),
+            udp_activity: Arc::new(std::sync::Mutex::new(Instant::now())),
         }
     }
 
     pub async fn rtp_receive_loop(&mut self, mut rtp_io: Box<dyn TNetIO + Send + Sync>) {
         let rtp_channel_out = self.rtp_channel.clone();
+        let udp_activity = self.udp_activity.clone();
         tokio::spawn(async move {
             let mut reader = BytesReader::new(BytesMut::new());
             let mut rtp_channel_in = rtp_channel_out.lock().await;
@@ -63,6 +68,7 @@ impl RtspTrack {
                 match rtp_io.read().await {
                     Ok(data) => {
                         //log::info!("read rtp data");
+                        *udp_activity.lock().unwrap() = Instant::now();
                         reader.extend_from_slice(&data[..]);
                         if let Err(err) = rtp_channel_in.on_packet(&mut reader).await {
                             log::error!("rtp_receive_loop on_packet error: {}", err);
@@ -79,6 +85,7 @@ impl RtspTrack {
     //send and receive rtcp data in a UDP channel
     pub async fn rtcp_receive_loop(&mut self, rtcp_io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>) {
         let rtcp_channel_out = self.rtcp_channel.clone();
+        let udp_activity = self.udp_activity.clone();
 
         tokio::spawn(async move {
             let mut reader = BytesReader::new(BytesMut::new());
@@ -92,6 +99,7 @@ impl RtspTrack {
                         break;
                     }
                 };
+                *udp_activity.lock().unwrap() = Instant::now();
                 reader.extend_from_slice(&data[..]);
                 rtcp_channel_in.on_rtcp(&mut reader, rtcp_io.clone()).await;
             }
@@ -25,6 +25,10 @@ pub struct RtspTransport {
     pub client_port: Option<[u16; 2]>,
     pub server_port: Option<[u16; 2]>,
     pub ssrc: Option<u32>,
+    //the multicast group address, port and time-to-live
+    pub destination: Option<String>,
+    pub port: Option<[u16; 2]>,
+    pub ttl: Option<u32>,
 }
 
 impl Unmarshal for RtspTransport {
@@ -94,6 +98,28 @@ impl Unmarshal for RtspTransport {
                         rtsp_transport.ssrc = Some(ssrc);
                     }
                 }
+                "destination" => {
+                    rtsp_transport.destination = Some(kv[1].to_string());
+                }
+                "port" => {
+                    let ports = scanf!(kv[1], '-', u16, u16);
+
+                    let mut multicast_ports: [u16; 2] = [0, 0];
+                    if let Some(port) = ports.0 {
+                        multicast_ports[0] = port;
+                        multicast_ports[1] = port.saturating_add(1);
+                    }
+                    if let Some(port) = ports.1 {
+                        multicast_ports[1] = port;
+                    }
+
+                    rtsp_transport.port = Some(multicast_ports);
+                }
+                "ttl" => {
+                    if let Ok(ttl) = kv[1].parse::<u32>() {
+                        rtsp_transport.ttl = Some(ttl);
+                    }
+                }
 
                 _ => {}
             }
@@ -139,13 +165,33 @@ impl Marshal for RtspTransport {
             String::from("")
         };
 
+        let destination = if let Some(destination) = &self.destination {
+            format!("destination={destination};")
+        } else {
+            String::from("")
+        };
+
+        let port = if let Some(ports) = self.port {
+            format!("port={}-{};", ports[0], ports[1])
+        } else {
+            String::from("")
+        };
+
+        let ttl = if let Some(ttl) = self.ttl {
+            format!("ttl={ttl};")
+        } else {
+            String::from("")
+        };
+
         let mode = if let Some(mode) = &self.transport_mod {
             format!("mode={mode}")
         } else {
             String::from("")
         };
 
-        format!("{protocol_type};{cast_type};{client_port}{server_port}{interleaved}{ssrc}{mode}")
+        format!(
+            "{protocol_type};{cast_type};{destination}{port}{ttl}{client_port}{server_port}{interleaved}{ssrc}{mode}"
+        )
     }
 }
 
@@ -155,7 +201,7 @@ mod tests {
     use crate::global_trait::Marshal;
     use crate::global_trait::Unmarshal;
 
-    use super::RtspTransport;
+    use super::{CastType, RtspTransport};
 
     #[test]
     fn test_parse_transport() {
@@ -175,4 +221,20 @@ mod tests {
 
         println!("marshal reslut: {}", parser.marshal());
     }
+
+    #[test]
+    fn test_multicast_transport() {
+        let transport =
+            RtspTransport::unmarshal("RTP/AVP;multicast;destination=239.0.0.1;port=5000-5001;ttl=16")
+                .unwrap();
+
+        assert_eq!(transport.cast_type, CastType::Multicast);
+        assert_eq!(transport.destination.as_deref(), Some("239.0.0.1"));
+        assert_eq!(transport.port, Some([5000, 5001]));
+        assert_eq!(transport.ttl, Some(16));
+        assert_eq!(
+            transport.marshal(),
+            "RTP/AVP/UDP;multicast;destination=239.0.0.1;port=5000-5001;ttl=16;"
+        );
+    }
 }
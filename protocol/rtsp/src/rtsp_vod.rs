@@ -0,0 +1,409 @@
+use base64::{engine::general_purpose, Engine as _};
+use bytes::BytesMut;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use streamhub::define::{FrameData, FrameDataReceiver};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use xmpegts::define::epsi_stream_type;
+use xmpegts::demuxer::{TsDemuxer, TsFrame};
+
+//rtsp://host:port/vod/{app}/{stream} plays the stream recorded by hls
+pub const VOD_PATH_PREFIX: &str = "vod/";
+//the range of the Scale header, the other scales are clamped into it
+pub const MIN_SCALE: f64 = 0.1;
+pub const MAX_SCALE: f64 = 16.0;
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+#[derive(Debug, Clone)]
+pub struct RecordSegment {
+    //the npt of the segment start in milliseconds
+    pub start: i64,
+    pub duration: i64,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct H264Config {
+    pub sps: BytesMut,
+    pub pps: BytesMut,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AacConfig {
+    pub object_type: u8,
+    pub sampling_frequency_index: u8,
+    pub channel_configuration: u8,
+}
+
+impl AacConfig {
+    pub fn sample_rate(&self) -> u32 {
+        AAC_SAMPLE_RATES
+            .get(self.sampling_frequency_index as usize)
+            .copied()
+            .unwrap_or(44100)
+    }
+
+    //the AudioSpecificConfig in the fmtp
+    fn asc(&self) -> [u8; 2] {
+        [
+            (self.object_type << 3) | (self.sampling_frequency_index >> 1),
+            ((self.sampling_frequency_index & 0x01) << 7) | (self.channel_configuration << 3),
+        ]
+    }
+}
+
+// The recorded hls segments of a stream, the vod m3u8 is written when the
+// recorded stream is unpublished.
+#[derive(Debug)]
+pub struct Recording {
+    pub segments: Vec<RecordSegment>,
+    //milliseconds
+    pub duration: i64,
+    h264_config: Option<H264Config>,
+    aac_config: Option<AacConfig>,
+}
+
+impl Recording {
+    pub async fn open(root: &str, stream_path: &str) -> Option<Self> {
+        let name = stream_path.strip_prefix(VOD_PATH_PREFIX)?.trim_matches('/');
+        if name.is_empty() || name.split('/').any(|part| part == "..") {
+            return None;
+        }
+
+        let stream_name = name.rsplit('/').next()?;
+        let folder = format!("{root}{name}");
+        let m3u8_path = format!("{folder}/vod_{stream_name}.m3u8");
+        let m3u8 = match tokio::fs::read_to_string(&m3u8_path).await {
+            Ok(m3u8) => m3u8,
+            Err(err) => {
+                log::warn!("open recording {} error: {}", m3u8_path, err);
+                return None;
+            }
+        };
+
+        let segments = Self::parse_m3u8(&folder, &m3u8);
+        let last_segment = segments.last()?;
+        let mut recording = Recording {
+            duration: last_segment.start + last_segment.duration,
+            segments,
+            h264_config: None,
+            aac_config: None,
+        };
+        recording.probe().await;
+
+        Some(recording)
+    }
+
+    fn parse_m3u8(folder: &str, m3u8: &str) -> Vec<RecordSegment> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut duration = None;
+
+        for line in m3u8.lines().map(str::trim) {
+            if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+                let seconds = extinf.split(',').next().unwrap_or_default();
+                duration = Some((seconds.parse::<f64>().unwrap_or(0.0) * 1000.0) as i64);
+            } else if !line.is_empty() && !line.starts_with('#') {
+                if let Some(duration) = duration.take() {
+                    segments.push(RecordSegment {
+                        start,
+                        duration,
+                        path: format!("{folder}/{line}"),
+                    });
+                    start += duration;
+                }
+            }
+        }
+        segments
+    }
+
+    //get the codec configs from the first segment
+    async fn probe(&mut self) {
+        let Ok(data) = tokio::fs::read(&self.segments[0].path).await else {
+            return;
+        };
+        let mut demuxer = TsDemuxer::new();
+        let mut frames = demuxer.demux(&data[..]);
+        frames.extend(demuxer.flush());
+
+        for frame in frames {
+            match frame.stream_type {
+                epsi_stream_type::PSI_STREAM_H264 if self.h264_config.is_none() => {
+                    let mut config = H264Config::default();
+                    for nalu in split_annexb(&frame.data) {
+                        match nalu.first().map(|header| header & 0x1F) {
+                            Some(7) => config.sps = BytesMut::from(nalu),
+                            Some(8) => config.pps = BytesMut::from(nalu),
+                            _ => {}
+                        }
+                    }
+                    if !config.sps.is_empty() && !config.pps.is_empty() {
+                        self.h264_config = Some(config);
+                    }
+                }
+                epsi_stream_type::PSI_STREAM_AAC if self.aac_config.is_none() => {
+                    if let Some((config, _, _)) = parse_adts(&frame.data) {
+                        self.aac_config = Some(config);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn sdp(&self) -> String {
+        let sdp = format!(
+            "v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=xiu vod\r\nc=IN IP4 0.0.0.0\r\nt=0 0\r\na=range:npt=0-{:.3}\r\n",
+            self.duration as f64 / 1000.0
+        );
+
+        sdp + &media_sdp(&self.h264_config, &self.aac_config)
+    }
+
+    //the index of the segment which contains the position
+    pub fn seek(&self, position: i64) -> usize {
+        self.segments
+            .iter()
+            .rposition(|segment| segment.start <= position)
+            .unwrap_or(0)
+    }
+
+    //the rtp timestamp of the npt in the clock rate
+    pub fn rtp_time(npt: i64, clock_rate: u32) -> u32 {
+        (npt * clock_rate as i64 / 1000) as u32
+    }
+
+    /* Read the segments from the index and send the frames paced by their
+    timestamps, the audio is dropped if it is played with a scale. The reading
+    stops if the receiver is dropped. */
+    pub fn play(
+        self: &Arc<Self>,
+        index: usize,
+        scale: f64,
+        position: Arc<AtomicI64>,
+    ) -> FrameDataReceiver {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let recording = self.clone();
+
+        tokio::spawn(async move {
+            let begin_instant = Instant::now();
+            let begin_npt = recording.segments[index].start;
+            let audio_sample_rate = recording
+                .aac_config
+                .as_ref()
+                .map(AacConfig::sample_rate)
+                .unwrap_or(44100);
+
+            for segment in &recording.segments[index..] {
+                let data = match tokio::fs::read(&segment.path).await {
+                    Ok(data) => data,
+                    Err(err) => {
+                        log::error!("read record segment {} error: {}", segment.path, err);
+                        continue;
+                    }
+                };
+
+                let mut demuxer = TsDemuxer::new();
+                let mut frames = demuxer.demux(&data[..]);
+                frames.extend(demuxer.flush());
+                frames.sort_by_key(|frame| frame.dts);
+
+                let Some(base_dts) = frames.first().map(|frame| frame.dts) else {
+                    continue;
+                };
+
+                for frame in frames {
+                    let npt = segment.start + (frame.dts - base_dts) / 90;
+                    let due = Duration::from_millis(((npt - begin_npt) as f64 / scale) as u64);
+                    let Some(deadline) = begin_instant.checked_add(due) else {
+                        log::error!("vod play error: the frame time {} is out of range", npt);
+                        return;
+                    };
+                    tokio::time::sleep_until(deadline).await;
+
+                    for frame_data in
+                        Self::frame_data(&frame, segment.start, base_dts, audio_sample_rate)
+                    {
+                        if matches!(frame_data, FrameData::Audio { .. }) && scale != 1.0 {
+                            continue;
+                        }
+                        if sender.send(frame_data).is_err() {
+                            return;
+                        }
+                    }
+                    position.store(npt, Ordering::Relaxed);
+                }
+            }
+            log::info!("vod play finished");
+        });
+
+        receiver
+    }
+
+    fn frame_data(
+        frame: &TsFrame,
+        segment_start: i64,
+        base_dts: i64,
+        sample_rate: u32,
+    ) -> Vec<FrameData> {
+        //the rtp timestamps follow the npt, so they are continuous across the segments
+        let timestamp_90k = segment_start * 90 + (frame.pts - base_dts);
+
+        match frame.stream_type {
+            epsi_stream_type::PSI_STREAM_H264 => vec![FrameData::Video {
+                timestamp: timestamp_90k as u32,
+                data: frame.data.clone(),
+            }],
+            epsi_stream_type::PSI_STREAM_AAC => {
+                let mut frames = Vec::new();
+                let mut data = &frame.data[..];
+                let mut timestamp = (timestamp_90k * sample_rate as i64 / 90000) as u32;
+                while let Some((_, raw_data, frame_length)) = parse_adts(data) {
+                    frames.push(FrameData::Audio {
+                        timestamp,
+                        data: BytesMut::from(raw_data),
+                    });
+                    timestamp = timestamp.wrapping_add(1024);
+                    data = &data[frame_length..];
+                }
+                frames
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+//the media descriptions of the h264 video(streamid=0) and the aac audio(streamid=1)
+pub(crate) fn media_sdp(
+    h264_config: &Option<H264Config>,
+    aac_config: &Option<AacConfig>,
+) -> String {
+    let mut sdp = String::new();
+
+    if let Some(h264_config) = h264_config {
+        let profile_level_id = hex::encode_upper(h264_config.sps.get(1..4).unwrap_or_default());
+        sdp += &format!(
+            "m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n\
+            a=fmtp:96 packetization-mode=1;sprop-parameter-sets={},{};profile-level-id={}\r\n\
+            a=control:streamid=0\r\n",
+            general_purpose::STANDARD.encode(&h264_config.sps),
+            general_purpose::STANDARD.encode(&h264_config.pps),
+            profile_level_id
+        );
+    }
+
+    if let Some(aac_config) = aac_config {
+        sdp += &format!(
+            "m=audio 0 RTP/AVP 97\r\na=rtpmap:97 MPEG4-GENERIC/{}/{}\r\n\
+            a=fmtp:97 profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config={}\r\n\
+            a=control:streamid=1\r\n",
+            aac_config.sample_rate(),
+            aac_config.channel_configuration,
+            hex::encode_upper(aac_config.asc())
+        );
+    }
+
+    sdp
+}
+
+//split the annexb data into nalus without the start codes
+fn split_annexb(data: &[u8]) -> Vec<&[u8]> {
+    let mut nalus = Vec::new();
+    let mut nalu_begin = None;
+    let mut index = 0;
+
+    while index + 3 <= data.len() {
+        if data[index..index + 3] == [0x00, 0x00, 0x01] {
+            if let Some(begin) = nalu_begin {
+                let mut end = index;
+                while end > begin && data[end - 1] == 0 {
+                    end -= 1;
+                }
+                nalus.push(&data[begin..end]);
+            }
+            index += 3;
+            nalu_begin = Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    if let Some(begin) = nalu_begin {
+        nalus.push(&data[begin..]);
+    }
+    nalus
+}
+
+//return the config, the raw aac data and the whole length of the first adts frame
+fn parse_adts(data: &[u8]) -> Option<(AacConfig, &[u8], usize)> {
+    if data.len() < 7 || data[0] != 0xFF || data[1] & 0xF0 != 0xF0 {
+        return None;
+    }
+
+    let protection_absent = data[1] & 0x01;
+    let config = AacConfig {
+        object_type: (data[2] >> 6) + 1,
+        sampling_frequency_index: (data[2] >> 2) & 0x0F,
+        channel_configuration: ((data[2] & 0x01) << 2) | (data[3] >> 6),
+    };
+    let frame_length =
+        (((data[3] & 0x03) as usize) << 11) | ((data[4] as usize) << 3) | ((data[5] >> 5) as usize);
+    let header_length = if protection_absent == 1 { 7 } else { 9 };
+
+    if frame_length < header_length || frame_length > data.len() {
+        return None;
+    }
+    Some((config, &data[header_length..frame_length], frame_length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_adts, split_annexb, Recording};
+
+    #[test]
+    fn test_parse_vod_m3u8() {
+        let m3u8 = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:5\n#EXT-X-MEDIA-SEQUENCE:0\n\
+            #EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:5.000\n0.ts\n#EXTINF:4.500\n1.ts\n\
+            #EXT-X-DISCONTINUITY\n#EXTINF:5.000\n2.ts\n#EXT-X-ENDLIST\n";
+
+        let segments = Recording::parse_m3u8("./live/test", m3u8);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[1].start, 5000);
+        assert_eq!(segments[2].start, 9500);
+        assert_eq!(segments[2].path, "./live/test/2.ts");
+
+        let recording = Recording {
+            duration: 14500,
+            segments,
+            h264_config: None,
+            aac_config: None,
+        };
+        assert_eq!(recording.seek(0), 0);
+        assert_eq!(recording.seek(9499), 1);
+        assert_eq!(recording.seek(120000), 2);
+    }
+
+    #[test]
+    fn test_parse_adts_and_annexb() {
+        let adts = [0xFF, 0xF1, 0x50, 0x80, 0x01, 0x1F, 0xFC, 0x21, 0x10];
+        let (config, raw_data, frame_length) = parse_adts(&adts).unwrap();
+        assert_eq!(config.sample_rate(), 44100);
+        assert_eq!(config.channel_configuration, 2);
+        assert_eq!(config.asc(), [0x12, 0x10]);
+        assert_eq!(raw_data, &[0x21]);
+        assert_eq!(frame_length, 8);
+
+        let annexb = [
+            0x00, 0x00, 0x00, 0x01, 0x67, 0x42, 0x00, 0x00, 0x01, 0x68, 0xCE,
+        ];
+        assert_eq!(
+            split_annexb(&annexb),
+            vec![&[0x67, 0x42][..], &[0x68, 0xCE][..]]
+        );
+    }
+}
@@ -29,11 +29,21 @@ pub struct Mpeg4Fmtp {
     index_length: u16,
     index_delta_length: u16,
 }
+#[derive(Debug, Clone, Default)]
+pub struct OpusFmtp {
+    pub payload_type: u16,
+    pub minptime: Option<u16>,
+    pub useinbandfec: Option<u8>,
+    pub stereo: Option<u8>,
+    pub sprop_stereo: Option<u8>,
+    pub maxplaybackrate: Option<u32>,
+}
 #[derive(Debug, Clone)]
 pub enum Fmtp {
     H264(H264Fmtp),
     H265(H265Fmtp),
     Mpeg4(Mpeg4Fmtp),
+    Opus(OpusFmtp),
 }
 
 impl Fmtp {
@@ -54,6 +64,11 @@ impl Fmtp {
                     return Some(Fmtp::Mpeg4(mpeg4_fmtp));
                 }
             }
+            "opus" => {
+                if let Some(opus_fmtp) = OpusFmtp::unmarshal(raw_data) {
+                    return Some(Fmtp::Opus(opus_fmtp));
+                }
+            }
             _ => {}
         }
         None
@@ -64,6 +79,7 @@ impl Fmtp {
             Fmtp::H264(h264fmtp) => h264fmtp.marshal(),
             Fmtp::H265(h265fmtp) => h265fmtp.marshal(),
             Fmtp::Mpeg4(mpeg4fmtp) => mpeg4fmtp.marshal(),
+            Fmtp::Opus(opusfmtp) => opusfmtp.marshal(),
         }
     }
 }
@@ -261,6 +277,77 @@ impl Marshal for Mpeg4Fmtp {
     }
 }
 
+impl Unmarshal for OpusFmtp {
+    //a=fmtp:111 minptime=10;useinbandfec=1;sprop-stereo=1
+    fn unmarshal(raw_data: &str) -> Option<Self> {
+        let mut opus_fmtp = OpusFmtp::default();
+        let eles: Vec<&str> = raw_data.splitn(2, ' ').collect();
+        if eles.len() < 2 {
+            log::warn!("OpusFmtpSdp parse err: {}", raw_data);
+            return None;
+        }
+
+        if let Ok(payload_type) = eles[0].parse::<u16>() {
+            opus_fmtp.payload_type = payload_type;
+        }
+
+        let parameters: Vec<&str> = eles[1].split(';').collect();
+        for parameter in parameters {
+            let kv: Vec<&str> = parameter.trim().splitn(2, '=').collect();
+            if kv.len() < 2 {
+                log::warn!("OpusFmtpSdp parse key=value err: {}", parameter);
+                continue;
+            }
+            match kv[0].to_lowercase().as_str() {
+                "minptime" => {
+                    opus_fmtp.minptime = kv[1].parse().ok();
+                }
+                "useinbandfec" => {
+                    opus_fmtp.useinbandfec = kv[1].parse().ok();
+                }
+                "stereo" => {
+                    opus_fmtp.stereo = kv[1].parse().ok();
+                }
+                "sprop-stereo" => {
+                    opus_fmtp.sprop_stereo = kv[1].parse().ok();
+                }
+                "maxplaybackrate" => {
+                    opus_fmtp.maxplaybackrate = kv[1].parse().ok();
+                }
+                _ => {
+                    log::info!("not parsed: {}", kv[0])
+                }
+            }
+        }
+
+        Some(opus_fmtp)
+    }
+}
+
+impl Marshal for OpusFmtp {
+    //a=fmtp:111 minptime=10;useinbandfec=1;sprop-stereo=1
+    fn marshal(&self) -> String {
+        let mut parameters = Vec::new();
+        if let Some(minptime) = self.minptime {
+            parameters.push(format!("minptime={minptime}"));
+        }
+        if let Some(useinbandfec) = self.useinbandfec {
+            parameters.push(format!("useinbandfec={useinbandfec}"));
+        }
+        if let Some(stereo) = self.stereo {
+            parameters.push(format!("stereo={stereo}"));
+        }
+        if let Some(sprop_stereo) = self.sprop_stereo {
+            parameters.push(format!("sprop-stereo={sprop_stereo}"));
+        }
+        if let Some(maxplaybackrate) = self.maxplaybackrate {
+            parameters.push(format!("maxplaybackrate={maxplaybackrate}"));
+        }
+
+        format!("{} {}\r\n", self.payload_type, parameters.join(";"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -269,6 +356,7 @@ mod tests {
     use super::H264Fmtp;
     use super::H265Fmtp;
     use super::Mpeg4Fmtp;
+    use super::OpusFmtp;
     use crate::global_trait::Marshal;
     use crate::global_trait::Unmarshal;
     use crate::rtsp_utils;
@@ -331,6 +419,22 @@ mod tests {
         print!("mpeg4 parser: {}", parser.marshal());
     }
 
+    #[test]
+    fn test_parse_opusfmtpsdp() {
+        let parser = OpusFmtp::unmarshal("111 minptime=10;useinbandfec=1; sprop-stereo=1").unwrap();
+
+        assert_eq!(parser.payload_type, 111);
+        assert_eq!(parser.minptime, Some(10));
+        assert_eq!(parser.useinbandfec, Some(1));
+        assert_eq!(parser.sprop_stereo, Some(1));
+        assert_eq!(parser.stereo, None);
+
+        assert_eq!(
+            parser.marshal(),
+            "111 minptime=10;useinbandfec=1;sprop-stereo=1\r\n"
+        );
+    }
+
     #[test]
     fn test_string() {
         let s = String::from("119056E500");
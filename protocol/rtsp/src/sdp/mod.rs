@@ -125,6 +125,14 @@ impl Unmarshal for SdpMediaInfo {
             cur_param_idx += 1;
         }
 
+        if let Some(rtpmap) = sdp_media
+            .fmts
+            .first()
+            .and_then(|fmt| RtpMap::from_static_payload_type(*fmt))
+        {
+            sdp_media.rtpmap = rtpmap;
+        }
+
         Some(sdp_media)
     }
 }
@@ -262,7 +270,13 @@ impl Unmarshal for Sdp {
                             match attr_name {
                                 "rtpmap" => {
                                     if let Some(rtpmap) = RtpMap::unmarshal(attr_value) {
-                                        cur_media.rtpmap = rtpmap;
+                                        //only the first format of the media is used, e.g. the
+                                        //telephone-event following the PCMU/PCMA is ignored
+                                        if cur_media.fmts.first().is_none_or(|fmt| {
+                                            *fmt as u16 == rtpmap.payload_type
+                                        }) {
+                                            cur_media.rtpmap = rtpmap;
+                                        }
                                         continue;
                                     }
                                 }
@@ -376,6 +390,29 @@ mod tests {
             println!("sdp str : {}", sdp.marshal());
         }
     }
+    #[test]
+    fn test_parse_static_payload_types() {
+        let data = "v=0\r\n\
+        o=- 0 0 IN IP4 192.168.1.64\r\n\
+        s=Media Presentation\r\n\
+        c=IN IP4 0.0.0.0\r\n\
+        t=0 0\r\n\
+        m=video 0 RTP/AVP 26\r\n\
+        a=control:trackID=1\r\n\
+        m=audio 0 RTP/AVP 8 101\r\n\
+        a=rtpmap:101 telephone-event/8000\r\n\
+        a=control:trackID=2\r\n";
+
+        let sdp = Sdp::unmarshal(data).unwrap();
+        assert_eq!(sdp.medias[0].rtpmap.encoding_name, "JPEG");
+        assert_eq!(sdp.medias[0].rtpmap.clock_rate, 90000);
+        assert_eq!(sdp.medias[1].rtpmap.encoding_name, "PCMA");
+        assert_eq!(sdp.medias[1].rtpmap.payload_type, 8);
+
+        //the rtpmap of the static payload type is written out
+        assert!(sdp.marshal().contains("a=rtpmap:8 PCMA/8000/1\r\n"));
+    }
+
     #[test]
     fn test_str() {
         //let fmts: Vec<u8> = vec![5];
@@ -8,6 +8,25 @@ pub struct RtpMap {
     pub encoding_param: String,
 }
 
+impl RtpMap {
+    //the static payload types(RFC 3551) can be used without an a=rtpmap line
+    pub fn from_static_payload_type(payload_type: u8) -> Option<Self> {
+        let (encoding_name, clock_rate, encoding_param) = match payload_type {
+            0 => ("PCMU", 8000, "1"),
+            8 => ("PCMA", 8000, "1"),
+            26 => ("JPEG", 90000, ""),
+            _ => return None,
+        };
+
+        Some(RtpMap {
+            payload_type: payload_type as u16,
+            encoding_name: encoding_name.to_string(),
+            clock_rate,
+            encoding_param: encoding_param.to_string(),
+        })
+    }
+}
+
 impl Unmarshal for RtpMap {
     // a=rtpmap:96 H264/90000\r\n\
     // a=rtpmap:97 MPEG4-GENERIC/48000/2\r\n\
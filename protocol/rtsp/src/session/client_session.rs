@@ -17,6 +17,7 @@ use streamhub::define::SubscriberInfo;
 
 use crate::rtp::RtpPacket;
 
+use crate::rtsp_auth::{self, RtspAuthorization, RtspCredential};
 use crate::rtsp_codec::RtspCodecInfo;
 use crate::rtsp_track::RtspTrack;
 use crate::rtsp_track::TrackType;
@@ -49,11 +50,11 @@ use super::define::USER_AGENT;
 
 use streamhub::{
     define::{
-        FrameData, NotifyInfo, PublishType, PublisherInfo, StreamHubEvent, StreamHubEventSender,
-        SubscribeType,
+        FrameData, FrameDataSender, NotifyInfo, PublishType, PublisherInfo, StreamHubEvent,
+        StreamHubEventSender, SubscribeType,
     },
     stream::StreamIdentifier,
-    utils::{RandomDigitCount, Uuid},
+    utils::{RandomDigitCount, TimestampRebaser, Uuid},
 };
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
@@ -80,6 +81,43 @@ pub struct RtspClientSession {
 
     event_producer: StreamHubEventSender,
     pub is_running: Arc<AtomicBool>,
+    /* the publication handed over from the previous relay session,
+    or the one published by this session */
+    relay_publication: Option<RtspRelayPublication>,
+    /* the credential parsed from the address(user:password@host:port) */
+    credential: Option<RtspCredential>,
+    /* answer the challenge of the server after it responds 401 */
+    authorization: Option<RtspAuthorization>,
+    last_request: Option<RtspRequest>,
+}
+
+/* The stream hub publication of a relay(pull) session. It outlives the session,
+so the next session can resume it after reconnecting to the remote server and
+the subscribers of the stream are not disconnected. */
+pub struct RtspRelayPublication {
+    stream_path: String,
+    info: PublisherInfo,
+    frame_sender: FrameDataSender,
+    stream_handler: Arc<RtspStreamHandler>,
+    /* the rtp timestamps are rebased for each track separately */
+    timestamp_rebasers: HashMap<TrackType, Arc<std::sync::Mutex<TimestampRebaser>>>,
+}
+
+impl RtspRelayPublication {
+    /* Give up the publication when the remote server cannot be reconnected. */
+    pub fn unpublish(self, event_producer: &StreamHubEventSender) -> Result<(), SessionError> {
+        log::info!("unpublish rtsp relay publication: {}", self.stream_path);
+        let event = StreamHubEvent::UnPublish {
+            identifier: StreamIdentifier::Rtsp {
+                stream_path: self.stream_path,
+            },
+            info: self.info,
+        };
+
+        event_producer.send(event).map_err(|_| SessionError {
+            value: SessionErrorValue::StreamHubEventSendErr,
+        })
+    }
 }
 
 impl RtspClientSession {
@@ -90,6 +128,7 @@ impl RtspClientSession {
         event_producer: StreamHubEventSender,
         client_type: ClientSessionType,
     ) -> Result<Self, SessionError> {
+        let (credential, address) = RtspCredential::split_address(&address);
         let stream = TcpStream::connect(address.clone()).await?;
 
         let net_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
@@ -112,9 +151,23 @@ impl RtspClientSession {
 
             stream_handler: Arc::new(RtspStreamHandler::new()),
             is_running: Arc::new(AtomicBool::new(true)),
+            relay_publication: None,
+            credential,
+            authorization: None,
+            last_request: None,
         })
     }
 
+    /* Resume the publication of a previous pull session after reconnecting. */
+    pub fn resume_relay_publication(&mut self, publication: RtspRelayPublication) {
+        self.relay_publication = Some(publication);
+    }
+
+    /* Take the publication out so that it is kept alive after this session ends. */
+    pub fn take_relay_publication(&mut self) -> Option<RtspRelayPublication> {
+        self.relay_publication.take()
+    }
+
     //publish stream: OPTIONS->ANNOUNCE->SETUP->RECORD->TEARDOWN
     //subscribe stream: OPTIONS->DESCRIBE->SETUP->PLAY->TEARDOWN
     pub async fn run(&mut self) -> Result<(), SessionError> {
@@ -327,6 +380,37 @@ impl RtspClientSession {
         self.exit()
     }
 
+    async fn publish_to_stream_hub(&mut self) -> Result<RtspRelayPublication, SessionError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+        let identifier = StreamIdentifier::Rtsp {
+            stream_path: self.stream_name.clone(),
+        };
+        let info = self.get_publisher_info();
+
+        let publish_event = StreamHubEvent::Publish {
+            identifier,
+            result_sender: event_result_sender,
+            info: info.clone(),
+            stream_handler: self.stream_handler.clone(),
+        };
+
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        let frame_sender = event_result_receiver.await??.0.unwrap();
+
+        Ok(RtspRelayPublication {
+            stream_path: self.stream_name.clone(),
+            info,
+            frame_sender,
+            stream_handler: self.stream_handler.clone(),
+            timestamp_rebasers: HashMap::new(),
+        })
+    }
+
     fn gen_request(&mut self, method_name: &str, uri_path: String) -> RtspRequest {
         let uri = Uri::unmarshal(&uri_path).unwrap();
 
@@ -351,9 +435,44 @@ impl RtspClientSession {
                 .insert("Session".to_string(), session_id.to_string());
         }
 
+        self.authorize(&mut request);
         request
     }
 
+    fn authorize(&mut self, request: &mut RtspRequest) {
+        if let Some(authorization) = &mut self.authorization {
+            let uri = request.uri.marshal();
+            if let Some(value) = authorization.authorization(&request.method, &uri) {
+                request
+                    .headers
+                    .insert("Authorization".to_string(), value);
+            }
+        }
+    }
+
+    /* Generate the last request again with the Authorization header if the server
+    challenges it, it is only retried once. */
+    fn authorize_last_request(&mut self, rtsp_response: &RtspResponse) -> Option<RtspRequest> {
+        if self.authorization.is_some() {
+            log::error!("rtsp client: the credential is rejected by the server");
+            return None;
+        }
+        let Some(credential) = self.credential.clone() else {
+            log::error!("rtsp client: the server requires authentication but no credential is set");
+            return None;
+        };
+        let challenge = rtsp_auth::get_header(&rtsp_response.headers, "WWW-Authenticate")?;
+        self.authorization = Some(RtspAuthorization::new(credential, challenge.clone()));
+
+        let mut request = self.last_request.clone()?;
+        request
+            .headers
+            .insert("CSeq".to_string(), self.cseq.to_string());
+        self.cseq += 1;
+        self.authorize(&mut request);
+        Some(request)
+    }
+
     fn get_subscriber_info(&mut self) -> SubscriberInfo {
         let id = if let Some(session_id) = &self.session_id {
             *session_id
@@ -401,15 +520,19 @@ impl RtspClientSession {
             let media_name = &media.media_type;
             match media_name.as_str() {
                 "audio" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("unsupported audio codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
                         sample_rate: media.rtpmap.clock_rate,
-                        channel_count: media.rtpmap.encoding_param.parse().unwrap(),
+                        //the channel count is optional and defaults to 1
+                        channel_count: media.rtpmap.encoding_param.parse().unwrap_or(1),
                     };
 
                     log::info!("audio codec info: {:?}", codec_info);
@@ -418,10 +541,13 @@ impl RtspClientSession {
                     self.tracks.insert(TrackType::Audio, track);
                 }
                 "video" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("unsupported video codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
@@ -441,11 +567,31 @@ impl RtspClientSession {
     async fn send_resquest(&mut self, request: &RtspRequest) -> Result<(), SessionError> {
         self.writer.write(request.marshal().as_bytes())?;
         self.writer.flush().await?;
+        self.last_request = Some(request.clone());
 
         Ok(())
     }
 
     async fn receive_response(&mut self, method_name: &str) -> Result<(), SessionError> {
+        let Some(mut rtsp_response) = self.read_response().await? else {
+            return Ok(());
+        };
+
+        if rtsp_response.status_code == http::StatusCode::UNAUTHORIZED {
+            if let Some(request) = self.authorize_last_request(&rtsp_response) {
+                log::info!("rtsp client: send {} with authorization", method_name);
+                self.send_resquest(&request).await?;
+                rtsp_response = match self.read_response().await? {
+                    Some(response) => response,
+                    None => return Ok(()),
+                };
+            }
+        }
+
+        self.handle_response(method_name, rtsp_response).await
+    }
+
+    async fn read_response(&mut self) -> Result<Option<RtspResponse>, SessionError> {
         let data = self.io.lock().await.read().await?;
         self.reader.extend_from_slice(&data[..]);
 
@@ -469,7 +615,7 @@ impl RtspClientSession {
                                     "corrupted rtsp message={}",
                                     std::str::from_utf8(&data)?
                                 );
-                                return Ok(());
+                                return Ok(None);
                             }
                             retry_count += 1;
                             let data_recv = self.io.lock().await.read().await?;
@@ -483,10 +629,18 @@ impl RtspClientSession {
                 break;
             } else {
                 log::error!("corrupted rtsp message={}", std::str::from_utf8(&data)?);
-                return Ok(());
+                return Ok(None);
             }
         }
 
+        Ok(Some(rtsp_response))
+    }
+
+    async fn handle_response(
+        &mut self,
+        method_name: &str,
+        rtsp_response: RtspResponse,
+    ) -> Result<(), SessionError> {
         if rtsp_response.status_code != http::StatusCode::OK {
             log::error!("rtsp response error: {}", rtsp_response.marshal());
             return Err(SessionError {
@@ -505,36 +659,50 @@ impl RtspClientSession {
                 if let Some(request_body) = &rtsp_response.body {
                     if let Some(sdp) = Sdp::unmarshal(request_body) {
                         self.sdp = sdp.clone();
-                        self.stream_handler.set_sdp(sdp).await;
 
                         self.new_tracks()?;
 
-                        let (event_result_sender, event_result_receiver) = oneshot::channel();
-                        let identifier = StreamIdentifier::Rtsp {
-                            stream_path: self.stream_name.clone(),
-                        };
-
-                        let publish_event = StreamHubEvent::Publish {
-                            identifier,
-                            result_sender: event_result_sender,
-                            info: self.get_publisher_info(),
-                            stream_handler: self.stream_handler.clone(),
+                        let mut publication = if let Some(publication) =
+                            self.relay_publication.take()
+                        {
+                            log::info!("resume rtsp relay publication: {}", self.stream_name);
+                            self.stream_handler = publication.stream_handler.clone();
+                            for rebaser in publication.timestamp_rebasers.values() {
+                                rebaser.lock().unwrap().discontinue();
+                            }
+                            if let Err(err) =
+                                publication.frame_sender.send(FrameData::Discontinuity {})
+                            {
+                                log::error!("send discontinuity error: {}", err);
+                            }
+                            publication
+                        } else {
+                            self.publish_to_stream_hub().await?
                         };
+                        self.stream_handler.set_sdp(sdp).await;
 
-                        if self.event_producer.send(publish_event).is_err() {
-                            return Err(SessionError {
-                                value: SessionErrorValue::StreamHubEventSendErr,
-                            });
-                        }
-
-                        let sender = event_result_receiver.await??.0.unwrap();
-
-                        for track in self.tracks.values_mut() {
-                            let sender_out = sender.clone();
+                        for (track_type, track) in self.tracks.iter_mut() {
+                            let sender_out = publication.frame_sender.clone();
+                            let rebaser = publication
+                                .timestamp_rebasers
+                                .entry(track_type.clone())
+                                .or_default()
+                                .clone();
 
                             let mut rtp_channel_guard = track.rtp_channel.lock().await;
                             rtp_channel_guard.on_frame_handler(Box::new(
                                 move |msg: FrameData| -> Result<(), UnPackerError> {
+                                    let msg = match msg {
+                                        FrameData::Audio { timestamp, data } => FrameData::Audio {
+                                            timestamp: rebaser.lock().unwrap().rebase(timestamp),
+                                            data,
+                                        },
+                                        FrameData::Video { timestamp, data } => FrameData::Video {
+                                            timestamp: rebaser.lock().unwrap().rebase(timestamp),
+                                            data,
+                                        },
+                                        _ => msg,
+                                    };
                                     if let Err(err) = sender_out.send(msg) {
                                         log::error!("send frame error: {}", err);
                                     }
@@ -552,6 +720,7 @@ impl RtspClientSession {
                                 },
                             ));
                         }
+                        self.relay_publication = Some(publication);
                     }
                 }
             }
@@ -48,4 +48,7 @@ impl fmt::Display for ServerSessionType {
     }
 }
 
+//seconds, a session without any RTSP or RTCP activity in time is torn down
+pub const DEFAULT_SESSION_TIMEOUT: u64 = 60;
+
 pub const USER_AGENT: &str = "xiu 0.12.8";
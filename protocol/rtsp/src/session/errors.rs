@@ -8,7 +8,7 @@ use {
     std::fmt,
     std::io::Error,
     std::str::Utf8Error,
-    streamhub::errors::StreamHubError,
+    streamhub::errors::{StreamHubError, StreamHubErrorValue},
     tokio::sync::oneshot::error::RecvError,
 };
 
@@ -47,6 +47,20 @@ pub enum SessionErrorValue {
     IOError(#[cause] Error),
     #[fail(display = "RTSP response status error")]
     RtspResponseStatusError,
+    #[fail(display = "session timeout")]
+    SessionTimeout,
+}
+
+impl SessionError {
+    /* The stream has been published by another session, e.g. the pull of another subscriber. */
+    pub fn is_stream_exists(&self) -> bool {
+        matches!(
+            self.value,
+            SessionErrorValue::ChannelError(StreamHubError {
+                value: StreamHubErrorValue::Exists
+            })
+        )
+    }
 }
 
 impl From<BytesIOError> for SessionError {
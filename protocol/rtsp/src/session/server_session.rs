@@ -16,7 +16,7 @@ use crate::rtsp_range::RtspRange;
 
 use crate::sdp::fmtp::Fmtp;
 
-use crate::rtsp_codec::RtspCodecInfo;
+use crate::rtsp_codec::{RtspCodecId, RtspCodecInfo};
 use crate::rtsp_track::RtspTrack;
 use crate::rtsp_track::TrackType;
 use crate::rtsp_transport::ProtocolType;
@@ -33,9 +33,12 @@ use bytesio::bytes_writer::BytesWriter;
 use bytesio::bytesio::UdpIO;
 use http::StatusCode;
 use streamhub::define::DataSender;
+use streamhub::define::FrameDataReceiver;
+use streamhub::define::AudioCodecType;
 use streamhub::define::MediaInfo;
 use streamhub::define::VideoCodecType;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 
 use crate::rtp::errors::UnPackerError;
 use crate::sdp::Sdp;
@@ -64,6 +67,17 @@ use streamhub::{
 };
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::rtsp_auth::{self, RtspAuthenticator};
+use crate::rtsp_multicast::{self, MulticastGroup, RtspMulticastManager};
+use crate::rtsp_transport::CastType;
+use crate::rtsp_range::RtspRangeType;
+use crate::rtsp_vod::{Recording, MAX_SCALE, MIN_SCALE, VOD_PATH_PREFIX};
+use crate::remuxer::rtmp2rtsp::Rtmp2RtspRemuxers;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+//the time to wait for the sequence headers and the first frame of the rtmp stream
+const RTMP_REMUX_READY_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct RtspServerSession {
     io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
@@ -83,6 +97,32 @@ pub struct RtspServerSession {
     pub stream_identifier: Option<StreamIdentifier>,
     pub is_normal_exit: bool,
     remote_addr: SocketAddr,
+    //the session is closed if nothing is received in time
+    read_timeout: Option<Duration>,
+    //the Basic/Digest authentication
+    authenticator: Option<Arc<RtspAuthenticator>>,
+    auth_nonce: String,
+    is_authenticated: bool,
+    //the shared multicast groups and the group this player session joined
+    multicast: Option<Arc<std::sync::Mutex<RtspMulticastManager>>>,
+    multicast_group: Option<MulticastGroup>,
+    //the frames of a playing session, none if it is paused
+    frame_receiver: Option<FrameDataReceiver>,
+    is_paused: bool,
+    session_timeout: Duration,
+    last_activity: Instant,
+    //the folder of the recordings and the recording played by the session
+    vod_path: Option<String>,
+    recording: Option<Arc<Recording>>,
+    vod_position: Arc<AtomicI64>,
+    //the remuxers of the rtmp streams played by rtsp, shared by the sessions
+    remuxers: Rtmp2RtspRemuxers,
+}
+
+enum ReadEvent {
+    Data(BytesMut),
+    Frame(Option<FrameData>),
+    Deadline,
 }
 
 pub struct InterleavedBinaryData {
@@ -115,10 +155,17 @@ impl InterleavedBinaryData {
 }
 
 impl RtspServerSession {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         stream: TcpStream,
         event_producer: StreamHubEventSender,
         auth: Option<Auth>,
+        read_timeout: Option<Duration>,
+        authenticator: Option<Arc<RtspAuthenticator>>,
+        multicast: Option<Arc<std::sync::Mutex<RtspMulticastManager>>>,
+        session_timeout: Duration,
+        vod_path: Option<String>,
+        remuxers: Rtmp2RtspRemuxers,
     ) -> Self {
         // let remote_addr = if let Ok(addr) = stream.peer_addr() {
         //     log::info!("server session: {}", addr.to_string());
@@ -145,13 +192,98 @@ impl RtspServerSession {
             stream_identifier: None,
             is_normal_exit: false,
             remote_addr,
+            read_timeout,
+            authenticator,
+            auth_nonce: rtsp_auth::gen_nonce(),
+            is_authenticated: false,
+            multicast,
+            multicast_group: None,
+            frame_receiver: None,
+            is_paused: false,
+            session_timeout,
+            last_activity: Instant::now(),
+            vod_path,
+            recording: None,
+            vod_position: Arc::new(AtomicI64::new(0)),
+            remuxers,
+        }
+    }
+
+    /* Read the next data from the client, the frames of a playing session are
+    sent meanwhile. The session is closed if there is no RTSP, RTP or RTCP
+    activity within the read or session timeout. */
+    async fn read_data(&mut self) -> Result<BytesMut, SessionError> {
+        let idle_timeout = match self.read_timeout {
+            Some(read_timeout) => std::cmp::min(read_timeout, self.session_timeout),
+            None => self.session_timeout,
+        };
+
+        loop {
+            let deadline = self.last_activity() + idle_timeout;
+
+            let event = {
+                let mut io = self.io.lock().await;
+                tokio::select! {
+                    data = io.read() => ReadEvent::Data(data?),
+                    frame_data = Self::receive_frame(&mut self.frame_receiver) => {
+                        ReadEvent::Frame(frame_data)
+                    }
+                    _ = tokio::time::sleep_until(deadline.into()) => ReadEvent::Deadline,
+                }
+            };
+
+            match event {
+                ReadEvent::Data(data) => {
+                    self.last_activity = Instant::now();
+                    return Ok(data);
+                }
+                ReadEvent::Frame(frame_data) => {
+                    self.on_play_frame(frame_data).await?;
+                }
+                ReadEvent::Deadline => {
+                    //the media received over UDP keeps the session alive
+                    if self.last_activity() + idle_timeout <= Instant::now() {
+                        return Err(SessionError {
+                            value: SessionErrorValue::SessionTimeout,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    async fn receive_frame(frame_receiver: &mut Option<FrameDataReceiver>) -> Option<FrameData> {
+        match frame_receiver {
+            Some(receiver) => receiver.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    //the latest RTSP message or RTP/RTCP packet received over UDP
+    fn last_activity(&self) -> Instant {
+        self.tracks
+            .values()
+            .map(|track| *track.udp_activity.lock().unwrap())
+            .fold(self.last_activity, Instant::max)
+    }
+
+    fn session_header(session_id: Uuid, session_timeout: Duration) -> String {
+        format!("{};timeout={}", session_id, session_timeout.as_secs())
+    }
+
+    fn insert_session_header(&self, response: &mut RtspResponse) {
+        if let Some(session_id) = self.session_id {
+            response.headers.insert(
+                "Session".to_string(),
+                Self::session_header(session_id, self.session_timeout),
+            );
         }
     }
 
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             while self.reader.len() < 4 {
-                let data = self.io.lock().await.read().await?;
+                let data = self.read_data().await?;
                 self.reader.extend_from_slice(&data[..]);
             }
             // If delivering media data using RTP over RTSP(TCP), then it should use InterleavedBinaryData
@@ -164,7 +296,7 @@ impl RtspServerSession {
                 match data {
                     Some(a) => {
                         while self.reader.len() < a.length as usize {
-                            let data = self.io.lock().await.read().await?;
+                            let data = self.read_data().await?;
                             self.reader.extend_from_slice(&data[..]);
                         }
                         self.on_rtp_over_rtsp_message(a.channel_identifier, a.length as usize)
@@ -226,7 +358,7 @@ impl RtspServerSession {
                                 return Ok(());
                             }
                             retry_count += 1;
-                            let data_recv = self.io.lock().await.read().await?;
+                            let data_recv = self.read_data().await?;
                             self.reader.extend_from_slice(&data_recv[..]);
                             continue;
                         }
@@ -241,6 +373,10 @@ impl RtspServerSession {
             break;
         }
 
+        if !self.check_authorization(&rtsp_request).await? {
+            return Ok(());
+        }
+
         match rtsp_request.method.as_str() {
             rtsp_method_name::OPTIONS => {
                 self.handle_options(&rtsp_request).await?;
@@ -265,9 +401,12 @@ impl RtspServerSession {
             rtsp_method_name::TEARDOWN => {
                 self.handle_teardown(&rtsp_request)?;
             }
-            rtsp_method_name::PAUSE => {}
-            rtsp_method_name::GET_PARAMETER => {}
-            rtsp_method_name::SET_PARAMETER => {}
+            rtsp_method_name::PAUSE => {
+                self.handle_pause(&rtsp_request).await?;
+            }
+            rtsp_method_name::GET_PARAMETER | rtsp_method_name::SET_PARAMETER => {
+                self.handle_parameter(&rtsp_request).await?;
+            }
             rtsp_method_name::REDIRECT => {}
 
             _ => {}
@@ -275,6 +414,48 @@ impl RtspServerSession {
         Ok(())
     }
 
+    /* Respond 401 with the challenge if the request is not authorized, the
+    client should send the request again with the Authorization header. */
+    async fn check_authorization(&mut self, rtsp_request: &RtspRequest) -> Result<bool, SessionError> {
+        let Some(authenticator) = self.authenticator.clone() else {
+            return Ok(true);
+        };
+        if self.is_authenticated {
+            return Ok(true);
+        }
+
+        let is_pull = match rtsp_request.method.as_str() {
+            rtsp_method_name::DESCRIBE | rtsp_method_name::PLAY => true,
+            rtsp_method_name::ANNOUNCE | rtsp_method_name::RECORD => false,
+            _ => return Ok(true),
+        };
+        if !authenticator.is_required(is_pull) {
+            return Ok(true);
+        }
+
+        let authorization = rtsp_auth::get_header(&rtsp_request.headers, "Authorization");
+        if authenticator.authenticate(&rtsp_request.method, authorization, &self.auth_nonce) {
+            self.is_authenticated = true;
+            return Ok(true);
+        }
+
+        if authorization.is_some() {
+            log::warn!(
+                "rtsp authentication failed: {} {}",
+                rtsp_request.method,
+                rtsp_request.uri.path
+            );
+        }
+        let mut response = Self::gen_response(http::StatusCode::UNAUTHORIZED, rtsp_request);
+        response.headers.insert(
+            "WWW-Authenticate".to_string(),
+            authenticator.challenge(&self.auth_nonce),
+        );
+        self.send_response(&response).await?;
+
+        Ok(false)
+    }
+
     async fn handle_options(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
         let status_code = http::StatusCode::OK;
         let mut response = Self::gen_response(status_code, rtsp_request);
@@ -286,6 +467,12 @@ impl RtspServerSession {
     }
 
     async fn handle_describe(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        if let Some(vod_path) = self.vod_path.clone() {
+            if rtsp_request.uri.path.starts_with(VOD_PATH_PREFIX) {
+                return self.handle_vod_describe(&vod_path, rtsp_request).await;
+            }
+        }
+
         let status_code = http::StatusCode::OK;
 
         // The sender is used for sending sdp information from the server session to client session
@@ -297,7 +484,10 @@ impl RtspServerSession {
         };
         self.stream_identifier = Some(identifier.clone());
 
-        let request_event = StreamHubEvent::Request { identifier, sender };
+        let request_event = StreamHubEvent::Request {
+            identifier: identifier.clone(),
+            sender,
+        };
 
         if self.event_producer.send(request_event).is_err() {
             return Err(SessionError {
@@ -305,7 +495,29 @@ impl RtspServerSession {
             });
         }
 
-        if let Some(Information::Sdp { data }) = receiver.recv().await {
+        let mut information = receiver.recv().await;
+        //no rtsp stream is published, try to remux it from the rtmp stream with the same path
+        if information.is_none()
+            && self
+                .remuxers
+                .remux(
+                    &rtsp_request.uri.path,
+                    self.event_producer.clone(),
+                    RTMP_REMUX_READY_TIMEOUT,
+                )
+                .await
+        {
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+            let request_event = StreamHubEvent::Request { identifier, sender };
+            if self.event_producer.send(request_event).is_err() {
+                return Err(SessionError {
+                    value: SessionErrorValue::StreamHubEventSendErr,
+                });
+            }
+            information = receiver.recv().await;
+        }
+
+        if let Some(Information::Sdp { data }) = information {
             if let Some(sdp) = Sdp::unmarshal(&data) {
                 self.sdp = sdp;
                 //it can new tracks when get the sdp information;
@@ -324,6 +536,33 @@ impl RtspServerSession {
         Ok(())
     }
 
+    async fn handle_vod_describe(
+        &mut self,
+        vod_path: &str,
+        rtsp_request: &RtspRequest,
+    ) -> Result<(), SessionError> {
+        let Some(recording) = Recording::open(vod_path, &rtsp_request.uri.path).await else {
+            let response = Self::gen_response(http::StatusCode::NOT_FOUND, rtsp_request);
+            return self.send_response(&response).await;
+        };
+
+        if let Some(sdp) = Sdp::unmarshal(&recording.sdp()) {
+            self.sdp = sdp;
+            self.new_tracks()?;
+        }
+        self.stream_identifier = Some(StreamIdentifier::Rtsp {
+            stream_path: rtsp_request.uri.path.clone(),
+        });
+        self.recording = Some(Arc::new(recording));
+
+        let mut response = Self::gen_response(http::StatusCode::OK, rtsp_request);
+        response.body = Some(self.sdp.marshal());
+        response
+            .headers
+            .insert("Content-Type".to_string(), "application/sdp".to_string());
+        self.send_response(&response).await
+    }
+
     async fn handle_announce(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
         if let Some(auth) = &self.auth {
             let stream_name = rtsp_request.uri.path.clone();
@@ -419,6 +658,49 @@ impl RtspServerSession {
                     let mut rtp_server_port: Option<u16> = None;
                     let mut rtcp_server_port: Option<u16> = None;
 
+                    if trans.cast_type == CastType::Multicast {
+                        let (Some(multicast), Some(identifier), None, None) = (
+                            &self.multicast,
+                            &self.stream_identifier,
+                            &trans.transport_mod,
+                            &self.recording,
+                        ) else {
+                            response = Self::gen_response(
+                                StatusCode::from_u16(461).unwrap(),
+                                rtsp_request,
+                            );
+                            response.reason_phrase = String::from("Unsupported Transport");
+                            break;
+                        };
+
+                        //all the tracks of a session are in the same group
+                        let group = match &self.multicast_group {
+                            Some(group) => group.clone(),
+                            None => {
+                                let group = multicast.lock().unwrap().join(identifier);
+                                self.multicast_group = Some(group.clone());
+                                group
+                            }
+                        };
+
+                        trans.protocol_type = ProtocolType::UDP;
+                        trans.client_port = None;
+                        trans.destination = Some(group.address.to_string());
+                        trans.port = Some(group.track_ports(&track.track_type));
+                        trans.ttl = Some(group.ttl);
+
+                        response
+                            .headers
+                            .insert("Transport".to_string(), trans.marshal());
+                        response.headers.insert(
+                            "Session".to_string(),
+                            Self::session_header(self.session_id.unwrap(), self.session_timeout),
+                        );
+
+                        track.set_transport(trans).await;
+                        break;
+                    }
+
                     match trans.protocol_type {
                         ProtocolType::TCP => {
                             track.create_packer(self.io.clone()).await;
@@ -471,9 +753,10 @@ impl RtspServerSession {
                     response
                         .headers
                         .insert("Transport".to_string(), new_transport_data);
-                    response
-                        .headers
-                        .insert("Session".to_string(), self.session_id.unwrap().to_string());
+                    response.headers.insert(
+                        "Session".to_string(),
+                        Self::session_header(self.session_id.unwrap(), self.session_timeout),
+                    );
 
                     track.set_transport(trans).await;
                 }
@@ -500,6 +783,11 @@ impl RtspServerSession {
             )?;
         }
 
+        //the frames are sent to the multicast group instead of the session
+        if self.multicast_group.is_some() {
+            return self.handle_multicast_play(rtsp_request).await;
+        }
+
         for track in self.tracks.values_mut() {
             let protocol_type = track.transport.protocol_type.clone();
 
@@ -545,8 +833,13 @@ impl RtspServerSession {
             }
         }
 
+        if self.recording.is_some() {
+            return self.handle_vod_play(rtsp_request).await;
+        }
+
         let status_code = http::StatusCode::OK;
-        let response = Self::gen_response(status_code, rtsp_request);
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
 
         self.send_response(&response).await?;
 
@@ -568,54 +861,198 @@ impl RtspServerSession {
             });
         }
 
-        let mut receiver = event_result_receiver.await??.0.frame_receiver.unwrap();
+        //the frames are sent while reading the next rtsp messages, so the
+        //session can still be paused, kept alive or torn down.
+        self.frame_receiver = Some(event_result_receiver.await??.0.frame_receiver.unwrap());
+        self.is_paused = false;
 
-        let mut retry_times = 0;
-        loop {
-            if let Some(frame_data) = receiver.recv().await {
-                match frame_data {
-                    FrameData::Audio {
-                        timestamp,
-                        mut data,
-                    } => {
-                        if let Some(audio_track) = self.tracks.get_mut(&TrackType::Audio) {
-                            audio_track
-                                .rtp_channel
-                                .lock()
-                                .await
-                                .on_frame(&mut data, timestamp)
-                                .await?;
-                        }
-                    }
-                    FrameData::Video {
-                        timestamp,
-                        mut data,
-                    } => {
-                        if let Some(video_track) = self.tracks.get_mut(&TrackType::Video) {
-                            video_track
-                                .rtp_channel
-                                .lock()
-                                .await
-                                .on_frame(&mut data, timestamp)
-                                .await?;
-                        }
-                    }
-                    _ => {}
+        Ok(())
+    }
+
+    /* Play the recording from the segment of the Range or the paused position,
+    the RTP-Info tells the client the first rtp packet of each track. */
+    async fn handle_vod_play(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        let Some(recording) = self.recording.clone() else {
+            return Ok(());
+        };
+
+        let mut position = self.vod_position.load(Ordering::Relaxed);
+        if let Some(range_str) = rtsp_request.get_header(&String::from("Range")) {
+            if let Some(range) = RtspRange::unmarshal(range_str) {
+                if range.range_type == RtspRangeType::NPT && !range_str.contains("now") {
+                    position = range.begin;
                 }
-            } else {
-                retry_times += 1;
-                log::info!(
-                    "send_channel_data: no data receives ,retry {} times!",
-                    retry_times
-                );
+            }
+        }
+        let scale_str = rtsp_request.get_header(&String::from("Scale"));
+        let scale = scale_str
+            .and_then(|scale| scale.trim().parse::<f64>().ok())
+            .filter(|scale| *scale > 0.0)
+            .map(|scale| scale.clamp(MIN_SCALE, MAX_SCALE))
+            .unwrap_or(1.0);
+
+        //stop reading from the previous position
+        self.frame_receiver = None;
+
+        let index = recording.seek(position);
+        let begin = recording.segments[index].start;
+        self.vod_position.store(begin, Ordering::Relaxed);
+
+        let base_url = rtsp_request.uri.marshal();
+        let mut rtp_infos = Vec::new();
+        for track in self.tracks.values() {
+            let rtp_channel = track.rtp_channel.lock().await;
+            if let Some(seq_number) = rtp_channel.seq_number() {
+                let clock_rate = rtp_channel.codec_info().sample_rate;
+                rtp_infos.push(format!(
+                    "url={}/{};seq={};rtptime={}",
+                    base_url.trim_end_matches('/'),
+                    track.media_control,
+                    seq_number,
+                    Recording::rtp_time(begin, clock_rate)
+                ));
+            }
+        }
+
+        let mut response = Self::gen_response(http::StatusCode::OK, rtsp_request);
+        let range = RtspRange {
+            range_type: RtspRangeType::NPT,
+            begin,
+            end: Some(recording.duration),
+        };
+        response
+            .headers
+            .insert(String::from("Range"), range.marshal());
+        //the scale which is actually applied
+        if scale_str.is_some() {
+            response
+                .headers
+                .insert(String::from("Scale"), scale.to_string());
+        }
+        response
+            .headers
+            .insert(String::from("RTP-Info"), rtp_infos.join(","));
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await?;
+
+        self.session_type = define::ServerSessionType::Pull;
+        self.frame_receiver = Some(recording.play(index, scale, self.vod_position.clone()));
+        self.is_paused = false;
+
+        Ok(())
+    }
+
+    async fn on_play_frame(&mut self, frame_data: Option<FrameData>) -> Result<(), SessionError> {
+        let Some(frame_data) = frame_data else {
+            self.frame_receiver = None;
+            //the player may seek or replay the recording after its end
+            if self.recording.is_some() {
+                log::info!("on_play_frame: the recording is finished");
+                return Ok(());
+            }
+            log::info!("on_play_frame: the stream is closed");
+            return Err(SessionError {
+                value: SessionErrorValue::CannotReceiveFrameData,
+            });
+        };
+
+        let (track_type, mut data, timestamp) = match frame_data {
+            FrameData::Audio { timestamp, data } => (TrackType::Audio, data, timestamp),
+            FrameData::Video { timestamp, data } => (TrackType::Video, data, timestamp),
+            _ => return Ok(()),
+        };
+
+        if let Some(track) = self.tracks.get_mut(&track_type) {
+            if let Err(err) = track
+                .rtp_channel
+                .lock()
+                .await
+                .on_frame(&mut data, timestamp)
+                .await
+            {
+                log::error!("on_play_frame: send frame error: {}", err);
+                self.frame_receiver = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    //stop sending frames, the next PLAY resumes from the cached gop
+    async fn handle_pause(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        if self.frame_receiver.is_none() && self.multicast_group.is_none() {
+            let status_code = StatusCode::from_u16(455).unwrap();
+            let mut response = Self::gen_response(status_code, rtsp_request);
+            response.reason_phrase = String::from("Method Not Valid in This State");
+            return self.send_response(&response).await;
+        }
 
-                if retry_times > 10 {
+        //a multicast group is shared by other viewers and keeps sending,
+        //a recording is resumed from the paused position
+        if self.frame_receiver.take().is_some() && self.recording.is_none() {
+            if let Some(identifier) = self.stream_identifier.clone() {
+                let unsubscribe_event = StreamHubEvent::UnSubscribe {
+                    identifier,
+                    info: self.get_subscriber_info(),
+                };
+                if self.event_producer.send(unsubscribe_event).is_err() {
                     return Err(SessionError {
-                        value: SessionErrorValue::CannotReceiveFrameData,
+                        value: SessionErrorValue::StreamHubEventSendErr,
                     });
                 }
             }
+            self.is_paused = true;
+        }
+
+        let status_code = http::StatusCode::OK;
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await
+    }
+
+    //GET_PARAMETER and SET_PARAMETER without parameters are used as keepalives
+    async fn handle_parameter(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        let is_setting = rtsp_request.method == rtsp_method_name::SET_PARAMETER
+            && rtsp_request
+                .body
+                .as_ref()
+                .is_some_and(|body| !body.trim().is_empty());
+
+        if is_setting {
+            let status_code = StatusCode::from_u16(451).unwrap();
+            let mut response = Self::gen_response(status_code, rtsp_request);
+            response.reason_phrase = String::from("Parameter Not Understood");
+            return self.send_response(&response).await;
+        }
+
+        let status_code = http::StatusCode::OK;
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await
+    }
+
+    async fn handle_multicast_play(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        if let (Some(multicast), Some(identifier)) = (&self.multicast, &self.stream_identifier) {
+            let mut codec_infos = Vec::new();
+            for track in self.tracks.values() {
+                let codec_info = track.rtp_channel.lock().await.codec_info().clone();
+                codec_infos.push((track.track_type.clone(), codec_info));
+            }
+
+            rtsp_multicast::start_multicast_sender(
+                multicast,
+                identifier.clone(),
+                codec_infos,
+                self.event_producer.clone(),
+            );
         }
+
+        self.session_type = define::ServerSessionType::Pull;
+
+        let status_code = http::StatusCode::OK;
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await
     }
 
     async fn handle_record(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
@@ -632,9 +1069,10 @@ impl RtspServerSession {
             }
         }
 
-        response
-            .headers
-            .insert("Session".to_string(), self.session_id.unwrap().to_string());
+        response.headers.insert(
+            "Session".to_string(),
+            Self::session_header(self.session_id.unwrap(), self.session_timeout),
+        );
 
         self.send_response(&response).await?;
 
@@ -650,6 +1088,21 @@ impl RtspServerSession {
     }
 
     pub fn exit(&mut self, identifier: StreamIdentifier) -> Result<(), SessionError> {
+        //a multicast player only leaves the group, the sender of the group subscribes the stream
+        if let (Some(multicast), Some(_)) = (&self.multicast, self.multicast_group.take()) {
+            if let Some(identifier) = &self.stream_identifier {
+                multicast.lock().unwrap().leave(identifier);
+            }
+            self.is_normal_exit = true;
+            return Ok(());
+        }
+
+        //a paused player has unsubscribed already and a recording is not subscribed
+        if self.is_paused || self.recording.is_some() {
+            self.is_normal_exit = true;
+            return Ok(());
+        }
+
         let event = match self.session_type {
             define::ServerSessionType::Pull => StreamHubEvent::UnSubscribe {
                 identifier,
@@ -691,15 +1144,19 @@ impl RtspServerSession {
             log::info!("media_name: {}", media_name);
             match media_name.as_str() {
                 "audio" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("unsupported audio codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
                         sample_rate: media.rtpmap.clock_rate,
-                        channel_count: media.rtpmap.encoding_param.parse().unwrap(),
+                        //the channel count is optional and defaults to 1
+                        channel_count: media.rtpmap.encoding_param.parse().unwrap_or(1),
                     };
 
                     log::info!("audio codec info: {:?}", codec_info);
@@ -708,10 +1165,13 @@ impl RtspServerSession {
                     self.tracks.insert(TrackType::Audio, track);
                 }
                 "video" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("unsupported video codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
@@ -797,17 +1257,22 @@ impl RtspServerSession {
 #[derive(Default)]
 pub struct RtspStreamHandler {
     sdp: Mutex<Sdp>,
+    subscriber_count: watch::Sender<usize>,
 }
 
 impl RtspStreamHandler {
     pub fn new() -> Self {
         Self {
             sdp: Mutex::new(Sdp::default()),
+            subscriber_count: watch::Sender::new(0),
         }
     }
     pub async fn set_sdp(&self, sdp: Sdp) {
         *self.sdp.lock().await = sdp;
     }
+    pub fn subscribe_subscriber_count(&self) -> watch::Receiver<usize> {
+        self.subscriber_count.subscribe()
+    }
 }
 
 #[async_trait]
@@ -832,8 +1297,30 @@ impl TStreamHandler for RtspStreamHandler {
                 let mut audio_clock_rate: u32 = 0;
 
                 let mut vcodec: VideoCodecType = VideoCodecType::H264;
+                let mut acodec: AudioCodecType = AudioCodecType::AAC;
 
                 for media in &sdp_info.medias {
+                    let codec_name = media.rtpmap.encoding_name.to_lowercase();
+                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID.get(codec_name.as_str());
+                    //the remuxer skips the tracks of the unsupported codecs,
+                    //G.711 has no fmtp, it is transcoded to aac by the remuxer
+                    match (media.media_type.as_str(), codec_id) {
+                        ("audio", Some(RtspCodecId::AAC)) => acodec = AudioCodecType::AAC,
+                        ("audio", Some(RtspCodecId::G711A)) => {
+                            acodec = AudioCodecType::G711A;
+                            audio_clock_rate = media.rtpmap.clock_rate;
+                        }
+                        ("audio", Some(RtspCodecId::G711U)) => {
+                            acodec = AudioCodecType::G711U;
+                            audio_clock_rate = media.rtpmap.clock_rate;
+                        }
+                        ("audio", _) => acodec = AudioCodecType::Unsupported,
+                        ("video", Some(RtspCodecId::H264)) => vcodec = VideoCodecType::H264,
+                        ("video", Some(RtspCodecId::H265)) => vcodec = VideoCodecType::H265,
+                        ("video", _) => vcodec = VideoCodecType::Unsupported,
+                        _ => {}
+                    }
+
                     let mut bytes_writer = BytesWriter::new();
                     if let Some(fmtp) = &media.fmtp {
                         match fmtp {
@@ -882,6 +1369,9 @@ impl TStreamHandler for RtspStreamHandler {
 
                                 audio_clock_rate = media.rtpmap.clock_rate;
                             }
+                            Fmtp::Opus(_) => {
+                                audio_clock_rate = media.rtpmap.clock_rate;
+                            }
                         }
                     }
                 }
@@ -892,6 +1382,7 @@ impl TStreamHandler for RtspStreamHandler {
                         video_clock_rate,
 
                         vcodec,
+                        acodec,
                     },
                 }) {
                     log::error!("send media info error: {}", err);
@@ -914,4 +1405,8 @@ impl TStreamHandler for RtspStreamHandler {
             log::error!("send_information of rtsp error: {}", err);
         }
     }
+
+    async fn on_subscriber_count(&self, count: usize) {
+        self.subscriber_count.send_replace(count);
+    }
 }
@@ -0,0 +1,209 @@
+use crate::errors::Aac2OpusError;
+use audiopus::coder::Encoder as OpusEncoder;
+use fdk_aac::dec::{Decoder as AacDecoder, DecoderError as AacDecoderError, Transport};
+
+//the rtp clock rate of opus is always 48000(RFC 7587)
+pub const OPUS_SAMPLE_RATE: u32 = 48000;
+const OPUS_CHANNELS: usize = 2;
+//20ms
+const OPUS_FRAME_SAMPLES: usize = 960;
+//the max decoded samples of an aac frame(HE-AAC is 2048 samples per channel)
+const AAC_MAX_FRAME_SAMPLES: usize = 2048 * 8;
+
+// Resample the interleaved pcm samples by the linear interpolation.
+struct LinearResampler {
+    channels: usize,
+    input_sample_rate: u32,
+    output_sample_rate: u32,
+    //the position of the next output sample in 1/output_sample_rate input samples,
+    //0 is the last sample of the previous input
+    offset: u64,
+    last_samples: Vec<i16>,
+}
+
+impl LinearResampler {
+    fn new(channels: usize, input_sample_rate: u32, output_sample_rate: u32) -> Self {
+        Self {
+            channels,
+            input_sample_rate,
+            output_sample_rate,
+            offset: output_sample_rate as u64,
+            last_samples: vec![0; channels],
+        }
+    }
+
+    fn resample(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let channels = self.channels;
+        let input_frames = input.len() / channels;
+        let output_sample_rate = self.output_sample_rate as u64;
+        let end = input_frames as u64 * output_sample_rate;
+
+        while self.offset < end {
+            let index = (self.offset / output_sample_rate) as usize;
+            let fraction = (self.offset % output_sample_rate) as i64;
+            for channel in 0..channels {
+                let left = match index {
+                    0 => self.last_samples[channel],
+                    _ => input[(index - 1) * channels + channel],
+                } as i64;
+                let right = input[index * channels + channel] as i64;
+                output.push((left + (right - left) * fraction / output_sample_rate as i64) as i16);
+            }
+            self.offset += self.input_sample_rate as u64;
+        }
+
+        if input_frames > 0 {
+            self.offset -= end;
+            self.last_samples
+                .copy_from_slice(&input[(input_frames - 1) * channels..input_frames * channels]);
+        }
+    }
+}
+
+// Transcode the ADTS aac frames to the 20ms stereo opus frames.
+pub struct Aac2OpusTranscoder {
+    decoder: AacDecoder,
+    encoder: OpusEncoder,
+    resampler: Option<LinearResampler>,
+    pcm_data: Vec<i16>,
+}
+
+impl Aac2OpusTranscoder {
+    pub fn new() -> Result<Self, Aac2OpusError> {
+        let mut decoder = AacDecoder::new(Transport::Adts);
+        //mono or multi-channel aac is up/down mixed to stereo by the decoder
+        decoder.set_min_output_channels(OPUS_CHANNELS)?;
+        decoder.set_max_output_channels(OPUS_CHANNELS)?;
+
+        let encoder = OpusEncoder::new(
+            audiopus::SampleRate::Hz48000,
+            audiopus::Channels::Stereo,
+            audiopus::Application::Audio,
+        )?;
+
+        Ok(Aac2OpusTranscoder {
+            decoder,
+            encoder,
+            resampler: None,
+            pcm_data: Vec::new(),
+        })
+    }
+
+    //the timestamp of the input is in milliseconds, the timestamps of the
+    //returned opus frames are rebased to the 48kHz rtp clock.
+    pub fn transcode(
+        &mut self,
+        input: &[u8],
+        timestamp: u32,
+    ) -> Result<Vec<(u32, Vec<u8>)>, Aac2OpusError> {
+        self.decoder.fill(input)?;
+
+        let mut decoded_samples: usize = 0;
+        let mut pcm_output: Vec<i16> = vec![0; AAC_MAX_FRAME_SAMPLES];
+        loop {
+            match self.decoder.decode_frame(&mut pcm_output) {
+                Ok(()) => {}
+                Err(AacDecoderError::NOT_ENOUGH_BITS) => break,
+                Err(err) => return Err(err.into()),
+            }
+
+            let sample_rate = self.decoder.stream_info().sampleRate as u32;
+            if sample_rate == 0 {
+                continue;
+            }
+            let resampler = match &mut self.resampler {
+                Some(resampler) if resampler.input_sample_rate == sample_rate => resampler,
+                resampler => resampler.insert(LinearResampler::new(
+                    OPUS_CHANNELS,
+                    sample_rate,
+                    OPUS_SAMPLE_RATE,
+                )),
+            };
+
+            let pcm_len = self.pcm_data.len();
+            let frame_size = self.decoder.decoded_frame_size();
+            resampler.resample(&pcm_output[..frame_size], &mut self.pcm_data);
+            decoded_samples += (self.pcm_data.len() - pcm_len) / OPUS_CHANNELS;
+        }
+
+        //the end of the buffered samples is the end of this input
+        let end_timestamp = timestamp
+            .wrapping_mul(OPUS_SAMPLE_RATE / 1000)
+            .wrapping_add(decoded_samples as u32);
+
+        let mut opus_output: Vec<u8> = vec![0; 1500];
+        let mut result = Vec::new();
+        while self.pcm_data.len() >= OPUS_FRAME_SAMPLES * OPUS_CHANNELS {
+            let frame_timestamp =
+                end_timestamp.wrapping_sub((self.pcm_data.len() / OPUS_CHANNELS) as u32);
+
+            let output_size = self.encoder.encode(
+                &self.pcm_data[..OPUS_FRAME_SAMPLES * OPUS_CHANNELS],
+                &mut opus_output,
+            )?;
+            self.pcm_data.drain(..OPUS_FRAME_SAMPLES * OPUS_CHANNELS);
+
+            result.push((frame_timestamp, opus_output[..output_size].to_vec()));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aac2OpusTranscoder, LinearResampler, OPUS_CHANNELS, OPUS_FRAME_SAMPLES};
+    use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+
+    #[test]
+    fn test_resample_stereo() {
+        let mut resampler = LinearResampler::new(2, 24000, 48000);
+        let mut output = Vec::new();
+        resampler.resample(&[100, -100, 200, -200], &mut output);
+        resampler.resample(&[300, -300], &mut output);
+        assert_eq!(output, vec![100, -100, 150, -150, 200, -200, 250, -250]);
+    }
+
+    #[test]
+    fn test_transcode() {
+        //encode one second of 44.1kHz mono sine wave to adts aac
+        let aac_encoder = Encoder::new(EncoderParams {
+            bit_rate: BitRate::VbrMedium,
+            sample_rate: 44100,
+            transport: Transport::Adts,
+            channels: ChannelMode::Mono,
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+        })
+        .unwrap();
+        let pcm: Vec<i16> = (0..44100)
+            .map(|n| ((n as f64 * 0.05).sin() * 8000.0) as i16)
+            .collect();
+
+        let mut transcoder = Aac2OpusTranscoder::new().unwrap();
+        let mut aac_output = vec![0; 2048];
+        let mut frames = Vec::new();
+        for (index, chunk) in pcm.chunks_exact(1024).enumerate() {
+            let info = aac_encoder.encode(chunk, &mut aac_output).unwrap();
+            if info.output_size == 0 {
+                continue;
+            }
+            let timestamp = (index * 1024 * 1000 / 44100) as u32;
+            frames.extend(
+                transcoder
+                    .transcode(&aac_output[..info.output_size], 1000 + timestamp)
+                    .unwrap(),
+            );
+        }
+
+        assert!(frames.len() > 35 && frames.len() <= 50);
+        assert!(frames.iter().all(|(_, data)| !data.is_empty()));
+        //the timestamps are continuous in the 48kHz clock, the millisecond
+        //input timestamps only give a small jitter
+        assert!(frames.windows(2).all(|frames| {
+            let delta = frames[1].0.wrapping_sub(frames[0].0) as i64;
+            (delta - OPUS_FRAME_SAMPLES as i64).abs() <= 48
+        }));
+        assert!(frames[0].0 >= 48000);
+        assert_eq!(transcoder.pcm_data.len() % OPUS_CHANNELS, 0);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use streamhub::define::{FrameData, FrameDataSender, PacketData, PacketDataSender};
+use tokio::sync::Mutex;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::peer_connection::RTCPeerConnection;
+
+// Relay the text messages of the data channels opened by a WHIP publisher to the
+// subscribers of the stream.
+pub fn relay_publisher_messages(
+    peer_connection: &RTCPeerConnection,
+    frame_sender: Option<FrameDataSender>,
+    packet_sender: Option<PacketDataSender>,
+) {
+    peer_connection.on_data_channel(Box::new(move |data_channel: Arc<RTCDataChannel>| {
+        log::info!("whip data channel opened: {}", data_channel.label());
+        relay_messages(&data_channel, frame_sender.clone(), packet_sender.clone());
+        Box::pin(async {})
+    }));
+}
+
+// Relay the text messages of a data channel to the stream, the binary messages
+// are not supported.
+pub fn relay_messages(
+    data_channel: &RTCDataChannel,
+    frame_sender: Option<FrameDataSender>,
+    packet_sender: Option<PacketDataSender>,
+) {
+    data_channel.on_message(Box::new(move |message: DataChannelMessage| {
+        if !message.is_string {
+            log::warn!("the binary data channel message is not supported");
+            return Box::pin(async {});
+        }
+        let Ok(text) = String::from_utf8(message.data.to_vec()) else {
+            log::error!("the data channel message is not utf8");
+            return Box::pin(async {});
+        };
+
+        if let Some(sender) = &frame_sender {
+            if let Err(err) = sender.send(FrameData::TextData { data: text.clone() }) {
+                log::error!("send text data frame error: {}", err);
+            }
+        }
+        if let Some(sender) = &packet_sender {
+            if let Err(err) = sender.send(PacketData::TextData { data: text }) {
+                log::error!("send text data packet error: {}", err);
+            }
+        }
+        Box::pin(async {})
+    }));
+}
+
+// The data channels opened by a WHEP subscriber, the text messages of the
+// subscribed stream are sent to all of them. A WHEP client has to create a data
+// channel before the offer is generated to receive the messages.
+#[derive(Clone, Default)]
+pub struct SubscriberDataChannels {
+    channels: Arc<Mutex<Vec<Arc<RTCDataChannel>>>>,
+}
+
+impl SubscriberDataChannels {
+    pub fn new(peer_connection: &RTCPeerConnection) -> Self {
+        let data_channels = Self::default();
+
+        let channels = data_channels.channels.clone();
+        peer_connection.on_data_channel(Box::new(move |data_channel: Arc<RTCDataChannel>| {
+            log::info!("whep data channel opened: {}", data_channel.label());
+            let channels = channels.clone();
+            Box::pin(async move {
+                channels.lock().await.push(data_channel);
+            })
+        }));
+
+        data_channels
+    }
+
+    //the data channel created by the local peer, e.g. the one of a WHIP client
+    pub async fn add(&self, data_channel: Arc<RTCDataChannel>) {
+        self.channels.lock().await.push(data_channel);
+    }
+
+    pub async fn send_text(&self, text: &str) {
+        let mut channels = self.channels.lock().await;
+        channels.retain(|channel| channel.ready_state() != RTCDataChannelState::Closed);
+
+        for channel in channels.iter() {
+            if channel.ready_state() != RTCDataChannelState::Open {
+                continue;
+            }
+            if let Err(err) = channel.send_text(text).await {
+                log::error!("send data channel message error: {}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use streamhub::define::PacketData;
+
+//the GOP longer than it is not cached, about 10 seconds of a 1Mbps stream
+const MAX_GOP_PACKETS: usize = 1024;
+
+// Cache the rtp packets of the last GOP of a whip stream, the joined WHEP
+// subscribers get them first so that the first frame is played without
+// waiting for the next key frame.
+#[derive(Default)]
+pub struct RtpGopCache {
+    packets: Vec<PacketData>,
+    //the timestamp of the key frame which starts the cached GOP, a key frame
+    //can be packetized into several packets(e.g. a STAP-A of the sps/pps and FU-As)
+    key_frame_timestamp: Option<u32>,
+}
+
+impl RtpGopCache {
+    pub fn save_packet_data(&mut self, data: &PacketData, is_key_frame: bool) {
+        match data {
+            PacketData::Video { timestamp, data: _ } => {
+                if is_key_frame && self.key_frame_timestamp != Some(*timestamp) {
+                    self.packets.clear();
+                    self.key_frame_timestamp = Some(*timestamp);
+                }
+            }
+            PacketData::Audio { .. } => {}
+            //the text messages are only sent to the current subscribers
+            PacketData::TextData { .. } => return,
+        }
+
+        //the packets before the first key frame can not be decoded
+        if self.key_frame_timestamp.is_none() {
+            return;
+        }
+        if self.packets.len() >= MAX_GOP_PACKETS {
+            self.packets.clear();
+            self.key_frame_timestamp = None;
+            return;
+        }
+        self.packets.push(data.clone());
+    }
+
+    pub fn get_packet_data(&self) -> Vec<PacketData> {
+        self.packets.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RtpGopCache;
+    use bytes::BytesMut;
+    use streamhub::define::PacketData;
+
+    fn video(timestamp: u32) -> PacketData {
+        PacketData::Video {
+            timestamp,
+            data: BytesMut::new(),
+        }
+    }
+
+    #[test]
+    fn test_gop_cache() {
+        let mut cache = RtpGopCache::default();
+        cache.save_packet_data(&video(0), false);
+        assert!(cache.get_packet_data().is_empty());
+
+        //the sps/pps and the idr of the same key frame
+        cache.save_packet_data(&video(3000), true);
+        cache.save_packet_data(&video(3000), true);
+        cache.save_packet_data(&video(6000), false);
+        cache.save_packet_data(
+            &PacketData::Audio {
+                timestamp: 960,
+                data: BytesMut::new(),
+            },
+            false,
+        );
+        assert_eq!(cache.get_packet_data().len(), 4);
+
+        cache.save_packet_data(&video(9000), true);
+        assert_eq!(cache.get_packet_data().len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use webrtc::api::setting_engine::SettingEngine;
+use webrtc::ice::udp_mux::{UDPMuxDefault, UDPMuxParams};
+use webrtc::ice::udp_network::{EphemeralUDP, UDPNetwork};
+use webrtc::ice_transport::ice_candidate_type::RTCIceCandidateType;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+
+const DEFAULT_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+#[derive(Debug, Clone, Default)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    //the credential of the TURN servers
+    pub username: String,
+    pub credential: String,
+}
+
+// The ICE settings of the WHIP/WHEP peer connections.
+#[derive(Clone)]
+pub struct IceConfig {
+    //the STUN/TURN servers, empty for the air-gapped networks
+    pub ice_servers: Vec<IceServer>,
+    //the public ips of a 1:1 NAT, they replace the ips of the host candidates
+    //or are added as the server reflexive candidates
+    pub nat_1to1_ips: Vec<String>,
+    pub nat_1to1_srflx: bool,
+    //the host candidates are gathered only from these interfaces and ips if not empty
+    pub interfaces: Vec<String>,
+    pub ips: Vec<IpAddr>,
+    //the range of the ephemeral udp ports
+    pub udp_port_range: Option<(u16, u16)>,
+    //all the peer connections share this udp port, they are demultiplexed by
+    //the ICE ufrag. The port range is not used if it is set.
+    pub udp_mux_port: Option<u16>,
+    udp_mux: Option<Arc<UDPMuxDefault>>,
+    //only the host candidates are gathered and no connectivity check is sent
+    pub lite: bool,
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        Self {
+            ice_servers: vec![IceServer {
+                urls: vec![DEFAULT_STUN_SERVER.to_owned()],
+                ..Default::default()
+            }],
+            nat_1to1_ips: Vec::new(),
+            nat_1to1_srflx: false,
+            interfaces: Vec::new(),
+            ips: Vec::new(),
+            udp_port_range: None,
+            udp_mux_port: None,
+            udp_mux: None,
+            lite: false,
+        }
+    }
+}
+
+impl IceConfig {
+    //bind the shared udp port before the peer connections are created
+    pub async fn bind_udp_mux(&mut self) -> Result<(), std::io::Error> {
+        if let Some(port) = self.udp_mux_port {
+            let socket = UdpSocket::bind(("0.0.0.0", port)).await?;
+            log::info!(
+                "WebRTC ice udp mux listening on udp://{}",
+                socket.local_addr()?
+            );
+            self.udp_mux = Some(UDPMuxDefault::new(UDPMuxParams::new(socket)));
+        }
+        Ok(())
+    }
+
+    pub fn rtc_configuration(&self) -> RTCConfiguration {
+        RTCConfiguration {
+            ice_servers: self
+                .ice_servers
+                .iter()
+                .map(|ice_server| RTCIceServer {
+                    urls: ice_server.urls.clone(),
+                    username: ice_server.username.clone(),
+                    credential: ice_server.credential.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn setting_engine(&self) -> Result<SettingEngine, webrtc::Error> {
+        let mut setting_engine = SettingEngine::default();
+
+        if !self.nat_1to1_ips.is_empty() {
+            let candidate_type = match self.nat_1to1_srflx {
+                true => RTCIceCandidateType::Srflx,
+                false => RTCIceCandidateType::Host,
+            };
+            setting_engine.set_nat_1to1_ips(self.nat_1to1_ips.clone(), candidate_type);
+        }
+
+        if !self.interfaces.is_empty() {
+            let interfaces = self.interfaces.clone();
+            setting_engine.set_interface_filter(Box::new(move |interface: &str| {
+                interfaces.iter().any(|name| name == interface)
+            }));
+        }
+
+        if !self.ips.is_empty() {
+            let ips = self.ips.clone();
+            setting_engine.set_ip_filter(Box::new(move |ip: IpAddr| ips.contains(&ip)));
+        }
+
+        if let Some(udp_mux) = &self.udp_mux {
+            setting_engine.set_udp_network(UDPNetwork::Muxed(udp_mux.clone()));
+        } else if let Some((port_min, port_max)) = self.udp_port_range {
+            let ephemeral_udp = EphemeralUDP::new(port_min, port_max)?;
+            setting_engine.set_udp_network(UDPNetwork::Ephemeral(ephemeral_udp));
+        }
+
+        setting_engine.set_lite(self.lite);
+
+        Ok(setting_engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IceConfig;
+
+    #[test]
+    fn test_setting_engine() {
+        let ice_config = IceConfig::default();
+        assert_eq!(ice_config.rtc_configuration().ice_servers.len(), 1);
+        assert!(ice_config.setting_engine().is_ok());
+
+        let ice_config = IceConfig {
+            ice_servers: Vec::new(),
+            udp_port_range: Some((20000, 20100)),
+            lite: true,
+            ..Default::default()
+        };
+        assert!(ice_config.rtc_configuration().ice_servers.is_empty());
+        assert!(ice_config.setting_engine().is_ok());
+
+        let ice_config = IceConfig {
+            udp_port_range: Some((20100, 20000)),
+            ..Default::default()
+        };
+        assert!(ice_config.setting_engine().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_udp_mux() {
+        let mut ice_config = IceConfig {
+            udp_port_range: Some((20100, 20000)),
+            udp_mux_port: Some(0),
+            ..Default::default()
+        };
+        ice_config.bind_udp_mux().await.unwrap();
+        assert!(ice_config.udp_mux.is_some());
+        //the port range is ignored by the udp mux
+        assert!(ice_config.setting_engine().is_ok());
+    }
+}
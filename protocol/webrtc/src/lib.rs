@@ -1,8 +1,16 @@
+pub mod data_channel;
 pub mod errors;
+pub mod gop_cache;
+pub mod ice_config;
 // pub mod http;
+pub mod relay;
 pub mod session;
 pub mod webrtc;
 pub mod whep;
 pub mod whip;
 pub mod opus2aac;
+pub mod aac2opus;
 pub mod rtp_queue;
+pub mod track_io;
+pub mod simulcast;
+pub mod trickle_ice;
@@ -0,0 +1,185 @@
+use {
+    super::{errors::RelayError, pull_client::WhepPullClient, push_client::WhipPushClient},
+    crate::ice_config::IceConfig,
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    },
+    streamhub::{
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            StreamHubEventSender,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        stream::StreamIdentifier,
+        utils::Backoff,
+    },
+};
+
+/* Start and stop the WHEP pull clients and the WHIP push clients of the webrtc
+streams by the relay api, only the events of the webrtc streams and the relay
+ids owned by the manager are handled. */
+pub struct WebRTCRelayClientManager {
+    pull_clients: HashMap<String, Arc<AtomicBool>>,
+    push_clients: HashMap<String, Arc<AtomicBool>>,
+    client_event_consumer: BroadcastEventReceiver,
+    channel_event_producer: StreamHubEventSender,
+    backoff: Backoff,
+    ice_config: IceConfig,
+}
+
+impl WebRTCRelayClientManager {
+    pub fn new(
+        consumer: BroadcastEventReceiver,
+        producer: StreamHubEventSender,
+        backoff: Backoff,
+        mut ice_config: IceConfig,
+    ) -> Self {
+        //the relay clients are the offerers, which cannot be the ice lite agents
+        ice_config.lite = false;
+
+        Self {
+            pull_clients: HashMap::new(),
+            push_clients: HashMap::new(),
+            client_event_consumer: consumer,
+            channel_event_producer: producer,
+            backoff,
+            ice_config,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), RelayError> {
+        log::info!("webrtc relay client manager run...");
+
+        loop {
+            let event = self.client_event_consumer.recv().await?;
+
+            match event {
+                BroadcastEvent::Subscribe {
+                    id,
+                    identifier: identifier @ StreamIdentifier::WebRTC { .. },
+                    server_address,
+                    token,
+                    result_sender,
+                } => {
+                    let result = self.on_start_pull(id, identifier, server_address, token);
+                    Self::send_result(result_sender, result).await;
+                }
+                BroadcastEvent::StartRestream {
+                    id,
+                    identifier: identifier @ StreamIdentifier::WebRTC { .. },
+                    url,
+                    token,
+                    result_sender,
+                } => {
+                    let result = self.on_start_push(id, identifier, url, token);
+                    Self::send_result(result_sender, result).await;
+                }
+                //the ids of the other relay clients are ignored
+                BroadcastEvent::UnSubscribe { id, result_sender } => {
+                    if let Some(is_running) = self.pull_clients.remove(&id) {
+                        is_running.store(false, Ordering::Release);
+                        Self::send_result(result_sender, Ok(())).await;
+                    }
+                }
+                BroadcastEvent::StopRestream { id, result_sender } => {
+                    if let Some(is_running) = self.push_clients.remove(&id) {
+                        is_running.store(false, Ordering::Release);
+                        Self::send_result(result_sender, Ok(())).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_start_pull(
+        &mut self,
+        id: String,
+        identifier: StreamIdentifier,
+        url: Option<String>,
+        token: Option<String>,
+    ) -> Result<(), StreamHubError> {
+        let Some(url) = url else {
+            return Err(relay_error("the whep url is not set"));
+        };
+        let is_running = Self::new_client(&mut self.pull_clients, &id)?;
+
+        log::info!(
+            "start whep pull client: {}, {} from {}",
+            id,
+            identifier,
+            url
+        );
+        let client = WhepPullClient::new(
+            id,
+            url,
+            identifier,
+            self.channel_event_producer.clone(),
+            self.ice_config.clone(),
+            token,
+        );
+        tokio::spawn(client.supervise(self.backoff.clone(), is_running));
+
+        Ok(())
+    }
+
+    fn on_start_push(
+        &mut self,
+        id: String,
+        identifier: StreamIdentifier,
+        url: String,
+        token: Option<String>,
+    ) -> Result<(), StreamHubError> {
+        let is_running = Self::new_client(&mut self.push_clients, &id)?;
+
+        log::info!("start whip push client: {}, {} to {}", id, identifier, url);
+        let client = WhipPushClient::new(
+            id,
+            url,
+            identifier,
+            self.channel_event_producer.clone(),
+            self.ice_config.clone(),
+            token,
+        );
+        tokio::spawn(client.supervise(self.backoff.clone(), is_running));
+
+        Ok(())
+    }
+
+    //the id of a client which gave up reconnecting can be reused
+    fn new_client(
+        clients: &mut HashMap<String, Arc<AtomicBool>>,
+        id: &str,
+    ) -> Result<Arc<AtomicBool>, StreamHubError> {
+        if let Some(is_running) = clients.get(id) {
+            if is_running.load(Ordering::Acquire) {
+                return Err(relay_error("the relay id exists"));
+            }
+        }
+
+        let is_running = Arc::new(AtomicBool::new(true));
+        clients.insert(id.to_string(), is_running.clone());
+        Ok(is_running)
+    }
+
+    async fn send_result(
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+        result: Result<(), StreamHubError>,
+    ) {
+        if let Some(sender) = result_sender {
+            if let Err(err) = sender.send(result).await {
+                log::error!("webrtc relay client manager send result error: {}", err);
+            }
+        }
+    }
+}
+
+fn relay_error(desp: &str) -> StreamHubError {
+    StreamHubError {
+        value: StreamHubErrorValue::WebRTCRelayError(String::from(desp)),
+    }
+}
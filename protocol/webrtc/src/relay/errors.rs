@@ -0,0 +1,102 @@
+#![allow(non_local_definitions)]
+use {
+    crate::errors::WebRTCError,
+    failure::{Backtrace, Fail},
+    std::fmt,
+    streamhub::errors::StreamHubError,
+    tokio::sync::broadcast::error::RecvError,
+    webrtc::error::Error as RTCError,
+};
+
+#[derive(Debug)]
+pub struct RelayError {
+    pub value: RelayErrorValue,
+}
+
+#[derive(Debug, Fail)]
+pub enum RelayErrorValue {
+    #[fail(display = "receive error: {}", _0)]
+    ReceiveError(#[cause] RecvError),
+    #[fail(display = "webrtc error: {}", _0)]
+    WebRTCError(#[cause] WebRTCError),
+    #[fail(display = "webrtc error: {}", _0)]
+    RTCError(#[cause] RTCError),
+    #[fail(display = "http request error: {}", _0)]
+    RequestError(#[cause] reqwest::Error),
+    #[fail(display = "stream hub error: {}", _0)]
+    StreamHubError(#[cause] StreamHubError),
+    #[fail(display = "the url is not valid: {}", _0)]
+    InvalidUrl(String),
+    #[fail(display = "the http response status is not expected: {}", _0)]
+    UnexpectedStatus(u16),
+    #[fail(display = "cannot get local description")]
+    CanNotGetLocalDescription,
+    #[fail(display = "stream hub event send error")]
+    StreamHubEventSendErr,
+    #[fail(display = "the peer connection is failed: {}", _0)]
+    PeerConnectionFailed(String),
+}
+
+impl From<RecvError> for RelayError {
+    fn from(error: RecvError) -> Self {
+        RelayError {
+            value: RelayErrorValue::ReceiveError(error),
+        }
+    }
+}
+
+impl From<WebRTCError> for RelayError {
+    fn from(error: WebRTCError) -> Self {
+        RelayError {
+            value: RelayErrorValue::WebRTCError(error),
+        }
+    }
+}
+
+impl From<RTCError> for RelayError {
+    fn from(error: RTCError) -> Self {
+        RelayError {
+            value: RelayErrorValue::RTCError(error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for RelayError {
+    fn from(error: reqwest::Error) -> Self {
+        RelayError {
+            value: RelayErrorValue::RequestError(error),
+        }
+    }
+}
+
+impl From<StreamHubError> for RelayError {
+    fn from(error: StreamHubError) -> Self {
+        RelayError {
+            value: RelayErrorValue::StreamHubError(error),
+        }
+    }
+}
+
+impl From<tokio::sync::oneshot::error::RecvError> for RelayError {
+    fn from(_: tokio::sync::oneshot::error::RecvError) -> Self {
+        RelayError {
+            value: RelayErrorValue::StreamHubEventSendErr,
+        }
+    }
+}
+
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for RelayError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
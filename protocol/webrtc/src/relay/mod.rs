@@ -0,0 +1,97 @@
+pub mod client_manager;
+pub mod errors;
+pub mod pull_client;
+pub mod push_client;
+pub mod signaling;
+
+use {
+    super::ice_config::IceConfig,
+    errors::{RelayError, RelayErrorValue},
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    tokio::{
+        sync::broadcast::{self, error::RecvError},
+        time::{sleep, Duration},
+    },
+    webrtc::{
+        api::APIBuilder,
+        api::{interceptor_registry::register_default_interceptors, media_engine::MediaEngine},
+        interceptor::registry::Registry,
+        peer_connection::{
+            peer_connection_state::RTCPeerConnectionState,
+            sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+        },
+    },
+};
+
+//the label of the data channel which carries the text messages of the stream
+const DATA_CHANNEL_LABEL: &str = "xiu";
+//the interval of checking if the relay is stopped
+const CHECK_RUNNING_INTERVAL: Duration = Duration::from_secs(1);
+
+async fn new_peer_connection(
+    mut media_engine: MediaEngine,
+    ice_config: &IceConfig,
+) -> Result<Arc<RTCPeerConnection>, RelayError> {
+    let registry = register_default_interceptors(Registry::new(), &mut media_engine)?;
+
+    let api = APIBuilder::new()
+        .with_media_engine(media_engine)
+        .with_interceptor_registry(registry)
+        .with_setting_engine(ice_config.setting_engine()?)
+        .build();
+
+    Ok(Arc::new(
+        api.new_peer_connection(ice_config.rtc_configuration())
+            .await?,
+    ))
+}
+
+/* Create the offer and wait until the ICE gathering is complete, the WHIP/WHEP
+endpoint gets all the candidates in one request. */
+async fn create_offer(
+    peer_connection: &RTCPeerConnection,
+) -> Result<RTCSessionDescription, RelayError> {
+    let offer = peer_connection.create_offer(None).await?;
+
+    let mut gather_complete = peer_connection.gathering_complete_promise().await;
+    peer_connection.set_local_description(offer).await?;
+    let _ = gather_complete.recv().await;
+
+    peer_connection.local_description().await.ok_or(RelayError {
+        value: RelayErrorValue::CanNotGetLocalDescription,
+    })
+}
+
+/* Wait until the relay is stopped or the peer connection is disconnected, the
+on_connected is called every time the peer connection is connected. */
+async fn wait_peer_connection(
+    state_receiver: &mut broadcast::Receiver<RTCPeerConnectionState>,
+    is_running: &AtomicBool,
+    mut on_connected: impl FnMut(),
+) -> Result<(), RelayError> {
+    while is_running.load(Ordering::Acquire) {
+        let state = tokio::select! {
+            state = state_receiver.recv() => state,
+            _ = sleep(CHECK_RUNNING_INTERVAL) => continue,
+        };
+
+        match state {
+            Ok(RTCPeerConnectionState::Connected) => on_connected(),
+            Ok(
+                state @ (RTCPeerConnectionState::Disconnected
+                | RTCPeerConnectionState::Failed
+                | RTCPeerConnectionState::Closed),
+            ) => {
+                return Err(RelayError {
+                    value: RelayErrorValue::PeerConnectionFailed(state.to_string()),
+                });
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+    }
+    Ok(())
+}
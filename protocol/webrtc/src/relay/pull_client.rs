@@ -0,0 +1,344 @@
+use {
+    super::{
+        create_offer,
+        errors::{RelayError, RelayErrorValue},
+        new_peer_connection, signaling, wait_peer_connection, DATA_CHANNEL_LABEL,
+    },
+    crate::{
+        data_channel::relay_messages,
+        ice_config::IceConfig,
+        session::WebRTCStreamHandler,
+        whip::{forward_track, spawn_pli_sender},
+    },
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    streamhub::{
+        define::{
+            FrameDataSender, NotifyInfo, PacketDataSender, PubDataType, PublishType, PublisherInfo,
+            StreamHubEvent, StreamHubEventSender,
+        },
+        stream::StreamIdentifier,
+        utils::{Backoff, RandomDigitCount, Uuid},
+    },
+    tokio::{
+        sync::{broadcast, oneshot},
+        time::sleep,
+    },
+    webrtc::{
+        api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS},
+        peer_connection::{
+            peer_connection_state::RTCPeerConnectionState,
+            sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+        },
+        rtp_transceiver::{
+            rtp_codec::{RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType},
+            rtp_transceiver_direction::RTCRtpTransceiverDirection,
+            RTCPFeedback, RTCRtpTransceiverInit,
+        },
+    },
+};
+
+/* Pull a stream from a remote WHEP endpoint and publish it as a local webrtc
+stream. The publication is kept during the reconnections, so the local
+subscribers stay connected during a short upstream outage. */
+pub struct WhepPullClient {
+    id: String,
+    url: String,
+    identifier: StreamIdentifier,
+    event_producer: StreamHubEventSender,
+    ice_config: IceConfig,
+    http_client: reqwest::Client,
+    //the bearer token of the remote endpoint
+    token: Option<String>,
+    publisher_info: PublisherInfo,
+    stream_handler: Arc<WebRTCStreamHandler>,
+    //the peer connection of the last session has been connected
+    is_connected: bool,
+}
+
+impl WhepPullClient {
+    pub fn new(
+        id: String,
+        url: String,
+        identifier: StreamIdentifier,
+        event_producer: StreamHubEventSender,
+        ice_config: IceConfig,
+        token: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            url: url.clone(),
+            identifier,
+            event_producer,
+            ice_config,
+            http_client: reqwest::Client::new(),
+            token,
+            publisher_info: PublisherInfo {
+                id: Uuid::new(RandomDigitCount::Zero),
+                pub_type: PublishType::WhepRelay,
+                pub_data_type: PubDataType::Both,
+                notify_info: NotifyInfo {
+                    request_url: url,
+                    remote_addr: String::from(""),
+                },
+            },
+            stream_handler: Arc::new(WebRTCStreamHandler::default()),
+            is_connected: false,
+        }
+    }
+
+    pub async fn supervise(mut self, mut backoff: Backoff, is_running: Arc<AtomicBool>) {
+        let (frame_sender, packet_sender) = match self.publish().await {
+            Ok(senders) => senders,
+            Err(err) => {
+                log::error!("whep pull client: {} publish error: {}", self.id, err);
+                self.release_id(&is_running);
+                return;
+            }
+        };
+
+        while is_running.load(Ordering::Acquire) {
+            if let Err(err) = self
+                .pull(frame_sender.clone(), packet_sender.clone(), &is_running)
+                .await
+            {
+                log::error!("whep pull client: {} pull error: {}", self.id, err);
+            }
+            if self.is_connected {
+                backoff.reset();
+            }
+
+            if !is_running.load(Ordering::Acquire) {
+                break;
+            }
+
+            let Some(delay) = backoff.next_delay() else {
+                log::error!(
+                    "whep pull client: {} gives up reconnecting to {} after {} retries",
+                    self.id,
+                    self.url,
+                    backoff.retries()
+                );
+                self.release_id(&is_running);
+                break;
+            };
+
+            log::info!(
+                "whep pull client: {} reconnects to {} in {:?}",
+                self.id,
+                self.url,
+                delay
+            );
+            sleep(delay).await;
+        }
+
+        self.unpublish();
+    }
+
+    async fn publish(&self) -> Result<(FrameDataSender, PacketDataSender), RelayError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        let publish_event = StreamHubEvent::Publish {
+            identifier: self.identifier.clone(),
+            result_sender,
+            info: self.publisher_info.clone(),
+            stream_handler: self.stream_handler.clone(),
+        };
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(RelayError {
+                value: RelayErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        match result_receiver.await?? {
+            (Some(frame_sender), Some(packet_sender), _) => Ok((frame_sender, packet_sender)),
+            _ => Err(RelayError {
+                value: RelayErrorValue::StreamHubEventSendErr,
+            }),
+        }
+    }
+
+    fn unpublish(&self) {
+        let unpublish_event = StreamHubEvent::UnPublish {
+            identifier: self.identifier.clone(),
+            info: self.publisher_info.clone(),
+        };
+        if self.event_producer.send(unpublish_event).is_err() {
+            log::error!("whep pull client: {} unpublish error", self.id);
+        }
+    }
+
+    //release the id in the stream hub if the client is not stopped by the api
+    fn release_id(&self, is_running: &AtomicBool) {
+        if is_running.swap(false, Ordering::AcqRel) {
+            let relay_stopped = StreamHubEvent::RelayStopped {
+                id: self.id.clone(),
+            };
+            if self.event_producer.send(relay_stopped).is_err() {
+                log::error!("whep pull client: {} send relay stopped error", self.id);
+            }
+        }
+    }
+
+    //the codecs with the payload types which the whip forwarders depend on
+    fn new_media_engine() -> Result<MediaEngine, RelayError> {
+        let mut m = MediaEngine::default();
+
+        m.register_codec(
+            RTCRtpCodecParameters {
+                capability: RTCRtpCodecCapability {
+                    mime_type: MIME_TYPE_H264.to_owned(),
+                    clock_rate: 90000,
+                    channels: 0,
+                    sdp_fmtp_line:
+                        "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f"
+                            .to_owned(),
+                    rtcp_feedback: vec![
+                        RTCPFeedback {
+                            typ: "ccm".to_owned(),
+                            parameter: "fir".to_owned(),
+                        },
+                        RTCPFeedback {
+                            typ: "nack".to_owned(),
+                            parameter: "".to_owned(),
+                        },
+                        RTCPFeedback {
+                            typ: "nack".to_owned(),
+                            parameter: "pli".to_owned(),
+                        },
+                    ],
+                },
+                payload_type: 96,
+                ..Default::default()
+            },
+            RTPCodecType::Video,
+        )?;
+        m.register_codec(
+            RTCRtpCodecParameters {
+                capability: RTCRtpCodecCapability {
+                    mime_type: MIME_TYPE_OPUS.to_owned(),
+                    clock_rate: 48000,
+                    channels: 2,
+                    sdp_fmtp_line: "minptime=10;useinbandfec=1".to_owned(),
+                    rtcp_feedback: vec![],
+                },
+                payload_type: 111,
+                ..Default::default()
+            },
+            RTPCodecType::Audio,
+        )?;
+
+        Ok(m)
+    }
+
+    async fn pull(
+        &mut self,
+        frame_sender: FrameDataSender,
+        packet_sender: PacketDataSender,
+        is_running: &AtomicBool,
+    ) -> Result<(), RelayError> {
+        self.is_connected = false;
+
+        let peer_connection =
+            new_peer_connection(Self::new_media_engine()?, &self.ice_config).await?;
+        let result = self
+            .run_peer_connection(&peer_connection, frame_sender, packet_sender, is_running)
+            .await;
+
+        if let Err(err) = peer_connection.close().await {
+            log::error!("whep pull client: {} close error: {}", self.id, err);
+        }
+        result
+    }
+
+    async fn run_peer_connection(
+        &mut self,
+        peer_connection: &Arc<RTCPeerConnection>,
+        frame_sender: FrameDataSender,
+        packet_sender: PacketDataSender,
+        is_running: &AtomicBool,
+    ) -> Result<(), RelayError> {
+        for kind in [RTPCodecType::Audio, RTPCodecType::Video] {
+            peer_connection
+                .add_transceiver_from_kind(
+                    kind,
+                    Some(RTCRtpTransceiverInit {
+                        direction: RTCRtpTransceiverDirection::Recvonly,
+                        send_encodings: Vec::new(),
+                    }),
+                )
+                .await?;
+        }
+
+        // The text messages of the remote stream are received by the data channel
+        let data_channel = peer_connection
+            .create_data_channel(DATA_CHANNEL_LABEL, None)
+            .await?;
+        relay_messages(
+            &data_channel,
+            Some(frame_sender.clone()),
+            Some(packet_sender.clone()),
+        );
+
+        let (state_sender, mut state_receiver) = broadcast::channel(16);
+        peer_connection.on_peer_connection_state_change(Box::new(
+            move |state: RTCPeerConnectionState| {
+                let _ = state_sender.send(state);
+                Box::pin(async {})
+            },
+        ));
+
+        let offer = create_offer(peer_connection).await?;
+
+        //the codecs of the received tracks are the ones of the offer
+        let pc = Arc::downgrade(peer_connection);
+        let local_sdp = offer.clone();
+        let stream_handler = self.stream_handler.clone();
+        peer_connection.on_track(Box::new(move |track, _, _| {
+            spawn_pli_sender(pc.clone(), track.ssrc(), stream_handler.key_frame_notify());
+            tokio::spawn(forward_track(
+                track,
+                local_sdp.clone(),
+                frame_sender.clone(),
+                packet_sender.clone(),
+                None,
+                stream_handler.clone(),
+            ));
+            Box::pin(async {})
+        }));
+
+        let resource = signaling::post_offer(
+            &self.http_client,
+            &self.url,
+            self.token.as_deref(),
+            offer.sdp,
+        )
+        .await?;
+        let mut result = match RTCSessionDescription::answer(resource.answer) {
+            Ok(answer) => peer_connection
+                .set_remote_description(answer)
+                .await
+                .map_err(RelayError::from),
+            Err(err) => Err(RelayError::from(err)),
+        };
+
+        if result.is_ok() {
+            log::info!("whep pull client: {} pulls from {}", self.id, self.url);
+            result = wait_peer_connection(&mut state_receiver, is_running, || {
+                self.is_connected = true;
+            })
+            .await;
+        }
+
+        if let Some(url) = resource.url {
+            if let Err(err) =
+                signaling::delete_resource(&self.http_client, url, self.token.as_deref()).await
+            {
+                log::error!("whep pull client: {} delete error: {}", self.id, err);
+            }
+        }
+        result
+    }
+}
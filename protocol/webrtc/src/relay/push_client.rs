@@ -0,0 +1,355 @@
+use {
+    super::{
+        create_offer,
+        errors::{RelayError, RelayErrorValue},
+        new_peer_connection, signaling, wait_peer_connection, DATA_CHANNEL_LABEL,
+    },
+    crate::{
+        data_channel::SubscriberDataChannels,
+        ice_config::IceConfig,
+        whep::{forward_frames, forward_packets, KeyFrameRequester},
+    },
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    streamhub::{
+        define::{
+            DataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventSender, SubDataType,
+            SubscribeType, SubscriberInfo,
+        },
+        stream::StreamIdentifier,
+        utils::{Backoff, RandomDigitCount, Uuid},
+    },
+    tokio::{
+        sync::{broadcast, oneshot},
+        time::sleep,
+    },
+    webrtc::{
+        api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS},
+        peer_connection::{
+            peer_connection_state::RTCPeerConnectionState,
+            sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+        },
+        rtp_transceiver::rtp_codec::RTCRtpCodecCapability,
+        track::track_local::{track_local_static_rtp::TrackLocalStaticRTP, TrackLocal},
+    },
+};
+
+/* Push a local stream to a remote WHIP endpoint(e.g. a cloud SFU). The stream
+published by whip is pushed as the rtp packets, the other streams are subscribed
+as frames from the rtmp stream like a WHEP subscriber. */
+pub struct WhipPushClient {
+    id: String,
+    url: String,
+    //the local webrtc stream
+    identifier: StreamIdentifier,
+    event_producer: StreamHubEventSender,
+    ice_config: IceConfig,
+    http_client: reqwest::Client,
+    //the bearer token of the remote endpoint
+    token: Option<String>,
+    //the peer connection of the last session has been connected
+    is_connected: bool,
+}
+
+impl WhipPushClient {
+    pub fn new(
+        id: String,
+        url: String,
+        identifier: StreamIdentifier,
+        event_producer: StreamHubEventSender,
+        ice_config: IceConfig,
+        token: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            url,
+            identifier,
+            event_producer,
+            ice_config,
+            http_client: reqwest::Client::new(),
+            token,
+            is_connected: false,
+        }
+    }
+
+    pub async fn supervise(mut self, mut backoff: Backoff, is_running: Arc<AtomicBool>) {
+        while is_running.load(Ordering::Acquire) {
+            if let Err(err) = self.push(&is_running).await {
+                log::error!("whip push client: {} push error: {}", self.id, err);
+            }
+            if self.is_connected {
+                backoff.reset();
+            }
+
+            if !is_running.load(Ordering::Acquire) {
+                break;
+            }
+
+            let Some(delay) = backoff.next_delay() else {
+                log::error!(
+                    "whip push client: {} gives up reconnecting to {} after {} retries",
+                    self.id,
+                    self.url,
+                    backoff.retries()
+                );
+                self.release_id(&is_running);
+                break;
+            };
+
+            log::info!(
+                "whip push client: {} reconnects to {} in {:?}",
+                self.id,
+                self.url,
+                delay
+            );
+            sleep(delay).await;
+        }
+    }
+
+    //release the id in the stream hub if the client is not stopped by the api
+    fn release_id(&self, is_running: &AtomicBool) {
+        if is_running.swap(false, Ordering::AcqRel) {
+            let relay_stopped = StreamHubEvent::RelayStopped {
+                id: self.id.clone(),
+            };
+            if self.event_producer.send(relay_stopped).is_err() {
+                log::error!("whip push client: {} send relay stopped error", self.id);
+            }
+        }
+    }
+
+    async fn push(&mut self, is_running: &AtomicBool) -> Result<(), RelayError> {
+        self.is_connected = false;
+
+        let (identifier, subscriber_info, receiver) = self.subscribe().await?;
+
+        let result = match new_peer_connection(Self::new_media_engine()?, &self.ice_config).await {
+            Ok(peer_connection) => {
+                let key_frame_requester =
+                    KeyFrameRequester::new(identifier.clone(), self.event_producer.clone());
+                let result = self
+                    .run_peer_connection(
+                        &peer_connection,
+                        receiver,
+                        key_frame_requester,
+                        is_running,
+                    )
+                    .await;
+
+                if let Err(err) = peer_connection.close().await {
+                    log::error!("whip push client: {} close error: {}", self.id, err);
+                }
+                result
+            }
+            Err(err) => Err(err),
+        };
+
+        self.unsubscribe(identifier, subscriber_info);
+        result
+    }
+
+    fn new_media_engine() -> Result<MediaEngine, RelayError> {
+        let mut m = MediaEngine::default();
+        m.register_default_codecs()?;
+        Ok(m)
+    }
+
+    async fn run_peer_connection(
+        &mut self,
+        peer_connection: &Arc<RTCPeerConnection>,
+        receiver: DataReceiver,
+        key_frame_requester: KeyFrameRequester,
+        is_running: &AtomicBool,
+    ) -> Result<(), RelayError> {
+        let video_track = Arc::new(TrackLocalStaticRTP::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_H264.to_owned(),
+                ..Default::default()
+            },
+            "video".to_owned(),
+            "xiu".to_owned(),
+        ));
+        let audio_track = Arc::new(TrackLocalStaticRTP::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_OPUS.to_owned(),
+                ..Default::default()
+            },
+            "audio".to_owned(),
+            "xiu".to_owned(),
+        ));
+
+        let rtp_sender = peer_connection
+            .add_track(Arc::clone(&video_track) as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+        peer_connection
+            .add_track(Arc::clone(&audio_track) as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+
+        // The key frames requested by the remote endpoint are requested from the
+        // publisher of the local stream
+        let rtcp_key_frame_requester = key_frame_requester.clone();
+        tokio::spawn(async move {
+            let mut rtcp_buf = vec![0u8; 1500];
+            while let Ok((packets, _)) = rtp_sender.read(&mut rtcp_buf).await {
+                if packets
+                    .iter()
+                    .any(|packet| KeyFrameRequester::is_key_frame_request(&**packet))
+                {
+                    rtcp_key_frame_requester.request();
+                }
+            }
+        });
+
+        // The text messages of the stream are sent by the data channel
+        let data_channels = SubscriberDataChannels::default();
+        data_channels
+            .add(
+                peer_connection
+                    .create_data_channel(DATA_CHANNEL_LABEL, None)
+                    .await?,
+            )
+            .await;
+
+        let (state_sender, mut state_receiver) = broadcast::channel(16);
+        let forward_state_receiver = state_sender.subscribe();
+        peer_connection.on_peer_connection_state_change(Box::new(
+            move |state: RTCPeerConnectionState| {
+                let _ = state_sender.send(state);
+                Box::pin(async {})
+            },
+        ));
+
+        let offer = create_offer(peer_connection).await?;
+        let resource = signaling::post_offer(
+            &self.http_client,
+            &self.url,
+            self.token.as_deref(),
+            offer.sdp,
+        )
+        .await?;
+        let mut result = match RTCSessionDescription::answer(resource.answer) {
+            Ok(answer) => peer_connection
+                .set_remote_description(answer)
+                .await
+                .map_err(RelayError::from),
+            Err(err) => Err(RelayError::from(err)),
+        };
+
+        if result.is_ok() {
+            if let Some(receiver) = receiver.packet_receiver {
+                tokio::spawn(forward_packets(
+                    receiver,
+                    video_track,
+                    audio_track,
+                    data_channels,
+                    forward_state_receiver,
+                ));
+            } else if let Some(receiver) = receiver.frame_receiver {
+                tokio::spawn(forward_frames(
+                    receiver,
+                    video_track,
+                    audio_track,
+                    data_channels,
+                    forward_state_receiver,
+                ));
+            }
+
+            log::info!("whip push client: {} pushes to {}", self.id, self.url);
+            result = wait_peer_connection(&mut state_receiver, is_running, || {
+                //the remote endpoint needs a key frame to start forwarding
+                key_frame_requester.request();
+                self.is_connected = true;
+            })
+            .await;
+        }
+
+        if let Some(url) = resource.url {
+            if let Err(err) =
+                signaling::delete_resource(&self.http_client, url, self.token.as_deref()).await
+            {
+                log::error!("whip push client: {} delete error: {}", self.id, err);
+            }
+        }
+        result
+    }
+
+    /* Subscribe the webrtc stream as the rtp packets, or the rtmp stream with the
+    same names as frames if the stream is not published by whip. */
+    async fn subscribe(
+        &self,
+    ) -> Result<(StreamIdentifier, SubscriberInfo, DataReceiver), RelayError> {
+        let subscriber_info = self.get_subscriber_info(SubDataType::Packet);
+        match self
+            .subscribe_stream(self.identifier.clone(), subscriber_info.clone())
+            .await
+        {
+            Ok(receiver) => Ok((self.identifier.clone(), subscriber_info, receiver)),
+            Err(err) => {
+                let StreamIdentifier::WebRTC {
+                    app_name,
+                    stream_name,
+                } = &self.identifier
+                else {
+                    return Err(err);
+                };
+                log::info!(
+                    "whip push client: {} the webrtc stream is not found: {}, try the rtmp stream",
+                    self.id,
+                    err
+                );
+
+                let identifier = StreamIdentifier::Rtmp {
+                    app_name: app_name.clone(),
+                    stream_name: stream_name.clone(),
+                };
+                let subscriber_info = self.get_subscriber_info(SubDataType::Frame);
+                let receiver = self
+                    .subscribe_stream(identifier.clone(), subscriber_info.clone())
+                    .await?;
+                Ok((identifier, subscriber_info, receiver))
+            }
+        }
+    }
+
+    async fn subscribe_stream(
+        &self,
+        identifier: StreamIdentifier,
+        info: SubscriberInfo,
+    ) -> Result<DataReceiver, RelayError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier,
+            info,
+            result_sender,
+        };
+        if self.event_producer.send(subscribe_event).is_err() {
+            return Err(RelayError {
+                value: RelayErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        Ok(result_receiver.await??.0)
+    }
+
+    fn unsubscribe(&self, identifier: StreamIdentifier, info: SubscriberInfo) {
+        let unsubscribe_event = StreamHubEvent::UnSubscribe { identifier, info };
+        if self.event_producer.send(unsubscribe_event).is_err() {
+            log::error!("whip push client: {} unsubscribe error", self.id);
+        }
+    }
+
+    fn get_subscriber_info(&self, sub_data_type: SubDataType) -> SubscriberInfo {
+        SubscriberInfo {
+            id: Uuid::new(RandomDigitCount::Zero),
+            sub_type: SubscribeType::WhipRelay,
+            sub_data_type,
+            notify_info: NotifyInfo {
+                request_url: self.url.clone(),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+}
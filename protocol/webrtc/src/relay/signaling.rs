@@ -0,0 +1,101 @@
+use {
+    super::errors::{RelayError, RelayErrorValue},
+    reqwest::{header, Client, RequestBuilder, StatusCode, Url},
+};
+
+/* The WHIP/WHEP resource created on the remote endpoint, it is deleted when the
+relay session ends. */
+pub struct RemoteResource {
+    pub answer: String,
+    //the url of the resource, the endpoint may not return it
+    pub url: Option<Url>,
+}
+
+/* Post the offer to a WHIP/WHEP endpoint and get the answer, the relative url of
+the Location header is resolved against the endpoint url. */
+pub async fn post_offer(
+    client: &Client,
+    endpoint: &str,
+    token: Option<&str>,
+    offer: String,
+) -> Result<RemoteResource, RelayError> {
+    let endpoint_url = parse_url(endpoint)?;
+
+    let request = client
+        .post(endpoint_url.clone())
+        .header(header::CONTENT_TYPE, "application/sdp")
+        .body(offer);
+    let response = with_token(request, token).send().await?;
+
+    let status = response.status();
+    if status != StatusCode::CREATED && status != StatusCode::OK {
+        return Err(RelayError {
+            value: RelayErrorValue::UnexpectedStatus(status.as_u16()),
+        });
+    }
+
+    let url = response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(|location| resolve_location(&endpoint_url, location));
+    let answer = response.text().await?;
+
+    Ok(RemoteResource { answer, url })
+}
+
+pub async fn delete_resource(
+    client: &Client,
+    url: Url,
+    token: Option<&str>,
+) -> Result<(), RelayError> {
+    let response = with_token(client.delete(url), token).send().await?;
+
+    let status = response.status();
+    if !status.is_success() && status != StatusCode::NOT_FOUND {
+        return Err(RelayError {
+            value: RelayErrorValue::UnexpectedStatus(status.as_u16()),
+        });
+    }
+    Ok(())
+}
+
+//the endpoints which require the authentication accept the bearer token
+fn with_token(request: RequestBuilder, token: Option<&str>) -> RequestBuilder {
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+fn parse_url(url: &str) -> Result<Url, RelayError> {
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(url),
+        _ => Err(RelayError {
+            value: RelayErrorValue::InvalidUrl(url.to_string()),
+        }),
+    }
+}
+
+fn resolve_location(endpoint_url: &Url, location: &str) -> Option<Url> {
+    endpoint_url.join(location).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_url, resolve_location};
+
+    #[test]
+    fn test_resolve_location() {
+        let endpoint = parse_url("http://127.0.0.1:8083/whep?app=live&stream=test").unwrap();
+
+        let url = resolve_location(&endpoint, "/session/1234").unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:8083/session/1234");
+
+        let url = resolve_location(&endpoint, "https://sfu.example.com/resource/1").unwrap();
+        assert_eq!(url.as_str(), "https://sfu.example.com/resource/1");
+
+        assert!(parse_url("rtmp://127.0.0.1/live/test").is_err());
+        assert!(parse_url("127.0.0.1:8083/whep").is_err());
+    }
+}
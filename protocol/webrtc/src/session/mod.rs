@@ -1,8 +1,9 @@
 pub mod errors;
 use streamhub::{
     define::{
-        DataSender, InformationSender, NotifyInfo, PublishType, PublisherInfo, StreamHubEvent,
-        StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler,
+        DataReceiver, DataSender, FrameDataSender, Information, InformationSender, NotifyInfo,
+        PacketData, PacketDataSender, PubDataType, PublishType, PublisherInfo, StreamHubEvent,
+        StreamHubEventSender, SubDataType, SubscribeType, SubscriberInfo, TStreamHandler,
     },
     errors::StreamHubError,
     statistics::StatisticsStream,
@@ -10,7 +11,7 @@ use streamhub::{
     utils::{RandomDigitCount, Uuid},
 };
 use tokio::sync::Mutex;
-use tokio::sync::{broadcast, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
 
 use bytesio::bytesio::TNetIO;
 use bytesio::bytesio::TcpIO;
@@ -26,7 +27,11 @@ use commonlib::http::Unmarshal as HttpUnmarshal;
 
 use commonlib::auth::Auth;
 
-use super::whep::handle_whep;
+use super::gop_cache::RtpGopCache;
+use super::ice_config::IceConfig;
+use super::simulcast::{layer_stream_name, parse_simulcast_rids, LayerEvent, LayerEventSender};
+use super::trickle_ice::{replace_ice_credentials, IceFragment, TRICKLE_ICE_SDPFRAG};
+use super::whep::{handle_whep, KeyFrameRequester, SimulcastReceiver};
 use super::whip::handle_whip;
 use async_trait::async_trait;
 
@@ -36,9 +41,14 @@ use bytesio::bytes_writer::AsyncBytesWriter;
 use errors::SessionError;
 use errors::SessionErrorValue;
 use http::StatusCode;
+use webrtc::error::Error as RTCError;
+use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::{sdp::session_description::RTCSessionDescription, RTCPeerConnection};
 
+//the link relation of the layer selection url of the WHEP resources
+const WHEP_LAYER_EXTENSION: &str = "urn:ietf:params:whep:ext:core:layer";
+
 pub struct WebRTCServerSession {
     io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
     reader: BytesReader,
@@ -47,11 +57,24 @@ pub struct WebRTCServerSession {
     event_sender: StreamHubEventSender,
     stream_handler: Arc<WebRTCStreamHandler>,
 
-    pub session_id: Option<Uuid>,
+    //the id of the WHIP/WHEP resource in the url, it is random and not guessable
+    pub session_id: Option<String>,
+    //the id of the publisher or subscriber in the stream hub
+    client_id: Uuid,
+    app_name: String,
+    stream_name: String,
+    is_subscriber: bool,
     pub http_request_data: Option<HttpRequest>,
     pub peer_connection: Option<Arc<RTCPeerConnection>>,
+    //the entity tag of the resource, it is changed by the ICE restarts
+    etag: Option<String>,
+    //the rids of the published simulcast layers or the subscribed ones
+    simulcast_rids: Vec<String>,
+    layer_event_sender: Option<LayerEventSender>,
+    pc_state_receiver: Option<broadcast::Receiver<RTCPeerConnectionState>>,
 
     auth: Option<Auth>,
+    ice_config: Arc<IceConfig>,
 }
 
 impl WebRTCServerSession {
@@ -59,6 +82,7 @@ impl WebRTCServerSession {
         stream: TcpStream,
         event_producer: StreamHubEventSender,
         auth: Option<Auth>,
+        ice_config: Arc<IceConfig>,
     ) -> Self {
         let net_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
         let io = Arc::new(Mutex::new(net_io));
@@ -70,9 +94,18 @@ impl WebRTCServerSession {
             event_sender: event_producer,
             stream_handler: Arc::new(WebRTCStreamHandler::default()),
             session_id: None,
+            client_id: Uuid::new(RandomDigitCount::Six),
+            app_name: String::new(),
+            stream_name: String::new(),
+            is_subscriber: false,
             http_request_data: None,
             peer_connection: None,
+            etag: None,
+            simulcast_rids: Vec::new(),
+            layer_event_sender: None,
+            pc_state_receiver: None,
             auth,
+            ice_config,
         }
     }
 
@@ -83,9 +116,45 @@ impl WebRTCServerSession {
         Ok(())
     }
 
+    /* Close the peer connection of the resource and unpublish the WHIP streams,
+    the WHEP subscribers are unsubscribed when their peer connections are closed. */
+    pub async fn close(&self) -> Result<(), SessionError> {
+        if let Err(err) = self.close_peer_connection().await {
+            log::error!("close peer connection failed: {}", err);
+        } else {
+            log::info!("close peer connection successfully.");
+        }
+
+        if !self.is_subscriber {
+            for rid in &self.simulcast_rids {
+                Self::unpublish_whip(
+                    self.app_name.clone(),
+                    layer_stream_name(&self.stream_name, rid),
+                    self.get_publisher_info(),
+                    self.event_sender.clone(),
+                )?;
+            }
+            Self::unpublish_whip(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.get_publisher_info(),
+                self.event_sender.clone(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /* The states of the peer connection, they are watched to remove the resource
+    when the peer connection fails or is closed. */
+    pub fn take_peer_connection_state_receiver(
+        &mut self,
+    ) -> Option<broadcast::Receiver<RTCPeerConnectionState>> {
+        self.pc_state_receiver.take()
+    }
+
     pub async fn run(
         &mut self,
-        uuid_2_sessions: Arc<Mutex<HashMap<Uuid, Arc<Mutex<WebRTCServerSession>>>>>,
+        uuid_2_sessions: Arc<Mutex<HashMap<String, Arc<Mutex<WebRTCServerSession>>>>>,
     ) -> Result<(), SessionError> {
         while self.reader.len() < 4 {
             let data = self.io.lock().await.read().await?;
@@ -165,7 +234,28 @@ impl WebRTCServerSession {
 
             log::info!("1:{},2:{},3:{}", t, app_name, stream_name);
 
+            let bearer_carrier = http_request
+                .get_header(&"Authorization".to_string())
+                .map(|header| SecretCarrier::Bearer(header.to_string()));
+            let query_carrier = http_request
+                .uri
+                .query
+                .as_ref()
+                .map(|q| SecretCarrier::Query(q.to_string()));
+
+            let token_carrier = bearer_carrier.or(query_carrier);
+
             match request_method {
+                http_method_name::POST if t.eq_ignore_ascii_case("whep/layer") => {
+                    let response = match self
+                        .find_session(&http_request, &uuid_2_sessions, &token_carrier)
+                        .await
+                    {
+                        Ok(session) => Self::handle_layer_select(&http_request, session).await,
+                        Err(status_code) => Self::gen_response(status_code),
+                    };
+                    self.send_response(&response).await?;
+                }
                 http_method_name::POST => {
                     let sdp_data = if let Some(body) = http_request.body.as_ref() {
                         body
@@ -174,26 +264,17 @@ impl WebRTCServerSession {
                             value: errors::SessionErrorValue::HttpRequestEmptySdp,
                         });
                     };
-                    self.session_id = Some(Uuid::new(RandomDigitCount::Zero));
+                    let session_id = format!("{:032x}", rand::random::<u128>());
+                    self.session_id = Some(session_id.clone());
 
+                    //the url of the created resource for the PATCH and DELETE requests
                     let path = format!(
-                        "{}?{}&session_id={}",
-                        http_request.uri.path,
-                        http_request.uri.query.as_ref().unwrap(),
-                        self.session_id.unwrap()
+                        "{}?app={}&stream={}&session_id={}",
+                        http_request.uri.path, app_name, stream_name, session_id
                     );
                     let offer = RTCSessionDescription::offer(sdp_data.clone())?;
-
-                    let bearer_carrier = http_request
-                        .get_header(&"Authorization".to_string())
-                        .map(|header| SecretCarrier::Bearer(header.to_string()));
-                    let query_carrier = http_request
-                        .uri
-                        .query
-                        .as_ref()
-                        .map(|q| SecretCarrier::Query(q.to_string()));
-
-                    let token_carrier = bearer_carrier.or(query_carrier);
+                    self.app_name = app_name.clone();
+                    self.stream_name = stream_name.clone();
 
                     match t.to_lowercase().as_str() {
                         "whip" => {
@@ -207,7 +288,9 @@ impl WebRTCServerSession {
                             if let Some(auth) = &self.auth {
                                 auth.authenticate(&stream_name, &token_carrier, true)?;
                             }
-                            self.subscribe_whep(app_name, stream_name, path, offer)
+                            self.is_subscriber = true;
+                            let layer = pars_map.get("layer").cloned();
+                            self.subscribe_whep(app_name, stream_name, path, offer, layer)
                                 .await?;
                         }
                         _ => {
@@ -226,55 +309,39 @@ impl WebRTCServerSession {
                     self.send_response(&Self::gen_response(http::StatusCode::OK))
                         .await?
                 }
-                http_method_name::PATCH => {}
+                http_method_name::PATCH => {
+                    let response = match self
+                        .find_session(&http_request, &uuid_2_sessions, &token_carrier)
+                        .await
+                    {
+                        Ok(session) => Self::handle_patch(&http_request, session).await,
+                        Err(status_code) => Self::gen_response(status_code),
+                    };
+                    self.send_response(&response).await?;
+                }
                 http_method_name::DELETE => {
-                    if let Some(session_id) = pars_map.get("session_id") {
-                        if let Some(uuid) = Uuid::from_str2(session_id) {
-                            //stop the running session and delete it.
-                            let mut uuid_2_sessions_unlock = uuid_2_sessions.lock().await;
-                            if let Some(session) = uuid_2_sessions_unlock.get(&uuid) {
-                                if let Err(err) = session.lock().await.close_peer_connection().await
-                                {
-                                    log::error!("close peer connection failed: {}", err);
-                                } else {
-                                    log::info!("close peer connection successfully.");
-                                }
-                                uuid_2_sessions_unlock.remove(&uuid);
-                            } else {
-                                log::warn!("the session :{}  is not exited.", uuid);
+                    let status_code = match self
+                        .find_session(&http_request, &uuid_2_sessions, &token_carrier)
+                        .await
+                    {
+                        Ok(session) => {
+                            if let Some(session_id) = pars_map.get("session_id") {
+                                uuid_2_sessions.lock().await.remove(session_id);
                             }
+                            //stop the running session and delete it.
+                            session.lock().await.close().await?;
+                            http::StatusCode::OK
                         }
-                    } else {
-                        log::error!(
-                            "the delete path does not contain session id: {}?{}",
-                            http_request.uri.path,
-                            http_request.uri.query.as_ref().unwrap()
-                        );
-                    }
-
-                    match t.to_lowercase().as_str() {
-                        "whip" => {
-                            Self::unpublish_whip(
-                                app_name,
-                                stream_name,
-                                self.get_publisher_info(),
-                                self.event_sender.clone(),
-                            )?;
-                        }
-                        "whep" => {}
-                        _ => {
-                            log::error!(
-                                "current path: {}, method: {}",
+                        Err(status_code) => {
+                            log::warn!(
+                                "the session of {} cannot be deleted: {}",
                                 http_request.uri.path,
-                                t.to_lowercase()
+                                status_code
                             );
-                            return Err(SessionError {
-                                value: errors::SessionErrorValue::HttpRequestNotSupported,
-                            });
+                            status_code
                         }
-                    }
+                    };
 
-                    let status_code = http::StatusCode::OK;
                     let response = Self::gen_response(status_code);
                     self.send_response(&response).await?;
                 }
@@ -299,40 +366,62 @@ impl WebRTCServerSession {
         path: String,
         offer: RTCSessionDescription,
     ) -> Result<(), SessionError> {
-        let (event_result_sender, event_result_receiver) = oneshot::channel();
-
-        let publish_event = StreamHubEvent::Publish {
-            identifier: StreamIdentifier::WebRTC {
-                app_name,
-                stream_name,
-            },
-            result_sender: event_result_sender,
-            info: self.get_publisher_info(),
-            stream_handler: self.stream_handler.clone(),
-        };
-
-        if self.event_sender.send(publish_event).is_err() {
-            return Err(SessionError {
-                value: SessionErrorValue::StreamHubEventSendErr,
-            });
+        let simulcast_rids = parse_simulcast_rids(&offer.sdp);
+        self.stream_handler
+            .set_simulcast_rids(simulcast_rids.clone())
+            .await;
+
+        let (frame_sender, packet_sender) = self
+            .publish_stream(
+                StreamIdentifier::WebRTC {
+                    app_name: app_name.clone(),
+                    stream_name: stream_name.clone(),
+                },
+                self.get_publisher_info(),
+                self.stream_handler.clone(),
+            )
+            .await?;
+
+        //every simulcast layer is published as a packet stream
+        let mut layer_senders = Vec::new();
+        for rid in simulcast_rids {
+            let info = PublisherInfo {
+                pub_type: PublishType::WhipSimulcastLayer,
+                pub_data_type: PubDataType::Packet,
+                ..self.get_publisher_info()
+            };
+            let identifier = StreamIdentifier::WebRTC {
+                app_name: app_name.clone(),
+                stream_name: layer_stream_name(&stream_name, &rid),
+            };
+            let layer_handler = Arc::new(self.stream_handler.new_layer_handler());
+            if let (_, Some(layer_sender)) = self
+                .publish_stream(identifier, info, layer_handler)
+                .await?
+            {
+                self.simulcast_rids.push(rid.clone());
+                layer_senders.push((rid, layer_sender));
+            }
         }
 
-        let sender = event_result_receiver.await??;
-
-        let response = match handle_whip(offer, sender.0, sender.1).await {
+        let (pc_state_sender, pc_state_receiver) = broadcast::channel(8);
+        self.pc_state_receiver = Some(pc_state_receiver);
+
+        let response = match handle_whip(
+            offer,
+            frame_sender,
+            packet_sender,
+            layer_senders,
+            self.stream_handler.clone(),
+            pc_state_sender,
+            &self.ice_config,
+        )
+        .await
+        {
             Ok((session_description, peer_connection)) => {
                 self.peer_connection = Some(peer_connection);
 
-                let status_code = http::StatusCode::CREATED;
-                let mut response = Self::gen_response(status_code);
-
-                response
-                    .headers
-                    .insert("Content-Type".to_string(), "application/sdp".to_string());
-                response.headers.insert("Location".to_string(), path);
-                response.body = Some(session_description.sdp);
-
-                response
+                self.gen_created_response(path, session_description.sdp)
             }
             Err(err) => {
                 log::error!("handle whip err: {}", err);
@@ -344,6 +433,31 @@ impl WebRTCServerSession {
         self.send_response(&response).await
     }
 
+    async fn publish_stream(
+        &self,
+        identifier: StreamIdentifier,
+        info: PublisherInfo,
+        stream_handler: Arc<WebRTCStreamHandler>,
+    ) -> Result<(Option<FrameDataSender>, Option<PacketDataSender>), SessionError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let publish_event = StreamHubEvent::Publish {
+            identifier,
+            result_sender: event_result_sender,
+            info,
+            stream_handler,
+        };
+
+        if self.event_sender.send(publish_event).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        let sender = event_result_receiver.await??;
+        Ok((sender.0, sender.1))
+    }
+
     fn unpublish_whip(
         app_name: String,
         stream_name: String,
@@ -373,36 +487,77 @@ impl WebRTCServerSession {
         stream_name: String,
         path: String,
         offer: RTCSessionDescription,
+        layer: Option<String>,
     ) -> Result<(), SessionError> {
-        let subscriber_info = self.get_subscriber_info();
-
-        let (event_result_sender, event_result_receiver) = oneshot::channel();
-
-        let subscribe_event = StreamHubEvent::Subscribe {
-            identifier: StreamIdentifier::WebRTC {
-                app_name: app_name.clone(),
-                stream_name: stream_name.clone(),
-            },
-            info: subscriber_info.clone(),
-            result_sender: event_result_sender,
+        let webrtc_identifier = StreamIdentifier::WebRTC {
+            app_name: app_name.clone(),
+            stream_name: stream_name.clone(),
+        };
+        let subscriber_info = self.get_subscriber_info(SubDataType::Packet);
+
+        //the streams which are not published by whip are subscribed as frames
+        //from the rtmp stream(rtmp, httpflv relay and rtsp remuxed).
+        let (identifier, subscriber_info, receiver) = match self
+            .subscribe_stream(webrtc_identifier.clone(), subscriber_info.clone())
+            .await
+        {
+            Ok(receiver) => (webrtc_identifier, subscriber_info, receiver),
+            Err(err) => {
+                log::info!(
+                    "subscribe whep: the webrtc stream is not found: {}, try the rtmp stream",
+                    err
+                );
+                let rtmp_identifier = StreamIdentifier::Rtmp {
+                    app_name,
+                    stream_name,
+                };
+                let subscriber_info = self.get_subscriber_info(SubDataType::Frame);
+                let receiver = self
+                    .subscribe_stream(rtmp_identifier.clone(), subscriber_info.clone())
+                    .await?;
+                (rtmp_identifier, subscriber_info, receiver)
+            }
         };
 
-        if self.event_sender.send(subscribe_event).is_err() {
-            return Err(SessionError {
-                value: SessionErrorValue::StreamHubEventSendErr,
-            });
+        let mut identifiers = vec![identifier.clone()];
+        let simulcast = if matches!(subscriber_info.sub_data_type, SubDataType::Packet) {
+            self.subscribe_simulcast_layers(&identifier, &subscriber_info, layer)
+                .await
+        } else {
+            None
+        };
+        if let StreamIdentifier::WebRTC {
+            app_name,
+            stream_name,
+        } = &identifier
+        {
+            for rid in &self.simulcast_rids {
+                identifiers.push(StreamIdentifier::WebRTC {
+                    app_name: app_name.clone(),
+                    stream_name: layer_stream_name(stream_name, rid),
+                });
+            }
         }
 
-        let receiver = event_result_receiver.await??.0.packet_receiver.unwrap();
-
-        let (pc_state_sender, mut pc_state_receiver) = broadcast::channel(1);
-
-        let response = match handle_whep(offer, receiver, pc_state_sender).await {
+        //the layer selection url of the simulcast stream
+        let layer_path = path.replacen('?', "/layer?", 1);
+        let key_frame_requester = KeyFrameRequester::new(identifier, self.event_sender.clone());
+        let (pc_state_sender, mut pc_state_receiver) = broadcast::channel(8);
+        self.pc_state_receiver = Some(pc_state_sender.subscribe());
+
+        let response = match handle_whep(
+            offer,
+            receiver,
+            simulcast,
+            key_frame_requester.clone(),
+            pc_state_sender,
+            &self.ice_config,
+        )
+        .await
+        {
             Ok((session_description, peer_connection)) => {
                 let pc_clone = peer_connection.clone();
 
-                let app_name_out = app_name.clone();
-                let stream_name_out = stream_name.clone();
                 let subscriber_info_out = subscriber_info.clone();
                 let sender_out = self.event_sender.clone();
 
@@ -411,20 +566,25 @@ impl WebRTCServerSession {
                         if let Ok(state) = pc_state_receiver.recv().await {
                             log::info!("state: {}", state);
                             match state {
-                                RTCPeerConnectionState::Disconnected
-                                | RTCPeerConnectionState::Failed => {
+                                //the joined subscriber needs a key frame to start playing
+                                RTCPeerConnectionState::Connected => {
+                                    key_frame_requester.request();
+                                }
+                                //a disconnected peer connection may recover by an ICE restart
+                                RTCPeerConnectionState::Failed => {
                                     if let Err(err) = pc_clone.close().await {
                                         log::error!("peer connection close error: {}", err);
                                     }
                                 }
                                 RTCPeerConnectionState::Closed => {
-                                    if let Err(err) = Self::unsubscribe_whep(
-                                        app_name_out,
-                                        stream_name_out,
-                                        subscriber_info_out,
-                                        sender_out,
-                                    ) {
-                                        log::error!("unsubscribe whep error: {}", err);
+                                    for identifier in identifiers {
+                                        if let Err(err) = Self::unsubscribe_whep(
+                                            identifier,
+                                            subscriber_info_out.clone(),
+                                            sender_out.clone(),
+                                        ) {
+                                            log::error!("unsubscribe whep error: {}", err);
+                                        }
                                     }
                                     break;
                                 }
@@ -438,14 +598,13 @@ impl WebRTCServerSession {
 
                 self.peer_connection = Some(peer_connection);
 
-                let status_code = http::StatusCode::CREATED;
-                let mut response = Self::gen_response(status_code);
-                response
-                    .headers
-                    .insert("Content-Type".to_string(), "application/sdp".to_string());
-                response.headers.insert("Location".to_string(), path);
-                response.body = Some(session_description.sdp);
-                log::info!("before whep 1");
+                let mut response = self.gen_created_response(path, session_description.sdp);
+                if !self.simulcast_rids.is_empty() {
+                    response.headers.insert(
+                        "Link".to_string(),
+                        format!("<{layer_path}>; rel=\"{WHEP_LAYER_EXTENSION}\""),
+                    );
+                }
                 response
             }
             Err(err) => {
@@ -457,17 +616,101 @@ impl WebRTCServerSession {
         self.send_response(&response).await
     }
 
+    /* Subscribe the simulcast layers of a whip stream, the layer is the initially
+    selected one, or all the layers are selected by the bandwidth if it is None. */
+    async fn subscribe_simulcast_layers(
+        &mut self,
+        identifier: &StreamIdentifier,
+        subscriber_info: &SubscriberInfo,
+        layer: Option<String>,
+    ) -> Option<SimulcastReceiver> {
+        let StreamIdentifier::WebRTC {
+            app_name,
+            stream_name,
+        } = identifier
+        else {
+            return None;
+        };
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let request_event = StreamHubEvent::Request {
+            identifier: identifier.clone(),
+            sender,
+        };
+        if self.event_sender.send(request_event).is_err() {
+            return None;
+        }
+        let Some(Information::SimulcastLayers { rids }) = receiver.recv().await else {
+            return None;
+        };
+
+        let mut layers = Vec::new();
+        for rid in rids {
+            let layer_identifier = StreamIdentifier::WebRTC {
+                app_name: app_name.clone(),
+                stream_name: layer_stream_name(stream_name, &rid),
+            };
+            match self
+                .subscribe_stream(layer_identifier, subscriber_info.clone())
+                .await
+            {
+                Ok(layer_receiver) => {
+                    if let Some(packet_receiver) = layer_receiver.packet_receiver {
+                        self.simulcast_rids.push(rid.clone());
+                        layers.push((rid, packet_receiver));
+                    }
+                }
+                Err(err) => {
+                    log::warn!("subscribe the simulcast layer {} error: {}", rid, err);
+                }
+            }
+        }
+        if layers.is_empty() {
+            return None;
+        }
+
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        if let Some(rid) = layer.filter(|rid| self.simulcast_rids.contains(rid)) {
+            let _ = event_sender.send(LayerEvent::Select { rid: Some(rid) });
+        }
+        self.layer_event_sender = Some(event_sender.clone());
+
+        Some(SimulcastReceiver {
+            layers,
+            event_sender,
+            event_receiver,
+        })
+    }
+
+    async fn subscribe_stream(
+        &self,
+        identifier: StreamIdentifier,
+        subscriber_info: SubscriberInfo,
+    ) -> Result<DataReceiver, SessionError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier,
+            info: subscriber_info,
+            result_sender: event_result_sender,
+        };
+
+        if self.event_sender.send(subscribe_event).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        Ok(event_result_receiver.await??.0)
+    }
+
     fn unsubscribe_whep(
-        app_name: String,
-        stream_name: String,
+        identifier: StreamIdentifier,
         subscriber_info: SubscriberInfo,
         sender: StreamHubEventSender,
     ) -> Result<(), SessionError> {
         let unsubscribe_event = StreamHubEvent::UnSubscribe {
-            identifier: StreamIdentifier::WebRTC {
-                app_name,
-                stream_name,
-            },
+            identifier,
             info: subscriber_info,
         };
 
@@ -479,17 +722,11 @@ impl WebRTCServerSession {
         Ok(())
     }
 
-    fn get_subscriber_info(&self) -> SubscriberInfo {
-        let id = if let Some(session_id) = &self.session_id {
-            *session_id
-        } else {
-            Uuid::new(RandomDigitCount::Zero)
-        };
-
+    fn get_subscriber_info(&self, sub_data_type: SubDataType) -> SubscriberInfo {
         SubscriberInfo {
-            id,
+            id: self.client_id,
             sub_type: SubscribeType::WhepPull,
-            sub_data_type: streamhub::define::SubDataType::Packet,
+            sub_data_type,
             notify_info: NotifyInfo {
                 request_url: String::from(""),
                 remote_addr: String::from(""),
@@ -498,14 +735,8 @@ impl WebRTCServerSession {
     }
 
     fn get_publisher_info(&self) -> PublisherInfo {
-        let id = if let Some(session_id) = &self.session_id {
-            *session_id
-        } else {
-            Uuid::new(RandomDigitCount::Zero)
-        };
-
         PublisherInfo {
-            id,
+            id: self.client_id,
             pub_type: PublishType::WhipPush,
             pub_data_type: streamhub::define::PubDataType::Both,
             notify_info: NotifyInfo {
@@ -515,6 +746,197 @@ impl WebRTCServerSession {
         }
     }
 
+    /* Find the resource of a PATCH, DELETE or layer selection request, the request
+    is authenticated with the token like the POST request which created the resource. */
+    async fn find_session(
+        &self,
+        http_request: &HttpRequest,
+        uuid_2_sessions: &Mutex<HashMap<String, Arc<Mutex<WebRTCServerSession>>>>,
+        token_carrier: &Option<SecretCarrier>,
+    ) -> Result<Arc<Mutex<WebRTCServerSession>>, http::StatusCode> {
+        let session = match http_request.query_pairs.get("session_id") {
+            Some(session_id) => uuid_2_sessions.lock().await.get(session_id).cloned(),
+            None => None,
+        };
+        let Some(session) = session else {
+            return Err(http::StatusCode::NOT_FOUND);
+        };
+
+        if let Some(auth) = &self.auth {
+            let session_unlock = session.lock().await;
+            if auth
+                .authenticate(
+                    &session_unlock.stream_name,
+                    token_carrier,
+                    session_unlock.is_subscriber,
+                )
+                .is_err()
+            {
+                return Err(http::StatusCode::UNAUTHORIZED);
+            }
+        }
+        Ok(session)
+    }
+
+    /* Select the video layer of a WHEP resource of a simulcast stream, the layer is
+    a rid or "auto" for the selection by the bandwidth of the client. */
+    async fn handle_layer_select(
+        http_request: &HttpRequest,
+        session: Arc<Mutex<WebRTCServerSession>>,
+    ) -> HttpResponse {
+        let Some(layer) = http_request.query_pairs.get("layer") else {
+            return Self::gen_response(http::StatusCode::BAD_REQUEST);
+        };
+        let status_code = if session.lock().await.select_layer(layer) {
+            http::StatusCode::NO_CONTENT
+        } else {
+            http::StatusCode::BAD_REQUEST
+        };
+        Self::gen_response(status_code)
+    }
+
+    fn select_layer(&self, layer: &str) -> bool {
+        let rid = match layer {
+            "auto" => None,
+            rid if self.simulcast_rids.iter().any(|r| r == rid) => Some(rid.to_string()),
+            _ => return false,
+        };
+        match &self.layer_event_sender {
+            Some(sender) => sender.send(LayerEvent::Select { rid }).is_ok(),
+            None => false,
+        }
+    }
+
+    /* Trickle the ICE candidates of the client or restart the ICE, the answer
+    fragment is responded for the ICE restart with the new entity tag. */
+    async fn handle_patch(
+        http_request: &HttpRequest,
+        session: Arc<Mutex<WebRTCServerSession>>,
+    ) -> HttpResponse {
+        let content_type = Self::get_header(http_request, "Content-Type").unwrap_or_default();
+        if !content_type.starts_with(TRICKLE_ICE_SDPFRAG) {
+            return Self::gen_response(http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+
+        let mut session = session.lock().await;
+        //"*" matches any entity tag, it is used by the clients for the ICE restarts
+        if let Some(if_match) = Self::get_header(http_request, "If-Match") {
+            if if_match != "*" && Some(if_match) != session.etag.as_deref() {
+                return Self::gen_response(http::StatusCode::PRECONDITION_FAILED);
+            }
+        }
+
+        let fragment = IceFragment::unmarshal(http_request.body.as_deref().unwrap_or_default());
+        match session.patch_ice(fragment).await {
+            Ok(Some(answer_fragment)) => {
+                let mut response = Self::gen_response(http::StatusCode::OK);
+                response
+                    .headers
+                    .insert("Content-Type".to_string(), TRICKLE_ICE_SDPFRAG.to_string());
+                if let Some(etag) = &session.etag {
+                    response.headers.insert("ETag".to_string(), etag.clone());
+                }
+                response.body = Some(answer_fragment);
+                response
+            }
+            Ok(None) => Self::gen_response(http::StatusCode::NO_CONTENT),
+            Err(err) => {
+                log::error!("patch ice error: {}", err);
+                Self::gen_response(http::StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+
+    async fn patch_ice(&mut self, fragment: IceFragment) -> Result<Option<String>, SessionError> {
+        let peer_connection = self
+            .peer_connection
+            .clone()
+            .ok_or(RTCError::ErrConnectionClosed)?;
+        let remote_description = peer_connection
+            .remote_description()
+            .await
+            .ok_or(RTCError::ErrNoRemoteDescription)?;
+
+        let current_ice_ufrag = IceFragment::unmarshal(&remote_description.sdp).ice_ufrag;
+        if !fragment.is_ice_restart(&current_ice_ufrag) {
+            Self::add_ice_candidates(&peer_connection, &fragment).await?;
+            return Ok(None);
+        }
+
+        //the ICE is restarted when the remote offer with the new credentials is set
+        let offer_sdp = replace_ice_credentials(
+            &remote_description.sdp,
+            fragment.ice_ufrag.as_deref().unwrap_or_default(),
+            fragment.ice_pwd.as_deref().unwrap_or_default(),
+        );
+        peer_connection
+            .set_remote_description(RTCSessionDescription::offer(offer_sdp)?)
+            .await?;
+        Self::add_ice_candidates(&peer_connection, &fragment).await?;
+
+        let answer = peer_connection.create_answer(None).await?;
+        let mut gather_complete = peer_connection.gathering_complete_promise().await;
+        peer_connection.set_local_description(answer).await?;
+        let _ = gather_complete.recv().await;
+
+        let local_description = peer_connection
+            .local_description()
+            .await
+            .ok_or(RTCError::ErrNoRemoteDescription)?;
+        let mut answer_fragment = IceFragment::unmarshal(&local_description.sdp);
+        answer_fragment.end_of_candidates = true;
+
+        self.etag = Some(Self::gen_etag());
+        log::info!("ice restarted, session id: {:?}", self.session_id);
+
+        Ok(Some(answer_fragment.marshal()))
+    }
+
+    async fn add_ice_candidates(
+        peer_connection: &RTCPeerConnection,
+        fragment: &IceFragment,
+    ) -> Result<(), SessionError> {
+        for media in &fragment.medias {
+            for candidate in &media.candidates {
+                peer_connection
+                    .add_ice_candidate(RTCIceCandidateInit {
+                        candidate: candidate.clone(),
+                        sdp_mid: media.mid.clone(),
+                        sdp_mline_index: None,
+                        username_fragment: fragment.ice_ufrag.clone(),
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_etag() -> String {
+        format!("\"{}\"", Uuid::new(RandomDigitCount::Six))
+    }
+
+    fn get_header<'a>(http_request: &'a HttpRequest, name: &str) -> Option<&'a str> {
+        http_request
+            .headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, header_value)| header_value.as_str())
+    }
+
+    fn gen_created_response(&mut self, path: String, sdp: String) -> HttpResponse {
+        let etag = Self::gen_etag();
+        self.etag = Some(etag.clone());
+
+        let mut response = Self::gen_response(http::StatusCode::CREATED);
+        response
+            .headers
+            .insert("Content-Type".to_string(), "application/sdp".to_string());
+        response.headers.insert("Location".to_string(), path);
+        response.headers.insert("ETag".to_string(), etag);
+        response.body = Some(sdp);
+        response
+    }
+
     fn gen_response(status_code: StatusCode) -> HttpResponse {
         let reason_phrase = if let Some(reason) = status_code.canonical_reason() {
             reason.to_string()
@@ -534,11 +956,16 @@ impl WebRTCServerSession {
             .insert("Access-Control-Allow-Origin".to_owned(), "*".to_owned());
         response.headers.insert(
             "Access-Control-Allow-Headers".to_owned(),
-            "content-type".to_owned(),
+            "content-type, authorization, if-match".to_owned(),
+        );
+        response.headers.insert(
+            "Access-Control-Allow-Methods".to_owned(),
+            "POST, PATCH, DELETE, OPTIONS".to_owned(),
+        );
+        response.headers.insert(
+            "Access-Control-Expose-Headers".to_owned(),
+            "location, etag, link".to_owned(),
         );
-        response
-            .headers
-            .insert("Access-Control-Allow-Method".to_owned(), "POST".to_owned());
         response
     }
 
@@ -571,9 +998,32 @@ impl WebRTCServerSession {
 pub struct WebRTCStreamHandler {
     sps: Mutex<Vec<u8>>,
     pps: Mutex<Vec<u8>>,
+    simulcast_rids: Mutex<Vec<String>>,
+    gop_cache: Mutex<RtpGopCache>,
+    //the video tracks of the whip stream send a PLI to the publisher when notified
+    key_frame_notify: Arc<Notify>,
 }
 
 impl WebRTCStreamHandler {
+    //the handler of a simulcast layer shares the key frame requests of its whip stream
+    pub fn new_layer_handler(&self) -> Self {
+        Self {
+            key_frame_notify: self.key_frame_notify.clone(),
+            ..Default::default()
+        }
+    }
+    pub fn key_frame_notify(&self) -> Arc<Notify> {
+        self.key_frame_notify.clone()
+    }
+    pub async fn save_packet_data(&self, data: &PacketData, is_key_frame: bool) {
+        self.gop_cache
+            .lock()
+            .await
+            .save_packet_data(data, is_key_frame);
+    }
+    pub async fn set_simulcast_rids(&self, rids: Vec<String>) {
+        *self.simulcast_rids.lock().await = rids;
+    }
     pub async fn set_sps(&self, sps: Vec<u8>) {
         *self.sps.lock().await = sps;
     }
@@ -586,14 +1036,33 @@ impl WebRTCStreamHandler {
 impl TStreamHandler for WebRTCStreamHandler {
     async fn send_prior_data(
         &self,
-        _data_sender: DataSender,
+        data_sender: DataSender,
         _sub_type: SubscribeType,
     ) -> Result<(), StreamHubError> {
+        if let DataSender::Packet { sender } = data_sender {
+            for packet_data in self.gop_cache.lock().await.get_packet_data() {
+                if sender.send(packet_data).is_err() {
+                    break;
+                }
+            }
+        }
         Ok(())
     }
     async fn get_statistic_data(&self) -> Option<StatisticsStream> {
         None
     }
 
-    async fn send_information(&self, _sender: InformationSender) {}
+    async fn send_information(&self, sender: InformationSender) {
+        let rids = self.simulcast_rids.lock().await.clone();
+        if rids.is_empty() {
+            return;
+        }
+        if let Err(err) = sender.send(Information::SimulcastLayers { rids }) {
+            log::error!("send simulcast layers error: {}", err);
+        }
+    }
+
+    async fn request_key_frame(&self) {
+        self.key_frame_notify.notify_waiters();
+    }
 }
@@ -0,0 +1,468 @@
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use webrtc::rtcp::packet::Packet as RtcpPacket;
+use webrtc::rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use webrtc::rtcp::receiver_report::ReceiverReport;
+use webrtc::rtp::packet::Packet;
+
+//the simulcast layer of a whip stream is published as the stream "<stream>@<rid>"
+pub const LAYER_SEPARATOR: char = '@';
+//the rids from the highest to the lowest quality, both the h/m/l and f/h/q namings are ordered
+const RID_QUALITY_ORDER: [&str; 5] = ["f", "h", "m", "l", "q"];
+//a layer which has no packets in this duration is paused by the publisher
+const LAYER_INACTIVE_TIMEOUT: Duration = Duration::from_secs(2);
+const BITRATE_WINDOW: Duration = Duration::from_secs(1);
+//the timestamp gap of the first frame of the switched layer, one frame of 30fps
+const SWITCH_TIMESTAMP_GAP: u32 = 3000;
+
+pub type LayerEventSender = mpsc::UnboundedSender<LayerEvent>;
+pub type LayerEventReceiver = mpsc::UnboundedReceiver<LayerEvent>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerEvent {
+    /* The receiver estimated maximum bitrate(bps) of the RTCP REMB feedback. */
+    Remb { bitrate: u64 },
+    /* The fraction lost of the RTCP receiver reports. */
+    FractionLost { fraction_lost: u8 },
+    /* Select a layer manually, None switches back to the automatic selection. */
+    Select { rid: Option<String> },
+}
+
+impl LayerEvent {
+    //the bandwidth feedbacks of the subscriber in the rtcp packets of the video sender
+    pub fn from_rtcp(packet: &(dyn RtcpPacket + Send + Sync)) -> Option<Self> {
+        let packet = packet.as_any();
+        if let Some(remb) = packet.downcast_ref::<ReceiverEstimatedMaximumBitrate>() {
+            return Some(LayerEvent::Remb {
+                bitrate: remb.bitrate as u64,
+            });
+        }
+        packet
+            .downcast_ref::<ReceiverReport>()
+            .and_then(|report| report.reports.iter().map(|r| r.fraction_lost).max())
+            .map(|fraction_lost| LayerEvent::FractionLost { fraction_lost })
+    }
+}
+
+pub fn layer_stream_name(stream_name: &str, rid: &str) -> String {
+    format!("{stream_name}{LAYER_SEPARATOR}{rid}")
+}
+
+/* Parse the send rids of the video media of a WHIP offer(RFC 8851/8853), the
+rids are sorted from the highest to the lowest quality. */
+pub fn parse_simulcast_rids(sdp: &str) -> Vec<String> {
+    let mut is_video = false;
+    let mut rids: Vec<String> = Vec::new();
+    let mut simulcast_rids: Vec<String> = Vec::new();
+
+    for line in sdp.lines() {
+        let line = line.trim_end();
+        if let Some(media) = line.strip_prefix("m=") {
+            is_video = media.starts_with("video");
+        } else if !is_video {
+            continue;
+        } else if let Some(rid) = line.strip_prefix("a=rid:") {
+            //a=rid:<rid-id> <direction> [params]
+            let mut fields = rid.split_whitespace();
+            if let (Some(id), Some("send")) = (fields.next(), fields.next()) {
+                rids.push(id.to_string());
+            }
+        } else if let Some(simulcast) = line.strip_prefix("a=simulcast:send ") {
+            //a=simulcast:send h;~m;l, the alternatives are separated by ',' and the
+            //paused ones are prefixed with '~'
+            simulcast_rids = simulcast
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .split([';', ','])
+                .map(|rid| rid.trim_start_matches('~').to_string())
+                .collect();
+        }
+    }
+
+    //the rids not listed in the simulcast attribute are not sent
+    if !simulcast_rids.is_empty() {
+        rids.retain(|rid| simulcast_rids.contains(rid));
+    }
+
+    let quality_rank = |rid: &String| {
+        RID_QUALITY_ORDER
+            .iter()
+            .position(|known| known == rid)
+            .unwrap_or(RID_QUALITY_ORDER.len())
+    };
+    //the stable sort keeps the offer order of the unknown rids
+    rids.sort_by_key(quality_rank);
+    rids
+}
+
+// Check if the h264 rtp payload(RFC 6184) is the start of a key frame, the
+// subscribers can only switch to another layer from a key frame.
+pub fn is_h264_keyframe(payload: &[u8]) -> bool {
+    const SPS: u8 = 7;
+    const IDR: u8 = 5;
+    const STAP_A: u8 = 24;
+    const FU_A: u8 = 28;
+
+    let Some(indicator) = payload.first() else {
+        return false;
+    };
+
+    match indicator & 0x1F {
+        SPS | IDR => true,
+        STAP_A => {
+            //[indicator][size(2 bytes)][nalu]...
+            let mut offset = 1;
+            while offset + 2 < payload.len() {
+                let size = u16::from_be_bytes([payload[offset], payload[offset + 1]]) as usize;
+                let nal_type = payload[offset + 2] & 0x1F;
+                if nal_type == SPS || nal_type == IDR {
+                    return true;
+                }
+                offset += 2 + size;
+            }
+            false
+        }
+        FU_A => match payload.get(1) {
+            //the start bit and the nalu type of the fu header
+            Some(header) => header & 0x80 != 0 && header & 0x1F == IDR,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+struct LayerStatistics {
+    bytes: u64,
+    window_start: Option<Instant>,
+    last_packet: Option<Instant>,
+    //bits per second of the last full window
+    bitrate: u64,
+}
+
+impl LayerStatistics {
+    fn on_packet(&mut self, size: usize, now: Instant) {
+        self.last_packet = Some(now);
+        self.bytes += size as u64;
+
+        let window_start = *self.window_start.get_or_insert(now);
+        let elapsed = now.duration_since(window_start);
+        if elapsed >= BITRATE_WINDOW {
+            self.bitrate = self.bytes * 8 * 1000 / elapsed.as_millis() as u64;
+            self.bytes = 0;
+            self.window_start = Some(now);
+        }
+    }
+
+    fn is_active(&self, now: Instant) -> bool {
+        matches!(self.last_packet, Some(last) if now.duration_since(last) < LAYER_INACTIVE_TIMEOUT)
+    }
+}
+
+// Select the video layer of a WHEP subscriber by the estimated bandwidth or
+// manually, and rewrite the rtp sequence numbers and timestamps so that the
+// switched layers are continuous for the player.
+pub struct LayerSelector {
+    //sorted from the highest to the lowest quality
+    rids: Vec<String>,
+    statistics: Vec<LayerStatistics>,
+    //the forwarded layer and the layer to switch to on its next key frame
+    current: Option<usize>,
+    target: usize,
+    manual: Option<usize>,
+    estimated_bitrate: Option<u64>,
+    //a key frame of the target layer is needed to switch to it
+    key_frame_requested: bool,
+
+    started: bool,
+    last_sequence_number: u16,
+    last_timestamp: u32,
+    sequence_number_offset: u16,
+    timestamp_offset: u32,
+}
+
+impl LayerSelector {
+    pub fn new(rids: Vec<String>) -> Self {
+        let statistics = rids.iter().map(|_| LayerStatistics::default()).collect();
+        Self {
+            rids,
+            statistics,
+            current: None,
+            target: 0,
+            manual: None,
+            estimated_bitrate: None,
+            key_frame_requested: false,
+            started: false,
+            last_sequence_number: 0,
+            last_timestamp: 0,
+            sequence_number_offset: 0,
+            timestamp_offset: 0,
+        }
+    }
+
+    pub fn current_rid(&self) -> Option<&str> {
+        self.current.map(|idx| self.rids[idx].as_str())
+    }
+
+    pub fn take_key_frame_request(&mut self) -> bool {
+        std::mem::take(&mut self.key_frame_requested)
+    }
+
+    //return false if the rid is not a layer of the stream
+    pub fn on_event(&mut self, event: LayerEvent, now: Instant) -> bool {
+        match event {
+            LayerEvent::Remb { bitrate } => {
+                self.estimated_bitrate = Some(bitrate);
+            }
+            LayerEvent::FractionLost { fraction_lost } => {
+                self.on_fraction_lost(fraction_lost);
+            }
+            LayerEvent::Select { rid } => match rid {
+                Some(rid) => match self.rids.iter().position(|layer| *layer == rid) {
+                    Some(idx) => self.manual = Some(idx),
+                    None => return false,
+                },
+                None => self.manual = None,
+            },
+        }
+        self.update_target(now);
+        true
+    }
+
+    /* The loss based bandwidth estimation of the Google congestion control, the
+    estimation is decreased when more than 10% packets are lost and is increased
+    by 5% when less than 2% packets are lost. */
+    fn on_fraction_lost(&mut self, fraction_lost: u8) {
+        let current_bitrate = match self.current {
+            Some(idx) => self.statistics[idx].bitrate,
+            None => return,
+        };
+        let loss = fraction_lost as f64 / 256.0;
+
+        let estimated_bitrate = self.estimated_bitrate.unwrap_or(current_bitrate);
+        if loss > 0.1 {
+            let decreased = (current_bitrate as f64 * (1.0 - 0.5 * loss)) as u64;
+            self.estimated_bitrate = Some(estimated_bitrate.min(decreased));
+        } else if loss < 0.02 && self.estimated_bitrate.is_some() {
+            self.estimated_bitrate = Some((estimated_bitrate as f64 * 1.05) as u64);
+        }
+    }
+
+    fn update_target(&mut self, now: Instant) {
+        let target = self.target;
+        self.select_target(now);
+        if self.target != target && self.current != Some(self.target) {
+            self.key_frame_requested = true;
+        }
+    }
+
+    fn select_target(&mut self, now: Instant) {
+        if let Some(manual) = self.manual {
+            self.target = manual;
+            return;
+        }
+
+        let active: Vec<usize> = (0..self.rids.len())
+            .filter(|idx| self.statistics[*idx].is_active(now))
+            .collect();
+        let (Some(highest), Some(lowest)) = (active.first(), active.last()) else {
+            return;
+        };
+
+        self.target = match self.estimated_bitrate {
+            //keep 10% of the bandwidth for the audio and the retransmissions
+            Some(estimated_bitrate) => *active
+                .iter()
+                .find(|idx| self.statistics[**idx].bitrate * 10 <= estimated_bitrate * 9)
+                .unwrap_or(lowest),
+            None => *highest,
+        };
+    }
+
+    /* Handle a video packet of the layer, the packet is rewritten and true is
+    returned if it should be forwarded to the subscriber. */
+    pub fn on_video_packet(&mut self, layer: usize, packet: &mut Packet, now: Instant) -> bool {
+        if layer >= self.rids.len() {
+            return false;
+        }
+
+        let was_active = self.statistics[layer].is_active(now);
+        self.statistics[layer].on_packet(packet.payload.len(), now);
+        //a layer is resumed or stopped by the publisher
+        let current_inactive =
+            matches!(self.current, Some(idx) if !self.statistics[idx].is_active(now));
+        if !was_active || current_inactive || self.current.is_none() {
+            self.update_target(now);
+        }
+
+        if self.current != Some(self.target)
+            && layer == self.target
+            && is_h264_keyframe(&packet.payload)
+        {
+            self.switch_to(layer, packet);
+        }
+
+        if self.current != Some(layer) {
+            return false;
+        }
+
+        packet.header.sequence_number = packet
+            .header
+            .sequence_number
+            .wrapping_add(self.sequence_number_offset);
+        packet.header.timestamp = packet.header.timestamp.wrapping_add(self.timestamp_offset);
+        //the extension ids are negotiated with the publisher, not the subscriber
+        packet.header.extension = false;
+        packet.header.extension_profile = 0;
+        packet.header.extensions.clear();
+
+        //only move forward for the out of order packets
+        if packet
+            .header
+            .sequence_number
+            .wrapping_sub(self.last_sequence_number)
+            < 0x8000
+        {
+            self.last_sequence_number = packet.header.sequence_number;
+            self.last_timestamp = packet.header.timestamp;
+        }
+        true
+    }
+
+    fn switch_to(&mut self, layer: usize, packet: &Packet) {
+        if self.started {
+            self.sequence_number_offset = self
+                .last_sequence_number
+                .wrapping_add(1)
+                .wrapping_sub(packet.header.sequence_number);
+            self.timestamp_offset = self
+                .last_timestamp
+                .wrapping_add(SWITCH_TIMESTAMP_GAP)
+                .wrapping_sub(packet.header.timestamp);
+        } else {
+            //the first packet is the start of the sequence
+            self.last_sequence_number = packet.header.sequence_number.wrapping_sub(1);
+            self.started = true;
+        }
+        log::info!(
+            "switch simulcast layer from {:?} to {}",
+            self.current_rid(),
+            self.rids[layer]
+        );
+        self.current = Some(layer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_h264_keyframe, parse_simulcast_rids, LayerEvent, LayerSelector};
+    use bytes::Bytes;
+    use std::time::{Duration, Instant};
+    use webrtc::rtp::packet::Packet;
+
+    fn packet(sequence_number: u16, timestamp: u32, payload: &[u8]) -> Packet {
+        let mut packet = Packet {
+            payload: Bytes::copy_from_slice(payload),
+            ..Default::default()
+        };
+        packet.header.sequence_number = sequence_number;
+        packet.header.timestamp = timestamp;
+        packet
+    }
+
+    #[test]
+    fn test_parse_simulcast_rids() {
+        let sdp = "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=rid:x send\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 96\r\na=rid:l send\r\na=rid:h send\r\n\
+            a=rid:m send max-width=640\r\na=rid:r recv\r\na=simulcast:send l;~h;m\r\n";
+        assert_eq!(parse_simulcast_rids(sdp), vec!["h", "m", "l"]);
+
+        let sdp = "m=video 9 UDP/TLS/RTP/SAVPF 96\r\na=rid:q send\r\na=rid:f send\r\n\
+            a=rid:h send\r\na=simulcast:send q;h;f\r\n";
+        assert_eq!(parse_simulcast_rids(sdp), vec!["f", "h", "q"]);
+
+        assert!(parse_simulcast_rids("m=video 9 UDP/TLS/RTP/SAVPF 96\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_is_h264_keyframe() {
+        assert!(is_h264_keyframe(&[0x65, 0x88]));
+        assert!(!is_h264_keyframe(&[0x41, 0x9a]));
+        //stap-a with the sps and pps
+        assert!(is_h264_keyframe(&[
+            0x78, 0x00, 0x02, 0x67, 0x42, 0x00, 0x01, 0x68
+        ]));
+        //fu-a start and middle fragments of an idr
+        assert!(is_h264_keyframe(&[0x7C, 0x85, 0x88]));
+        assert!(!is_h264_keyframe(&[0x7C, 0x05, 0x88]));
+        assert!(!is_h264_keyframe(&[]));
+    }
+
+    #[test]
+    fn test_layer_switch() {
+        let mut selector = LayerSelector::new(vec!["h".to_string(), "l".to_string()]);
+        let now = Instant::now();
+
+        //wait for the key frame of the highest layer
+        assert!(!selector.on_video_packet(0, &mut packet(100, 1000, &[0x41]), now));
+        assert!(!selector.on_video_packet(1, &mut packet(500, 9000, &[0x65]), now));
+        assert!(selector.on_video_packet(0, &mut packet(101, 1000, &[0x65]), now));
+        assert!(selector.on_video_packet(0, &mut packet(102, 4000, &[0x41]), now));
+        assert_eq!(selector.current_rid(), Some("h"));
+
+        assert!(selector.on_event(
+            LayerEvent::Select {
+                rid: Some("l".to_string())
+            },
+            now
+        ));
+        assert!(selector.take_key_frame_request());
+        assert!(!selector.on_event(
+            LayerEvent::Select {
+                rid: Some("m".to_string())
+            },
+            now
+        ));
+        assert!(!selector.take_key_frame_request());
+
+        //keep the current layer until the key frame of the target layer
+        assert!(!selector.on_video_packet(1, &mut packet(501, 12000, &[0x41]), now));
+        assert!(selector.on_video_packet(0, &mut packet(103, 4000, &[0x41]), now));
+
+        let mut keyframe = packet(502, 15000, &[0x65]);
+        assert!(selector.on_video_packet(1, &mut keyframe, now));
+        assert_eq!(keyframe.header.sequence_number, 104);
+        assert_eq!(keyframe.header.timestamp, 7000);
+        assert!(!selector.on_video_packet(0, &mut packet(104, 7000, &[0x41]), now));
+        assert_eq!(selector.current_rid(), Some("l"));
+    }
+
+    #[test]
+    fn test_bandwidth_selection() {
+        let mut selector = LayerSelector::new(vec!["h".to_string(), "l".to_string()]);
+        let start = Instant::now();
+
+        //2.4Mbps for the high layer and 240kbps for the low layer
+        for i in 0..=10u16 {
+            let now = start + Duration::from_millis(100 * i as u64);
+            selector.on_video_packet(0, &mut packet(i, 0, &[0x65; 30000]), now);
+            selector.on_video_packet(1, &mut packet(i, 0, &[0x65; 3000]), now);
+        }
+        assert_eq!(selector.current_rid(), Some("h"));
+
+        let now = start + Duration::from_millis(1100);
+        selector.on_event(LayerEvent::Remb { bitrate: 1_000_000 }, now);
+        selector.on_video_packet(1, &mut packet(11, 0, &[0x65]), now);
+        assert_eq!(selector.current_rid(), Some("l"));
+
+        selector.on_event(LayerEvent::Remb { bitrate: 5_000_000 }, now);
+        selector.on_video_packet(0, &mut packet(11, 0, &[0x65]), now);
+        assert_eq!(selector.current_rid(), Some("h"));
+
+        //half of the packets are lost
+        selector.on_event(LayerEvent::FractionLost { fraction_lost: 128 }, now);
+        selector.on_video_packet(1, &mut packet(12, 0, &[0x65]), now);
+        assert_eq!(selector.current_rid(), Some("l"));
+    }
+}
@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use bytesio::bytesio::{NetType, TNetIO};
+use bytesio::bytesio_errors::{BytesIOError, BytesIOErrorValue};
+use std::sync::Arc;
+use std::time::Duration;
+use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+use webrtc::track::track_local::TrackLocalWriter;
+
+// Write the marshalled rtp packets to a local track, so the rtp packers of
+// the rtsp protocol can be used to send the frames to the WebRTC clients.
+pub struct RtpTrackIO {
+    track: Arc<TrackLocalStaticRTP>,
+}
+
+impl RtpTrackIO {
+    pub fn new(track: Arc<TrackLocalStaticRTP>) -> Self {
+        Self { track }
+    }
+}
+
+#[async_trait]
+impl TNetIO for RtpTrackIO {
+    fn get_net_type(&self) -> NetType {
+        NetType::UDP
+    }
+
+    async fn write(&mut self, bytes: Bytes) -> Result<(), BytesIOError> {
+        self.track
+            .write(&bytes[..])
+            .await
+            .map_err(|err| BytesIOError {
+                value: BytesIOErrorValue::IOError(std::io::Error::other(err.to_string())),
+            })?;
+        Ok(())
+    }
+
+    //the rtcp packets are read from the rtp sender of the peer connection
+    async fn read_timeout(&mut self, _duration: Duration) -> Result<BytesMut, BytesIOError> {
+        self.read().await
+    }
+
+    async fn read(&mut self) -> Result<BytesMut, BytesIOError> {
+        Err(BytesIOError {
+            value: BytesIOErrorValue::NoneReturn,
+        })
+    }
+}
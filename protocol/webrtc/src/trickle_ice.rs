@@ -0,0 +1,148 @@
+//the media type of the PATCH body of the WHIP/WHEP resources(RFC 8840)
+pub const TRICKLE_ICE_SDPFRAG: &str = "application/trickle-ice-sdpfrag";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IceFragmentMedia {
+    pub kind: String,
+    pub mid: Option<String>,
+    //the candidate attributes without the "a=" prefix
+    pub candidates: Vec<String>,
+}
+
+// The ICE credentials and candidates of a trickle ICE sdp fragment, it can also
+// be parsed from a full sdp because the fragment is a subset of the sdp lines.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IceFragment {
+    pub ice_ufrag: Option<String>,
+    pub ice_pwd: Option<String>,
+    pub medias: Vec<IceFragmentMedia>,
+    pub end_of_candidates: bool,
+}
+
+impl IceFragment {
+    pub fn unmarshal(data: &str) -> Self {
+        let mut fragment = IceFragment::default();
+
+        for line in data.lines() {
+            let line = line.trim_end();
+            if let Some(media) = line.strip_prefix("m=") {
+                fragment.medias.push(IceFragmentMedia {
+                    kind: media.split(' ').next().unwrap_or_default().to_string(),
+                    ..Default::default()
+                });
+            } else if let Some(ice_ufrag) = line.strip_prefix("a=ice-ufrag:") {
+                fragment.ice_ufrag.get_or_insert(ice_ufrag.to_string());
+            } else if let Some(ice_pwd) = line.strip_prefix("a=ice-pwd:") {
+                fragment.ice_pwd.get_or_insert(ice_pwd.to_string());
+            } else if let Some(mid) = line.strip_prefix("a=mid:") {
+                if let Some(media) = fragment.medias.last_mut() {
+                    media.mid = Some(mid.to_string());
+                }
+            } else if let Some(candidate) = line.strip_prefix("a=") {
+                if candidate.starts_with("candidate:") {
+                    //the candidates before the first m line belong to the first media
+                    if fragment.medias.is_empty() {
+                        fragment.medias.push(IceFragmentMedia::default());
+                    }
+                    if let Some(media) = fragment.medias.last_mut() {
+                        media.candidates.push(candidate.to_string());
+                    }
+                } else if candidate == "end-of-candidates" {
+                    fragment.end_of_candidates = true;
+                }
+            }
+        }
+
+        fragment
+    }
+
+    pub fn marshal(&self) -> String {
+        let mut data = String::new();
+        if let Some(ice_ufrag) = &self.ice_ufrag {
+            data += &format!("a=ice-ufrag:{ice_ufrag}\r\n");
+        }
+        if let Some(ice_pwd) = &self.ice_pwd {
+            data += &format!("a=ice-pwd:{ice_pwd}\r\n");
+        }
+
+        for media in &self.medias {
+            data += &format!("m={} 9 RTP/AVP 0\r\n", media.kind);
+            if let Some(mid) = &media.mid {
+                data += &format!("a=mid:{mid}\r\n");
+            }
+            for candidate in &media.candidates {
+                data += &format!("a={candidate}\r\n");
+            }
+            if self.end_of_candidates {
+                data += "a=end-of-candidates\r\n";
+            }
+        }
+
+        data
+    }
+
+    //an ICE restart is requested if the credentials are changed
+    pub fn is_ice_restart(&self, current_ice_ufrag: &Option<String>) -> bool {
+        self.ice_ufrag.is_some() && self.ice_ufrag != *current_ice_ufrag
+    }
+}
+
+//replace the ICE credentials of the remote offer for the ICE restart, the
+//candidates of the old ICE session are removed.
+pub fn replace_ice_credentials(sdp: &str, ice_ufrag: &str, ice_pwd: &str) -> String {
+    let mut data = String::new();
+    for line in sdp.lines() {
+        let line = line.trim_end();
+        if line.starts_with("a=ice-ufrag:") {
+            data += &format!("a=ice-ufrag:{ice_ufrag}\r\n");
+        } else if line.starts_with("a=ice-pwd:") {
+            data += &format!("a=ice-pwd:{ice_pwd}\r\n");
+        } else if !line.starts_with("a=candidate:") && line != "a=end-of-candidates" {
+            data += line;
+            data += "\r\n";
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_ice_credentials, IceFragment};
+
+    #[test]
+    fn test_ice_fragment() {
+        let data = "a=ice-ufrag:EsAw\r\na=ice-pwd:P2uYro0UCOQ4zxjKXaWCBui1\r\n\
+            m=audio 9 RTP/AVP 0\r\na=mid:0\r\n\
+            a=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0\r\n\
+            a=candidate:3471623853 1 udp 2122194687 198.51.100.2 61765 typ host generation 0\r\n\
+            a=end-of-candidates\r\n";
+
+        let fragment = IceFragment::unmarshal(data);
+        assert_eq!(fragment.ice_ufrag.as_deref(), Some("EsAw"));
+        assert_eq!(
+            fragment.ice_pwd.as_deref(),
+            Some("P2uYro0UCOQ4zxjKXaWCBui1")
+        );
+        assert_eq!(fragment.medias.len(), 1);
+        assert_eq!(fragment.medias[0].kind, "audio");
+        assert_eq!(fragment.medias[0].mid.as_deref(), Some("0"));
+        assert_eq!(fragment.medias[0].candidates.len(), 2);
+        assert!(fragment.end_of_candidates);
+        assert_eq!(fragment.marshal(), data);
+
+        assert!(!fragment.is_ice_restart(&Some(String::from("EsAw"))));
+        assert!(fragment.is_ice_restart(&Some(String::from("ysXw"))));
+        assert!(!IceFragment::default().is_ice_restart(&Some(String::from("ysXw"))));
+    }
+
+    #[test]
+    fn test_replace_ice_credentials() {
+        let sdp = "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=ice-ufrag:EsAw\r\na=ice-pwd:P2uY\r\n\
+            a=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host\r\na=mid:0\r\n";
+
+        assert_eq!(
+            replace_ice_credentials(sdp, "ysXw", "vw5L"),
+            "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=ice-ufrag:ysXw\r\na=ice-pwd:vw5L\r\na=mid:0\r\n"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use streamhub::define::StreamHubEventSender;
 
+use super::ice_config::IceConfig;
 use super::session::WebRTCServerSession;
 
 use commonlib::auth::Auth;
@@ -7,31 +8,40 @@ use commonlib::define::http_method_name;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use streamhub::utils::Uuid;
 use tokio::io::Error;
 use tokio::net::TcpListener;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::Mutex;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 
 pub struct WebRTCServer {
     address: String,
     event_producer: StreamHubEventSender,
-    uuid_2_sessions: Arc<Mutex<HashMap<Uuid, Arc<Mutex<WebRTCServerSession>>>>>,
+    uuid_2_sessions: Arc<Mutex<HashMap<String, Arc<Mutex<WebRTCServerSession>>>>>,
     auth: Option<Auth>,
+    ice_config: Arc<IceConfig>,
 }
 
 impl WebRTCServer {
-    pub fn new(address: String, event_producer: StreamHubEventSender, auth: Option<Auth>) -> Self {
+    pub fn new(
+        address: String,
+        event_producer: StreamHubEventSender,
+        auth: Option<Auth>,
+        ice_config: IceConfig,
+    ) -> Self {
         Self {
             address,
             event_producer,
             uuid_2_sessions: Arc::new(Mutex::new(HashMap::new())),
             auth,
+            ice_config: Arc::new(ice_config),
         }
     }
 
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
+        Arc::make_mut(&mut self.ice_config).bind_udp_mux().await?;
 
         log::info!("WebRTC server listening on tcp://{}", socket_addr);
         loop {
@@ -40,6 +50,7 @@ impl WebRTCServer {
                 tcp_stream,
                 self.event_producer.clone(),
                 self.auth.clone(),
+                self.ice_config.clone(),
             )));
             let uuid_2_sessions = self.uuid_2_sessions.clone();
             tokio::spawn(async move {
@@ -52,9 +63,22 @@ impl WebRTCServer {
                     let mut uuid_2_session_unlock = uuid_2_sessions.lock().await;
 
                     match http_request_data.method.as_str() {
+                        //the resource is kept for the PATCH and DELETE requests
                         http_method_name::POST => {
-                            if let Some(uuid) = session_unlock.session_id {
-                                uuid_2_session_unlock.insert(uuid, session.clone());
+                            if let (Some(session_id), Some(_)) = (
+                                session_unlock.session_id.clone(),
+                                &session_unlock.peer_connection,
+                            ) {
+                                uuid_2_session_unlock.insert(session_id.clone(), session.clone());
+                                if let Some(state_receiver) =
+                                    session_unlock.take_peer_connection_state_receiver()
+                                {
+                                    Self::watch_peer_connection(
+                                        uuid_2_sessions.clone(),
+                                        session_id,
+                                        state_receiver,
+                                    );
+                                }
                             }
                         }
                         http_method_name::OPTIONS => {}
@@ -66,4 +90,30 @@ impl WebRTCServer {
             });
         }
     }
+
+    /* Remove the resource when its peer connection fails or is closed, otherwise
+    it is kept until a DELETE request which may never come. */
+    fn watch_peer_connection(
+        uuid_2_sessions: Arc<Mutex<HashMap<String, Arc<Mutex<WebRTCServerSession>>>>>,
+        session_id: String,
+        mut state_receiver: broadcast::Receiver<RTCPeerConnectionState>,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                match state_receiver.recv().await {
+                    Ok(RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed)
+                    | Err(RecvError::Closed) => break,
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                }
+            }
+
+            let session = uuid_2_sessions.lock().await.remove(&session_id);
+            if let Some(session) = session {
+                log::info!("remove the webrtc session: {}", session_id);
+                if let Err(err) = session.lock().await.close().await {
+                    log::error!("close webrtc session error: {}", err);
+                }
+            }
+        });
+    }
 }
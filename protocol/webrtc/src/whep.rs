@@ -1,20 +1,43 @@
 use super::errors::WebRTCError;
 use super::errors::WebRTCErrorValue;
+use super::ice_config::IceConfig;
 
+use super::aac2opus::Aac2OpusTranscoder;
+use super::data_channel::SubscriberDataChannels;
+use super::errors::Aac2OpusError;
+use super::simulcast::{LayerEvent, LayerEventReceiver, LayerEventSender, LayerSelector};
+use super::track_io::RtpTrackIO;
+use bytes::{Bytes, BytesMut};
+use bytesio::bytes_writer::AsyncBytesWriter;
+use bytesio::bytesio::TNetIO;
 use std::sync::Arc;
+use std::time::Instant;
+use streamhub::define::DataReceiver;
+use streamhub::define::FrameData;
+use streamhub::define::FrameDataReceiver;
 use streamhub::define::PacketData;
 use streamhub::define::PacketDataReceiver;
+use streamhub::define::{StreamHubEvent, StreamHubEventSender};
+use streamhub::stream::StreamIdentifier;
+use tokio::sync::{mpsc, Mutex};
+use webrtc::util::Unmarshal;
+use xflv::demuxer::{FlvAudioTagDemuxer, FlvVideoTagDemuxer};
+use xrtsp::rtp::utils::{Marshal as RtpMarshal, TPacker};
+use xrtsp::rtp::{rtp_h264::RtpH264Packer, RtpPacket};
 
 use webrtc::api::interceptor_registry::register_default_interceptors;
 use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS};
 use webrtc::api::APIBuilder;
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
-use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::interceptor::registry::Registry;
-use webrtc::peer_connection::configuration::RTCConfiguration;
 
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtcp::packet::Packet as RtcpPacket;
+use webrtc::rtcp::payload_feedbacks::full_intra_request::FullIntraRequest;
+use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use webrtc::rtp::header::Header;
+use webrtc::rtp::packet::Packet;
 
 use tokio::sync::broadcast;
 use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
@@ -23,12 +46,61 @@ use webrtc::track::track_local::TrackLocal;
 use webrtc::track::track_local::TrackLocalWriter;
 
 pub type Result<T> = std::result::Result<T, WebRTCError>;
+
+//keep the rtp packets smaller than the usual path mtu with the srtp overhead
+const RTP_MTU: usize = 1200;
+const H264_CLOCK_RATE: u32 = 90000;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 
+// Request a key frame from the publisher of the subscribed stream through the
+// stream hub, it is requested when the subscriber joins or the client sends a
+// PLI/FIR.
+#[derive(Clone)]
+pub struct KeyFrameRequester {
+    identifier: StreamIdentifier,
+    event_sender: StreamHubEventSender,
+}
+
+impl KeyFrameRequester {
+    pub fn new(identifier: StreamIdentifier, event_sender: StreamHubEventSender) -> Self {
+        Self {
+            identifier,
+            event_sender,
+        }
+    }
+
+    pub fn request(&self) {
+        let request_event = StreamHubEvent::RequestKeyFrame {
+            identifier: self.identifier.clone(),
+        };
+        if self.event_sender.send(request_event).is_err() {
+            log::error!("request key frame error: {}", self.identifier);
+        }
+    }
+
+    pub(crate) fn is_key_frame_request(packet: &(dyn RtcpPacket + Send + Sync)) -> bool {
+        let packet = packet.as_any();
+        packet.downcast_ref::<PictureLossIndication>().is_some()
+            || packet.downcast_ref::<FullIntraRequest>().is_some()
+    }
+}
+
+// The layer streams of a simulcast whip stream, they are sorted from the highest
+// to the lowest quality. The layer events are the bandwidth feedbacks of the
+// subscriber and the manual layer selections.
+pub struct SimulcastReceiver {
+    pub layers: Vec<(String, PacketDataReceiver)>,
+    pub event_sender: LayerEventSender,
+    pub event_receiver: LayerEventReceiver,
+}
+
 pub async fn handle_whep(
     offer: RTCSessionDescription,
-    mut receiver: PacketDataReceiver,
+    receiver: DataReceiver,
+    simulcast: Option<SimulcastReceiver>,
+    key_frame_requester: KeyFrameRequester,
     state_sender: broadcast::Sender<RTCPeerConnectionState>,
+    ice_config: &IceConfig,
 ) -> Result<(RTCSessionDescription, Arc<RTCPeerConnection>)> {
     // Everything below is the WebRTC-rs API! Thanks for using it ❤️.
 
@@ -50,16 +122,11 @@ pub async fn handle_whep(
     let api = APIBuilder::new()
         .with_media_engine(m)
         .with_interceptor_registry(registry)
+        .with_setting_engine(ice_config.setting_engine()?)
         .build();
 
     // Prepare the configuration
-    let config = RTCConfiguration {
-        ice_servers: vec![RTCIceServer {
-            urls: vec!["stun:stun.l.google.com:19302".to_owned()],
-            ..Default::default()
-        }],
-        ..Default::default()
-    };
+    let config = ice_config.rtc_configuration();
 
     // Create a new RTCPeerConnection
     let peer_connection = Arc::new(api.new_peer_connection(config).await?);
@@ -93,12 +160,28 @@ pub async fn handle_whep(
         .add_track(Arc::clone(&audio_track) as Arc<dyn TrackLocal + Send + Sync>)
         .await?;
 
+    // The text messages of the stream are sent by the data channels of the client
+    let data_channels = SubscriberDataChannels::new(&peer_connection);
+
     // Read incoming RTCP packets
     // Before these packets are returned they are processed by interceptors. For things
     // like NACK this needs to be called.
+    let layer_event_sender = simulcast.as_ref().map(|s| s.event_sender.clone());
+    let rtcp_key_frame_requester = key_frame_requester.clone();
     tokio::spawn(async move {
         let mut rtcp_buf = vec![0u8; 1500];
-        while let Ok((_, _)) = rtp_sender.read(&mut rtcp_buf).await {}
+        while let Ok((packets, _)) = rtp_sender.read(&mut rtcp_buf).await {
+            for packet in packets {
+                if KeyFrameRequester::is_key_frame_request(&*packet) {
+                    rtcp_key_frame_requester.request();
+                }
+                if let Some(layer_event_sender) = &layer_event_sender {
+                    if let Some(event) = LayerEvent::from_rtcp(&*packet) {
+                        let _ = layer_event_sender.send(event);
+                    }
+                }
+            }
+        }
         Result::<()>::Ok(())
     });
 
@@ -116,7 +199,7 @@ pub async fn handle_whep(
 
     // Set the handler for Peer connection state
     // This will notify you when the peer has connected/disconnected
-    let mut state_receiver = state_sender.subscribe();
+    let state_receiver = state_sender.subscribe();
     peer_connection.on_peer_connection_state_change(Box::new(move |s: RTCPeerConnectionState| {
         log::info!("Peer Connection State has changed: {s}");
 
@@ -151,43 +234,312 @@ pub async fn handle_whep(
     // in a production application you should exchange ICE Candidates via OnICECandidate
     let _ = gather_complete.recv().await;
 
-    // Read RTP packets forever and send them to the WebRTC Client
-    tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                av_data = receiver.recv() =>{
-                    if let Some(data) = av_data {
-                        match data {
-                            PacketData::Video { timestamp: _, data } => {
-                                if let Err(err) = video_track.write(&data[..]).await {
-                                    log::error!("send video data error: {}", err);
-                                }
+    if let Some(receiver) = receiver.packet_receiver {
+        if let Some(simulcast) = simulcast {
+            tokio::spawn(forward_simulcast_packets(
+                receiver,
+                simulcast,
+                key_frame_requester,
+                video_track,
+                audio_track,
+                data_channels,
+                state_receiver,
+            ));
+        } else {
+            tokio::spawn(forward_packets(
+                receiver,
+                video_track,
+                audio_track,
+                data_channels,
+                state_receiver,
+            ));
+        }
+    } else if let Some(receiver) = receiver.frame_receiver {
+        tokio::spawn(forward_frames(
+            receiver,
+            video_track,
+            audio_track,
+            data_channels,
+            state_receiver,
+        ));
+    }
+
+    // Output the answer in base64 so we can paste it in browser
+    if let Some(local_desc) = peer_connection.local_description().await {
+        Ok((local_desc, peer_connection))
+    } else {
+        Err(WebRTCError {
+            value: WebRTCErrorValue::CanNotGetLocalDescription,
+        })
+    }
+}
+
+// Read RTP packets forever and send them to the WebRTC Client
+pub(crate) async fn forward_packets(
+    mut receiver: PacketDataReceiver,
+    video_track: Arc<TrackLocalStaticRTP>,
+    audio_track: Arc<TrackLocalStaticRTP>,
+    data_channels: SubscriberDataChannels,
+    mut state_receiver: broadcast::Receiver<RTCPeerConnectionState>,
+) {
+    loop {
+        tokio::select! {
+            av_data = receiver.recv() =>{
+                if let Some(data) = av_data {
+                    match data {
+                        PacketData::Video { timestamp: _, data } => {
+                            if let Err(err) = video_track.write(&data[..]).await {
+                                log::error!("send video data error: {}", err);
                             }
-                            PacketData::Audio { timestamp: _, data } => {
-                                if let Err(err) = audio_track.write(&data[..]).await {
-                                    log::error!("send audio data error: {}", err);
-                                }
+                        }
+                        PacketData::Audio { timestamp: _, data } => {
+                            if let Err(err) = audio_track.write(&data[..]).await {
+                                log::error!("send audio data error: {}", err);
                             }
                         }
+                        PacketData::TextData { data } => {
+                            data_channels.send_text(&data).await;
+                        }
                     }
                 }
-                pc_state = state_receiver.recv() =>{
-                    if let Ok(state) = pc_state{
-                        if state == RTCPeerConnectionState::Closed {
-                            break;
+            }
+            pc_state = state_receiver.recv() =>{
+                if let Ok(state) = pc_state{
+                    if state == RTCPeerConnectionState::Closed {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Read the audio packets of the stream and the video packets of its simulcast
+// layers, the video of the layer selected by the bandwidth of the WHEP client
+// or manually is sent to the client.
+async fn forward_simulcast_packets(
+    mut receiver: PacketDataReceiver,
+    simulcast: SimulcastReceiver,
+    key_frame_requester: KeyFrameRequester,
+    video_track: Arc<TrackLocalStaticRTP>,
+    audio_track: Arc<TrackLocalStaticRTP>,
+    data_channels: SubscriberDataChannels,
+    mut state_receiver: broadcast::Receiver<RTCPeerConnectionState>,
+) {
+    let (rids, layer_receivers): (Vec<String>, Vec<PacketDataReceiver>) =
+        simulcast.layers.into_iter().unzip();
+    let mut layer_selector = LayerSelector::new(rids);
+    let mut event_receiver = simulcast.event_receiver;
+
+    //merge the video packets of the layers, they are tagged with the layer index
+    let (video_sender, mut video_receiver) = mpsc::unbounded_channel();
+    for (layer, mut layer_receiver) in layer_receivers.into_iter().enumerate() {
+        let video_sender = video_sender.clone();
+        tokio::spawn(async move {
+            while let Some(packet_data) = layer_receiver.recv().await {
+                if let PacketData::Video { timestamp: _, data } = packet_data {
+                    if video_sender.send((layer, data)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+    drop(video_sender);
+
+    loop {
+        tokio::select! {
+            av_data = receiver.recv() =>{
+                match av_data {
+                    Some(PacketData::Audio { timestamp: _, data }) => {
+                        if let Err(err) = audio_track.write(&data[..]).await {
+                            log::error!("send audio data error: {}", err);
                         }
                     }
+                    //the video of the primary layer is received from its layer stream
+                    Some(PacketData::Video { .. }) => {}
+                    Some(PacketData::TextData { data }) => {
+                        data_channels.send_text(&data).await;
+                    }
+                    None => break,
+                }
+            }
+            Some((layer, data)) = video_receiver.recv() =>{
+                let mut packet = match Packet::unmarshal(&mut &data[..]) {
+                    Ok(packet) => packet,
+                    Err(err) => {
+                        log::error!("unmarshal video layer packet error: {}", err);
+                        continue;
+                    }
+                };
+                if layer_selector.on_video_packet(layer, &mut packet, Instant::now()) {
+                    if let Err(err) = video_track.write_rtp(&packet).await {
+                        log::error!("send video data error: {}", err);
+                    }
+                }
+            }
+            Some(event) = event_receiver.recv() =>{
+                layer_selector.on_event(event, Instant::now());
+            }
+            pc_state = state_receiver.recv() =>{
+                if let Ok(state) = pc_state{
+                    if state == RTCPeerConnectionState::Closed {
+                        break;
+                    }
                 }
             }
         }
-    });
 
-    // Output the answer in base64 so we can paste it in browser
-    if let Some(local_desc) = peer_connection.local_description().await {
-        Ok((local_desc, peer_connection))
-    } else {
-        Err(WebRTCError {
-            value: WebRTCErrorValue::CanNotGetLocalDescription,
+        //the switched layer waits for a key frame
+        if layer_selector.take_key_frame_request() {
+            key_frame_requester.request();
+        }
+    }
+}
+
+// Packetize the frames of a frame based stream(RTMP, HTTP-FLV relay, RTSP remuxed)
+// into the local tracks, the h264 frames are converted to annexb with the sps/pps
+// before the key frames, and the aac audio is transcoded to opus.
+struct FramePacketizer {
+    video_demuxer: FlvVideoTagDemuxer,
+    video_packer: RtpH264Packer,
+    audio_demuxer: FlvAudioTagDemuxer,
+    aac2opus_transcoder: Aac2OpusTranscoder,
+    audio_header: Header,
+    audio_track: Arc<TrackLocalStaticRTP>,
+}
+
+impl FramePacketizer {
+    fn new(
+        video_track: Arc<TrackLocalStaticRTP>,
+        audio_track: Arc<TrackLocalStaticRTP>,
+    ) -> std::result::Result<Self, Aac2OpusError> {
+        let video_io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>> =
+            Arc::new(Mutex::new(Box::new(RtpTrackIO::new(video_track))));
+        //the payload type and ssrc are rewritten by the track
+        let mut video_packer = RtpH264Packer::new(0, 0, 0, RTP_MTU, video_io);
+        video_packer.on_packet_handler(Box::new(
+            move |io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>, packet: RtpPacket| {
+                Box::pin(async move {
+                    let mut bytes_writer = AsyncBytesWriter::new(io);
+
+                    let msg = packet.marshal()?;
+                    bytes_writer.write(&msg)?;
+                    bytes_writer.flush().await?;
+                    Ok(())
+                })
+            },
+        ));
+
+        Ok(Self {
+            video_demuxer: FlvVideoTagDemuxer::new(),
+            video_packer,
+            audio_demuxer: FlvAudioTagDemuxer::new(),
+            aac2opus_transcoder: Aac2OpusTranscoder::new()?,
+            audio_header: Header {
+                version: 2,
+                ..Default::default()
+            },
+            audio_track,
         })
     }
+
+    async fn on_video(&mut self, timestamp: u32, data: BytesMut) {
+        let video_data = match self.video_demuxer.demux(timestamp, data) {
+            Ok(Some(video_data)) => video_data,
+            //the avc sequence header and the not supported video codecs
+            Ok(None) => return,
+            Err(err) => {
+                log::error!("whep demux video data error: {}", err);
+                return;
+            }
+        };
+
+        let mut nalus = video_data.data;
+        let timestamp = (video_data.pts as u32).wrapping_mul(H264_CLOCK_RATE / 1000);
+        if let Err(err) = self.video_packer.pack(&mut nalus, timestamp).await {
+            log::error!("send video data error: {}", err);
+        }
+    }
+
+    async fn on_audio(&mut self, timestamp: u32, data: BytesMut) {
+        let audio_data = match self.audio_demuxer.demux(timestamp, data) {
+            Ok(audio_data) => audio_data,
+            Err(err) => {
+                log::error!("whep demux audio data error: {}", err);
+                return;
+            }
+        };
+        //the aac sequence header and the not supported audio codecs
+        if !audio_data.has_data {
+            return;
+        }
+
+        let opus_frames = match self
+            .aac2opus_transcoder
+            .transcode(&audio_data.data, timestamp)
+        {
+            Ok(opus_frames) => opus_frames,
+            Err(err) => {
+                log::error!("aac2opus transcode error: {:?}", err);
+                return;
+            }
+        };
+
+        for (timestamp, payload) in opus_frames {
+            self.audio_header.timestamp = timestamp;
+            let packet = Packet {
+                header: self.audio_header.clone(),
+                payload: Bytes::from(payload),
+            };
+            if let Err(err) = self.audio_track.write_rtp(&packet).await {
+                log::error!("send audio data error: {}", err);
+            }
+            self.audio_header.sequence_number = self.audio_header.sequence_number.wrapping_add(1);
+        }
+    }
+}
+
+// Read the frames of a frame based stream forever and send them to the WebRTC Client
+pub(crate) async fn forward_frames(
+    mut receiver: FrameDataReceiver,
+    video_track: Arc<TrackLocalStaticRTP>,
+    audio_track: Arc<TrackLocalStaticRTP>,
+    data_channels: SubscriberDataChannels,
+    mut state_receiver: broadcast::Receiver<RTCPeerConnectionState>,
+) {
+    let mut packetizer = match FramePacketizer::new(video_track, audio_track) {
+        Ok(packetizer) => packetizer,
+        Err(err) => {
+            log::error!("new whep frame packetizer error: {:?}", err);
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            frame_data = receiver.recv() =>{
+                match frame_data {
+                    Some(FrameData::Video { timestamp, data }) => {
+                        packetizer.on_video(timestamp, data).await;
+                    }
+                    Some(FrameData::Audio { timestamp, data }) => {
+                        packetizer.on_audio(timestamp, data).await;
+                    }
+                    Some(FrameData::TextData { data }) => {
+                        data_channels.send_text(&data).await;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            pc_state = state_receiver.recv() =>{
+                if let Ok(state) = pc_state{
+                    if state == RTCPeerConnectionState::Closed {
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
@@ -2,21 +2,24 @@ use crate::opus2aac::Opus2AacTranscoder;
 
 use super::errors::WebRTCError;
 use super::errors::WebRTCErrorValue;
+use super::ice_config::IceConfig;
 use bytes::BytesMut;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use streamhub::define::VideoCodecType;
-use streamhub::define::{FrameData, PacketData};
+use streamhub::define::{FrameData, FrameDataSender, PacketData, PacketDataSender};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Notify;
 use webrtc::rtp::codecs::opus::OpusPacket;
+use webrtc::rtp_transceiver::rtp_codec::RTCRtpHeaderExtensionCapability;
+use webrtc::track::track_remote::TrackRemote;
 
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 use webrtc::api::interceptor_registry::register_default_interceptors;
 use webrtc::api::media_engine::MediaEngine;
 use webrtc::api::APIBuilder;
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
-use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::interceptor::registry::Registry;
-use webrtc::peer_connection::configuration::RTCConfiguration;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
@@ -24,7 +27,10 @@ use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndicat
 use webrtc::rtp::codecs::h264::H264Packet;
 use webrtc::sdp::util::Codec;
 
+use super::data_channel::relay_publisher_messages;
 use super::rtp_queue::RtpQueue;
+use super::session::WebRTCStreamHandler;
+use super::simulcast::is_h264_keyframe;
 use webrtc::rtp::packetizer::Depacketizer;
 use webrtc::rtp_transceiver::rtp_codec::RTPCodecType;
 use webrtc::rtp_transceiver::rtp_transceiver_direction::RTCRtpTransceiverDirection;
@@ -41,6 +47,9 @@ pub type Result<T> = std::result::Result<T, WebRTCError>;
 //     pub const NO_IDR_FRAME: u8 = 0x01; //0x41 B/P frame
 // }
 
+//the minimum interval of the PLIs requested by the subscribers
+const MIN_PLI_INTERVAL: Duration = Duration::from_millis(500);
+
 mod nal_payload_type {
     pub const H264: u8 = 96;
     pub const OPUS: u8 = 111;
@@ -86,16 +95,307 @@ pub(crate) fn parse_rtpmap(rtpmap: &str) -> Result<Codec> {
     })
 }
 
+// Read the rtp packets of a simulcast layer which is not the primary one, they
+// are only forwarded to the layer stream for the WHEP subscribers.
+async fn forward_layer_packets(track: Arc<TrackRemote>, sender: PacketDataSender) -> Result<()> {
+    let mut b = vec![0u8; 3000];
+    while let Ok((rtp_packet, _)) = track.read(&mut b).await {
+        let n = rtp_packet.marshal_to(&mut b)?;
+        let video_packet = PacketData::Video {
+            timestamp: rtp_packet.header.timestamp,
+            data: BytesMut::from(&b[..n]),
+        };
+        if sender.send(video_packet).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Send a PLI on an interval so that the publisher is pushing a keyframe every 3 seconds,
+// or when a key frame is requested by the subscribers through the stream hub
+pub(crate) fn spawn_pli_sender(
+    pc: Weak<RTCPeerConnection>,
+    media_ssrc: u32,
+    key_frame_notify: Arc<Notify>,
+) {
+    tokio::spawn(async move {
+        let mut result = Result::<usize>::Ok(0);
+        let mut last_pli_time = Instant::now();
+        while result.is_ok() {
+            let timeout = tokio::time::sleep(Duration::from_secs(3));
+            tokio::pin!(timeout);
+
+            tokio::select! {
+                _ = timeout.as_mut() =>{}
+                _ = key_frame_notify.notified() =>{
+                    //the requests of the subscribers joined at the same time are merged
+                    if last_pli_time.elapsed() < MIN_PLI_INTERVAL {
+                        continue;
+                    }
+                }
+            };
+
+            if let Some(pc) = pc.upgrade() {
+                result = pc
+                    .write_rtcp(&[Box::new(PictureLossIndication {
+                        sender_ssrc: 0,
+                        media_ssrc,
+                    })])
+                    .await
+                    .map_err(Into::into);
+                last_pli_time = Instant::now();
+            } else {
+                break;
+            }
+        }
+    });
+}
+
+// Forward the rtp packets of a received track to the stream, and the frames
+// depacketized from them(the opus is transcoded to aac) for the remuxers. The
+// codecs are parsed from the sdp of the remote peer.
+pub(crate) async fn forward_track(
+    track: Arc<TrackRemote>,
+    remote_sdp: RTCSessionDescription,
+    frame_sender: FrameDataSender,
+    packet_sender: PacketDataSender,
+    layer_sender: Option<PacketDataSender>,
+    stream_handler: Arc<WebRTCStreamHandler>,
+) -> Result<()> {
+    let mut b = vec![0u8; 3000];
+    let mut h264_packet = H264Packet::default();
+    let mut opus_packet = OpusPacket;
+
+    let mut video_codec = Codec::default();
+    let mut audio_codec = Codec::default();
+    let mut vcodec: VideoCodecType = VideoCodecType::H264;
+    let mut opus2aac_transcoder = Opus2AacTranscoder::new(
+        48000,
+        audiopus::Channels::Stereo,
+        48000,
+        fdk_aac::enc::ChannelMode::Stereo,
+    )
+    .unwrap();
+
+    //111 OPUS/48000/2
+    //96 H264/90000
+    if let Ok(session_description) = remote_sdp.unmarshal() {
+        for m in session_description.media_descriptions {
+            for a in &m.attributes {
+                let attr = a.to_string();
+                if attr.starts_with("rtpmap:") {
+                    if let Ok(codec) = parse_rtpmap(&attr) {
+                        log::info!("codec: {}", codec);
+                        match codec.name.to_uppercase().as_str() {
+                            "H264" => {
+                                video_codec = codec;
+                            }
+                            "H265" => {
+                                video_codec = codec;
+                                vcodec = VideoCodecType::H265;
+                            }
+                            "OPUS" => {
+                                audio_codec = codec;
+                                let channels = match audio_codec.encoding_parameters.as_str() {
+                                    "1" => audiopus::Channels::Mono,
+                                    "2" => audiopus::Channels::Stereo,
+                                    _ => audiopus::Channels::Stereo,
+                                };
+
+                                opus2aac_transcoder = Opus2AacTranscoder::new(
+                                    audio_codec.clock_rate as i32,
+                                    channels,
+                                    audio_codec.clock_rate,
+                                    fdk_aac::enc::ChannelMode::Stereo,
+                                )
+                                .unwrap();
+                            }
+                            _ => {
+                                log::warn!("not supported codec: {}", codec);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let media_info = FrameData::MediaInfo {
+        media_info: streamhub::define::MediaInfo {
+            audio_clock_rate: audio_codec.clock_rate,
+            video_clock_rate: video_codec.clock_rate,
+            vcodec,
+            //the opus is transcoded to aac
+            acodec: streamhub::define::AudioCodecType::AAC,
+        },
+    };
+
+    if let Err(err) = frame_sender.send(media_info) {
+        log::error!("send media info error: {}", err);
+    } else {
+        log::info!("send media info suceess: {:?} {}", audio_codec, video_codec);
+    }
+
+    let _sps_sent: bool = false;
+    let _pps_sent: bool = false;
+    let mut aac_asc_sent: bool = false;
+
+    let mut rtp_queue = RtpQueue::new(100);
+
+    while let Ok((rtp_packet, _)) = track.read(&mut b).await {
+        let n = rtp_packet.marshal_to(&mut b)?;
+
+        match rtp_packet.header.payload_type {
+            //video h264
+            nal_payload_type::H264 => {
+                let video_packet = PacketData::Video {
+                    timestamp: rtp_packet.header.timestamp,
+                    data: BytesMut::from(&b[..n]),
+                };
+                let is_key_frame = is_h264_keyframe(&rtp_packet.payload);
+                stream_handler
+                    .save_packet_data(&video_packet, is_key_frame)
+                    .await;
+                if let Some(layer_sender) = &layer_sender {
+                    if let Err(err) = layer_sender.send(video_packet.clone()) {
+                        log::error!("send video layer packet error: {}", err);
+                    }
+                }
+                if let Err(err) = packet_sender.send(video_packet) {
+                    log::error!("send video packet error: {}", err);
+                }
+
+                rtp_queue.write_queue(rtp_packet);
+
+                while let Some(rtp_packet_ordered) = rtp_queue.read_queue() {
+                    match h264_packet.depacketize(&rtp_packet_ordered.payload) {
+                        Ok(rv) => {
+                            if !rv.is_empty() {
+                                let byte_array = rv.to_vec();
+                                let nal_type = byte_array[4] & 0x1F;
+
+                                if nal_type != 0x0C {
+                                    let video_frame = FrameData::Video {
+                                        timestamp: rtp_packet_ordered.header.timestamp,
+                                        data: BytesMut::from(&byte_array[..]),
+                                    };
+
+                                    if let Err(err) = frame_sender.send(video_frame) {
+                                        log::error!("send video frame error: {}", err);
+                                    } else {
+                                        // log::info!("send video frame suceess: {}", nal_type);
+                                    }
+                                }
+                            }
+                        }
+                        Err(_err) => {
+                            // log::error!("The h264 packet payload err:{}", err);
+                            // let hex_string = hex::encode(b.to_vec());
+                            // log::error!(
+                            //     "The h264 packet payload err string :{}",
+                            //     hex_string
+                            // );
+                        }
+                    }
+                }
+            }
+            //aac 111(opus)
+            nal_payload_type::OPUS => {
+                let audio_packet = PacketData::Audio {
+                    timestamp: rtp_packet.header.timestamp,
+                    data: BytesMut::from(&b[..n]),
+                };
+                stream_handler.save_packet_data(&audio_packet, false).await;
+                if let Err(err) = packet_sender.send(audio_packet) {
+                    log::error!("send audio packet error: {}", err);
+                }
+
+                if !aac_asc_sent {
+                    if let Ok(aac) = Mpeg4Aac::new(2, 48000, 2) {
+                        if let Ok(asc) = aac.gen_audio_specific_config() {
+                            let audio_frame = FrameData::Audio {
+                                timestamp: 0,
+                                data: asc,
+                            };
+                            if let Err(err) = frame_sender.send(audio_frame) {
+                                log::error!("send audio frame error: {}", err);
+                            }
+                        }
+                    }
+                    aac_asc_sent = true;
+                }
+
+                match opus_packet.depacketize(&rtp_packet.payload) {
+                    Ok(rv) => {
+                        if !rv.is_empty() {
+                            // log::info!("audio timestamp: {}", rtp_packet.header.timestamp);
+                            let byte_array = rv.to_vec();
+                            match opus2aac_transcoder.transcode(&byte_array) {
+                                Ok(data) => {
+                                    for data_val in data {
+                                        let audio_frame = FrameData::Audio {
+                                            timestamp: rtp_packet.header.timestamp,
+                                            data: BytesMut::from(&data_val[..]),
+                                        };
+
+                                        if let Err(err) = frame_sender.send(audio_frame) {
+                                            log::error!("send audio frame error: {}", err);
+                                        } else {
+                                            // log::info!("send aidop frame suceess");
+                                        }
+                                    }
+                                }
+                                Err(err) => {
+                                    log::error!("opus2aac transcode error: {:?}", err);
+                                }
+                            }
+                        }
+                    }
+                    Err(_err) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/* The layer senders are the packet senders of the simulcast layers sorted from the
+highest to the lowest quality, the highest layer is also sent to the frame and
+packet senders of the stream as the primary layer. */
 pub async fn handle_whip(
     offer: RTCSessionDescription,
     frame_sender: Option<UnboundedSender<FrameData>>,
     packet_sender: Option<UnboundedSender<PacketData>>,
+    layer_senders: Vec<(String, PacketDataSender)>,
+    stream_handler: Arc<WebRTCStreamHandler>,
+    state_sender: broadcast::Sender<RTCPeerConnectionState>,
+    ice_config: &IceConfig,
 ) -> Result<(RTCSessionDescription, Arc<RTCPeerConnection>)> {
     // Create a MediaEngine object to configure the supported codec
     let mut m = MediaEngine::default();
 
     m.register_default_codecs()?;
 
+    // The simulcast layers are demuxed by the mid and rid header extensions
+    if !layer_senders.is_empty() {
+        for uri in [
+            webrtc::sdp::extmap::SDES_MID_URI,
+            webrtc::sdp::extmap::SDES_RTP_STREAM_ID_URI,
+            webrtc::sdp::extmap::SDES_REPAIR_RTP_STREAM_ID_URI,
+        ] {
+            m.register_header_extension(
+                RTCRtpHeaderExtensionCapability {
+                    uri: uri.to_owned(),
+                },
+                RTPCodecType::Video,
+                None,
+            )?;
+        }
+    }
+
     // Create a InterceptorRegistry. This is the user configurable RTP/RTCP Pipeline.
     // This provides NACKs, RTCP Reports and other features. If you use `webrtc.NewPeerConnection`
     // this is enabled by default. If you are manually managing You MUST create a InterceptorRegistry
@@ -109,16 +409,11 @@ pub async fn handle_whip(
     let api = APIBuilder::new()
         .with_media_engine(m)
         .with_interceptor_registry(registry)
+        .with_setting_engine(ice_config.setting_engine()?)
         .build();
 
     // Prepare the configuration
-    let config = RTCConfiguration {
-        ice_servers: vec![RTCIceServer {
-            urls: vec!["stun:stun.l.google.com:19302".to_owned()],
-            ..Default::default()
-        }],
-        ..Default::default()
-    };
+    let config = ice_config.rtc_configuration();
 
     // Create a new RTCPeerConnection
     let peer_connection = Arc::new(api.new_peer_connection(config).await?);
@@ -143,228 +438,46 @@ pub async fn handle_whip(
         )
         .await?;
 
+    // The text messages of the publisher's data channels are relayed to the stream
+    relay_publisher_messages(
+        &peer_connection,
+        frame_sender.clone(),
+        packet_sender.clone(),
+    );
+
     let offer_in = offer.clone();
     // Set a handler for when a new remote track starts, this handler will forward data to
     // our UDP listeners.
     // In your application this is where you would handle/process audio/video
     let pc = Arc::downgrade(&peer_connection);
     peer_connection.on_track(Box::new(move |track, _, _| {
-        // Send a PLI on an interval so that the publisher is pushing a keyframe every rtcpPLIInterval
-        let media_ssrc = track.ssrc();
-        let pc2 = pc.clone();
-        tokio::spawn(async move {
-            let mut result = Result::<usize>::Ok(0);
-            while result.is_ok() {
-                let timeout = tokio::time::sleep(Duration::from_secs(3));
-                tokio::pin!(timeout);
-
-                tokio::select! {
-                    _ = timeout.as_mut() =>{
-                        if let Some(pc) = pc2.upgrade(){
-                            result = pc.write_rtcp(&[Box::new(PictureLossIndication{
-                                sender_ssrc: 0,
-                                media_ssrc,
-                            })]).await.map_err(Into::into);
-                        }else{
-                            break;
-                        }
-                    }
-                };
-            }
-        });
-        let packet_sender_clone = packet_sender.clone().unwrap();
-        let frame_sender_clone = frame_sender.clone().unwrap();
-        let offer_clone = offer_in.clone();
-        tokio::spawn(async move {
-            let mut b = vec![0u8; 3000];
-            let mut h264_packet = H264Packet::default();
-            let mut opus_packet = OpusPacket;
-
-            let mut video_codec = Codec::default();
-            let mut audio_codec = Codec::default();
-            let mut vcodec: VideoCodecType = VideoCodecType::H264;
-            let mut opus2aac_transcoder = Opus2AacTranscoder::new(
-                48000,
-                audiopus::Channels::Stereo,
-                48000,
-                fdk_aac::enc::ChannelMode::Stereo,
-            )
-            .unwrap();
-
-            //111 OPUS/48000/2
-            //96 H264/90000
-            if let Ok(session_description) = offer_clone.unmarshal() {
-                for m in session_description.media_descriptions {
-                    for a in &m.attributes {
-                        let attr = a.to_string();
-                        if attr.starts_with("rtpmap:") {
-                            if let Ok(codec) = parse_rtpmap(&attr) {
-                                log::info!("codec: {}", codec);
-                                match codec.name.to_uppercase().as_str() {
-                                    "H264" => {
-                                        video_codec = codec;
-                                    }
-                                    "H265" => {
-                                        video_codec = codec;
-                                        vcodec = VideoCodecType::H265;
-                                    }
-                                    "OPUS" => {
-                                        audio_codec = codec;
-                                        let channels =
-                                            match audio_codec.encoding_parameters.as_str() {
-                                                "1" => audiopus::Channels::Mono,
-                                                "2" => audiopus::Channels::Stereo,
-                                                _ => audiopus::Channels::Stereo,
-                                            };
-
-                                        opus2aac_transcoder = Opus2AacTranscoder::new(
-                                            audio_codec.clock_rate as i32,
-                                            channels,
-                                            audio_codec.clock_rate,
-                                            fdk_aac::enc::ChannelMode::Stereo,
-                                        )
-                                        .unwrap();
-                                    }
-                                    _ => {
-                                        log::warn!("not supported codec: {}", codec);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            let media_info = FrameData::MediaInfo {
-                media_info: streamhub::define::MediaInfo {
-                    audio_clock_rate: audio_codec.clock_rate,
-                    video_clock_rate: video_codec.clock_rate,
-                    vcodec,
-                },
-            };
-
-            if let Err(err) = frame_sender_clone.send(media_info) {
-                log::error!("send media info error: {}", err);
-            } else {
-                log::info!("send media info suceess: {:?} {}", audio_codec, video_codec);
-            }
-
-            let _sps_sent: bool = false;
-            let _pps_sent: bool = false;
-            let mut aac_asc_sent: bool = false;
-
-            let mut rtp_queue = RtpQueue::new(100);
-
-            while let Ok((rtp_packet, _)) = track.read(&mut b).await {
-                let n = rtp_packet.marshal_to(&mut b)?;
-
-                match rtp_packet.header.payload_type {
-                    //video h264
-                    nal_payload_type::H264 => {
-                        let video_packet = PacketData::Video {
-                            timestamp: rtp_packet.header.timestamp,
-                            data: BytesMut::from(&b[..n]),
-                        };
-                        if let Err(err) = packet_sender_clone.send(video_packet) {
-                            log::error!("send video packet error: {}", err);
-                        }
-
-                        rtp_queue.write_queue(rtp_packet);
-
-                        while let Some(rtp_packet_ordered) = rtp_queue.read_queue() {
-                            match h264_packet.depacketize(&rtp_packet_ordered.payload) {
-                                Ok(rv) => {
-                                    if !rv.is_empty() {
-                                        let byte_array = rv.to_vec();
-                                        let nal_type = byte_array[4] & 0x1F;
-
-                                        if nal_type != 0x0C {
-                                            let video_frame = FrameData::Video {
-                                                timestamp: rtp_packet_ordered.header.timestamp,
-                                                data: BytesMut::from(&byte_array[..]),
-                                            };
-
-                                            if let Err(err) = frame_sender_clone.send(video_frame) {
-                                                log::error!("send video frame error: {}", err);
-                                            } else {
-                                                // log::info!("send video frame suceess: {}", nal_type);
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(_err) => {
-                                    // log::error!("The h264 packet payload err:{}", err);
-                                    // let hex_string = hex::encode(b.to_vec());
-                                    // log::error!(
-                                    //     "The h264 packet payload err string :{}",
-                                    //     hex_string
-                                    // );
-                                }
-                            }
-                        }
-                    }
-                    //aac 111(opus)
-                    nal_payload_type::OPUS => {
-                        let audio_packet = PacketData::Audio {
-                            timestamp: rtp_packet.header.timestamp,
-                            data: BytesMut::from(&b[..n]),
-                        };
-                        if let Err(err) = packet_sender_clone.send(audio_packet) {
-                            log::error!("send audio packet error: {}", err);
-                        }
-
-                        if !aac_asc_sent {
-                            if let Ok(aac) = Mpeg4Aac::new(2, 48000, 2) {
-                                if let Ok(asc) = aac.gen_audio_specific_config() {
-                                    let audio_frame = FrameData::Audio {
-                                        timestamp: 0,
-                                        data: asc,
-                                    };
-                                    if let Err(err) = frame_sender_clone.send(audio_frame) {
-                                        log::error!("send audio frame error: {}", err);
-                                    }
-                                }
-                            }
-                            aac_asc_sent = true;
-                        }
-
-                        match opus_packet.depacketize(&rtp_packet.payload) {
-                            Ok(rv) => {
-                                if !rv.is_empty() {
-                                    // log::info!("audio timestamp: {}", rtp_packet.header.timestamp);
-                                    let byte_array = rv.to_vec();
-                                    match opus2aac_transcoder.transcode(&byte_array) {
-                                        Ok(data) => {
-                                            for data_val in data {
-                                                let audio_frame = FrameData::Audio {
-                                                    timestamp: rtp_packet.header.timestamp,
-                                                    data: BytesMut::from(&data_val[..]),
-                                                };
-
-                                                if let Err(err) =
-                                                    frame_sender_clone.send(audio_frame)
-                                                {
-                                                    log::error!("send audio frame error: {}", err);
-                                                } else {
-                                                    // log::info!("send aidop frame suceess");
-                                                }
-                                            }
-                                        }
-                                        Err(err) => {
-                                            log::error!("opus2aac transcode error: {:?}", err);
-                                        }
-                                    }
-                                }
-                            }
-                            Err(_err) => {}
-                        }
-                    }
-                    _ => {}
-                }
+        spawn_pli_sender(pc.clone(), track.ssrc(), stream_handler.key_frame_notify());
+
+        let rid = track.rid().to_owned();
+        let layer_sender = layer_senders
+            .iter()
+            .find(|(layer_rid, _)| *layer_rid == rid)
+            .map(|(_, sender)| sender.clone());
+        //the audio and the not simulcast video tracks have no rid
+        let is_primary = rid.is_empty()
+            || layer_senders
+                .first()
+                .is_none_or(|(primary_rid, _)| *primary_rid == rid);
+        if !is_primary {
+            if let Some(layer_sender) = layer_sender {
+                tokio::spawn(forward_layer_packets(track, layer_sender));
             }
+            return Box::pin(async {});
+        }
 
-            Result::<()>::Ok(())
-        });
+        tokio::spawn(forward_track(
+            track,
+            offer_in.clone(),
+            frame_sender.clone().unwrap(),
+            packet_sender.clone().unwrap(),
+            layer_sender,
+            stream_handler.clone(),
+        ));
 
         Box::pin(async {})
     }));
@@ -393,6 +506,8 @@ pub async fn handle_whip(
             // Note that the PeerConnection may come back from PeerConnectionStateDisconnected.
             println!("Peer Connection has gone to failed exiting: Done forwarding");
         }
+        //the state is not watched any more after the resource is removed
+        let _ = state_sender.send(s);
 
         Box::pin(async {})
     }));